@@ -166,6 +166,10 @@ fn main() {
     generate_contract_with_config("BaoswapRouter", |builder| {
         builder.add_network_str("100", "0x6093AeBAC87d62b1A5a4cEec91204e35020E38bE")
     });
+    generate_contract_with_config("CoWSwapEthFlow", |builder| {
+        builder.contract_mod_override("cowswap_eth_flow")
+    });
+    generate_contract("ERC1271SignatureValidator");
     generate_contract("ERC20");
     generate_contract("ERC20Mintable");
     generate_contract("GPv2AllowListAuthentication");
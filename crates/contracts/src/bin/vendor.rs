@@ -98,6 +98,10 @@ fn run() -> Result<()> {
             "balancer-labs/balancer-subgraph-v2/2b97edd5e65aed06718ce64a69111ccdabccf048/\
              abis/StablePool.json",
         )?
+        .npm(
+            "ERC1271SignatureValidator",
+            "@cowprotocol/contracts@1.1.2/build/artifacts/src/contracts/interfaces/GPv2EIP1271.sol/EIP1271Verifier.json",
+        )?
         .npm(
             "ERC20",
             "@openzeppelin/contracts@3.3.0/build/contracts/ERC20.json",
@@ -114,6 +118,10 @@ fn run() -> Result<()> {
             "IUniswapLikeRouter",
             "@uniswap/v2-periphery@1.1.0-beta.0/build/IUniswapV2Router02.json",
         )?
+        .manual(
+            "CoWSwapEthFlow",
+            "Only the events of the ETH-flow contract are needed and its ABI is not published",
+        )
         .manual(
             "BalancerV2BasePool",
             "Balancer does not publish ABIs for base contracts",
@@ -5,12 +5,16 @@ use orderbook::{
     account_balances::Web3BalanceFetcher,
     api::order_validation::OrderValidator,
     api::post_quote::OrderQuoter,
+    api::ChainApi,
     cow_subsidy::FixedCowSubsidy,
     database::Postgres,
     event_updater::EventUpdater,
     fee::{FeeSubsidyConfiguration, MinFeeCalculator},
+    fee_subsidy::{CurrentFeeSubsidy, VersionedFeeSubsidy},
     metrics::NoopMetrics,
+    order_status_updates::OrderStatusUpdates,
     orderbook::Orderbook,
+    signature_validator::Web3SignatureValidator,
     solvable_orders::SolvableOrdersCache,
 };
 use reqwest::Client;
@@ -101,9 +105,11 @@ impl OrderbookServices {
     pub async fn new(web3: &Web3, contracts: &Contracts) -> Self {
         let db = Arc::new(Postgres::new("postgresql://").unwrap());
         db.clear().await.unwrap();
+        let status_updates = Arc::new(OrderStatusUpdates::default());
         let event_updater = Arc::new(EventUpdater::new(
             contracts.gp_settlement.clone(),
             db.as_ref().clone(),
+            status_updates.clone(),
             None,
         ));
         let pair_provider = uniswap_pair_provider(contracts);
@@ -141,18 +147,23 @@ impl OrderbookServices {
             contracts.weth.address(),
             1_000_000_000_000_000_000_u128.into(),
         ));
+        let fee_subsidy = Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+            fee_subsidy: FeeSubsidyConfiguration {
+                fee_factor: 0.,
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
         let fee_calculator = Arc::new(MinFeeCalculator::new(
             price_estimator.clone(),
-            gas_estimator,
+            gas_estimator.clone(),
             db.clone(),
             bad_token_detector.clone(),
-            FeeSubsidyConfiguration {
-                fee_factor: 0.,
-                ..Default::default()
-            },
+            fee_subsidy,
             native_price_estimator.clone(),
             Arc::new(FixedCowSubsidy(1.0)),
             Default::default(),
+            db.clone(),
         ));
         let balance_fetcher = Arc::new(Web3BalanceFetcher::new(
             web3.clone(),
@@ -160,15 +171,21 @@ impl OrderbookServices {
             contracts.allowance,
             contracts.gp_settlement.address(),
         ));
+        let signature_validator = Arc::new(Web3SignatureValidator::new(web3.clone()));
         let solvable_orders_cache = SolvableOrdersCache::new(
             Duration::from_secs(120),
             db.clone(),
+            db.clone(),
             Default::default(),
             balance_fetcher.clone(),
             bad_token_detector.clone(),
+            signature_validator.clone(),
             current_block_stream.clone(),
             native_price_estimator,
+            gas_estimator,
             Arc::new(NoopMetrics),
+            status_updates.clone(),
+            db.clone(),
         );
         let order_validator = Arc::new(OrderValidator::new(
             Box::new(web3.clone()),
@@ -176,9 +193,13 @@ impl OrderbookServices {
             HashSet::default(),
             HashSet::default(),
             Duration::from_secs(120),
+            Duration::MAX,
+            Duration::MAX,
             fee_calculator.clone(),
             bad_token_detector.clone(),
             balance_fetcher,
+            signature_validator,
+            db.clone(),
         ));
         let orderbook = Arc::new(Orderbook::new(
             contracts.domain_separator,
@@ -188,7 +209,10 @@ impl OrderbookServices {
             true,
             solvable_orders_cache.clone(),
             Duration::from_secs(600),
+            chrono::Duration::seconds(120),
             order_validator.clone(),
+            status_updates,
+            Default::default(),
         ));
         let maintenance = ServiceMaintenance {
             maintainers: vec![db.clone(), event_updater],
@@ -197,14 +221,22 @@ impl OrderbookServices {
             fee_calculator,
             price_estimator.clone(),
             order_validator,
+            db.clone(),
         ));
         orderbook::serve_api(
-            db.clone(),
-            orderbook,
-            quoter,
+            vec![ChainApi {
+                chain_id: web3.eth().chain_id().await.unwrap().as_u64(),
+                database: db.clone(),
+                orderbook,
+                quoter,
+                solver_competition: db.clone(),
+                order_events: db.clone(),
+                app_data: db.clone(),
+            }],
             API_HOST[7..].parse().expect("Couldn't parse API address"),
             pending(),
-            Default::default(),
+            None,
+            100,
         );
 
         Self {
@@ -0,0 +1,246 @@
+//! The app data document that an order's `appData` field commits to.
+//!
+//! The `AppId` of an order is the sha256 digest of the IPFS CIDv0 of the
+//! document. This is what the app data SDK computes before uploading the
+//! document to IPFS so the orderbook can verify documents without having to
+//! resolve them from IPFS itself.
+
+use crate::{app_id::AppId, order::Hooks};
+use primitive_types::H160;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
+use sha2::{Digest, Sha256};
+
+/// Documents have to fit into a single IPFS block (256 KiB) for the hash
+/// computation to be correct. Real documents are much smaller than that.
+pub const MAX_APP_DATA_SIZE: usize = 8192;
+
+/// Computes the `AppId` that the given document gets when added to IPFS.
+///
+/// This is the digest of the sha256 multihash of the dag-pb node wrapping the
+/// document as a UnixFS file.
+pub fn app_data_hash(document: &[u8]) -> AppId {
+    fn varint(buffer: &mut Vec<u8>, mut value: usize) {
+        while value >= 0x80 {
+            buffer.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        buffer.push(value as u8);
+    }
+
+    // UnixFS `Data` message: type = File, data, filesize.
+    let mut unixfs = vec![0x08, 0x02];
+    if !document.is_empty() {
+        unixfs.push(0x12);
+        varint(&mut unixfs, document.len());
+        unixfs.extend_from_slice(document);
+    }
+    unixfs.push(0x18);
+    varint(&mut unixfs, document.len());
+
+    // dag-pb `PBNode` message without links.
+    let mut node = vec![0x0a];
+    varint(&mut node, unixfs.len());
+    node.extend_from_slice(&unixfs);
+
+    AppId(Sha256::digest(&node).into())
+}
+
+/// The schema of app data documents. Only the fields that the orderbook uses
+/// are validated, other fields are allowed to be anything.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDataDocument {
+    pub version: String,
+    #[serde(default)]
+    pub app_code: Option<String>,
+    #[serde(default)]
+    pub metadata: AppDataMetadata,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppDataMetadata {
+    #[serde(default)]
+    pub referrer: Option<ReferrerMetadata>,
+    #[serde(default)]
+    pub quote: Option<QuoteMetadata>,
+    /// Interactions to execute with the order. They are stored with the order
+    /// when it is created, see `OrderMetadata::hooks`.
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+}
+
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReferrerMetadata {
+    pub version: String,
+    pub address: H160,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QuoteMetadata {
+    pub version: String,
+    /// Older versions of the schema encode the slippage as a string.
+    #[serde_as(as = "PickFirst<(_, DisplayFromStr)>")]
+    pub slippage_bips: u32,
+}
+
+/// The fields of an app data document that the orderbook makes use of.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedAppData {
+    pub app_code: Option<String>,
+    pub referrer: Option<H160>,
+    pub slippage_bips: Option<u32>,
+}
+
+impl From<AppDataDocument> for ParsedAppData {
+    fn from(document: AppDataDocument) -> Self {
+        Self {
+            app_code: document.app_code,
+            referrer: document.metadata.referrer.map(|referrer| referrer.address),
+            slippage_bips: document.metadata.quote.map(|quote| quote.slippage_bips),
+        }
+    }
+}
+
+/// A stored app data document.
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AppData {
+    /// The document exactly as it was uploaded.
+    pub full_app_data: String,
+    #[serde(flatten)]
+    pub parsed: ParsedAppData,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::order::Hook;
+    use hex_literal::hex;
+    use serde_json::json;
+
+    #[test]
+    fn computes_ipfs_hash() {
+        // `ipfs add` of an empty file: QmbFMke1KXqnYyBBWxB74N4c5SBnJMVAiMNRcGu6x1AwQH
+        assert_eq!(
+            app_data_hash(b""),
+            AppId(hex!(
+                "bfccda787baba32b59c78450ac3d20b633360b43992c77289f9ed46d843561e6"
+            ))
+        );
+        // `ipfs add` of "hello world\n": QmT78zSuBmuS4z925WZfrqQ1qHaJ56DQaTfyMUF7F8ff5o
+        assert_eq!(
+            app_data_hash(b"hello world\n"),
+            AppId(hex!(
+                "46d44814b9c5af141c3aaab7c05dc5e844ead5f91f12858b021eba45768b4c0e"
+            ))
+        );
+    }
+
+    #[test]
+    fn parses_document() {
+        let document: AppDataDocument = serde_json::from_value(json!({
+            "version": "0.4.0",
+            "appCode": "CowSwap",
+            "environment": "production",
+            "metadata": {
+                "referrer": {
+                    "version": "0.1.0",
+                    "address": "0x0101010101010101010101010101010101010101",
+                },
+                "quote": {
+                    "version": "0.1.0",
+                    "slippageBips": "50",
+                },
+            },
+        }))
+        .unwrap();
+        assert_eq!(
+            ParsedAppData::from(document),
+            ParsedAppData {
+                app_code: Some("CowSwap".to_string()),
+                referrer: Some(H160([1; 20])),
+                slippage_bips: Some(50),
+            }
+        );
+
+        let document: AppDataDocument = serde_json::from_value(json!({
+            "version": "0.5.0",
+            "metadata": {
+                "quote": {
+                    "version": "0.2.0",
+                    "slippageBips": 100,
+                },
+            },
+        }))
+        .unwrap();
+        assert_eq!(
+            ParsedAppData::from(document),
+            ParsedAppData {
+                slippage_bips: Some(100),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn parses_hooks() {
+        let document: AppDataDocument = serde_json::from_value(json!({
+            "version": "0.6.0",
+            "metadata": {
+                "hooks": {
+                    "pre": [{
+                        "target": "0x0101010101010101010101010101010101010101",
+                        "callData": "0x0102",
+                        "gasLimit": "50000",
+                    }],
+                },
+            },
+        }))
+        .unwrap();
+        assert_eq!(
+            document.metadata.hooks,
+            Hooks {
+                pre: vec![Hook {
+                    target: H160([1; 20]),
+                    call_data: vec![1, 2],
+                    gas_limit: 50_000,
+                }],
+                post: vec![],
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_document() {
+        for document in [
+            json!({}),
+            json!({ "version": 1 }),
+            json!({ "version": "0.4.0", "appCode": 1 }),
+            json!({ "version": "0.4.0", "metadata": { "referrer": { "version": "0.1.0" } } }),
+            json!({
+                "version": "0.4.0",
+                "metadata": { "referrer": { "version": "0.1.0", "address": "0x01" } },
+            }),
+            json!({
+                "version": "0.4.0",
+                "metadata": { "quote": { "version": "0.1.0", "slippageBips": "-1" } },
+            }),
+            json!({
+                "version": "0.6.0",
+                "metadata": { "hooks": { "pre": [{ "target": "0x01", "callData": "0x", "gasLimit": 1 }] } },
+            }),
+        ] {
+            assert!(
+                serde_json::from_value::<AppDataDocument>(document.clone()).is_err(),
+                "{}",
+                document
+            );
+        }
+    }
+}
@@ -1,16 +1,26 @@
 //! Module defining a batch auction.
 
-use crate::{order::Order, u256_decimal::DecimalU256};
+use crate::{
+    order::{Order, OrderUid},
+    u256_decimal::DecimalU256,
+};
 use primitive_types::{H160, U256};
 use serde::{Deserialize, Serialize};
 use serde_with::serde_as;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
+
+/// The globally unique id of an auction. Ids are assigned by the order book in
+/// increasing order and are never reused.
+pub type AuctionId = u64;
 
 /// A batch auction.
 #[serde_as]
 #[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Auction {
+    /// The unique id of the auction.
+    pub id: AuctionId,
+
     /// The block that this auction is valid for.
     /// The block number for the auction. Orders and prices are guaranteed to be
     /// valid on this block.
@@ -30,10 +40,57 @@ pub struct Auction {
     pub prices: BTreeMap<H160, U256>,
 }
 
+/// A new auction pushed to subscribers as soon as the order book computed it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuctionUpdate {
+    /// The full auction. Its block is the block the update was computed for.
+    pub auction: Auction,
+
+    /// The orders that are part of this auction but were not part of the
+    /// previous one.
+    pub added_orders: Vec<OrderUid>,
+
+    /// The orders that were part of the previous auction but are not part of
+    /// this one anymore.
+    pub removed_orders: Vec<OrderUid>,
+}
+
+impl AuctionUpdate {
+    /// Creates the update from the previous to the new auction.
+    pub fn new(previous: &Auction, auction: Auction) -> Self {
+        let uids = |auction: &Auction| {
+            auction
+                .orders
+                .iter()
+                .map(|order| order.metadata.uid)
+                .collect::<HashSet<_>>()
+        };
+        let (previous_uids, uids) = (uids(previous), uids(&auction));
+        let added_orders = auction
+            .orders
+            .iter()
+            .map(|order| order.metadata.uid)
+            .filter(|uid| !previous_uids.contains(uid))
+            .collect();
+        let removed_orders = previous
+            .orders
+            .iter()
+            .map(|order| order.metadata.uid)
+            .filter(|uid| !uids.contains(uid))
+            .collect();
+        Self {
+            auction,
+            added_orders,
+            removed_orders,
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::order::{OrderMetadata, OrderUid};
+    use crate::order::OrderMetadata;
     use maplit::btreemap;
     use serde_json::json;
 
@@ -47,6 +104,7 @@ mod tests {
             ..Default::default()
         };
         let auction = Auction {
+            id: 1,
             block: 42,
             latest_settlement_block: 40,
             orders: vec![order(1), order(2)],
@@ -59,6 +117,7 @@ mod tests {
         assert_eq!(
             serde_json::to_value(&auction).unwrap(),
             json!({
+                "id": 1,
                 "block": 42,
                 "latestSettlementBlock": 40,
                 "orders": [
@@ -76,4 +135,29 @@ mod tests {
             auction,
         );
     }
+
+    #[test]
+    fn auction_update_diffs_orders() {
+        let order = |uid_byte: u8| Order {
+            metadata: OrderMetadata {
+                uid: OrderUid([uid_byte; 56]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let previous = Auction {
+            orders: vec![order(1), order(2)],
+            ..Default::default()
+        };
+        let auction = Auction {
+            block: 1,
+            orders: vec![order(2), order(3)],
+            ..Default::default()
+        };
+
+        let update = AuctionUpdate::new(&previous, auction.clone());
+        assert_eq!(update.auction, auction);
+        assert_eq!(update.added_orders, vec![OrderUid([3; 56])]);
+        assert_eq!(update.removed_orders, vec![OrderUid([1; 56])]);
+    }
 }
@@ -1,9 +1,11 @@
 //! Contains models that are shared between the orderbook and the solver.
 
+pub mod app_data;
 pub mod app_id;
 pub mod auction;
 pub mod bytes_hex;
 pub mod order;
+pub mod order_event;
 pub mod ratio_as_decimal;
 pub mod signature;
 pub mod solver_competition;
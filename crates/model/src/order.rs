@@ -6,7 +6,7 @@ use crate::{
     u256_decimal::{self, DecimalU256},
     DomainSeparator, TokenPair,
 };
-use anyhow::{ensure, Context as _, Result};
+use anyhow::{anyhow, ensure, Context as _, Result};
 use chrono::{offset::Utc, DateTime, NaiveDateTime};
 use derivative::Derivative;
 use hex_literal::hex;
@@ -14,7 +14,7 @@ use num::BigUint;
 use primitive_types::{H160, H256, U256};
 use secp256k1::ONE_KEY;
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
-use serde_with::serde_as;
+use serde_with::{serde_as, DisplayFromStr, PickFirst};
 use std::{
     collections::HashSet,
     fmt::{self, Debug, Display},
@@ -67,7 +67,54 @@ pub enum OrderStatus {
     Expired,
 }
 
+/// A transition of an order into a new status, as pushed to clients that
+/// subscribe to order status updates.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderStatusUpdate {
+    pub uid: OrderUid,
+    pub owner: H160,
+    /// The user that placed the order through an on-chain order contract
+    /// which is the `owner`. See `OrderMetadata::onchain_user`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onchain_user: Option<H160>,
+    pub kind: OrderStatusUpdateKind,
+}
+
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderStatusUpdateKind {
+    Created,
+    PresignatureConfirmed,
+    PartiallyFilled,
+    Fulfilled,
+    Cancelled,
+    Expired,
+    /// The native token of an expired or cancelled ETH-flow order has been
+    /// refunded to the user.
+    Refunded,
+}
+
 impl Order {
+    /// The time until which the order can be filled. For ETH-flow orders this
+    /// is the validity chosen by the user and not the one of the signed order.
+    pub fn user_valid_to(&self) -> u32 {
+        match &self.metadata.ethflow_data {
+            Some(ethflow_data) => ethflow_data.user_valid_to,
+            None => self.creation.valid_to,
+        }
+    }
+
+    /// The account that signed the order and whose funds are traded. This is
+    /// the `owner` except for orders that an on-chain order contract placed
+    /// on behalf of a user, which the contract signs.
+    pub fn signer(&self) -> H160 {
+        match self.metadata.onchain_user {
+            Some(_) => self.metadata.uid.owner(),
+            None => self.metadata.owner,
+        }
+    }
+
     pub fn from_order_creation(
         order_creation: &OrderCreation,
         domain: &DomainSeparator,
@@ -86,7 +133,7 @@ impl Order {
                 is_liquidity_order,
                 ..Default::default()
             },
-            creation: *order_creation,
+            creation: order_creation.clone(),
         }
     }
 
@@ -149,6 +196,41 @@ impl Order {
             full_fee_amount: scale(self.metadata.full_fee_amount)?,
         })
     }
+
+    /// Returns the remaining amounts for the order limited to what the
+    /// `available_balance` of a partially fillable order can pay for.
+    ///
+    /// The order book includes partially fillable orders in auctions even if
+    /// the balance of their owner only covers part of their remaining amounts.
+    /// Fill-or-kill orders are never limited since they can't be executed
+    /// without the full balance.
+    pub fn executable_amounts(&self) -> Result<RemainingOrderAmounts> {
+        let remaining = self.remaining_amounts()?;
+        let balance = match self.metadata.available_balance {
+            Some(balance) if self.creation.partially_fillable => balance,
+            _ => return Ok(remaining),
+        };
+        let needed_balance = remaining
+            .sell_amount
+            .checked_add(remaining.fee_amount)
+            .context("overflow computing needed balance")?;
+        if balance >= needed_balance {
+            return Ok(remaining);
+        }
+
+        let scale = |amount: U256| -> Result<U256> {
+            amount
+                .checked_mul(balance)
+                .and_then(|product| product.checked_div(needed_balance))
+                .context("overflow scaling remaining amounts to balance")
+        };
+        Ok(RemainingOrderAmounts {
+            sell_amount: scale(remaining.sell_amount)?,
+            buy_amount: scale(remaining.buy_amount)?,
+            fee_amount: scale(remaining.fee_amount)?,
+            full_fee_amount: scale(remaining.full_fee_amount)?,
+        })
+    }
 }
 
 /// Remaining order buy, sell and fee amounts.
@@ -257,7 +339,7 @@ impl OrderBuilder {
 
 /// An order as provided to the orderbook by the frontend.
 #[serde_as]
-#[derive(Eq, PartialEq, Copy, Clone, Deserialize, Debug, Serialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Deserialize, Debug, Serialize, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct OrderCreation {
     pub sell_token: H160,
@@ -280,13 +362,23 @@ pub struct OrderCreation {
     pub sell_token_balance: SellTokenSource,
     #[serde(default)]
     pub buy_token_balance: BuyTokenDestination,
+    /// The quote the order was placed against. It is not part of the signed
+    /// order data.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quote_id: Option<QuoteId>,
 }
 
+/// Identifies a quote that was stored by the order book.
+pub type QuoteId = i64;
+
 #[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub struct OrderCreationPayload {
     #[serde(flatten)]
     pub order_creation: OrderCreation,
     pub from: Option<H160>,
+    /// Like the quote id this is not part of the signed order data.
+    #[serde(default)]
+    pub class: OrderClass,
 }
 
 impl Default for OrderCreation {
@@ -307,6 +399,7 @@ impl Default for OrderCreation {
             signature: Default::default(),
             sell_token_balance: Default::default(),
             buy_token_balance: Default::default(),
+            quote_id: None,
         };
         result.signature = EcdsaSignature::sign(
             signing_scheme,
@@ -435,6 +528,68 @@ impl OrderCancellation {
     }
 }
 
+/// A cancellation of multiple orders that is signed once.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderCancellations {
+    pub order_uids: Vec<OrderUid>,
+}
+
+// EIP-712
+impl OrderCancellations {
+    // keccak256("OrderCancellations(bytes[] orderUids)")
+    const TYPE_HASH: [u8; 32] =
+        hex!("4c89efb91ae246f78d2fe68b47db2fa1444a121a4f2dc3fda7a5a408c2e3588e");
+
+    pub fn hash_struct(&self) -> [u8; 32] {
+        // Arrays are encoded as the hash of their concatenated encoded
+        // elements and dynamic `bytes` elements are encoded as their hash.
+        let mut encoded_uids = Vec::with_capacity(32 * self.order_uids.len());
+        for uid in &self.order_uids {
+            encoded_uids.extend_from_slice(&signing::keccak256(&uid.0));
+        }
+        let mut hash_data = [0u8; 64];
+        hash_data[0..32].copy_from_slice(&Self::TYPE_HASH);
+        hash_data[32..64].copy_from_slice(&signing::keccak256(&encoded_uids));
+        signing::keccak256(&hash_data)
+    }
+}
+
+/// Multiple order cancellations as provided to the orderbook by the frontend.
+#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedOrderCancellations {
+    #[serde(flatten)]
+    pub data: OrderCancellations,
+    pub signature: EcdsaSignature,
+    pub signing_scheme: EcdsaSigningScheme,
+}
+
+impl SignedOrderCancellations {
+    pub fn sign(
+        data: OrderCancellations,
+        signing_scheme: EcdsaSigningScheme,
+        domain_separator: &DomainSeparator,
+        key: SecretKeyRef,
+    ) -> Self {
+        let signature =
+            EcdsaSignature::sign(signing_scheme, domain_separator, &data.hash_struct(), key);
+        Self {
+            data,
+            signature,
+            signing_scheme,
+        }
+    }
+
+    pub fn validate(&self, domain_separator: &DomainSeparator) -> Option<H160> {
+        self.signature.validate(
+            self.signing_scheme,
+            domain_separator,
+            &self.data.hash_struct(),
+        )
+    }
+}
+
 /// An order as provided to the orderbook by the frontend.
 #[serde_as]
 #[derive(Eq, PartialEq, Clone, Derivative, Deserialize, Serialize, Hash)]
@@ -462,6 +617,36 @@ pub struct OrderMetadata {
     #[serde(default, with = "u256_decimal")]
     pub full_fee_amount: U256,
     pub is_liquidity_order: bool,
+    #[serde(default)]
+    pub class: OrderClass,
+    /// The fee that is taken from the surplus when a limit order is executed.
+    /// It is not signed by the user but computed for every auction from the
+    /// current gas and native token prices, so it is zero outside of auctions
+    /// and for market orders.
+    #[serde(default, with = "u256_decimal")]
+    pub surplus_fee: U256,
+    /// The user that placed the order through an on-chain order contract like
+    /// the ETH-flow contract. The user is the `owner` of such orders while
+    /// the contract signs them. See `Order::signer`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub onchain_user: Option<H160>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ethflow_data: Option<EthflowData>,
+    /// Interactions that the settlement executes before and after the order's
+    /// trade. They are declared in the order's app data document.
+    #[serde(default, skip_serializing_if = "Hooks::is_empty")]
+    pub hooks: Hooks,
+}
+
+/// Additional data of orders placed through the ETH-flow contract.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Default, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct EthflowData {
+    /// The validity chosen by the user. The order signed by the contract is
+    /// valid forever and becomes unfillable once the user's ETH is refunded.
+    pub user_valid_to: u32,
+    /// The transaction that refunded the user's ETH.
+    pub refund_tx_hash: Option<H256>,
 }
 
 impl Default for OrderMetadata {
@@ -480,10 +665,58 @@ impl Default for OrderMetadata {
             settlement_contract: H160::default(),
             full_fee_amount: U256::default(),
             is_liquidity_order: false,
+            class: OrderClass::default(),
+            surplus_fee: U256::default(),
+            onchain_user: None,
+            ethflow_data: None,
+            hooks: Hooks::default(),
         }
     }
 }
 
+/// The hooks of an order.
+#[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize, Hash)]
+pub struct Hooks {
+    /// Executed before any of the settlement's trades, for example to set an
+    /// allowance with a permit.
+    #[serde(default)]
+    pub pre: Vec<Hook>,
+    /// Executed after all of the settlement's trades, for example to bridge
+    /// the bought tokens.
+    #[serde(default)]
+    pub post: Vec<Hook>,
+}
+
+impl Hooks {
+    pub fn is_empty(&self) -> bool {
+        self.pre.is_empty() && self.post.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Hook> {
+        self.pre.iter().chain(&self.post)
+    }
+
+    /// The sum of the gas limits of all hooks.
+    pub fn gas_limit(&self) -> u64 {
+        self.iter()
+            .fold(0, |total, hook| total.saturating_add(hook.gas_limit))
+    }
+}
+
+/// A call that the settlement contract executes on behalf of an order.
+#[serde_as]
+#[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct Hook {
+    pub target: H160,
+    #[serde(with = "crate::bytes_hex")]
+    pub call_data: Vec<u8>,
+    /// The maximum amount of gas the call is expected to use. Like other
+    /// numbers in app data documents it can be a string or a number.
+    #[serde_as(as = "PickFirst<(DisplayFromStr, _)>")]
+    pub gas_limit: u64,
+}
+
 // uid as 56 bytes: 32 for orderDigest, 20 for ownerAddress and 4 for validTo
 #[derive(Clone, Copy, Eq, Hash, PartialEq)]
 pub struct OrderUid(pub [u8; 56]);
@@ -495,6 +728,11 @@ impl OrderUid {
         uid.0[0..4].copy_from_slice(&i.to_le_bytes());
         uid
     }
+
+    /// The owner address that is encoded in the uid.
+    pub fn owner(&self) -> H160 {
+        H160::from_slice(&self.0[32..52])
+    }
 }
 
 impl FromStr for OrderUid {
@@ -593,12 +831,43 @@ impl OrderKind {
     }
 }
 
+impl OrderKind {
+    /// Decodes the order kind the way it is encoded in the order struct of
+    /// the settlement contract.
+    pub fn from_contract_bytes(bytes: [u8; 32]) -> Result<Self> {
+        match bytes {
+            OrderCreation::KIND_SELL => Ok(Self::Sell),
+            OrderCreation::KIND_BUY => Ok(Self::Buy),
+            _ => Err(anyhow!("unknown order kind {:?}", H256(bytes))),
+        }
+    }
+}
+
 impl Default for OrderKind {
     fn default() -> Self {
         Self::Buy
     }
 }
 
+/// Determines how the protocol fee of an order is charged.
+#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderClass {
+    /// Orders that are expected to be executed right away and pay the fee
+    /// they signed.
+    Market,
+    /// Orders that stay in the book until the market reaches their limit price.
+    /// They sign a zero fee and the protocol fee is taken from the surplus at
+    /// execution time instead.
+    Limit,
+}
+
+impl Default for OrderClass {
+    fn default() -> Self {
+        Self::Market
+    }
+}
+
 /// Source from which the sellAmount should be drawn upon order fulfilment
 #[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash, enum_utils::FromStr)]
 #[enumeration(case_insensitive)]
@@ -612,6 +881,19 @@ pub enum SellTokenSource {
     External,
 }
 
+impl SellTokenSource {
+    /// Decodes the sell token source the way it is encoded in the order
+    /// struct of the settlement contract.
+    pub fn from_contract_bytes(bytes: [u8; 32]) -> Result<Self> {
+        match bytes {
+            OrderCreation::BALANCE_ERC20 => Ok(Self::Erc20),
+            OrderCreation::BALANCE_EXTERNAL => Ok(Self::External),
+            OrderCreation::BALANCE_INTERNAL => Ok(Self::Internal),
+            _ => Err(anyhow!("unknown sell token source {:?}", H256(bytes))),
+        }
+    }
+}
+
 impl Default for SellTokenSource {
     fn default() -> Self {
         Self::Erc20
@@ -629,6 +911,18 @@ pub enum BuyTokenDestination {
     Internal,
 }
 
+impl BuyTokenDestination {
+    /// Decodes the buy token destination the way it is encoded in the order
+    /// struct of the settlement contract.
+    pub fn from_contract_bytes(bytes: [u8; 32]) -> Result<Self> {
+        match bytes {
+            OrderCreation::BALANCE_ERC20 => Ok(Self::Erc20),
+            OrderCreation::BALANCE_INTERNAL => Ok(Self::Internal),
+            _ => Err(anyhow!("unknown buy token destination {:?}", H256(bytes))),
+        }
+    }
+}
+
 impl Default for BuyTokenDestination {
     fn default() -> Self {
         Self::Erc20
@@ -700,6 +994,8 @@ mod tests {
             "sellTokenBalance": "external",
             "buyTokenBalance": "internal",
             "isLiquidityOrder": false,
+            "class": "limit",
+            "surplusFee": "2",
         });
         let signing_scheme = EcdsaSigningScheme::Eip712;
         let expected = Order {
@@ -717,6 +1013,11 @@ mod tests {
                 settlement_contract: H160::from_low_u64_be(2),
                 full_fee_amount: U256::MAX,
                 is_liquidity_order: false,
+                class: OrderClass::Limit,
+                surplus_fee: 2.into(),
+                onchain_user: None,
+                ethflow_data: None,
+                hooks: Hooks::default(),
             },
             creation: OrderCreation {
                 sell_token: H160::from_low_u64_be(10),
@@ -745,6 +1046,7 @@ mod tests {
                 .to_signature(signing_scheme),
                 sell_token_balance: SellTokenSource::External,
                 buy_token_balance: BuyTokenDestination::Internal,
+                quote_id: None,
             },
         };
         let deserialized: Order = serde_json::from_value(value.clone()).unwrap();
@@ -753,6 +1055,39 @@ mod tests {
         assert_eq!(serialized, value);
     }
 
+    #[test]
+    fn hooks_serialization() {
+        let hooks = Hooks {
+            pre: vec![Hook {
+                target: H160([1; 20]),
+                call_data: vec![0x01, 0x02],
+                gas_limit: 50_000,
+            }],
+            post: vec![],
+        };
+        let value = json!({
+            "pre": [{
+                "target": "0x0101010101010101010101010101010101010101",
+                "callData": "0x0102",
+                "gasLimit": "50000",
+            }],
+            "post": [],
+        });
+        assert_eq!(serde_json::to_value(&hooks).unwrap(), value);
+        assert_eq!(serde_json::from_value::<Hooks>(value).unwrap(), hooks);
+
+        let hooks: Hooks = serde_json::from_value(json!({
+            "post": [{
+                "target": "0x0101010101010101010101010101010101010101",
+                "callData": "0x",
+                "gasLimit": 100000,
+            }],
+        }))
+        .unwrap();
+        assert!(hooks.pre.is_empty());
+        assert_eq!(hooks.gas_limit(), 100_000);
+    }
+
     // from the test `should recover signing address for all supported ECDSA-based schemes` in
     // <https://github.com/cowprotocol/contracts/blob/v1.1.2/test/GPv2Signing.test.ts#L280>.
     #[test]
@@ -796,6 +1131,7 @@ mod tests {
                 sell_token_balance: SellTokenSource::Erc20,
                 buy_token_balance: BuyTokenDestination::Erc20,
                 signature: Signature::from_bytes(*signing_scheme, signature).unwrap(),
+                quote_id: None,
             };
 
             let owner = order
@@ -868,6 +1204,37 @@ mod tests {
         }
     }
 
+    #[test]
+    fn order_cancellations_type_hash() {
+        assert_eq!(
+            OrderCancellations::TYPE_HASH,
+            keccak256(b"OrderCancellations(bytes[] orderUids)")
+        );
+    }
+
+    #[test]
+    fn order_cancellations_signature_roundtrip() {
+        let domain_separator = DomainSeparator([1; 32]);
+        let owner = SecretKeyRef::new(&ONE_KEY).address();
+        let data = OrderCancellations {
+            order_uids: vec![OrderUid([1; 56]), OrderUid([2; 56])],
+        };
+        for signing_scheme in &[EcdsaSigningScheme::Eip712, EcdsaSigningScheme::EthSign] {
+            let cancellations = SignedOrderCancellations::sign(
+                data.clone(),
+                *signing_scheme,
+                &domain_separator,
+                SecretKeyRef::new(&ONE_KEY),
+            );
+            assert_eq!(cancellations.validate(&domain_separator), Some(owner));
+
+            // The signature covers the exact list of orders.
+            let mut other = cancellations.clone();
+            other.data.order_uids.pop();
+            assert_ne!(other.validate(&domain_separator), Some(owner));
+        }
+    }
+
     #[test]
     fn domain_separator_does_not_panic_in_debug() {
         println!("{:?}", DomainSeparator::default());
@@ -1055,6 +1422,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn executable_amounts_are_limited_by_balance() {
+        let order = Order {
+            creation: OrderCreation {
+                sell_amount: 80.into(),
+                buy_amount: 40.into(),
+                fee_amount: 20.into(),
+                partially_fillable: true,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                full_fee_amount: 40.into(),
+                available_balance: Some(50.into()),
+                ..Default::default()
+            },
+        };
+        assert_eq!(
+            order.executable_amounts().unwrap(),
+            RemainingOrderAmounts {
+                sell_amount: 40.into(),
+                buy_amount: 20.into(),
+                fee_amount: 10.into(),
+                full_fee_amount: 20.into(),
+            }
+        );
+
+        // Enough balance or no balance at all doesn't limit the order.
+        for available_balance in [Some(100.into()), None] {
+            let order = Order {
+                metadata: OrderMetadata {
+                    available_balance,
+                    ..order.metadata.clone()
+                },
+                ..order.clone()
+            };
+            assert_eq!(
+                order.executable_amounts().unwrap(),
+                order.remaining_amounts().unwrap()
+            );
+        }
+
+        // Fill-or-kill orders are never limited.
+        let order = Order {
+            creation: OrderCreation {
+                partially_fillable: false,
+                ..order.creation.clone()
+            },
+            ..order
+        };
+        assert_eq!(
+            order.executable_amounts().unwrap(),
+            order.remaining_amounts().unwrap()
+        );
+    }
+
     #[test]
     fn remaining_amount_errors() {
         // Partially fillable order overflow when computing fill ratio.
@@ -1117,4 +1539,45 @@ mod tests {
         .remaining_amounts()
         .is_err());
     }
+
+    #[test]
+    fn decodes_contract_order_enums() {
+        assert_eq!(
+            OrderKind::from_contract_bytes(OrderCreation::KIND_SELL).unwrap(),
+            OrderKind::Sell
+        );
+        assert_eq!(
+            OrderKind::from_contract_bytes(OrderCreation::KIND_BUY).unwrap(),
+            OrderKind::Buy
+        );
+        assert!(OrderKind::from_contract_bytes(OrderCreation::BALANCE_ERC20).is_err());
+
+        assert_eq!(
+            SellTokenSource::from_contract_bytes(OrderCreation::BALANCE_EXTERNAL).unwrap(),
+            SellTokenSource::External
+        );
+        assert_eq!(
+            BuyTokenDestination::from_contract_bytes(OrderCreation::BALANCE_INTERNAL).unwrap(),
+            BuyTokenDestination::Internal
+        );
+        assert!(BuyTokenDestination::from_contract_bytes(OrderCreation::BALANCE_EXTERNAL).is_err());
+    }
+
+    #[test]
+    fn onchain_orders_are_signed_by_the_contract() {
+        let mut uid = OrderUid::default();
+        uid.0[32..52].copy_from_slice(&[1; 20]);
+        let mut order = Order {
+            metadata: OrderMetadata {
+                owner: H160([2; 20]),
+                uid,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(order.signer(), H160([2; 20]));
+
+        order.metadata.onchain_user = Some(H160([2; 20]));
+        assert_eq!(order.signer(), H160([1; 20]));
+    }
 }
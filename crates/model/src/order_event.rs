@@ -0,0 +1,81 @@
+//! Contains the audit trail of an order's lifecycle as exposed by the order book api.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A transition in the lifecycle of an order.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderEvent {
+    pub timestamp: DateTime<Utc>,
+    pub label: OrderEventLabel,
+    /// Why the transition happened. Only set for some labels.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<OrderEventReason>,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderEventLabel {
+    /// The order was added to the order book.
+    Created,
+    /// The order became part of the solvable orders.
+    Ready,
+    /// The order was removed from the solvable orders for the given reason.
+    Filtered,
+    /// The presignature of the order was set on chain.
+    Presigned,
+    /// The order was (partially) executed.
+    Traded,
+    /// The order was cancelled, either off or on chain.
+    Cancelled,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OrderEventReason {
+    BannedUser,
+    UnsupportedToken,
+    InvalidSignature,
+    InsufficientBalance,
+    MissingNativePrice,
+    /// The surplus fee of a limit order exceeds its remaining sell amount.
+    InsufficientSurplusFee,
+    OffChain,
+    OnChain,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDateTime;
+    use serde_json::json;
+
+    #[test]
+    fn serialization() {
+        let event = OrderEvent {
+            timestamp: DateTime::from_utc(NaiveDateTime::from_timestamp(3, 0), Utc),
+            label: OrderEventLabel::Filtered,
+            reason: Some(OrderEventReason::InsufficientBalance),
+        };
+        let value = json!({
+            "timestamp": "1970-01-01T00:00:03Z",
+            "label": "filtered",
+            "reason": "insufficientBalance",
+        });
+        assert_eq!(serde_json::to_value(&event).unwrap(), value);
+        assert_eq!(serde_json::from_value::<OrderEvent>(value).unwrap(), event);
+
+        let event = OrderEvent {
+            reason: None,
+            label: OrderEventLabel::Created,
+            ..event
+        };
+        let value = json!({
+            "timestamp": "1970-01-01T00:00:03Z",
+            "label": "created",
+        });
+        assert_eq!(serde_json::to_value(&event).unwrap(), value);
+        assert_eq!(serde_json::from_value::<OrderEvent>(value).unwrap(), event);
+    }
+}
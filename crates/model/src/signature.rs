@@ -13,14 +13,18 @@ use web3::{
 pub enum SigningScheme {
     Eip712,
     EthSign,
+    Eip1271,
     PreSign,
 }
-#[derive(Eq, PartialEq, Clone, Copy, Debug, Deserialize, Serialize, Hash)]
+#[derive(Eq, PartialEq, Clone, Debug, Deserialize, Serialize, Hash)]
 #[serde(rename_all = "lowercase")]
 #[serde(tag = "signingScheme", content = "signature")]
 pub enum Signature {
     Eip712(EcdsaSignature),
     EthSign(EcdsaSignature),
+    /// Smart contract signature bytes that are verified by calling
+    /// `isValidSignature` on the order owner.
+    Eip1271(#[serde(with = "crate::bytes_hex")] Vec<u8>),
     PreSign(H160),
 }
 
@@ -35,12 +39,18 @@ impl Signature {
         match scheme {
             SigningScheme::Eip712 => Signature::Eip712(Default::default()),
             SigningScheme::EthSign => Signature::EthSign(Default::default()),
+            SigningScheme::Eip1271 => Signature::Eip1271(Default::default()),
             SigningScheme::PreSign => Signature::PreSign(Default::default()),
         }
     }
 }
 
 impl Signature {
+    /// Recovers the owner of the signed struct.
+    ///
+    /// EIP-1271 signatures do not encode their signer, so `None` is returned
+    /// for them and the owner has to be specified separately and verified
+    /// on-chain.
     pub fn validate(
         &self,
         domain_separator: &DomainSeparator,
@@ -54,6 +64,7 @@ impl Signature {
                 domain_separator,
                 struct_hash,
             ),
+            Signature::Eip1271(_) => None,
             Signature::PreSign(account) => Some(*account),
         }
     }
@@ -75,6 +86,7 @@ impl Signature {
                         .expect("scheme is an ecdsa scheme"),
                 )
             }
+            SigningScheme::Eip1271 => Signature::Eip1271(bytes.to_vec()),
             SigningScheme::PreSign => Signature::PreSign(H160(
                 bytes
                     .try_into()
@@ -87,14 +99,26 @@ impl Signature {
     pub fn to_bytes(&self) -> Vec<u8> {
         match self {
             Signature::Eip712(sig) | Signature::EthSign(sig) => sig.to_bytes().to_vec(),
+            Signature::Eip1271(signature) => signature.clone(),
             Signature::PreSign(account) => account.0.to_vec(),
         }
     }
 
+    /// Encodes the signature the way the settlement contract expects it in a
+    /// trade. Smart contract signatures are prefixed with the address of the
+    /// verifying contract, which is the order owner.
+    pub fn encode_for_settlement(&self, owner: H160) -> Vec<u8> {
+        match self {
+            Signature::Eip1271(signature) => [owner.as_bytes(), signature].concat(),
+            _ => self.to_bytes(),
+        }
+    }
+
     pub fn scheme(&self) -> SigningScheme {
         match self {
             Signature::Eip712(_) => SigningScheme::Eip712,
             Signature::EthSign(_) => SigningScheme::EthSign,
+            Signature::Eip1271(_) => SigningScheme::Eip1271,
             Signature::PreSign(_) => SigningScheme::PreSign,
         }
     }
@@ -125,7 +149,7 @@ impl SigningScheme {
         match self {
             Self::Eip712 => Some(EcdsaSigningScheme::Eip712),
             Self::EthSign => Some(EcdsaSigningScheme::EthSign),
-            Self::PreSign => None,
+            Self::Eip1271 | Self::PreSign => None,
         }
     }
 }
@@ -327,6 +351,14 @@ mod tests {
             Signature::from_bytes(SigningScheme::PreSign, &[0u8; 20]).unwrap(),
             Signature::default_with(SigningScheme::PreSign)
         );
+        assert_eq!(
+            Signature::from_bytes(SigningScheme::Eip1271, &[]).unwrap(),
+            Signature::default_with(SigningScheme::Eip1271)
+        );
+        assert_eq!(
+            Signature::from_bytes(SigningScheme::Eip1271, &[1, 2, 3]).unwrap(),
+            Signature::Eip1271(vec![1, 2, 3])
+        );
     }
 
     #[test]
@@ -350,6 +382,32 @@ mod tests {
                 .to_bytes(),
             [1u8; 20].to_vec()
         );
+        assert_eq!(Signature::Eip1271(vec![1, 2, 3]).to_bytes(), vec![1, 2, 3]);
+    }
+
+    #[test]
+    fn eip1271_signature_does_not_recover_owner() {
+        assert_eq!(
+            Signature::Eip1271(vec![0x42; 65]).validate(&Default::default(), &Default::default()),
+            None,
+        );
+    }
+
+    #[test]
+    fn encode_signature_for_settlement() {
+        let owner = H160([0x42; 20]);
+        assert_eq!(
+            Signature::default_with(SigningScheme::Eip712).encode_for_settlement(owner),
+            [0u8; 65].to_vec()
+        );
+        assert_eq!(
+            Signature::PreSign(owner).encode_for_settlement(owner),
+            [0x42; 20].to_vec()
+        );
+        assert_eq!(
+            Signature::Eip1271(vec![1, 2, 3]).encode_for_settlement(owner),
+            [&[0x42; 20][..], &[1, 2, 3]].concat()
+        );
     }
 
     #[test]
@@ -358,7 +416,8 @@ mod tests {
             let scheme = SigningScheme::from(ecdsa_scheme);
             assert!(scheme.is_ecdsa_scheme())
         }
-        assert!(!SigningScheme::PreSign.is_ecdsa_scheme())
+        assert!(!SigningScheme::PreSign.is_ecdsa_scheme());
+        assert!(!SigningScheme::Eip1271.is_ecdsa_scheme());
     }
 
     #[test]
@@ -395,4 +454,18 @@ mod tests {
             "failed to decode \"42\" as hex ecdsa signature: Invalid string length"
         );
     }
+
+    #[test]
+    fn eip1271_deserialize_and_back() {
+        let value = json!(
+        {
+            "signature": "0x010203",
+            "signingScheme": "eip1271"
+        });
+        let expected = Signature::Eip1271(vec![1, 2, 3]);
+        let deserialized: Signature = serde_json::from_value(value.clone()).unwrap();
+        assert_eq!(deserialized, expected);
+        let serialized = serde_json::to_value(expected).unwrap();
+        assert_eq!(value, serialized);
+    }
 }
@@ -10,6 +10,8 @@ use std::collections::HashMap;
 #[derive(Clone, Debug, Default, Deserialize, Serialize, PartialEq)]
 #[serde(rename_all = "camelCase")]
 pub struct SolverCompetitionResponse {
+    #[serde(default)]
+    pub auction_id: u64,
     pub gas_price: f64,
     pub liquidity_collected_block: u64,
     pub competition_simulation_block: u64,
@@ -56,6 +58,7 @@ mod tests {
     #[test]
     fn serialize() {
         let correct = serde_json::json!({
+            "auctionId": 0,
             "transactionHash": "0x1111111111111111111111111111111111111111111111111111111111111111",
             "gasPrice": 1.0f64,
             "liquidityCollectedBlock": 14u64,
@@ -88,6 +91,7 @@ mod tests {
         ));
         let order_id =OrderUid(hex!("1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111"));
         let orig = SolverCompetitionResponse {
+            auction_id: 0,
             gas_price: 1.,
             liquidity_collected_block: 14,
             competition_simulation_block: 15,
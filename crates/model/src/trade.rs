@@ -1,9 +1,10 @@
 //! Contains the Trade type as described by the specification with serialization as described by the openapi documentation.
 
 use crate::order::OrderUid;
-use num::BigUint;
+use num::{BigInt, BigUint};
 use primitive_types::{H160, H256};
 use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
 
 #[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -25,6 +26,34 @@ pub struct Trade {
     pub tx_hash: Option<H256>,
 }
 
+/// The fees and surplus of a trade as they were accounted in its settlement.
+#[serde_as]
+#[derive(Eq, PartialEq, Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TradeAccounting {
+    pub order_uid: OrderUid,
+    pub solver: H160,
+    pub sell_token: H160,
+    /// The fee in sell token that was taken from the trade.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub executed_fee: BigUint,
+    /// The fee in sell token the user signed for the whole order.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub subsidized_fee: BigUint,
+    /// The fee in sell token the whole order would have paid without subsidies.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub unsubsidized_fee: BigUint,
+    /// The amount in sell token the trade was executed better than the
+    /// order's limit price.
+    #[serde(with = "serde_with::rust::display_fromstr")]
+    pub surplus_in_sell_token: BigInt,
+    /// The surplus converted with the sell token price the order's fee was
+    /// computed with. Missing if the order has no stored fee parameters.
+    #[serde_as(as = "Option<DisplayFromStr>")]
+    #[serde(default)]
+    pub surplus_in_native_token: Option<BigInt>,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,6 +93,35 @@ mod tests {
         assert_eq!(serialized, value);
     }
 
+    #[test]
+    fn trade_accounting_serialization() {
+        let accounting = TradeAccounting {
+            order_uid: OrderUid([17u8; 56]),
+            solver: H160::from_low_u64_be(1),
+            sell_token: H160::from_low_u64_be(2),
+            executed_fee: BigUint::from(3u8),
+            subsidized_fee: BigUint::from(4u8),
+            unsubsidized_fee: BigUint::from(5u8),
+            surplus_in_sell_token: BigInt::from(-6),
+            surplus_in_native_token: Some(BigInt::from(7)),
+        };
+        let value = json!({
+            "orderUid": "0x1111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111111",
+            "solver": "0x0000000000000000000000000000000000000001",
+            "sellToken": "0x0000000000000000000000000000000000000002",
+            "executedFee": "3",
+            "subsidizedFee": "4",
+            "unsubsidizedFee": "5",
+            "surplusInSellToken": "-6",
+            "surplusInNativeToken": "7",
+        });
+        assert_eq!(serde_json::to_value(&accounting).unwrap(), value);
+        assert_eq!(
+            serde_json::from_value::<TradeAccounting>(value).unwrap(),
+            accounting
+        );
+    }
+
     #[test]
     fn debug_trade_data() {
         dbg!(Trade::default());
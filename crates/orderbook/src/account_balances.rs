@@ -1,3 +1,5 @@
+pub mod event_cache;
+
 use anyhow::{anyhow, Context, Result};
 use contracts::{BalancerV2Vault, ERC20};
 use ethcontract::{batch::CallBatch, Account};
@@ -18,7 +20,7 @@ pub struct Query {
 impl Query {
     pub fn from_order(o: &Order) -> Self {
         Self {
-            owner: o.metadata.owner,
+            owner: o.signer(),
             token: o.creation.sell_token,
             source: o.creation.sell_token_balance,
         }
@@ -0,0 +1,643 @@
+//! A balance cache that is kept up to date by indexing the events that can
+//! change the balance of an owner instead of refetching every balance on every
+//! block.
+//!
+//! For every new block we fetch the ERC20 `Transfer` and `Approval` events of
+//! the cached tokens as well as the Balancer Vault events affecting internal
+//! balances and relayer approvals and drop all cache entries they touch. Tokens
+//! that change balances without emitting standard events (for example rebasing
+//! tokens) are always fetched from the node.
+
+use super::{BalanceFetching, Query, TransferSimulationError};
+use anyhow::{anyhow, Context, Result};
+use model::order::SellTokenSource;
+use primitive_types::{H160, H256, U256};
+use shared::{
+    current_block::{block_number, CurrentBlockStream},
+    Web3,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+use tokio::sync::Mutex;
+use web3::{
+    signing::keccak256,
+    types::{BlockId, BlockNumber, FilterBuilder, Log},
+};
+
+/// If more blocks than this passed since the last update we drop the whole
+/// cache instead of fetching the events of all the missed blocks.
+const MAX_BLOCK_GAP: u64 = 10;
+
+/// Entries are refetched after this many blocks even if no event touched them,
+/// in case an event was missed or a balance changed without one.
+const MAX_ENTRY_AGE_BLOCKS: u64 = 100;
+
+pub struct EventBalanceCache {
+    inner: Arc<dyn BalanceFetching>,
+    web3: Web3,
+    vault: Option<H160>,
+    vault_relayer: H160,
+    always_fetch: HashSet<H160>,
+    current_block: CurrentBlockStream,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Number and hash of the block up to which events have been processed.
+    block: Option<(u64, H256)>,
+    balances: HashMap<Query, Entry>,
+    /// Tokens that were seen emitting events we cannot interpret.
+    non_standard_tokens: HashSet<H160>,
+}
+
+struct Entry {
+    balance: U256,
+    /// The block at which the balance was fetched.
+    fetched: u64,
+}
+
+impl EventBalanceCache {
+    /// Balances of `always_fetch` tokens are never cached.
+    pub fn new(
+        inner: Arc<dyn BalanceFetching>,
+        web3: Web3,
+        vault: Option<H160>,
+        vault_relayer: H160,
+        always_fetch: HashSet<H160>,
+        current_block: CurrentBlockStream,
+    ) -> Self {
+        Self {
+            inner,
+            web3,
+            vault,
+            vault_relayer,
+            always_fetch,
+            current_block,
+            state: Default::default(),
+        }
+    }
+
+    fn is_cacheable(&self, state: &State, query: &Query) -> bool {
+        !self.always_fetch.contains(&query.token)
+            && !state.non_standard_tokens.contains(&query.token)
+    }
+
+    /// Processes the events of all blocks since the last update. Drops the
+    /// whole cache when this isn't possible, for example after a reorg.
+    async fn update(&self, state: &mut State) -> Result<()> {
+        let block = self.current_block.borrow().clone();
+        let number = block_number(&block)?;
+        let hash = block.hash.context("block without hash")?;
+
+        let filter = match state.block {
+            Some((last_number, last_hash)) if last_number == number && last_hash == hash => {
+                return Ok(())
+            }
+            Some((last_number, last_hash))
+                if last_number + 1 == number && block.parent_hash == last_hash =>
+            {
+                Some(FilterBuilder::default().block_hash(hash))
+            }
+            Some((last_number, last_hash))
+                if last_number < number
+                    && number - last_number <= MAX_BLOCK_GAP
+                    && self.block_hash(last_number).await? == Some(last_hash) =>
+            {
+                Some(
+                    FilterBuilder::default()
+                        .from_block(BlockNumber::Number((last_number + 1).into()))
+                        .to_block(BlockNumber::Number(number.into())),
+                )
+            }
+            _ => None,
+        };
+
+        match filter {
+            Some(filter) if !state.balances.is_empty() => {
+                let mut addresses = state
+                    .balances
+                    .keys()
+                    .map(|query| query.token)
+                    .collect::<HashSet<_>>();
+                addresses.extend(self.vault);
+                let filter = filter
+                    .address(addresses.into_iter().collect())
+                    .topics(Some(event_topics().to_vec()), None, None, None)
+                    .build();
+                let logs = self.web3.eth().logs(filter).await.context("logs")?;
+                let invalidations = Invalidations::from_logs(&logs, self.vault, self.vault_relayer);
+                invalidations.apply(state);
+            }
+            Some(_) => (),
+            None => state.balances.clear(),
+        }
+
+        state
+            .balances
+            .retain(|_, entry| number.saturating_sub(entry.fetched) <= MAX_ENTRY_AGE_BLOCKS);
+        state.block = Some((number, hash));
+        Ok(())
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<Option<H256>> {
+        let block = self
+            .web3
+            .eth()
+            .block(BlockId::Number(BlockNumber::Number(number.into())))
+            .await
+            .context("block")?;
+        Ok(block.and_then(|block| block.hash))
+    }
+}
+
+#[async_trait::async_trait]
+impl BalanceFetching for EventBalanceCache {
+    async fn get_balances(&self, queries: &[Query]) -> Vec<Result<U256>> {
+        let mut results = queries.iter().map(|_| None).collect::<Vec<_>>();
+        let (block, missing) = {
+            let mut state = self.state.lock().await;
+            if let Err(err) = self.update(&mut state).await {
+                tracing::warn!(?err, "failed to update balance cache");
+                *state = Default::default();
+            }
+            let block = state.block;
+            let mut missing = Vec::new();
+            for (i, query) in queries.iter().enumerate() {
+                match state.balances.get(query) {
+                    Some(entry) => results[i] = Some(Ok(entry.balance)),
+                    None => missing.push(i),
+                }
+            }
+            (block, missing)
+        };
+
+        let missing_queries = missing.iter().map(|i| queries[*i]).collect::<Vec<_>>();
+        tracing::debug!(
+            cached = queries.len() - missing.len(),
+            fetched = missing.len(),
+            "balance cache"
+        );
+        let fetched = self.inner.get_balances(&missing_queries).await;
+
+        let mut state = self.state.lock().await;
+        // Only cache the new balances if no events were processed in the mean
+        // time, otherwise we might miss their invalidations.
+        let cache = block.is_some() && state.block == block;
+        for (i, result) in missing.into_iter().zip(fetched) {
+            let query = queries[i];
+            if let (true, Ok(balance)) = (cache, &result) {
+                if self.is_cacheable(&state, &query) {
+                    state.balances.insert(
+                        query,
+                        Entry {
+                            balance: *balance,
+                            fetched: block.unwrap().0,
+                        },
+                    );
+                }
+            }
+            results[i] = Some(result);
+        }
+
+        results
+            .into_iter()
+            .map(|result| result.unwrap_or_else(|| Err(anyhow!("missing balance result"))))
+            .collect()
+    }
+
+    async fn can_transfer(
+        &self,
+        token: H160,
+        from: H160,
+        amount: U256,
+        source: SellTokenSource,
+    ) -> Result<(), TransferSimulationError> {
+        self.inner.can_transfer(token, from, amount, source).await
+    }
+}
+
+fn event_topic(signature: &str) -> H256 {
+    H256(keccak256(signature.as_bytes()))
+}
+
+/// `Transfer`, `Approval`, `InternalBalanceChanged` and
+/// `RelayerApprovalChanged` in that order.
+fn event_topics() -> [H256; 4] {
+    [
+        event_topic("Transfer(address,address,uint256)"),
+        event_topic("Approval(address,address,uint256)"),
+        event_topic("InternalBalanceChanged(address,address,int256)"),
+        event_topic("RelayerApprovalChanged(address,address,bool)"),
+    ]
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct Invalidations {
+    /// Token and owner pairs whose balances changed.
+    balances: HashSet<(H160, H160)>,
+    /// Owners whose relayer approval on the vault changed.
+    owners: HashSet<H160>,
+    /// Tokens emitting events without the standard indexed topics.
+    non_standard_tokens: HashSet<H160>,
+}
+
+impl Invalidations {
+    fn from_logs(logs: &[Log], vault: Option<H160>, vault_relayer: H160) -> Self {
+        let [transfer, approval, internal_balance_changed, relayer_approval_changed] =
+            event_topics();
+        let address = |topic: &H256| H160::from(*topic);
+        let mut result = Self::default();
+        for log in logs {
+            let topics = log.topics.as_slice();
+            if Some(log.address) == vault {
+                match topics {
+                    [topic, user, token] if *topic == internal_balance_changed => {
+                        result.balances.insert((address(token), address(user)));
+                    }
+                    [topic, relayer, sender]
+                        if *topic == relayer_approval_changed
+                            && address(relayer) == vault_relayer =>
+                    {
+                        result.owners.insert(address(sender));
+                    }
+                    _ => (),
+                }
+                continue;
+            }
+            match topics {
+                [topic, from, to] if *topic == transfer => {
+                    result.balances.insert((log.address, address(from)));
+                    result.balances.insert((log.address, address(to)));
+                }
+                // Both the vault relayer (ERC20 balances) and the vault itself
+                // (external balances) are relevant spenders.
+                [topic, owner, spender]
+                    if *topic == approval
+                        && (address(spender) == vault_relayer
+                            || Some(address(spender)) == vault) =>
+                {
+                    result.balances.insert((log.address, address(owner)));
+                }
+                [topic, _, _] if *topic == approval => (),
+                [topic, ..] if *topic == transfer || *topic == approval => {
+                    result.non_standard_tokens.insert(log.address);
+                }
+                _ => (),
+            }
+        }
+        result
+    }
+
+    fn apply(self, state: &mut State) {
+        state.balances.retain(|query, _| {
+            !self.balances.contains(&(query.token, query.owner))
+                && !self.owners.contains(&query.owner)
+                && !self.non_standard_tokens.contains(&query.token)
+        });
+        state.non_standard_tokens.extend(self.non_standard_tokens);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethcontract::dyns::DynTransport;
+    use maplit::hashset;
+    use shared::{current_block::Block, transport::mock::MockTransport};
+    use tokio::sync::{watch, Notify};
+
+    fn topic(address: H160) -> H256 {
+        address.into()
+    }
+
+    fn log(address: H160, topics: Vec<H256>) -> Log {
+        Log {
+            address,
+            topics,
+            data: Default::default(),
+            block_hash: None,
+            block_number: None,
+            transaction_hash: None,
+            transaction_index: None,
+            log_index: None,
+            transaction_log_index: None,
+            log_type: None,
+            removed: None,
+        }
+    }
+
+    #[test]
+    fn event_topics_match_contract_events() {
+        let [transfer, ..] = event_topics();
+        assert_eq!(
+            transfer,
+            H256(hex_literal::hex!(
+                "ddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef"
+            ))
+        );
+    }
+
+    #[test]
+    fn invalidations_from_logs() {
+        let [transfer, approval, internal_balance_changed, relayer_approval_changed] =
+            event_topics();
+        let vault = H160([0xfe; 20]);
+        let vault_relayer = H160([0xff; 20]);
+        let token = H160([1; 20]);
+        let non_standard = H160([2; 20]);
+        let logs = vec![
+            log(
+                token,
+                vec![transfer, topic(H160([3; 20])), topic(H160([4; 20]))],
+            ),
+            log(
+                token,
+                vec![approval, topic(H160([5; 20])), topic(vault_relayer)],
+            ),
+            log(token, vec![approval, topic(H160([6; 20])), topic(vault)]),
+            // approvals for other spenders don't change the available balance
+            log(
+                token,
+                vec![approval, topic(H160([7; 20])), topic(H160([8; 20]))],
+            ),
+            log(non_standard, vec![transfer]),
+            log(
+                vault,
+                vec![internal_balance_changed, topic(H160([9; 20])), topic(token)],
+            ),
+            log(
+                vault,
+                vec![
+                    relayer_approval_changed,
+                    topic(vault_relayer),
+                    topic(H160([10; 20])),
+                ],
+            ),
+            log(
+                vault,
+                vec![
+                    relayer_approval_changed,
+                    topic(H160([11; 20])),
+                    topic(H160([12; 20])),
+                ],
+            ),
+        ];
+
+        assert_eq!(
+            Invalidations::from_logs(&logs, Some(vault), vault_relayer),
+            Invalidations {
+                balances: hashset! {
+                    (token, H160([3; 20])),
+                    (token, H160([4; 20])),
+                    (token, H160([5; 20])),
+                    (token, H160([6; 20])),
+                    (token, H160([9; 20])),
+                },
+                owners: hashset! { H160([10; 20]) },
+                non_standard_tokens: hashset! { non_standard },
+            }
+        );
+    }
+
+    #[test]
+    fn applies_invalidations() {
+        let mut state = State::default();
+        for query in [
+            query(1, 1),
+            query(1, 2),
+            query(2, 1),
+            query(3, 3),
+            query(4, 4),
+        ] {
+            state.balances.insert(
+                query,
+                Entry {
+                    balance: 1.into(),
+                    fetched: 0,
+                },
+            );
+        }
+
+        Invalidations {
+            balances: hashset! { (H160([1; 20]), H160([1; 20])) },
+            owners: hashset! { H160([3; 20]) },
+            non_standard_tokens: hashset! { H160([4; 20]) },
+        }
+        .apply(&mut state);
+
+        assert_eq!(
+            state.balances.keys().copied().collect::<HashSet<_>>(),
+            hashset! { query(1, 2), query(2, 1) }
+        );
+        assert_eq!(state.non_standard_tokens, hashset! { H160([4; 20]) });
+    }
+
+    fn query(owner: u8, token: u8) -> Query {
+        Query {
+            owner: H160([owner; 20]),
+            token: H160([token; 20]),
+            source: SellTokenSource::Erc20,
+        }
+    }
+
+    fn block(number: u64, parent: u64) -> Block {
+        Block {
+            number: Some(number.into()),
+            hash: Some(H256::from_low_u64_be(number)),
+            parent_hash: H256::from_low_u64_be(parent),
+            ..Default::default()
+        }
+    }
+
+    /// A transfer of token 1 from owner 1.
+    fn transfer_logs() -> serde_json::Value {
+        let [transfer, ..] = event_topics();
+        serde_json::to_value(vec![log(
+            H160([1; 20]),
+            vec![transfer, topic(H160([1; 20])), topic(H160([3; 20]))],
+        )])
+        .unwrap()
+    }
+
+    /// Returns a balance of 1 for every query and records the queries.
+    #[derive(Default)]
+    struct RecordingFetcher {
+        fetched: std::sync::Mutex<Vec<Query>>,
+        /// If set the first fetch only completes once this is notified.
+        gate: Option<Arc<Notify>>,
+    }
+
+    impl RecordingFetcher {
+        fn fetched(&self) -> Vec<Query> {
+            self.fetched.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl BalanceFetching for RecordingFetcher {
+        async fn get_balances(&self, queries: &[Query]) -> Vec<Result<U256>> {
+            let first = {
+                let mut fetched = self.fetched.lock().unwrap();
+                let first = fetched.is_empty();
+                fetched.extend_from_slice(queries);
+                first
+            };
+            if let (true, Some(gate)) = (first, &self.gate) {
+                gate.notified().await;
+            }
+            queries.iter().map(|_| Ok(1.into())).collect()
+        }
+
+        async fn can_transfer(
+            &self,
+            _: H160,
+            _: H160,
+            _: U256,
+            _: SellTokenSource,
+        ) -> Result<(), TransferSimulationError> {
+            unimplemented!()
+        }
+    }
+
+    fn cache(
+        inner: Arc<RecordingFetcher>,
+    ) -> (EventBalanceCache, MockTransport, watch::Sender<Block>) {
+        let transport = MockTransport::new();
+        let (blocks, current_block) = watch::channel(block(1, 0));
+        let cache = EventBalanceCache::new(
+            inner,
+            Web3::new(DynTransport::new(transport.clone())),
+            None,
+            H160([0xff; 20]),
+            Default::default(),
+            current_block,
+        );
+        (cache, transport, blocks)
+    }
+
+    #[tokio::test]
+    async fn invalidates_balances_touched_in_next_block() {
+        let inner = Arc::new(RecordingFetcher::default());
+        let (cache, transport, blocks) = cache(inner.clone());
+        let (a, b) = (query(1, 1), query(2, 1));
+        cache.get_balances(&[a, b]).await;
+        cache.get_balances(&[a, b]).await;
+        assert_eq!(inner.fetched(), vec![a, b]);
+
+        let logs = transfer_logs();
+        transport
+            .mock()
+            .expect_execute()
+            .withf(|method, params| {
+                method == "eth_getLogs"
+                    && params[0]["blockHash"] == serde_json::json!(H256::from_low_u64_be(2))
+            })
+            .returning(move |_, _| Ok(logs.clone()));
+        blocks.send(block(2, 1)).unwrap();
+        cache.get_balances(&[a, b]).await;
+        assert_eq!(inner.fetched(), vec![a, b, a]);
+    }
+
+    #[tokio::test]
+    async fn fetches_events_of_missed_blocks() {
+        let inner = Arc::new(RecordingFetcher::default());
+        let (cache, transport, blocks) = cache(inner.clone());
+        let (a, b) = (query(1, 1), query(2, 1));
+        cache.get_balances(&[a, b]).await;
+
+        let block_1 = serde_json::to_value(block(1, 0)).unwrap();
+        transport
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_getBlockByNumber")
+            .returning(move |_, _| Ok(block_1.clone()));
+        let logs = transfer_logs();
+        transport
+            .mock()
+            .expect_execute()
+            .withf(|method, params| {
+                method == "eth_getLogs"
+                    && params[0]["fromBlock"] == "0x2"
+                    && params[0]["toBlock"] == "0x4"
+            })
+            .returning(move |_, _| Ok(logs.clone()));
+        blocks.send(block(4, 3)).unwrap();
+        cache.get_balances(&[a, b]).await;
+        assert_eq!(inner.fetched(), vec![a, b, a]);
+    }
+
+    #[tokio::test]
+    async fn drops_cache_on_reorg() {
+        let inner = Arc::new(RecordingFetcher::default());
+        let (cache, transport, blocks) = cache(inner.clone());
+        let (a, b) = (query(1, 1), query(2, 1));
+        cache.get_balances(&[a, b]).await;
+
+        // Block 1 was replaced so its events can't be trusted.
+        let reorged_block_1 = serde_json::to_value(Block {
+            hash: Some(H256::from_low_u64_be(100)),
+            ..block(1, 0)
+        })
+        .unwrap();
+        transport
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_getBlockByNumber")
+            .returning(move |_, _| Ok(reorged_block_1.clone()));
+        blocks.send(block(2, 100)).unwrap();
+        cache.get_balances(&[a, b]).await;
+        assert_eq!(inner.fetched(), vec![a, b, a, b]);
+    }
+
+    #[tokio::test]
+    async fn refetches_old_entries() {
+        let inner = Arc::new(RecordingFetcher::default());
+        let (cache, transport, blocks) = cache(inner.clone());
+        let a = query(1, 1);
+        cache.get_balances(&[a]).await;
+
+        transport
+            .mock()
+            .expect_execute()
+            .withf(|method, _| method == "eth_getLogs")
+            .returning(|_, _| Ok(serde_json::json!([])));
+        for number in 2..=MAX_ENTRY_AGE_BLOCKS + 1 {
+            blocks.send(block(number, number - 1)).unwrap();
+            cache.get_balances(&[a]).await;
+        }
+        assert_eq!(inner.fetched(), vec![a]);
+
+        blocks
+            .send(block(MAX_ENTRY_AGE_BLOCKS + 2, MAX_ENTRY_AGE_BLOCKS + 1))
+            .unwrap();
+        cache.get_balances(&[a]).await;
+        assert_eq!(inner.fetched(), vec![a, a]);
+    }
+
+    #[tokio::test]
+    async fn does_not_cache_balances_fetched_during_concurrent_update() {
+        let gate = Arc::new(Notify::new());
+        let inner = Arc::new(RecordingFetcher {
+            gate: Some(gate.clone()),
+            ..Default::default()
+        });
+        let (cache, _transport, blocks) = cache(inner.clone());
+        let (a, b) = (query(1, 1), query(2, 1));
+
+        // `a` is fetched at block 1 but the cache moves on to block 2 before
+        // the fetch completes, so events of block 2 touching `a` would not
+        // invalidate it.
+        futures::join!(cache.get_balances(&[a]), async {
+            blocks.send(block(2, 1)).unwrap();
+            cache.get_balances(&[b]).await;
+            gate.notify_one();
+        });
+        assert_eq!(inner.fetched(), vec![a, b]);
+
+        cache.get_balances(&[a, b]).await;
+        assert_eq!(inner.fetched(), vec![a, b, a]);
+    }
+}
@@ -1,44 +1,224 @@
 mod cancel_order;
+mod cancel_orders;
 mod create_order;
+mod get_app_data;
 mod get_auction;
 mod get_fee_and_quote;
 mod get_fee_info;
+mod get_fee_subsidy;
+mod get_market_depth;
 mod get_markets;
 mod get_order_by_uid;
+mod get_order_events;
 mod get_orders;
 mod get_orders_by_tx;
 mod get_solvable_orders;
 mod get_solvable_orders_v2;
 pub mod get_solver_competition;
+mod get_trade_accounting;
 mod get_trades;
 mod get_user_orders;
 pub mod order_validation;
 pub mod post_quote;
 pub mod post_solver_competition;
+mod put_app_data;
+mod replace_order;
+mod stream_auction;
+mod stream_order_status;
 
-use crate::solver_competition::SolverCompetition;
 use crate::{
-    api::post_quote::OrderQuoter, database::trades::TradeRetrieving, orderbook::Orderbook,
+    api::post_quote::OrderQuoter,
+    database::{
+        app_data::AppDataStoring, order_events::OrderEventStoring,
+        solver_competition::SolverCompetitionStoring, trades::TradeRetrieving,
+    },
+    fee_subsidy::CurrentFeeSubsidy,
+    orderbook::Orderbook,
+    rate_limiter::IpRateLimiter,
 };
 use anyhow::{Error as anyhowError, Result};
+use futures::StreamExt;
 use serde::{de::DeserializeOwned, Serialize};
 use shared::{metrics::get_metric_storage_registry, price_estimation::PriceEstimationError};
 use std::fmt::Debug;
+use std::net::SocketAddr;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::time::Instant;
 use std::{convert::Infallible, sync::Arc};
+use tokio::sync::Semaphore;
 use warp::{
-    hyper::StatusCode,
-    reply::{json, with_status, Json, WithStatus},
+    filters::BoxedFilter,
+    hyper::{Body, StatusCode},
+    reply::{json, with_status, Json, Response, WithStatus},
     Filter, Rejection, Reply,
 };
 
+/// The components serving the API of a single chain.
+pub struct ChainApi {
+    pub chain_id: u64,
+    pub database: Arc<dyn TradeRetrieving>,
+    pub orderbook: Arc<Orderbook>,
+    pub quoter: Arc<OrderQuoter>,
+    pub solver_competition: Arc<dyn SolverCompetitionStoring>,
+    pub order_events: Arc<dyn OrderEventStoring>,
+    pub app_data: Arc<dyn AppDataStoring>,
+}
+
+/// Serves the API of every chain under a `/<chain_id>` prefix. The first chain
+/// is additionally served without a prefix so that single chain deployments
+/// keep their urls.
 pub fn handle_all_routes(
-    database: Arc<dyn TradeRetrieving>,
-    orderbook: Arc<Orderbook>,
-    quoter: Arc<OrderQuoter>,
-    solver_competition: Arc<SolverCompetition>,
+    chains: Vec<ChainApi>,
+    ip_rate_limiter: Option<Arc<IpRateLimiter>>,
+    max_stream_connections: usize,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let mut routes_chains = Vec::new();
+    let mut routes_stream_chains = Vec::new();
+    for (i, chain) in chains.into_iter().enumerate() {
+        let prefix = chain.chain_id.to_string();
+        let label = prefix.clone();
+        let (routes, routes_stream) = chain_routes(chain);
+        let routes = routes
+            .map(move |response: Response, method: &'static str| (response, method, label.clone()))
+            .untuple_one()
+            .boxed();
+        if i == 0 {
+            routes_chains.push(routes.clone());
+            routes_stream_chains.push(routes_stream.clone());
+        }
+        routes_chains.push(warp::path(prefix.clone()).and(routes).boxed());
+        routes_stream_chains.push(warp::path(prefix).and(routes_stream).boxed());
+    }
+    let routes_chains = routes_chains
+        .into_iter()
+        .reduce(|a, b| a.or(b).unify().boxed())
+        .expect("no chain to serve");
+    let routes_stream = routes_stream_chains
+        .into_iter()
+        .reduce(|a, b| a.or(b).unify().boxed())
+        .expect("no chain to serve");
+    let routes_stream = limit_connections(
+        routes_stream,
+        Arc::new(Semaphore::new(max_stream_connections)),
+    );
+
+    // Fallback route that handles all 404s.
+
+    // Since we `.map()` all requests to collect metrics, we need to report
+    // all 404s as `Ok(ApiReply)`, and not `Err(Rejection)`.
+
+    let routes_fallback = warp::any()
+        .and_then(|| async move {
+            Result::<(Response, &str, String), Infallible>::Ok((
+                with_status(
+                    error("NotFound", "You've passed an invalid URL"),
+                    StatusCode::NOT_FOUND,
+                )
+                .into_response(),
+                "unknown_method",
+                "unknown_chain".to_string(),
+            ))
+        })
+        .untuple_one()
+        .boxed();
+
+    // Requests exceeding the rate limit, including the ones opening a
+    // stream, are answered before reaching the actual routes.
+
+    let routes_rate_limited = rate_limited(ip_rate_limiter).boxed();
+
+    // Routes combined
+
+    let routes = routes_chains.or(routes_fallback).unify().boxed();
+
+    // Metrics
+
+    let metrics = ApiMetrics::instance(get_metric_storage_registry()).unwrap();
+    let with_metrics = move |routes: BoxedFilter<(Response, &'static str, String)>| {
+        warp::any()
+            .map(Instant::now) // Start a timer at the beginning of response processing
+            .and(routes) // Parse requests
+            .map(
+                move |timer: Instant, response: Response, method: &str, chain: String| {
+                    metrics
+                        .requests_complete
+                        .with_label_values(&[&chain, method, response.status().as_str()])
+                        .inc();
+                    metrics
+                        .requests_duration_seconds
+                        .with_label_values(&[&chain, method])
+                        .observe(timer.elapsed().as_secs_f64());
+
+                    response
+                },
+            )
+            .boxed()
+    };
+
+    // Final setup
+
+    let cors = warp::cors()
+        .allow_any_origin()
+        .allow_methods(vec!["GET", "POST", "DELETE", "OPTIONS", "PUT", "PATCH"])
+        .allow_headers(vec!["Origin", "Content-Type", "X-Auth-Token", "X-AppId"])
+        .expose_headers(vec![get_trades::TOTAL_COUNT_HEADER]);
+
+    // Give each request a unique tracing span.
+    // This allows us to match log statements across concurrent API requests.
+    let request_id = Arc::new(AtomicUsize::new(0));
+    let tracing_span = warp::trace(move |_| {
+        tracing::info_span!("request", id = request_id.fetch_add(1, Ordering::SeqCst))
+    });
+
+    with_metrics(routes_rate_limited)
+        .or(routes_stream)
+        .unify()
+        .or(with_metrics(routes))
+        .unify()
+        .recover(handle_rejection)
+        .with(cors)
+        .with(warp::log::log("orderbook::api::request_summary"))
+        .with(tracing_span)
+}
+
+/// Routes for operating the order book that must not be part of the public
+/// API so they are served with the metrics. Like the API, the routes of every
+/// chain are served under a `/<chain_id>` prefix and the first chain is
+/// additionally served without one.
+pub fn handle_admin_routes(
+    fee_subsidies: Vec<(u64, Arc<CurrentFeeSubsidy>)>,
 ) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let mut routes_chains = Vec::new();
+    for (i, (chain_id, fee_subsidy)) in fee_subsidies.into_iter().enumerate() {
+        let routes = get_fee_subsidy::get_fee_subsidy(fee_subsidy).boxed();
+        if i == 0 {
+            routes_chains.push(routes.clone());
+        }
+        routes_chains.push(warp::path(chain_id.to_string()).and(routes).boxed());
+    }
+    routes_chains
+        .into_iter()
+        .reduce(|a, b| a.or(b).unify().boxed())
+        .expect("no chain to serve")
+}
+
+/// The metered and the streaming routes of a single chain.
+fn chain_routes(
+    chain: ChainApi,
+) -> (
+    BoxedFilter<(Response, &'static str)>,
+    BoxedFilter<(Response,)>,
+) {
+    let ChainApi {
+        chain_id: _,
+        database,
+        orderbook,
+        quoter,
+        solver_competition,
+        order_events,
+        app_data,
+    } = chain;
+
     // Routes for api v1.
 
     // Note that we add a string with endpoint's name to all responses.
@@ -46,52 +226,77 @@ pub fn handle_all_routes(
     // It is not used to form the actual server response.
 
     let create_order = create_order::create_order(orderbook.clone())
-        .map(|result| (result, "v1/create_order"))
+        .map(|result| (result.into_response(), "v1/create_order"))
         .boxed();
     let get_orders = get_orders::get_orders(orderbook.clone())
-        .map(|result| (result, "v1/get_orders"))
+        .map(|result| (result.into_response(), "v1/get_orders"))
         .boxed();
     let fee_info = get_fee_info::get_fee_info(quoter.fee_calculator.clone())
-        .map(|result| (result, "v1/fee_info"))
+        .map(|result| (result.into_response(), "v1/fee_info"))
         .boxed();
     let get_order = get_order_by_uid::get_order_by_uid(orderbook.clone())
-        .map(|result| (result, "v1/get_order"))
+        .map(|result| (result.into_response(), "v1/get_order"))
         .boxed();
     let get_solvable_orders = get_solvable_orders::get_solvable_orders(orderbook.clone())
-        .map(|result| (result, "v1/get_solvable_orders"))
+        .map(|result| (result.into_response(), "v1/get_solvable_orders"))
+        .boxed();
+    let get_trades = get_trades::get_trades(database.clone())
+        .map(|result| (result.into_response(), "v1/get_trades"))
         .boxed();
-    let get_trades = get_trades::get_trades(database)
-        .map(|result| (result, "v1/get_trades"))
+    let get_trade_accounting = get_trade_accounting::get_trade_accounting(database)
+        .map(|result| (result.into_response(), "v1/get_trade_accounting"))
         .boxed();
     let cancel_order = cancel_order::cancel_order(orderbook.clone())
-        .map(|result| (result, "v1/cancel_order"))
+        .map(|result| (result.into_response(), "v1/cancel_order"))
+        .boxed();
+    let cancel_orders = cancel_orders::cancel_orders(orderbook.clone())
+        .map(|result| (result.into_response(), "v1/cancel_orders"))
+        .boxed();
+    let replace_order = replace_order::replace_order(orderbook.clone())
+        .map(|result| (result.into_response(), "v1/replace_order"))
         .boxed();
     let get_amount_estimate = get_markets::get_amount_estimate(quoter.price_estimator.clone())
-        .map(|result| (result, "v1/get_amount_estimate"))
+        .map(|result| (result.into_response(), "v1/get_amount_estimate"))
+        .boxed();
+    let get_market_depth = get_market_depth::get_market_depth(orderbook.clone())
+        .map(|result| (result.into_response(), "v1/get_market_depth"))
         .boxed();
     let get_fee_and_quote_sell = get_fee_and_quote::get_fee_and_quote_sell(quoter.clone())
-        .map(|result| (result, "v1/get_fee_and_quote_sell"))
+        .map(|result| (result.into_response(), "v1/get_fee_and_quote_sell"))
         .boxed();
     let get_fee_and_quote_buy = get_fee_and_quote::get_fee_and_quote_buy(quoter.clone())
-        .map(|result| (result, "v1/get_fee_and_quote_buy"))
+        .map(|result| (result.into_response(), "v1/get_fee_and_quote_buy"))
         .boxed();
     let get_user_orders = get_user_orders::get_user_orders(orderbook.clone())
-        .map(|result| (result, "v1/get_user_orders"))
+        .map(|result| (result.into_response(), "v1/get_user_orders"))
         .boxed();
     let get_orders_by_tx = get_orders_by_tx::get_orders_by_tx(orderbook.clone())
-        .map(|result| (result, "v1/get_orders_by_tx"))
+        .map(|result| (result.into_response(), "v1/get_orders_by_tx"))
         .boxed();
     let post_quote = post_quote::post_quote(quoter)
-        .map(|result| (result, "v1/post_quote"))
+        .map(|result| (result.into_response(), "v1/post_quote"))
         .boxed();
     let get_auction = get_auction::get_auction(orderbook.clone())
-        .map(|result| (result, "v1/auction"))
+        .map(|result| (result.into_response(), "v1/auction"))
         .boxed();
     let get_solver_competition = get_solver_competition::get(solver_competition.clone())
-        .map(|result| (result, "v1/solver_competition"))
+        .map(|result| (result.into_response(), "v1/solver_competition"))
         .boxed();
+    let get_solver_competition_by_tx_hash =
+        get_solver_competition::get_by_tx_hash(solver_competition.clone())
+            .map(|result| (result.into_response(), "v1/solver_competition_by_tx_hash"))
+            .boxed();
     let post_solver_competition = post_solver_competition::post(solver_competition)
-        .map(|result| (result, "v1/solver_competition"))
+        .map(|result| (result.into_response(), "v1/solver_competition"))
+        .boxed();
+    let get_order_events = get_order_events::get_order_events(order_events)
+        .map(|result| (result.into_response(), "v1/get_order_events"))
+        .boxed();
+    let put_app_data = put_app_data::put_app_data(app_data.clone())
+        .map(|result| (result.into_response(), "v1/put_app_data"))
+        .boxed();
+    let get_app_data = get_app_data::get_app_data(app_data)
+        .map(|result| (result.into_response(), "v1/get_app_data"))
         .boxed();
 
     let routes_v1 = warp::path!("api" / "v1" / ..)
@@ -109,8 +314,14 @@ pub fn handle_all_routes(
                 .unify()
                 .or(cancel_order)
                 .unify()
+                .or(cancel_orders)
+                .unify()
+                .or(replace_order)
+                .unify()
                 .or(get_amount_estimate)
                 .unify()
+                .or(get_market_depth)
+                .unify()
                 .or(get_fee_and_quote_sell)
                 .unify()
                 .or(get_fee_and_quote_buy)
@@ -119,99 +330,122 @@ pub fn handle_all_routes(
                 .unify()
                 .or(get_orders_by_tx)
                 .unify()
+                .or(get_trade_accounting)
+                .unify()
                 .or(post_quote)
                 .unify()
                 .or(get_auction)
                 .unify()
                 .or(get_solver_competition)
                 .unify()
+                .or(get_solver_competition_by_tx_hash)
+                .unify()
                 .or(post_solver_competition)
+                .unify()
+                .or(get_order_events)
+                .unify()
+                .or(put_app_data)
+                .unify()
+                .or(get_app_data)
                 .unify(),
         )
         .untuple_one()
         .boxed();
 
-    // Routes for api v2.
+    // Streaming routes for api v1.
 
-    let get_solvable_orders_v2 = get_solvable_orders_v2::get_solvable_orders(orderbook)
-        .map(|result| (result, "v2/get_solvable_orders"))
-        .boxed();
-
-    let routes_v2 = warp::path!("api" / "v2" / ..)
-        .and(get_solvable_orders_v2)
-        .untuple_one();
-
-    // Fallback route that handles all 404s.
+    // Server-sent event streams stay open for as long as the client is
+    // subscribed so they are not reported in the request duration metrics.
 
-    // Since we `.map()` all requests to collect metrics, we need to report
-    // all 404s as `Ok(ApiReply)`, and not `Err(Rejection)`.
-
-    let routes_fallback = warp::any()
-        .and_then(|| async move {
-            Result::<(ApiReply, &str), Infallible>::Ok((
-                with_status(
-                    error("NotFound", "You've passed an invalid URL"),
-                    StatusCode::NOT_FOUND,
-                ),
-                "unknown_method",
-            ))
-        })
-        .untuple_one()
+    let stream_order_status = stream_order_status::stream_order_status(orderbook.clone())
+        .map(Reply::into_response)
+        .boxed();
+    let stream_auction = stream_auction::stream_auction(orderbook.clone())
+        .map(Reply::into_response)
         .boxed();
 
-    // Routes combined
-
-    let routes = routes_v1
-        .or(routes_v2)
-        .unify()
-        .or(routes_fallback)
-        .unify()
+    let routes_stream_v1 = warp::path!("api" / "v1" / ..)
+        .and(stream_order_status.or(stream_auction).unify())
         .boxed();
 
-    // Metrics
+    // Routes for api v2.
 
-    let metrics = ApiMetrics::instance(get_metric_storage_registry()).unwrap();
-    let routes_with_metrics = warp::any()
-        .map(Instant::now) // Start a timer at the beginning of response processing
-        .and(routes) // Parse requests
-        .map(|timer: Instant, reply: ApiReply, method: &str| {
-            let response = reply.into_response();
-
-            metrics
-                .requests_complete
-                .with_label_values(&[method, response.status().as_str()])
-                .inc();
-            metrics
-                .requests_duration_seconds
-                .with_label_values(&[method])
-                .observe(timer.elapsed().as_secs_f64());
-
-            response
-        })
+    let get_solvable_orders_v2 = get_solvable_orders_v2::get_solvable_orders(orderbook)
+        .map(|result| (result.into_response(), "v2/get_solvable_orders"))
         .boxed();
 
-    // Final setup
+    let routes_v2 = warp::path!("api" / "v2" / ..)
+        .and(get_solvable_orders_v2)
+        .untuple_one();
 
-    let cors = warp::cors()
-        .allow_any_origin()
-        .allow_methods(vec!["GET", "POST", "DELETE", "OPTIONS", "PUT", "PATCH"])
-        .allow_headers(vec!["Origin", "Content-Type", "X-Auth-Token", "X-AppId"]);
+    (routes_v1.or(routes_v2).unify().boxed(), routes_stream_v1)
+}
 
-    // Give each request a unique tracing span.
-    // This allows us to match log statements across concurrent API requests.
-    let request_id = Arc::new(AtomicUsize::new(0));
-    let tracing_span = warp::trace(move |_| {
-        tracing::info_span!("request", id = request_id.fetch_add(1, Ordering::SeqCst))
-    });
+pub type ApiReply = warp::reply::WithStatus<warp::reply::Json>;
 
-    routes_with_metrics
-        .recover(handle_rejection)
-        .with(cors)
-        .with(warp::log::log("orderbook::api::request_summary"))
-        .with(tracing_span)
+/// Matches requests from clients that exceeded their rate limit and rejects
+/// all others so that they are handled by the following routes.
+fn rate_limited(
+    limiter: Option<Arc<IpRateLimiter>>,
+) -> impl Filter<Extract = (Response, &'static str, String), Error = Rejection> + Clone {
+    warp::addr::remote()
+        .and(warp::header::optional::<String>("x-forwarded-for"))
+        .and_then(
+            move |remote: Option<SocketAddr>, forwarded_for: Option<String>| {
+                let limiter = limiter.clone();
+                async move {
+                    match limiter {
+                        Some(limiter) if !limiter.try_acquire(remote, forwarded_for.as_deref()) => {
+                            Ok((
+                                with_status(
+                                    error(
+                                        "TooManyRequests",
+                                        "rate limit exceeded, try again later",
+                                    ),
+                                    StatusCode::TOO_MANY_REQUESTS,
+                                )
+                                .into_response(),
+                                "rate_limited",
+                                "unknown_chain".to_string(),
+                            ))
+                        }
+                        _ => Err(warp::reject()),
+                    }
+                }
+            },
+        )
+        .untuple_one()
 }
 
-pub type ApiReply = warp::reply::WithStatus<warp::reply::Json>;
+/// Rejects streams while `connections` of them are open. A stream stays open
+/// until its response body is dropped.
+fn limit_connections(
+    routes: BoxedFilter<(Response,)>,
+    connections: Arc<Semaphore>,
+) -> BoxedFilter<(Response,)> {
+    routes
+        .map(
+            move |response: Response| match connections.clone().try_acquire_owned() {
+                Ok(permit) => {
+                    let (parts, body) = response.into_parts();
+                    let body = Body::wrap_stream(body.map(move |chunk| {
+                        let _permit = &permit;
+                        chunk
+                    }));
+                    Response::from_parts(parts, body)
+                }
+                Err(_) => with_status(
+                    error(
+                        "TooManyConnections",
+                        "too many open streams, try again later",
+                    ),
+                    StatusCode::SERVICE_UNAVAILABLE,
+                )
+                .into_response(),
+            },
+        )
+        .boxed()
+}
 
 // We turn Rejection into Reply to workaround warp not setting CORS headers on rejections.
 async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
@@ -230,7 +464,7 @@ async fn handle_rejection(err: Rejection) -> Result<impl Reply, Infallible> {
 #[metric(subsystem = "api")]
 struct ApiMetrics {
     /// Number of completed API requests.
-    #[metric(labels("method", "status_code"))]
+    #[metric(labels("chain", "method", "status_code"))]
     requests_complete: prometheus::CounterVec,
 
     /// Number of rejected API requests.
@@ -238,7 +472,7 @@ struct ApiMetrics {
     requests_rejected: prometheus::CounterVec,
 
     /// Execution time for each API request.
-    #[metric(labels("method"))]
+    #[metric(labels("chain", "method"))]
     requests_duration_seconds: prometheus::HistogramVec,
 }
 
@@ -354,8 +588,137 @@ fn extract_payload<T: DeserializeOwned + Send>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{
+        account_balances::MockBalanceFetching,
+        api::order_validation::{MockOrderValidating, OrderValidator},
+        database::{
+            auctions::MockAuctionStoring, order_events::MockOrderEventStoring,
+            orders::MockOrderStoring, quotes::MockQuoteStoring, Postgres,
+        },
+        fee::MockMinFeeCalculating,
+        metrics::NoopMetrics,
+        orderbook::OwnerLimits,
+        signature_validator::MockSignatureValidating,
+        solvable_orders::SolvableOrdersCache,
+    };
+    use contracts::WETH9;
+    use maplit::hashset;
+    use model::{
+        order::{Order, OrderMetadata, OrderUid},
+        DomainSeparator,
+    };
+    use primitive_types::H160;
     use serde::ser;
     use serde_json::json;
+    use shared::{
+        bad_token::MockBadTokenDetecting,
+        dummy_contract,
+        gas_price_estimation::FakeGasPriceEstimator,
+        price_estimation::{native::MockNativePriceEstimating, MockPriceEstimating},
+        web3_traits::MockCodeFetching,
+    };
+    use std::time::Duration;
+
+    /// The api of a chain whose orders are all owned by the address of the
+    /// chain id.
+    fn chain_api(chain_id: u64) -> ChainApi {
+        let mut database = MockOrderStoring::new();
+        database.expect_single_order().returning(move |uid| {
+            Ok(Some(Order {
+                metadata: OrderMetadata {
+                    uid: *uid,
+                    owner: H160::from_low_u64_be(chain_id),
+                    ..Default::default()
+                },
+                ..Default::default()
+            }))
+        });
+        let database = Arc::new(database);
+        let (_, current_block) = tokio::sync::watch::channel(Default::default());
+        let solvable_orders = SolvableOrdersCache::new(
+            Duration::ZERO,
+            database.clone(),
+            Arc::new(MockAuctionStoring::new()),
+            Default::default(),
+            Arc::new(MockBalanceFetching::new()),
+            Arc::new(MockBadTokenDetecting::new()),
+            Arc::new(MockSignatureValidating::new()),
+            current_block,
+            Arc::new(MockNativePriceEstimating::new()),
+            Arc::new(FakeGasPriceEstimator::default()),
+            Arc::new(NoopMetrics),
+            Default::default(),
+            Arc::new(MockOrderEventStoring::new()),
+        );
+        let order_validator = OrderValidator::new(
+            Box::new(MockCodeFetching::new()),
+            dummy_contract!(WETH9, [0xef; 20]),
+            hashset!(),
+            hashset!(),
+            Duration::ZERO,
+            Duration::MAX,
+            Duration::MAX,
+            Arc::new(MockMinFeeCalculating::new()),
+            Arc::new(MockBadTokenDetecting::new()),
+            Arc::new(MockBalanceFetching::new()),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
+        );
+        let orderbook = Arc::new(Orderbook::new(
+            DomainSeparator::default(),
+            H160::zero(),
+            database,
+            Arc::new(MockBadTokenDetecting::new()),
+            false,
+            solvable_orders,
+            Duration::MAX,
+            chrono::Duration::zero(),
+            Arc::new(order_validator),
+            Default::default(),
+            OwnerLimits::default(),
+        ));
+        let quoter = Arc::new(OrderQuoter::new(
+            Arc::new(MockMinFeeCalculating::new()),
+            Arc::new(MockPriceEstimating::new()),
+            Arc::new(MockOrderValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
+        ));
+        // The other components are never queried so they can use a database
+        // that isn't connected to.
+        let postgres = Arc::new(Postgres::new("postgresql://").unwrap());
+        ChainApi {
+            chain_id,
+            database: postgres.clone(),
+            orderbook,
+            quoter,
+            solver_competition: postgres.clone(),
+            order_events: postgres.clone(),
+            app_data: postgres,
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_requests_to_chains() {
+        let routes = handle_all_routes(vec![chain_api(1), chain_api(100)], None, 1);
+        let uid = OrderUid::from_integer(42);
+
+        for (prefix, chain_id) in [("", 1), ("/1", 1), ("/100", 100)] {
+            let response = warp::test::request()
+                .path(&format!("{}/api/v1/orders/{}", prefix, uid))
+                .reply(&routes)
+                .await;
+            assert_eq!(response.status(), StatusCode::OK);
+            let order: Order = serde_json::from_slice(response.body()).unwrap();
+            assert_eq!(order.metadata.uid, uid);
+            assert_eq!(order.metadata.owner, H160::from_low_u64_be(chain_id));
+        }
+
+        let response = warp::test::request()
+            .path(&format!("/5/api/v1/orders/{}", uid))
+            .reply(&routes)
+            .await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
 
     #[test]
     fn rich_errors_skip_unset_data_field() {
@@ -414,4 +777,50 @@ mod tests {
             })
         );
     }
+
+    #[tokio::test]
+    async fn rate_limited_requests() {
+        let limiter = Arc::new(IpRateLimiter::new("2/60".parse().unwrap(), false));
+        let filter = rate_limited(Some(limiter));
+        let request = |ip: [u8; 4]| {
+            warp::test::request()
+                .path("/api/v1/orders")
+                .remote_addr((ip, 1234).into())
+        };
+
+        assert!(request([1, 1, 1, 1]).filter(&filter).await.is_err());
+        assert!(request([1, 1, 1, 1]).filter(&filter).await.is_err());
+        let (response, method, _) = request([1, 1, 1, 1]).filter(&filter).await.unwrap();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(method, "rate_limited");
+        assert!(request([2, 2, 2, 2]).filter(&filter).await.is_err());
+
+        let filter = rate_limited(None);
+        for _ in 0..3 {
+            assert!(request([1, 1, 1, 1]).filter(&filter).await.is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn limits_open_streams() {
+        let stream = warp::path("stream")
+            .map(|| Response::new(Body::empty()))
+            .boxed();
+        let filter = limit_connections(stream, Arc::new(Semaphore::new(1)));
+        let request = || warp::test::request().path("/stream");
+
+        let open = request().filter(&filter).await.unwrap();
+        assert_eq!(open.status(), StatusCode::OK);
+        let rejected = request().filter(&filter).await.unwrap();
+        assert_eq!(rejected.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        drop(open);
+        let open = request().filter(&filter).await.unwrap();
+        assert_eq!(open.status(), StatusCode::OK);
+        assert!(warp::test::request()
+            .path("/other")
+            .filter(&filter)
+            .await
+            .is_err());
+    }
 }
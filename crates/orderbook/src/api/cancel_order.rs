@@ -64,6 +64,13 @@ impl IntoWarpReply for OrderCancellationError {
                 super::error("OnChainOrder", "On-chain orders must be cancelled on-chain"),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::TooManyOrders { max } => with_status(
+                super::error(
+                    "TooManyOrders",
+                    format!("At most {} orders can be cancelled at once", max),
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::Other(err) => with_status(
                 super::internal_error(err.context("cancel_order")),
                 StatusCode::INTERNAL_SERVER_ERROR,
@@ -0,0 +1,130 @@
+use crate::api::{convert_json_response, extract_payload};
+use crate::orderbook::{OrderCancellationError, Orderbook};
+use anyhow::Result;
+use model::order::{OrderUid, SignedOrderCancellations};
+use serde::Serialize;
+use std::{convert::Infallible, sync::Arc};
+use warp::{Filter, Rejection};
+
+/// Whether a single order of a batch cancellation was cancelled.
+#[derive(Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct CancellationResult {
+    order_uid: OrderUid,
+    /// `Cancelled` or the error type that the single order cancellation
+    /// endpoint would have returned for the order.
+    result: &'static str,
+}
+
+fn cancellation_result(result: &Result<(), OrderCancellationError>) -> &'static str {
+    match result {
+        Ok(()) => "Cancelled",
+        Err(OrderCancellationError::InvalidSignature) => "InvalidSignature",
+        Err(OrderCancellationError::WrongOwner) => "WrongOwner",
+        Err(OrderCancellationError::OrderNotFound) => "OrderNotFound",
+        Err(OrderCancellationError::AlreadyCancelled) => "AlreadyCancelled",
+        Err(OrderCancellationError::OrderFullyExecuted) => "OrderFullyExecuted",
+        Err(OrderCancellationError::OrderExpired) => "OrderExpired",
+        Err(OrderCancellationError::OnChainOrder) => "OnChainOrder",
+        Err(OrderCancellationError::TooManyOrders { .. }) => "TooManyOrders",
+        Err(OrderCancellationError::Other(_)) => "InternalServerError",
+    }
+}
+
+fn cancel_orders_request(
+) -> impl Filter<Extract = (SignedOrderCancellations,), Error = Rejection> + Clone {
+    warp::path!("orders")
+        .and(warp::delete())
+        .and(extract_payload())
+}
+
+fn cancel_orders_response(
+    result: Result<Vec<(OrderUid, Result<(), OrderCancellationError>)>, OrderCancellationError>,
+) -> super::ApiReply {
+    convert_json_response(result.map(|results| {
+        results
+            .iter()
+            .map(|(order_uid, result)| CancellationResult {
+                order_uid: *order_uid,
+                result: cancellation_result(result),
+            })
+            .collect::<Vec<_>>()
+    }))
+}
+
+pub fn cancel_orders(
+    orderbook: Arc<Orderbook>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    cancel_orders_request().and_then(move |cancellations| {
+        let orderbook = orderbook.clone();
+        async move {
+            let result = orderbook.cancel_orders(cancellations).await;
+            Result::<_, Infallible>::Ok(cancel_orders_response(result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::response_body;
+    use model::{
+        order::OrderCancellations,
+        signature::{EcdsaSignature, EcdsaSigningScheme},
+    };
+    use serde_json::json;
+    use warp::{hyper::StatusCode, test::request, Reply};
+
+    #[tokio::test]
+    async fn cancel_orders_request_ok() {
+        let cancellations = SignedOrderCancellations {
+            data: OrderCancellations {
+                order_uids: vec![OrderUid([1; 56]), OrderUid([2; 56])],
+            },
+            signature: EcdsaSignature::default(),
+            signing_scheme: EcdsaSigningScheme::Eip712,
+        };
+        let request = request()
+            .path("/orders")
+            .method("DELETE")
+            .header("content-type", "application/json")
+            .json(&cancellations);
+        let result = request.filter(&cancel_orders_request()).await.unwrap();
+        assert_eq!(result, cancellations);
+    }
+
+    #[tokio::test]
+    async fn cancel_orders_response_ok() {
+        let response = cancel_orders_response(Ok(vec![
+            (OrderUid([1; 56]), Ok(())),
+            (
+                OrderUid([2; 56]),
+                Err(OrderCancellationError::AlreadyCancelled),
+            ),
+        ]))
+        .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response_body(response).await).unwrap();
+        assert_eq!(
+            body,
+            json!([
+                {
+                    "orderUid": OrderUid([1; 56]),
+                    "result": "Cancelled",
+                },
+                {
+                    "orderUid": OrderUid([2; 56]),
+                    "result": "AlreadyCancelled",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn cancel_orders_response_err() {
+        let response =
+            cancel_orders_response(Err(OrderCancellationError::InvalidSignature)).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -27,10 +27,28 @@ impl IntoWarpReply for AddOrderError {
                 super::error("DuplicatedOrder", "order already exists"),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::RateLimited => with_status(
+                super::error(
+                    "TooManyRequests",
+                    "too many orders created by this owner, try again later",
+                ),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
+            Self::TooManyOpenOrders { max } => with_status(
+                super::error(
+                    "TooManyOpenOrders",
+                    format!("owner already has the maximum of {} open orders", max),
+                ),
+                StatusCode::TOO_MANY_REQUESTS,
+            ),
             Self::Database(err) => with_status(
                 super::internal_error(anyhow::Error::new(err).context("create_order")),
                 StatusCode::INTERNAL_SERVER_ERROR,
             ),
+            Self::Other(err) => with_status(
+                super::internal_error(err.context("create_order")),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
         }
     }
 }
@@ -98,4 +116,24 @@ mod tests {
             json!({"errorType": "DuplicatedOrder", "description": "order already exists"});
         assert_eq!(body, expected_error);
     }
+
+    #[tokio::test]
+    async fn create_order_response_owner_limits() {
+        let response = create_order_response(Err(AddOrderError::RateLimited)).into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = response_body(response).await;
+        let body: serde_json::Value = serde_json::from_slice(body.as_slice()).unwrap();
+        assert_eq!(body["errorType"], "TooManyRequests");
+
+        let response = create_order_response(Err(AddOrderError::TooManyOpenOrders { max: 10 }))
+            .into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+        let body = response_body(response).await;
+        let body: serde_json::Value = serde_json::from_slice(body.as_slice()).unwrap();
+        let expected_error = json!({
+            "errorType": "TooManyOpenOrders",
+            "description": "owner already has the maximum of 10 open orders",
+        });
+        assert_eq!(body, expected_error);
+    }
 }
@@ -0,0 +1,96 @@
+use crate::{api::IntoWarpReply, database::app_data::AppDataStoring};
+use anyhow::Result;
+use model::{app_data::AppData, app_id::AppId};
+use std::{convert::Infallible, sync::Arc};
+use warp::{
+    hyper::StatusCode,
+    reply::{json, with_status},
+    Filter, Rejection,
+};
+
+fn request() -> impl Filter<Extract = (AppId,), Error = Rejection> + Clone {
+    warp::path!("app_data" / AppId).and(warp::get())
+}
+
+fn response(result: Result<Option<AppData>>) -> super::ApiReply {
+    match result {
+        Ok(Some(app_data)) => with_status(json(&app_data), StatusCode::OK),
+        Ok(None) => with_status(
+            super::error("NotFound", "App data was not found"),
+            StatusCode::NOT_FOUND,
+        ),
+        Err(err) => err.into_warp_reply(),
+    }
+}
+
+pub fn get_app_data(
+    database: Arc<dyn AppDataStoring>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    request().and_then(move |app_id: AppId| {
+        let database = database.clone();
+        async move {
+            let result = database.app_data(&app_id).await;
+            Result::<_, Infallible>::Ok(response(result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::response_body, database::app_data::MockAppDataStoring};
+    use model::app_data::ParsedAppData;
+    use serde_json::json;
+    use warp::{test::request, Reply};
+
+    #[tokio::test]
+    async fn returns_stored_app_data() {
+        let mut database = MockAppDataStoring::new();
+        database
+            .expect_app_data()
+            .withf(|app_id| *app_id == AppId([1; 32]))
+            .returning(|_| {
+                Ok(Some(AppData {
+                    full_app_data: r#"{"version":"0.4.0","appCode":"CowSwap"}"#.to_string(),
+                    parsed: ParsedAppData {
+                        app_code: Some("CowSwap".to_string()),
+                        ..Default::default()
+                    },
+                }))
+            });
+        database
+            .expect_app_data()
+            .withf(|app_id| *app_id == AppId([2; 32]))
+            .returning(|_| Ok(None));
+        let filter = get_app_data(Arc::new(database));
+
+        let response = request()
+            .path(&format!("/app_data/{:?}", AppId([1; 32])))
+            .method("GET")
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response_body(response).await).unwrap();
+        assert_eq!(
+            body,
+            json!({
+                "fullAppData": r#"{"version":"0.4.0","appCode":"CowSwap"}"#,
+                "appCode": "CowSwap",
+                "referrer": null,
+                "slippageBips": null,
+            })
+        );
+
+        let response = request()
+            .path(&format!("/app_data/{:?}", AppId([2; 32])))
+            .method("GET")
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+}
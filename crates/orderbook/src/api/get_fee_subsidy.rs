@@ -0,0 +1,61 @@
+//! This is a private, undocumented api which exposes the currently active fee subsidy
+//! configuration so that changes to it can be verified without looking at the file. It is served
+//! on the metrics port instead of the public API.
+
+use crate::fee_subsidy::CurrentFeeSubsidy;
+use std::{convert::Infallible, sync::Arc};
+use warp::{hyper::StatusCode, reply, Filter, Rejection};
+
+fn request() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path!("admin" / "fee_subsidy").and(warp::get())
+}
+
+pub fn get_fee_subsidy(
+    current: Arc<CurrentFeeSubsidy>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    request().and_then(move || {
+        let current = current.clone();
+        async move {
+            Result::<_, Infallible>::Ok(reply::with_status(
+                reply::json(&*current.get()),
+                StatusCode::OK,
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        api::response_body, fee::FeeSubsidyConfiguration, fee_subsidy::VersionedFeeSubsidy,
+    };
+    use serde_json::json;
+    use warp::{test::request, Reply};
+
+    #[tokio::test]
+    async fn returns_current_configuration() {
+        let current = Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+            version: 3,
+            fee_subsidy: FeeSubsidyConfiguration {
+                fee_factor: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        }));
+        let filter = get_fee_subsidy(current);
+
+        let response = request()
+            .path("/admin/fee_subsidy")
+            .method("GET")
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response_body(response).await).unwrap();
+        assert_eq!(body["version"], json!(3));
+        assert_eq!(body["feeFactor"], json!(0.5));
+    }
+}
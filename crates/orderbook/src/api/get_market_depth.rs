@@ -0,0 +1,44 @@
+use super::get_markets::Market;
+use crate::{api::convert_json_response, orderbook::Orderbook};
+use anyhow::Result;
+use std::{convert::Infallible, sync::Arc};
+use warp::{Filter, Rejection};
+
+fn get_market_depth_request() -> impl Filter<Extract = (Market,), Error = Rejection> + Clone {
+    warp::path!("markets" / Market / "depth").and(warp::get())
+}
+
+pub fn get_market_depth(
+    orderbook: Arc<Orderbook>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    get_market_depth_request().and_then(move |market: Market| {
+        let orderbook = orderbook.clone();
+        async move {
+            let result = orderbook.get_market_depth(market.base_token, market.quote_token);
+            Result::<_, Infallible>::Ok(convert_json_response(result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitive_types::H160;
+    use warp::test::request;
+
+    #[tokio::test]
+    async fn get_market_depth_request_ok() {
+        let market = request()
+            .path("/markets/0x0101010101010101010101010101010101010101-0x0202020202020202020202020202020202020202/depth")
+            .filter(&get_market_depth_request())
+            .await
+            .unwrap();
+        assert_eq!(
+            market,
+            Market {
+                base_token: H160([1; 20]),
+                quote_token: H160([2; 20]),
+            }
+        );
+    }
+}
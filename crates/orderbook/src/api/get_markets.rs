@@ -31,9 +31,9 @@ impl FromStr for TokenAmount {
 }
 
 #[derive(Clone, Debug, PartialEq, Default)]
-struct Market {
-    base_token: H160,
-    quote_token: H160,
+pub(super) struct Market {
+    pub(super) base_token: H160,
+    pub(super) quote_token: H160,
 }
 
 impl FromStr for Market {
@@ -0,0 +1,82 @@
+use crate::{api::convert_json_response, database::order_events::OrderEventStoring};
+use anyhow::Result;
+use model::order::OrderUid;
+use std::{convert::Infallible, sync::Arc};
+use warp::{Filter, Rejection};
+
+fn get_order_events_request() -> impl Filter<Extract = (OrderUid,), Error = Rejection> + Clone {
+    warp::path!("orders" / OrderUid / "events").and(warp::get())
+}
+
+pub fn get_order_events(
+    database: Arc<dyn OrderEventStoring>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    get_order_events_request().and_then(move |uid: OrderUid| {
+        let database = database.clone();
+        async move {
+            let result = database.order_events(&uid).await;
+            Result::<_, Infallible>::Ok(convert_json_response(result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::response_body, database::order_events::MockOrderEventStoring};
+    use anyhow::anyhow;
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use model::order_event::{OrderEvent, OrderEventLabel};
+    use warp::{hyper::StatusCode, test::request, Reply};
+
+    #[tokio::test]
+    async fn get_order_events_request_ok() {
+        let uid = OrderUid([1; 56]);
+        let request = request()
+            .path(&format!("/orders/{}/events", uid))
+            .method("GET");
+        let result = request.filter(&get_order_events_request()).await.unwrap();
+        assert_eq!(result, uid);
+    }
+
+    #[tokio::test]
+    async fn get_order_events_response() {
+        let event = OrderEvent {
+            timestamp: DateTime::from_utc(NaiveDateTime::from_timestamp(3, 0), Utc),
+            label: OrderEventLabel::Created,
+            reason: None,
+        };
+        let mut database = MockOrderEventStoring::new();
+        database
+            .expect_order_events()
+            .withf(|uid| *uid == OrderUid([1; 56]))
+            .returning(move |_| Ok(vec![event]));
+        database
+            .expect_order_events()
+            .returning(|_| Err(anyhow!("error")));
+        let filter = get_order_events(Arc::new(database));
+
+        let path = format!("/orders/{}/events", OrderUid([1; 56]));
+        let response = request()
+            .path(&path)
+            .method("GET")
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response_body(response).await;
+        let events: Vec<OrderEvent> = serde_json::from_slice(&body).unwrap();
+        assert_eq!(events, vec![event]);
+
+        let path = format!("/orders/{}/events", OrderUid([2; 56]));
+        let response = request()
+            .path(&path)
+            .method("GET")
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+}
@@ -1,25 +1,50 @@
-use crate::solver_competition::SolverCompetition;
+use crate::{api::IntoWarpReply, database::solver_competition::SolverCompetitionStoring};
 use anyhow::Result;
+use ethcontract::H256;
+use model::solver_competition::SolverCompetitionResponse;
 use reqwest::StatusCode;
 use std::{convert::Infallible, sync::Arc};
-use warp::{Filter, Rejection};
+use warp::{reply, Filter, Rejection};
 
-fn request() -> impl Filter<Extract = (u64,), Error = Rejection> + Clone {
+fn request_id() -> impl Filter<Extract = (u64,), Error = Rejection> + Clone {
     warp::path!("solver_competition" / u64).and(warp::get())
 }
 
+fn request_tx_hash() -> impl Filter<Extract = (H256,), Error = Rejection> + Clone {
+    warp::path!("solver_competition" / "by_tx_hash" / H256).and(warp::get())
+}
+
+fn response(result: Result<Option<SolverCompetitionResponse>>) -> super::ApiReply {
+    match result {
+        Ok(Some(response)) => reply::with_status(reply::json(&response), StatusCode::OK),
+        Ok(None) => reply::with_status(
+            super::error("NotFound", "Solver competition was not found"),
+            StatusCode::NOT_FOUND,
+        ),
+        Err(err) => err.into_warp_reply(),
+    }
+}
+
 pub fn get(
-    handler: Arc<SolverCompetition>,
+    handler: Arc<dyn SolverCompetitionStoring>,
 ) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
-    request().and_then(move |auction_id| {
+    request_id().and_then(move |auction_id| {
         let handler = handler.clone();
         async move {
-            let (json, status) = match handler.get(auction_id) {
-                Some(response) => (warp::reply::json(&response), StatusCode::OK),
-                None => (super::error("NotFound", ""), StatusCode::NOT_FOUND),
-            };
-            let reply = warp::reply::with_status(json, status);
-            Result::<_, Infallible>::Ok(reply)
+            let result = handler.load(auction_id).await;
+            Result::<_, Infallible>::Ok(response(result))
+        }
+    })
+}
+
+pub fn get_by_tx_hash(
+    handler: Arc<dyn SolverCompetitionStoring>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    request_tx_hash().and_then(move |tx_hash: H256| {
+        let handler = handler.clone();
+        async move {
+            let result = handler.load_by_tx_hash(&tx_hash).await;
+            Result::<_, Infallible>::Ok(response(result))
         }
     })
 }
@@ -27,13 +52,26 @@ pub fn get(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::solver_competition::MockSolverCompetitionStoring;
+    use anyhow::anyhow;
     use warp::{test::request, Reply};
 
     #[tokio::test]
     async fn test() {
-        let handler = SolverCompetition::default();
-        handler.set(0, Default::default());
-        let filter = get(Arc::new(handler));
+        let mut storage = MockSolverCompetitionStoring::new();
+        storage
+            .expect_load()
+            .withf(|id| *id == 0)
+            .returning(|_| Ok(Some(Default::default())));
+        storage
+            .expect_load()
+            .withf(|id| *id == 1)
+            .returning(|_| Ok(None));
+        storage
+            .expect_load()
+            .withf(|id| *id == 2)
+            .returning(|_| Err(anyhow!("error")));
+        let filter = get(Arc::new(storage));
 
         let request_ = request().path("/solver_competition/0").method("GET");
         let response = request_.filter(&filter).await.unwrap().into_response();
@@ -44,5 +82,42 @@ mod tests {
         let response = request_.filter(&filter).await.unwrap().into_response();
         dbg!(&response);
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let request_ = request().path("/solver_competition/2").method("GET");
+        let response = request_.filter(&filter).await.unwrap().into_response();
+        dbg!(&response);
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
+
+    #[tokio::test]
+    async fn by_tx_hash() {
+        let tx_hash = H256([1; 32]);
+        let mut storage = MockSolverCompetitionStoring::new();
+        storage
+            .expect_load_by_tx_hash()
+            .withf(move |hash| *hash == tx_hash)
+            .returning(|_| Ok(Some(Default::default())));
+        storage
+            .expect_load_by_tx_hash()
+            .withf(move |hash| *hash != tx_hash)
+            .returning(|_| Ok(None));
+        let filter = get_by_tx_hash(Arc::new(storage));
+
+        let request_ = request()
+            .path(&format!("/solver_competition/by_tx_hash/{:?}", tx_hash))
+            .method("GET");
+        let response = request_.filter(&filter).await.unwrap().into_response();
+        dbg!(&response);
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let request_ = request()
+            .path(&format!(
+                "/solver_competition/by_tx_hash/{:?}",
+                H256([2; 32])
+            ))
+            .method("GET");
+        let response = request_.filter(&filter).await.unwrap().into_response();
+        dbg!(&response);
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 }
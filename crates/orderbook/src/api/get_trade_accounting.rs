@@ -0,0 +1,39 @@
+use crate::{api::convert_json_response, database::trades::TradeRetrieving};
+use anyhow::Result;
+use ethcontract::H256;
+use std::{convert::Infallible, sync::Arc};
+use warp::{Filter, Rejection};
+
+fn get_trade_accounting_request() -> impl Filter<Extract = (H256,), Error = Rejection> + Clone {
+    warp::path!("transactions" / H256 / "accounting").and(warp::get())
+}
+
+pub fn get_trade_accounting(
+    database: Arc<dyn TradeRetrieving>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    get_trade_accounting_request().and_then(move |hash: H256| {
+        let database = database.clone();
+        async move {
+            let result = database.trade_accounting(&hash).await;
+            Result::<_, Infallible>::Ok(convert_json_response(result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    #[tokio::test]
+    async fn request_ok() {
+        let hash_str = "0x0191dbb560e936bd3320d5a505c9c05580a0ebb7e12fe117551ac26e484f295e";
+        let result = warp::test::request()
+            .path(&format!("/transactions/{:}/accounting", hash_str))
+            .method("GET")
+            .filter(&get_trade_accounting_request())
+            .await
+            .unwrap();
+        assert_eq!(result, H256::from_str(hash_str).unwrap());
+    }
+}
@@ -1,24 +1,50 @@
 use crate::{
-    api::convert_json_response,
+    api::IntoWarpReply,
     database::trades::{TradeFilter, TradeRetrieving},
 };
 use anyhow::{Context, Result};
 use model::order::OrderUid;
-use primitive_types::H160;
+use primitive_types::{H160, H256};
 use serde::Deserialize;
 use std::{convert::Infallible, sync::Arc};
-use warp::{hyper::StatusCode, Filter, Rejection};
+use warp::{
+    hyper::StatusCode,
+    reply::{self, Response},
+    Filter, Rejection, Reply,
+};
+
+/// Response header containing the number of trades matching the filter
+/// regardless of pagination.
+pub const TOTAL_COUNT_HEADER: &str = "X-Total-Count";
+
+const MIN_LIMIT: u64 = 1;
+const MAX_LIMIT: u64 = 1000;
+const DEFAULT_LIMIT: u64 = 100;
 
 #[derive(Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct Query {
     pub order_uid: Option<OrderUid>,
     pub owner: Option<H160>,
+    pub token: Option<H160>,
+    pub from_block: Option<u64>,
+    pub to_block: Option<u64>,
+    pub tx_hash: Option<H256>,
+    pub offset: Option<u64>,
+    pub limit: Option<u64>,
 }
 
 #[derive(Debug, Eq, PartialEq)]
 enum TradeFilterError {
     InvalidFilter(String),
+    LimitOutOfBounds,
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct TradesRequest {
+    filter: TradeFilter,
+    offset: u64,
+    limit: u64,
 }
 
 impl Query {
@@ -26,21 +52,46 @@ impl Query {
         TradeFilter {
             order_uid: self.order_uid,
             owner: self.owner,
+            token: self.token,
+            from_block: self.from_block,
+            to_block: self.to_block,
+            tx_hash: self.tx_hash,
         }
     }
 
-    fn validate(&self) -> Result<TradeFilter, TradeFilterError> {
-        match (self.order_uid.as_ref(), self.owner.as_ref()) {
-            (Some(_), None) | (None, Some(_)) => Ok(self.trade_filter()),
-            _ => Err(TradeFilterError::InvalidFilter(
-                "Must specify exactly one of owner and order_uid.".to_owned(),
-            )),
+    fn validate(&self) -> Result<TradesRequest, TradeFilterError> {
+        match (
+            self.order_uid.as_ref(),
+            self.owner.as_ref(),
+            self.tx_hash.as_ref(),
+        ) {
+            (Some(_), None, _) | (None, Some(_), _) | (None, None, Some(_)) => (),
+            _ => {
+                return Err(TradeFilterError::InvalidFilter(
+                    "Must specify exactly one of owner and orderUid or only txHash.".to_owned(),
+                ))
+            }
         }
+        if let (Some(from_block), Some(to_block)) = (self.from_block, self.to_block) {
+            if from_block > to_block {
+                return Err(TradeFilterError::InvalidFilter(
+                    "fromBlock must not be larger than toBlock.".to_owned(),
+                ));
+            }
+        }
+        if matches!(self.limit, Some(limit) if !(MIN_LIMIT..=MAX_LIMIT).contains(&limit)) {
+            return Err(TradeFilterError::LimitOutOfBounds);
+        }
+        Ok(TradesRequest {
+            filter: self.trade_filter(),
+            offset: self.offset.unwrap_or_default(),
+            limit: self.limit.unwrap_or(DEFAULT_LIMIT),
+        })
     }
 }
 
 fn get_trades_request(
-) -> impl Filter<Extract = (Result<TradeFilter, TradeFilterError>,), Error = Rejection> + Clone {
+) -> impl Filter<Extract = (Result<TradesRequest, TradeFilterError>,), Error = Rejection> + Clone {
     warp::path!("trades")
         .and(warp::get())
         .and(warp::query::<Query>())
@@ -49,20 +100,37 @@ fn get_trades_request(
 
 pub fn get_trades(
     db: Arc<dyn TradeRetrieving>,
-) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+) -> impl Filter<Extract = (Response,), Error = Rejection> + Clone {
     get_trades_request().and_then(move |request_result| {
         let database = db.clone();
         async move {
-            match request_result {
-                Ok(trade_filter) => {
-                    let result = database.trades(&trade_filter).await.context("get_trades");
-                    Result::<_, Infallible>::Ok(convert_json_response(result))
-                }
+            let response = match request_result {
+                Ok(request) => match database
+                    .trades(&request.filter, request.offset, request.limit)
+                    .await
+                    .context("get_trades")
+                {
+                    Ok(page) => reply::with_header(
+                        reply::with_status(reply::json(&page.trades), StatusCode::OK),
+                        TOTAL_COUNT_HEADER,
+                        page.total_count.to_string(),
+                    )
+                    .into_response(),
+                    Err(err) => err.into_warp_reply().into_response(),
+                },
                 Err(TradeFilterError::InvalidFilter(msg)) => {
                     let err = super::error("InvalidTradeFilter", msg);
-                    Ok(warp::reply::with_status(err, StatusCode::BAD_REQUEST))
+                    reply::with_status(err, StatusCode::BAD_REQUEST).into_response()
                 }
-            }
+                Err(TradeFilterError::LimitOutOfBounds) => {
+                    let err = super::error(
+                        "LIMIT_OUT_OF_BOUNDS",
+                        &format!("The pagination limit is [{},{}].", MIN_LIMIT, MAX_LIMIT),
+                    );
+                    reply::with_status(err, StatusCode::BAD_REQUEST).into_response()
+                }
+            };
+            Result::<_, Infallible>::Ok(response)
         }
     })
 }
@@ -87,8 +155,8 @@ mod tests {
             .await
             .unwrap()
             .unwrap();
-        assert_eq!(result.owner, Some(owner));
-        assert_eq!(result.order_uid, None);
+        assert_eq!(result.filter.owner, Some(owner));
+        assert_eq!(result.filter.order_uid, None);
 
         let uid = OrderUid([1u8; 56]);
         let order_uid_path = format!("/trades?orderUid={:}", uid);
@@ -96,8 +164,42 @@ mod tests {
             .await
             .unwrap()
             .unwrap();
-        assert_eq!(result.owner, None);
-        assert_eq!(result.order_uid, Some(uid));
+        assert_eq!(result.filter.owner, None);
+        assert_eq!(result.filter.order_uid, Some(uid));
+
+        let tx_hash = H256::from_low_u64_be(2);
+        let path = format!(
+            "/trades?owner=0x{:x}&token=0x{:x}&fromBlock=1&toBlock=2&txHash={:?}&offset=3&limit=4",
+            owner, owner, tx_hash
+        );
+        let result = trade_filter(request().path(path.as_str()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            result,
+            TradesRequest {
+                filter: TradeFilter {
+                    owner: Some(owner),
+                    token: Some(owner),
+                    from_block: Some(1),
+                    to_block: Some(2),
+                    tx_hash: Some(tx_hash),
+                    ..Default::default()
+                },
+                offset: 3,
+                limit: 4,
+            }
+        );
+
+        let path = format!("/trades?txHash={:?}", tx_hash);
+        let result = trade_filter(request().path(path.as_str()))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(result.filter.tx_hash, Some(tx_hash));
+        assert_eq!(result.offset, 0);
+        assert_eq!(result.limit, DEFAULT_LIMIT);
     }
 
     #[tokio::test]
@@ -117,5 +219,13 @@ mod tests {
         let path = "/trades";
         let result = trade_filter(request().path(path)).await.unwrap();
         assert!(result.is_err());
+
+        let path = format!("/trades?owner=0x{:x}&fromBlock=2&toBlock=1", owner);
+        let result = trade_filter(request().path(path.as_str())).await.unwrap();
+        assert!(matches!(result, Err(TradeFilterError::InvalidFilter(_))));
+
+        let path = format!("/trades?owner=0x{:x}&limit=1001", owner);
+        let result = trade_filter(request().path(path.as_str())).await.unwrap();
+        assert_eq!(result, Err(TradeFilterError::LimitOutOfBounds));
     }
 }
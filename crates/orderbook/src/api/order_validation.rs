@@ -1,15 +1,23 @@
 use crate::{
     account_balances::{BalanceFetching, TransferSimulationError},
     api::IntoWarpReply,
+    database::{
+        app_data::AppDataStoring,
+        quotes::{QuoteData, QuoteStoring},
+    },
     fee::{FeeData, FeeParameters, GetUnsubsidizedMinFeeError, MinFeeCalculating},
+    signature_validator::{SignatureCheck, SignatureValidating, SignatureValidationError},
 };
+use chrono::Utc;
 use contracts::WETH9;
 use ethcontract::{H160, U256};
 use model::{
+    app_data::AppDataDocument,
     order::{
-        BuyTokenDestination, Order, OrderCreation, OrderKind, SellTokenSource, BUY_ETH_ADDRESS,
+        BuyTokenDestination, Hooks, Order, OrderClass, OrderCreation, OrderKind, SellTokenSource,
+        BUY_ETH_ADDRESS,
     },
-    signature::SigningScheme,
+    signature::{Signature, SigningScheme},
     DomainSeparator,
 };
 use shared::{
@@ -40,9 +48,15 @@ pub trait OrderValidating: Send + Sync {
     /// (i.e. once all the required fields on an Order are provided). Specifically, verifying that
     ///     - buy & sell amounts are non-zero,
     ///     - order's owner matches the from field (if specified),
-    ///     - fee is sufficient,
+    ///     - limit orders sign a zero fee and aren't placed by liquidity
+    ///       order owners,
+    ///     - EIP-1271 signatures are accepted by the owner contract,
+    ///     - fee is sufficient (except for limit orders which pay their fee
+    ///       from surplus),
     ///     - buy & sell tokens passed "bad token" detection,
-    ///     - user has sufficient (transferable) funds to execute the order.
+    ///     - user has sufficient (transferable) funds to execute the order,
+    ///     - hooks declared in the app data only call allowed contracts and
+    ///       stay within the gas limit.
     ///
     /// Furthermore, full order validation also calls partial_validate to ensure that
     /// other aspects of the order are not malformed.
@@ -50,6 +64,7 @@ pub trait OrderValidating: Send + Sync {
         &self,
         order_creation: OrderCreation,
         sender: Option<H160>,
+        class: OrderClass,
         domain_separator: &DomainSeparator,
         settlement_contract: H160,
     ) -> Result<(Order, FeeParameters), ValidationError>;
@@ -59,12 +74,12 @@ pub trait OrderValidating: Send + Sync {
 pub enum PartialValidationError {
     Forbidden,
     InsufficientValidTo,
+    ExcessiveValidTo,
     TransferEthToContract,
     InvalidNativeSellToken,
     SameBuyAndSellToken,
     UnsupportedBuyTokenDestination(BuyTokenDestination),
     UnsupportedSellTokenSource(SellTokenSource),
-    UnsupportedOrderType,
     Other(anyhow::Error),
 }
 
@@ -79,13 +94,6 @@ impl IntoWarpReply for PartialValidationError {
                 super::error("UnsupportedSellTokenSource", format!("Type {:?}", src)),
                 StatusCode::BAD_REQUEST,
             ),
-            Self::UnsupportedOrderType => with_status(
-                super::error(
-                    "UnsupportedOrderType",
-                    "This order type is currently not supported",
-                ),
-                StatusCode::BAD_REQUEST,
-            ),
             Self::Forbidden => with_status(
                 super::error("Forbidden", "Forbidden, your account is deny-listed"),
                 StatusCode::FORBIDDEN,
@@ -97,6 +105,10 @@ impl IntoWarpReply for PartialValidationError {
                 ),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::ExcessiveValidTo => with_status(
+                super::error("ExcessiveValidTo", "validTo is too far into the future"),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::TransferEthToContract => with_status(
                 super::error(
                     "TransferEthToContract",
@@ -130,13 +142,19 @@ impl IntoWarpReply for PartialValidationError {
 pub enum ValidationError {
     Partial(PartialValidationError),
     InsufficientFee,
+    NonZeroFee,
+    UnsupportedOrderClass(OrderClass),
     InsufficientBalance,
     InsufficientAllowance,
     InvalidSignature,
+    // EIP-1271 signatures don't encode the owner so it must be specified
+    MissingFrom,
     // If fee and sell amount overflow u256
     SellAmountOverflow,
     TransferSimulationFailed,
     UnsupportedToken(H160),
+    UnsupportedHookTarget(H160),
+    ExcessiveHookGasLimit { max: u64 },
     WrongOwner(H160),
     ZeroAmount,
     Other(anyhow::Error),
@@ -150,6 +168,20 @@ impl IntoWarpReply for ValidationError {
                 super::error("UnsupportedToken", format!("Token address {}", token)),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::UnsupportedHookTarget(target) => with_status(
+                super::error(
+                    "UnsupportedHookTarget",
+                    format!("Hooks calling {} are not supported", target),
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::ExcessiveHookGasLimit { max } => with_status(
+                super::error(
+                    "ExcessiveHookGasLimit",
+                    format!("The hooks of an order can use at most {} gas", max),
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::WrongOwner(owner) => with_status(
                 super::error(
                     "WrongOwner",
@@ -178,10 +210,28 @@ impl IntoWarpReply for ValidationError {
                 super::error("InvalidSignature", "invalid signature"),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::MissingFrom => with_status(
+                super::error(
+                    "MissingFrom",
+                    "From address must be specified for EIP-1271 signatures",
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::InsufficientFee => with_status(
                 super::error("InsufficientFee", "Order does not include sufficient fee"),
                 StatusCode::BAD_REQUEST,
             ),
+            Self::NonZeroFee => with_status(
+                super::error("NonZeroFee", "Limit orders must sign a zero fee"),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::UnsupportedOrderClass(class) => with_status(
+                super::error(
+                    "UnsupportedOrderClass",
+                    format!("{:?} orders are not supported for this owner", class),
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
             Self::SellAmountOverflow => with_status(
                 super::error(
                     "SellAmountOverflow",
@@ -216,10 +266,27 @@ pub struct OrderValidator {
     banned_users: HashSet<H160>,
     liquidity_order_owners: HashSet<H160>,
     min_order_validity_period: Duration,
+    max_order_validity_period: Duration,
+    max_limit_order_validity_period: Duration,
     /// For Full-Validation: performed time of order placement
     fee_validator: Arc<dyn MinFeeCalculating>,
     bad_token_detector: Arc<dyn BadTokenDetecting>,
     balance_fetcher: Arc<dyn BalanceFetching>,
+    signature_validator: Arc<dyn SignatureValidating>,
+    quotes: Arc<dyn QuoteStoring>,
+    hooks: Option<HookValidation>,
+}
+
+/// Configures which of the hooks that orders declare in their app data
+/// documents are accepted.
+pub struct HookValidation {
+    pub app_data: Arc<dyn AppDataStoring>,
+    /// Hooks are executed by the settlement contract so they may only call
+    /// contracts that can't use its privileges, like a trampoline contract
+    /// that forwards the calls.
+    pub allowed_targets: HashSet<H160>,
+    /// The maximum sum of the gas limits of an order's hooks.
+    pub max_gas_limit: u64,
 }
 
 #[derive(Default, Debug, PartialEq)]
@@ -232,7 +299,7 @@ pub struct PreOrderData {
     pub partially_fillable: bool,
     pub buy_token_balance: BuyTokenDestination,
     pub sell_token_balance: SellTokenSource,
-    pub is_liquidity_order: bool,
+    pub class: OrderClass,
 }
 
 fn actual_receiver(owner: H160, order: &OrderCreation) -> H160 {
@@ -245,11 +312,7 @@ fn actual_receiver(owner: H160, order: &OrderCreation) -> H160 {
 }
 
 impl PreOrderData {
-    pub fn from_order_creation(
-        owner: H160,
-        order: &OrderCreation,
-        is_liquidity_order: bool,
-    ) -> Self {
+    pub fn from_order_creation(owner: H160, order: &OrderCreation) -> Self {
         Self {
             owner,
             sell_token: order.sell_token,
@@ -259,7 +322,7 @@ impl PreOrderData {
             partially_fillable: order.partially_fillable,
             buy_token_balance: order.buy_token_balance,
             sell_token_balance: order.sell_token_balance,
-            is_liquidity_order,
+            class: OrderClass::Market,
         }
     }
 }
@@ -272,9 +335,13 @@ impl OrderValidator {
         banned_users: HashSet<H160>,
         liquidity_order_owners: HashSet<H160>,
         min_order_validity_period: Duration,
+        max_order_validity_period: Duration,
+        max_limit_order_validity_period: Duration,
         fee_validator: Arc<dyn MinFeeCalculating>,
         bad_token_detector: Arc<dyn BadTokenDetecting>,
         balance_fetcher: Arc<dyn BalanceFetching>,
+        signature_validator: Arc<dyn SignatureValidating>,
+        quotes: Arc<dyn QuoteStoring>,
     ) -> Self {
         Self {
             code_fetcher,
@@ -282,19 +349,32 @@ impl OrderValidator {
             banned_users,
             liquidity_order_owners,
             min_order_validity_period,
+            max_order_validity_period,
+            max_limit_order_validity_period,
             fee_validator,
             bad_token_detector,
             balance_fetcher,
+            signature_validator,
+            quotes,
+            hooks: None,
         }
     }
+
+    /// Attaches the hooks declared in the app data document of orders to
+    /// them. Without this hooks are ignored.
+    pub fn with_hooks(mut self, hooks: HookValidation) -> Self {
+        self.hooks = Some(hooks);
+        self
+    }
+
+    pub fn is_liquidity_order_owner(&self, owner: &H160) -> bool {
+        self.liquidity_order_owners.contains(owner)
+    }
 }
 
 #[async_trait::async_trait]
 impl OrderValidating for OrderValidator {
     async fn partial_validate(&self, order: PreOrderData) -> Result<(), PartialValidationError> {
-        if order.partially_fillable && !order.is_liquidity_order {
-            return Err(PartialValidationError::UnsupportedOrderType);
-        }
         if self.banned_users.contains(&order.owner) {
             return Err(PartialValidationError::Forbidden);
         }
@@ -311,11 +391,23 @@ impl OrderValidating for OrderValidator {
                 order.sell_token_balance,
             ));
         }
-        if order.valid_to
-            < shared::time::now_in_epoch_seconds() + self.min_order_validity_period.as_secs() as u32
-        {
+        let now = shared::time::now_in_epoch_seconds();
+        if order.valid_to < now + self.min_order_validity_period.as_secs() as u32 {
             return Err(PartialValidationError::InsufficientValidTo);
         }
+        // Liquidity orders are placed by market makers that manage the
+        // lifetime of their orders themselves.
+        if !self.liquidity_order_owners.contains(&order.owner) {
+            let max_order_validity_period = match order.class {
+                OrderClass::Market => self.max_order_validity_period,
+                OrderClass::Limit => self.max_limit_order_validity_period,
+            };
+            if order.valid_to as u64
+                > (now as u64).saturating_add(max_order_validity_period.as_secs())
+            {
+                return Err(PartialValidationError::ExcessiveValidTo);
+            }
+        }
         if has_same_buy_and_sell_token(&order, &self.native_token) {
             return Err(PartialValidationError::SameBuyAndSellToken);
         }
@@ -339,13 +431,16 @@ impl OrderValidating for OrderValidator {
         &self,
         order_creation: OrderCreation,
         sender: Option<H160>,
+        class: OrderClass,
         domain_separator: &DomainSeparator,
         settlement_contract: H160,
     ) -> Result<(Order, FeeParameters), ValidationError> {
-        let owner = order_creation
-            .signature
-            .validate(domain_separator, &order_creation.hash_struct())
-            .ok_or(ValidationError::InvalidSignature)?;
+        let owner = match &order_creation.signature {
+            Signature::Eip1271(_) => sender.ok_or(ValidationError::MissingFrom)?,
+            signature => signature
+                .validate(domain_separator, &order_creation.hash_struct())
+                .ok_or(ValidationError::InvalidSignature)?,
+        };
 
         if order_creation.buy_amount.is_zero() || order_creation.sell_amount.is_zero() {
             return Err(ValidationError::ZeroAmount);
@@ -353,6 +448,20 @@ impl OrderValidating for OrderValidator {
         if matches!(sender, Some(from) if from != owner) {
             return Err(ValidationError::WrongOwner(owner));
         }
+        if let Some(check) = SignatureCheck::for_signature(
+            &order_creation.signature,
+            owner,
+            domain_separator,
+            &order_creation.hash_struct(),
+        ) {
+            self.signature_validator
+                .validate_signature(&check)
+                .await
+                .map_err(|err| match err {
+                    SignatureValidationError::Invalid => ValidationError::InvalidSignature,
+                    SignatureValidationError::Other(err) => ValidationError::Other(err),
+                })?;
+        }
         for &token in &[order_creation.sell_token, order_creation.buy_token] {
             if !self
                 .bad_token_detector
@@ -366,45 +475,21 @@ impl OrderValidating for OrderValidator {
         }
 
         let is_liquidity_order = self.liquidity_order_owners.contains(&owner);
-        self.partial_validate(PreOrderData::from_order_creation(
-            owner,
-            &order_creation,
-            is_liquidity_order,
-        ))
+        validate_order_class(class, &order_creation, is_liquidity_order)?;
+        self.partial_validate(PreOrderData {
+            class,
+            ..PreOrderData::from_order_creation(owner, &order_creation)
+        })
         .await
         .map_err(ValidationError::Partial)?;
 
-        let unsubsidized_fee = self
-            .fee_validator
-            .get_unsubsidized_min_fee(
-                FeeData {
-                    sell_token: order_creation.sell_token,
-                    buy_token: order_creation.buy_token,
-                    amount: match order_creation.kind {
-                        OrderKind::Buy => order_creation.buy_amount,
-                        OrderKind::Sell => order_creation.sell_amount,
-                    },
-                    kind: order_creation.kind,
-                },
-                order_creation.app_data,
-                order_creation.fee_amount,
-                owner,
-            )
-            .await
-            .map_err(|err| match err {
-                GetUnsubsidizedMinFeeError::Other(err) => ValidationError::Other(err),
-                GetUnsubsidizedMinFeeError::PriceEstimationError(PriceEstimationError::Other(
-                    err,
-                )) => ValidationError::Other(err),
-                GetUnsubsidizedMinFeeError::InsufficientFee => ValidationError::InsufficientFee,
-                // Some of the possible errors here have been already checked in this function or
-                // should have been checked when the order was pre-validated. There is no good way
-                // and not much need to bubble them up and we don't want to error log for them so
-                // treat them as insufficient fee.
-                GetUnsubsidizedMinFeeError::PriceEstimationError(_) => {
-                    ValidationError::InsufficientFee
-                }
-            })?;
+        let hooks = self.validate_hooks(&order_creation).await?;
+        let unsubsidized_fee = match class {
+            // Limit orders don't sign a fee. Their fee is computed when they
+            // get executed and taken from the surplus instead.
+            OrderClass::Limit => FeeParameters::default(),
+            OrderClass::Market => self.validate_fee(&order_creation, owner, &hooks).await?,
+        };
 
         let min_balance = match minimum_balance(&order_creation) {
             Some(amount) => amount,
@@ -453,7 +538,7 @@ impl OrderValidating for OrderValidator {
             },
         }
 
-        let order = Order::from_order_creation(
+        let mut order = Order::from_order_creation(
             &order_creation,
             domain_separator,
             settlement_contract,
@@ -461,10 +546,134 @@ impl OrderValidating for OrderValidator {
             owner,
             is_liquidity_order,
         );
+        order.metadata.class = class;
+        order.metadata.hooks = hooks;
         Ok((order, unsubsidized_fee))
     }
 }
 
+impl OrderValidator {
+    /// Returns the hooks of the order's app data document.
+    ///
+    /// An unknown or unparseable document is treated as declaring no hooks
+    /// and the order is accepted. Only orders whose stored document declares
+    /// hooks that fail validation are rejected.
+    async fn validate_hooks(
+        &self,
+        order_creation: &OrderCreation,
+    ) -> Result<Hooks, ValidationError> {
+        let validation = match &self.hooks {
+            Some(validation) => validation,
+            None => return Ok(Hooks::default()),
+        };
+        let app_data = match validation
+            .app_data
+            .app_data(&order_creation.app_data)
+            .await
+            .map_err(ValidationError::Other)?
+        {
+            Some(app_data) => app_data,
+            None => return Ok(Hooks::default()),
+        };
+        let hooks = match serde_json::from_str::<AppDataDocument>(&app_data.full_app_data) {
+            Ok(document) => document.metadata.hooks,
+            Err(err) => {
+                tracing::debug!(?err, app_data = ?order_creation.app_data, "unparseable app data");
+                return Ok(Hooks::default());
+            }
+        };
+
+        if let Some(hook) = hooks
+            .iter()
+            .find(|hook| !validation.allowed_targets.contains(&hook.target))
+        {
+            return Err(ValidationError::UnsupportedHookTarget(hook.target));
+        }
+        if hooks.gas_limit() > validation.max_gas_limit {
+            return Err(ValidationError::ExcessiveHookGasLimit {
+                max: validation.max_gas_limit,
+            });
+        }
+        Ok(hooks)
+    }
+
+    /// Checks that the signed fee of a market order covers the minimum fee.
+    ///
+    /// Orders referencing a still valid quote that they are compatible with
+    /// are validated against the quoted fee instead of a freshly computed one.
+    /// Quotes don't include the gas of hooks so orders with hooks always get
+    /// a fresh fee that covers it.
+    async fn validate_fee(
+        &self,
+        order_creation: &OrderCreation,
+        owner: H160,
+        hooks: &Hooks,
+    ) -> Result<FeeParameters, ValidationError> {
+        if let Some(id) = order_creation.quote_id.filter(|_| hooks.is_empty()) {
+            let quote = self
+                .quotes
+                .find_quote(id, Utc::now())
+                .await
+                .map_err(ValidationError::Other)?;
+            match quote {
+                Some(quote) if quote_covers_order(&quote, order_creation, owner) => {
+                    return Ok(quote.fee_parameters)
+                }
+                _ => tracing::debug!(%id, "order does not match quote; computing fee"),
+            }
+        }
+
+        self.fee_validator
+            .get_unsubsidized_min_fee(
+                FeeData {
+                    sell_token: order_creation.sell_token,
+                    buy_token: order_creation.buy_token,
+                    amount: match order_creation.kind {
+                        OrderKind::Buy => order_creation.buy_amount,
+                        OrderKind::Sell => order_creation.sell_amount,
+                    },
+                    kind: order_creation.kind,
+                },
+                order_creation.app_data,
+                order_creation.fee_amount,
+                owner,
+                hooks.gas_limit(),
+            )
+            .await
+            .map_err(|err| match err {
+                GetUnsubsidizedMinFeeError::Other(err) => ValidationError::Other(err),
+                GetUnsubsidizedMinFeeError::PriceEstimationError(PriceEstimationError::Other(
+                    err,
+                )) => ValidationError::Other(err),
+                GetUnsubsidizedMinFeeError::InsufficientFee => ValidationError::InsufficientFee,
+                // Some of the possible errors here have been already checked when validating the
+                // order or should have been checked when the order was pre-validated. There is no good way
+                // and not much need to bubble them up and we don't want to error log for them so
+                // treat them as insufficient fee.
+                GetUnsubsidizedMinFeeError::PriceEstimationError(_) => {
+                    ValidationError::InsufficientFee
+                }
+            })
+    }
+}
+
+/// Limit orders pay their fee from the surplus so they can't sign one.
+/// Liquidity orders never pay a fee and are always market orders.
+fn validate_order_class(
+    class: OrderClass,
+    order: &OrderCreation,
+    is_liquidity_order: bool,
+) -> Result<(), ValidationError> {
+    match class {
+        OrderClass::Market => Ok(()),
+        OrderClass::Limit if is_liquidity_order => {
+            Err(ValidationError::UnsupportedOrderClass(class))
+        }
+        OrderClass::Limit if !order.fee_amount.is_zero() => Err(ValidationError::NonZeroFee),
+        OrderClass::Limit => Ok(()),
+    }
+}
+
 /// Returns true if the orders have same buy and sell tokens.
 ///
 /// This also checks for orders selling wrapped native token for native token.
@@ -473,15 +682,35 @@ fn has_same_buy_and_sell_token(order: &PreOrderData, native_token: &WETH9) -> bo
         || (order.sell_token == native_token.address() && order.buy_token == BUY_ETH_ADDRESS)
 }
 
+/// Whether the quote is for the same token pair, order kind, owner, app data
+/// and balances and the order neither trades more than was quoted nor pays
+/// less fee.
+fn quote_covers_order(quote: &QuoteData, order: &OrderCreation, owner: H160) -> bool {
+    let amount_covered = match order.kind {
+        OrderKind::Sell => order.sell_amount <= quote.sell_amount,
+        OrderKind::Buy => order.buy_amount <= quote.buy_amount,
+    };
+    quote.sell_token == order.sell_token
+        && quote.buy_token == order.buy_token
+        && quote.kind == order.kind
+        && quote.from == owner
+        && quote.app_data == order.app_data
+        && quote.sell_token_balance == order.sell_token_balance
+        && quote.buy_token_balance == order.buy_token_balance
+        && amount_covered
+        && order.fee_amount >= quote.fee_amount
+}
+
 /// Min balance user must have in sell token for order to be accepted.
 ///
+/// Partially fillable orders can be executed with whatever balance is
+/// available, so it is enough for them to be able to transfer any amount.
+///
 /// None when addition overflows.
 fn minimum_balance(order: &OrderCreation) -> Option<U256> {
-    // TODO: Note that we are pessimistic here for partially fillable orders,
-    // since they don't need the full balance in order for the order to be
-    // tradable. However, since they are currently only used for PMMs for
-    // matching against user orders, it makes sense for the full sell token
-    // amount balance to be required.
+    if order.partially_fillable {
+        return Some(U256::one());
+    }
     order.sell_amount.checked_add(order.fee_amount)
 }
 
@@ -490,13 +719,20 @@ mod tests {
     use super::*;
     use crate::{
         account_balances::MockBalanceFetching,
+        database::{app_data::MockAppDataStoring, quotes::MockQuoteStoring},
         fee::{GetUnsubsidizedMinFeeError, MockMinFeeCalculating},
+        signature_validator::MockSignatureValidating,
     };
     use anyhow::anyhow;
     use ethcontract::web3::signing::SecretKeyRef;
     use maplit::hashset;
-    use model::{order::OrderBuilder, signature::EcdsaSigningScheme};
+    use model::{
+        app_data::AppData,
+        order::{Hook, OrderBuilder},
+        signature::EcdsaSigningScheme,
+    };
     use secp256k1::ONE_KEY;
+    use serde_json::json;
     use shared::{
         bad_token::{MockBadTokenDetecting, TokenQuality},
         dummy_contract,
@@ -517,6 +753,13 @@ mod tests {
             ..Default::default()
         };
         assert_eq!(minimum_balance(&order), Some(U256::from(2)));
+        let order = OrderCreation {
+            sell_amount: U256::MAX,
+            fee_amount: U256::from(1),
+            partially_fillable: true,
+            ..Default::default()
+        };
+        assert_eq!(minimum_balance(&order), Some(U256::from(1)));
     }
 
     #[test]
@@ -564,6 +807,8 @@ mod tests {
         let mut code_fetcher = Box::new(MockCodeFetching::new());
         let native_token = dummy_contract!(WETH9, [0xef; 20]);
         let min_order_validity_period = Duration::from_secs(1);
+        let max_order_validity_period = Duration::from_secs(100);
+        let max_limit_order_validity_period = Duration::from_secs(200);
         let banned_users = hashset![H160::from_low_u64_be(1)];
         let legit_valid_to =
             shared::time::now_in_epoch_seconds() + min_order_validity_period.as_secs() as u32 + 2;
@@ -577,19 +822,14 @@ mod tests {
             banned_users,
             hashset!(),
             min_order_validity_period,
+            max_order_validity_period,
+            max_limit_order_validity_period,
             Arc::new(MockMinFeeCalculating::new()),
             Arc::new(MockBadTokenDetecting::new()),
             Arc::new(MockBalanceFetching::new()),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
-        assert!(matches!(
-            validator
-                .partial_validate(PreOrderData {
-                    partially_fillable: true,
-                    ..Default::default()
-                })
-                .await,
-            Err(PartialValidationError::UnsupportedOrderType)
-        ));
         assert!(matches!(
             validator
                 .partial_validate(PreOrderData {
@@ -630,6 +870,25 @@ mod tests {
                 .await,
             Err(PartialValidationError::InsufficientValidTo)
         ));
+        assert!(matches!(
+            validator
+                .partial_validate(PreOrderData {
+                    valid_to: legit_valid_to + 150,
+                    ..Default::default()
+                })
+                .await,
+            Err(PartialValidationError::ExcessiveValidTo)
+        ));
+        assert!(matches!(
+            validator
+                .partial_validate(PreOrderData {
+                    valid_to: legit_valid_to + 250,
+                    class: OrderClass::Limit,
+                    ..Default::default()
+                })
+                .await,
+            Err(PartialValidationError::ExcessiveValidTo)
+        ));
         assert!(matches!(
             validator
                 .partial_validate(PreOrderData {
@@ -674,9 +933,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(MockMinFeeCalculating::new()),
             Arc::new(MockBadTokenDetecting::new()),
             Arc::new(MockBalanceFetching::new()),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
 
         assert!(matches!(
@@ -693,17 +956,20 @@ mod tests {
 
     #[tokio::test]
     async fn pre_validate_ok() {
-        let liquidity_order_owner = H160::from_low_u64_be(0x42);
         let min_order_validity_period = Duration::from_secs(1);
         let validator = OrderValidator::new(
             Box::new(MockCodeFetching::new()),
             dummy_contract!(WETH9, [0xef; 20]),
             hashset!(),
-            hashset!(liquidity_order_owner),
+            hashset!(),
             min_order_validity_period,
+            Duration::from_secs(100),
+            Duration::from_secs(200),
             Arc::new(MockMinFeeCalculating::new()),
             Arc::new(MockBadTokenDetecting::new()),
             Arc::new(MockBalanceFetching::new()),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = || PreOrderData {
             valid_to: shared::time::now_in_epoch_seconds()
@@ -718,8 +984,14 @@ mod tests {
         assert!(validator
             .partial_validate(PreOrderData {
                 partially_fillable: true,
-                is_liquidity_order: true,
-                owner: liquidity_order_owner,
+                ..order()
+            })
+            .await
+            .is_ok());
+        assert!(validator
+            .partial_validate(PreOrderData {
+                valid_to: order().valid_to + 150,
+                class: OrderClass::Limit,
                 ..order()
             })
             .await
@@ -733,7 +1005,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Ok(Default::default()));
+            .returning(|_, _, _, _, _| Ok(Default::default()));
         bad_token_detector
             .expect_detect()
             .returning(|_| Ok(TokenQuality::Good));
@@ -746,9 +1018,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -759,12 +1035,135 @@ mod tests {
             ..Default::default()
         };
         let (order, _) = validator
-            .validate_and_construct_order(order, None, &Default::default(), Default::default())
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
             .await
             .unwrap();
         assert_eq!(order.metadata.full_fee_amount, order.creation.fee_amount);
     }
 
+    #[tokio::test]
+    async fn post_validate_uses_referenced_quote() {
+        let quoted_fee = FeeParameters {
+            gas_amount: 1.,
+            gas_price: 2.,
+            sell_token_price: 3.,
+            ..Default::default()
+        };
+        let mut fee_calculator = MockMinFeeCalculating::new();
+        let mut bad_token_detector = MockBadTokenDetecting::new();
+        let mut balance_fetcher = MockBalanceFetching::new();
+        let mut quotes = MockQuoteStoring::new();
+        let order = OrderCreation {
+            valid_to: shared::time::now_in_epoch_seconds() + 2,
+            sell_token: H160::from_low_u64_be(1),
+            buy_token: H160::from_low_u64_be(2),
+            sell_amount: 10.into(),
+            buy_amount: 5.into(),
+            fee_amount: 2.into(),
+            kind: OrderKind::Sell,
+            quote_id: Some(42),
+            ..Default::default()
+        };
+        let owner = order
+            .signature
+            .validate(&Default::default(), &order.hash_struct())
+            .unwrap();
+        fee_calculator
+            .expect_get_unsubsidized_min_fee()
+            .times(2)
+            .returning(|_, _, _, _, _| Ok(Default::default()));
+        bad_token_detector
+            .expect_detect()
+            .returning(|_| Ok(TokenQuality::Good));
+        balance_fetcher
+            .expect_can_transfer()
+            .returning(|_, _, _, _| Ok(()));
+        quotes.expect_find_quote().returning(move |id, _| {
+            Ok(Some(QuoteData {
+                sell_token: H160::from_low_u64_be(1),
+                buy_token: H160::from_low_u64_be(2),
+                sell_amount: 10.into(),
+                buy_amount: 5.into(),
+                fee_amount: 2.into(),
+                kind: OrderKind::Sell,
+                // Quote 43 was handed out to someone else.
+                from: if id == 43 {
+                    H160::from_low_u64_be(3)
+                } else {
+                    owner
+                },
+                app_data: Default::default(),
+                sell_token_balance: Default::default(),
+                buy_token_balance: Default::default(),
+                fee_parameters: quoted_fee,
+                expiration: Utc::now(),
+            }))
+        });
+        let validator = OrderValidator::new(
+            Box::new(MockCodeFetching::new()),
+            dummy_contract!(WETH9, [0xef; 20]),
+            hashset!(),
+            hashset!(),
+            Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
+            Arc::new(fee_calculator),
+            Arc::new(bad_token_detector),
+            Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(quotes),
+        );
+        let (_, fee_parameters) = validator
+            .validate_and_construct_order(
+                order.clone(),
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fee_parameters, quoted_fee);
+
+        // Paying less fee than quoted falls back to computing the fee.
+        let (_, fee_parameters) = validator
+            .validate_and_construct_order(
+                OrderCreation {
+                    fee_amount: 1.into(),
+                    ..order.clone()
+                },
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fee_parameters, FeeParameters::default());
+
+        // Another owner's quote is not honoured.
+        let (_, fee_parameters) = validator
+            .validate_and_construct_order(
+                OrderCreation {
+                    quote_id: Some(43),
+                    ..order
+                },
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(fee_parameters, FeeParameters::default());
+    }
+
     #[tokio::test]
     async fn post_validate_err_zero_amount() {
         let mut fee_calculator = MockMinFeeCalculating::new();
@@ -772,7 +1171,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Ok(Default::default()));
+            .returning(|_, _, _, _, _| Ok(Default::default()));
         bad_token_detector
             .expect_detect()
             .returning(|_| Ok(TokenQuality::Good));
@@ -785,9 +1184,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -798,7 +1201,13 @@ mod tests {
             ..Default::default()
         };
         let result = validator
-            .validate_and_construct_order(order, None, &Default::default(), Default::default())
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
             .await;
         dbg!(&result);
         assert!(matches!(result, Err(ValidationError::ZeroAmount)));
@@ -811,7 +1220,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Ok(Default::default()));
+            .returning(|_, _, _, _, _| Ok(Default::default()));
         bad_token_detector
             .expect_detect()
             .returning(|_| Ok(TokenQuality::Good));
@@ -824,9 +1233,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -840,6 +1253,7 @@ mod tests {
             .validate_and_construct_order(
                 order,
                 Some(Default::default()),
+                OrderClass::Market,
                 &Default::default(),
                 Default::default(),
             )
@@ -854,7 +1268,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Err(GetUnsubsidizedMinFeeError::InsufficientFee));
+            .returning(|_, _, _, _, _| Err(GetUnsubsidizedMinFeeError::InsufficientFee));
         bad_token_detector
             .expect_detect()
             .returning(|_| Ok(TokenQuality::Good));
@@ -867,9 +1281,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -877,10 +1295,17 @@ mod tests {
             buy_token: H160::from_low_u64_be(2),
             buy_amount: U256::from(1),
             sell_amount: U256::from(1),
+            fee_amount: U256::from(1),
             ..Default::default()
         };
         let result = validator
-            .validate_and_construct_order(order, None, &Default::default(), Default::default())
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
             .await;
         dbg!(&result);
         assert!(matches!(result, Err(ValidationError::InsufficientFee)));
@@ -893,7 +1318,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Ok(Default::default()));
+            .returning(|_, _, _, _, _| Ok(Default::default()));
         bad_token_detector.expect_detect().returning(|_| {
             Ok(TokenQuality::Bad {
                 reason: Default::default(),
@@ -908,9 +1333,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -921,7 +1350,13 @@ mod tests {
             ..Default::default()
         };
         let result = validator
-            .validate_and_construct_order(order, None, &Default::default(), Default::default())
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
             .await;
         dbg!(&result);
         assert!(matches!(result, Err(ValidationError::UnsupportedToken(_))));
@@ -934,7 +1369,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Ok(Default::default()));
+            .returning(|_, _, _, _, _| Ok(Default::default()));
         bad_token_detector
             .expect_detect()
             .returning(|_| Ok(TokenQuality::Good));
@@ -947,9 +1382,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -961,7 +1400,13 @@ mod tests {
             ..Default::default()
         };
         let result = validator
-            .validate_and_construct_order(order, None, &Default::default(), Default::default())
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
             .await;
         dbg!(&result);
         assert!(matches!(result, Err(ValidationError::SellAmountOverflow)));
@@ -974,7 +1419,7 @@ mod tests {
         let mut balance_fetcher = MockBalanceFetching::new();
         fee_calculator
             .expect_get_unsubsidized_min_fee()
-            .returning(|_, _, _, _| Ok(Default::default()));
+            .returning(|_, _, _, _, _| Ok(Default::default()));
         bad_token_detector
             .expect_detect()
             .returning(|_| Ok(TokenQuality::Good));
@@ -987,9 +1432,13 @@ mod tests {
             hashset!(),
             hashset!(),
             Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
             Arc::new(fee_calculator),
             Arc::new(bad_token_detector),
             Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         );
         let order = OrderCreation {
             valid_to: shared::time::now_in_epoch_seconds() + 2,
@@ -1000,12 +1449,351 @@ mod tests {
             ..Default::default()
         };
         let result = validator
-            .validate_and_construct_order(order, None, &Default::default(), Default::default())
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
             .await;
         dbg!(&result);
         assert!(matches!(result, Err(ValidationError::InsufficientBalance)));
     }
 
+    #[tokio::test]
+    async fn post_validate_limit_order() {
+        let mut fee_calculator = MockMinFeeCalculating::new();
+        let mut bad_token_detector = MockBadTokenDetecting::new();
+        let mut balance_fetcher = MockBalanceFetching::new();
+        fee_calculator
+            .expect_get_unsubsidized_min_fee()
+            .returning(|_, _, _, _, _| Err(GetUnsubsidizedMinFeeError::InsufficientFee));
+        bad_token_detector
+            .expect_detect()
+            .returning(|_| Ok(TokenQuality::Good));
+        balance_fetcher
+            .expect_can_transfer()
+            .returning(|_, _, _, _| Ok(()));
+        let validator = OrderValidator::new(
+            Box::new(MockCodeFetching::new()),
+            dummy_contract!(WETH9, [0xef; 20]),
+            hashset!(),
+            hashset!(),
+            Duration::from_secs(1),
+            Duration::from_secs(10),
+            Duration::from_secs(100),
+            Arc::new(fee_calculator),
+            Arc::new(bad_token_detector),
+            Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
+        );
+        let order = OrderCreation {
+            valid_to: shared::time::now_in_epoch_seconds() + 50,
+            sell_token: H160::from_low_u64_be(1),
+            buy_token: H160::from_low_u64_be(2),
+            buy_amount: U256::from(1),
+            sell_amount: U256::from(1),
+            ..Default::default()
+        };
+
+        let (order_, _) = validator
+            .validate_and_construct_order(
+                order.clone(),
+                None,
+                OrderClass::Limit,
+                &Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(order_.metadata.class, OrderClass::Limit);
+        assert_eq!(order_.metadata.full_fee_amount, U256::zero());
+
+        // Limit orders can't sign a fee.
+        let result = validator
+            .validate_and_construct_order(
+                OrderCreation {
+                    fee_amount: U256::from(1),
+                    ..order.clone()
+                },
+                None,
+                OrderClass::Limit,
+                &Default::default(),
+                Default::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(ValidationError::NonZeroFee)));
+
+        // Market orders have a shorter lifetime and need to pass fee
+        // validation.
+        let result = validator
+            .validate_and_construct_order(
+                OrderCreation {
+                    fee_amount: U256::from(1),
+                    ..order.clone()
+                },
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
+            .await;
+        assert!(matches!(
+            result,
+            Err(ValidationError::Partial(
+                PartialValidationError::ExcessiveValidTo
+            ))
+        ));
+        let result = validator
+            .validate_and_construct_order(
+                OrderCreation {
+                    valid_to: shared::time::now_in_epoch_seconds() + 5,
+                    fee_amount: U256::from(1),
+                    ..order
+                },
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
+            .await;
+        assert!(matches!(result, Err(ValidationError::InsufficientFee)));
+    }
+
+    #[tokio::test]
+    async fn post_validate_partially_fillable_order() {
+        let mut fee_calculator = MockMinFeeCalculating::new();
+        let mut bad_token_detector = MockBadTokenDetecting::new();
+        let mut balance_fetcher = MockBalanceFetching::new();
+        fee_calculator
+            .expect_get_unsubsidized_min_fee()
+            .returning(|_, _, _, _, _| Ok(Default::default()));
+        bad_token_detector
+            .expect_detect()
+            .returning(|_| Ok(TokenQuality::Good));
+        balance_fetcher
+            .expect_can_transfer()
+            .withf(|_, _, amount, _| *amount == U256::one())
+            .returning(|_, _, _, _| Ok(()));
+        let validator = OrderValidator::new(
+            Box::new(MockCodeFetching::new()),
+            dummy_contract!(WETH9, [0xef; 20]),
+            hashset!(),
+            hashset!(),
+            Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
+            Arc::new(fee_calculator),
+            Arc::new(bad_token_detector),
+            Arc::new(balance_fetcher),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
+        );
+        let order = OrderCreation {
+            valid_to: shared::time::now_in_epoch_seconds() + 2,
+            sell_token: H160::from_low_u64_be(1),
+            buy_token: H160::from_low_u64_be(2),
+            buy_amount: U256::from(1),
+            sell_amount: U256::from(100),
+            partially_fillable: true,
+            ..Default::default()
+        };
+        let (order, _) = validator
+            .validate_and_construct_order(
+                order,
+                None,
+                OrderClass::Market,
+                &Default::default(),
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert!(order.creation.partially_fillable);
+        assert!(!order.metadata.is_liquidity_order);
+    }
+
+    #[tokio::test]
+    async fn validates_hooks() {
+        let mut app_data = MockAppDataStoring::new();
+        app_data.expect_app_data().returning(|app_id| {
+            let gas_limit = match app_id.0[0] {
+                0 => return Ok(None),
+                1 => 100_000,
+                _ => 100_001,
+            };
+            Ok(Some(AppData {
+                full_app_data: json!({
+                    "version": "0.6.0",
+                    "metadata": {
+                        "hooks": {
+                            "pre": [{
+                                "target": format!("{:?}", H160([app_id.0[1]; 20])),
+                                "callData": "0x01",
+                                "gasLimit": gas_limit.to_string(),
+                            }],
+                        },
+                    },
+                })
+                .to_string(),
+                ..Default::default()
+            }))
+        });
+        let validator = OrderValidator::new(
+            Box::new(MockCodeFetching::new()),
+            dummy_contract!(WETH9, [0xef; 20]),
+            hashset!(),
+            hashset!(),
+            Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
+            Arc::new(MockMinFeeCalculating::new()),
+            Arc::new(MockBadTokenDetecting::new()),
+            Arc::new(MockBalanceFetching::new()),
+            Arc::new(MockSignatureValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
+        )
+        .with_hooks(HookValidation {
+            app_data: Arc::new(app_data),
+            allowed_targets: hashset!(H160([1; 20])),
+            max_gas_limit: 100_000,
+        });
+        let order = |app_data: [u8; 2]| {
+            let mut order = OrderCreation::default();
+            order.app_data.0[..2].copy_from_slice(&app_data);
+            order
+        };
+
+        assert_eq!(
+            validator.validate_hooks(&order([0, 0])).await.unwrap(),
+            Hooks::default()
+        );
+        assert_eq!(
+            validator.validate_hooks(&order([0, 1])).await.unwrap(),
+            Hooks::default()
+        );
+        assert_eq!(
+            validator.validate_hooks(&order([1, 1])).await.unwrap(),
+            Hooks {
+                pre: vec![Hook {
+                    target: H160([1; 20]),
+                    call_data: vec![1],
+                    gas_limit: 100_000,
+                }],
+                post: vec![],
+            }
+        );
+        assert!(matches!(
+            validator.validate_hooks(&order([1, 2])).await,
+            Err(ValidationError::UnsupportedHookTarget(target)) if target == H160([2; 20])
+        ));
+        assert!(matches!(
+            validator.validate_hooks(&order([2, 1])).await,
+            Err(ValidationError::ExcessiveHookGasLimit { max: 100_000 })
+        ));
+    }
+
+    #[tokio::test]
+    async fn post_validate_eip1271_signature() {
+        let mut fee_calculator = MockMinFeeCalculating::new();
+        let mut bad_token_detector = MockBadTokenDetecting::new();
+        let mut balance_fetcher = MockBalanceFetching::new();
+        let mut signature_validator = MockSignatureValidating::new();
+        fee_calculator
+            .expect_get_unsubsidized_min_fee()
+            .returning(|_, _, _, _, _| Ok(Default::default()));
+        bad_token_detector
+            .expect_detect()
+            .returning(|_| Ok(TokenQuality::Good));
+        balance_fetcher
+            .expect_can_transfer()
+            .returning(|_, _, _, _| Ok(()));
+
+        let owner = H160([0x42; 20]);
+        let domain_separator = DomainSeparator([1; 32]);
+        let order = OrderCreation {
+            valid_to: shared::time::now_in_epoch_seconds() + 2,
+            sell_token: H160::from_low_u64_be(1),
+            buy_token: H160::from_low_u64_be(2),
+            buy_amount: U256::from(1),
+            sell_amount: U256::from(1),
+            signature: Signature::Eip1271(vec![1, 2, 3]),
+            ..Default::default()
+        };
+        let expected_check = SignatureCheck {
+            signer: owner,
+            hash: model::signature::hashed_eip712_message(&domain_separator, &order.hash_struct()),
+            signature: vec![1, 2, 3],
+        };
+        signature_validator
+            .expect_validate_signature()
+            .withf(move |check| *check == expected_check)
+            .times(1)
+            .returning(|_| Ok(()));
+        signature_validator
+            .expect_validate_signature()
+            .withf(|check| check.signature == [4])
+            .times(1)
+            .returning(|_| Err(SignatureValidationError::Invalid));
+
+        let validator = OrderValidator::new(
+            Box::new(MockCodeFetching::new()),
+            dummy_contract!(WETH9, [0xef; 20]),
+            hashset!(),
+            hashset!(),
+            Duration::from_secs(1),
+            Duration::MAX,
+            Duration::MAX,
+            Arc::new(fee_calculator),
+            Arc::new(bad_token_detector),
+            Arc::new(balance_fetcher),
+            Arc::new(signature_validator),
+            Arc::new(MockQuoteStoring::new()),
+        );
+
+        assert!(matches!(
+            validator
+                .validate_and_construct_order(
+                    order.clone(),
+                    None,
+                    OrderClass::Market,
+                    &domain_separator,
+                    Default::default()
+                )
+                .await,
+            Err(ValidationError::MissingFrom)
+        ));
+
+        let (validated, _) = validator
+            .validate_and_construct_order(
+                order.clone(),
+                Some(owner),
+                OrderClass::Market,
+                &domain_separator,
+                Default::default(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(validated.metadata.owner, owner);
+
+        assert!(matches!(
+            validator
+                .validate_and_construct_order(
+                    OrderCreation {
+                        signature: Signature::Eip1271(vec![4]),
+                        ..order
+                    },
+                    Some(owner),
+                    OrderClass::Market,
+                    &domain_separator,
+                    Default::default()
+                )
+                .await,
+            Err(ValidationError::InvalidSignature)
+        ));
+    }
+
     #[tokio::test]
     async fn allows_insufficient_allowance_and_balance_for_presign_orders() {
         macro_rules! assert_allows_failed_transfer {
@@ -1015,7 +1803,7 @@ mod tests {
                 let mut balance_fetcher = MockBalanceFetching::new();
                 fee_calculator
                     .expect_get_unsubsidized_min_fee()
-                    .returning(|_, _, _, _| Ok(Default::default()));
+                    .returning(|_, _, _, _, _| Ok(Default::default()));
                 bad_token_detector
                     .expect_detect()
                     .returning(|_| Ok(TokenQuality::Good));
@@ -1028,9 +1816,13 @@ mod tests {
                     hashset!(),
                     hashset!(),
                     Duration::from_secs(1),
+                    Duration::MAX,
+                    Duration::MAX,
                     Arc::new(fee_calculator),
                     Arc::new(bad_token_detector),
                     Arc::new(balance_fetcher),
+                    Arc::new(MockSignatureValidating::new()),
+                    Arc::new(MockQuoteStoring::new()),
                 );
 
                 let order = OrderBuilder::default()
@@ -1054,6 +1846,7 @@ mod tests {
                                     .build()
                                     .creation,
                                 None,
+                                OrderClass::Market,
                                 &Default::default(),
                                 Default::default()
                             )
@@ -1067,6 +1860,7 @@ mod tests {
                         .validate_and_construct_order(
                             order.with_presign(Default::default()).build().creation,
                             None,
+                            OrderClass::Market,
                             &Default::default(),
                             Default::default()
                         )
@@ -4,6 +4,7 @@ use crate::{
         order_validation::{OrderValidating, PreOrderData, ValidationError},
         IntoWarpReply,
     },
+    database::quotes::{QuoteData, QuoteStoring},
     fee::{FeeData, MinFeeCalculating, PriceQuality},
 };
 use anyhow::Result;
@@ -12,7 +13,7 @@ use ethcontract::{H160, U256};
 use futures::try_join;
 use model::{
     app_id::AppId,
-    order::{BuyTokenDestination, OrderKind, SellTokenSource},
+    order::{BuyTokenDestination, OrderClass, OrderKind, QuoteId, SellTokenSource},
     u256_decimal,
 };
 use serde::{Deserialize, Serialize};
@@ -53,7 +54,8 @@ impl From<&OrderQuoteRequest> for PreOrderData {
             partially_fillable: quote_request.partially_fillable,
             buy_token_balance: quote_request.buy_token_balance,
             sell_token_balance: quote_request.sell_token_balance,
-            is_liquidity_order: quote_request.partially_fillable,
+            // Quotes are for orders that get executed right away.
+            class: OrderClass::Market,
         }
     }
 }
@@ -121,6 +123,10 @@ pub struct OrderQuoteResponse {
     pub quote: OrderQuote,
     pub from: H160,
     pub expiration: DateTime<Utc>,
+    /// Orders can reference the quote by this id to be validated against the
+    /// quoted fee. Missing if the quote could not be stored.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub id: Option<QuoteId>,
 }
 
 #[derive(Debug)]
@@ -175,6 +181,8 @@ struct FeeParameters {
     fee_amount: U256,
     expiration: DateTime<Utc>,
     kind: OrderKind,
+    #[serde(skip)]
+    unsubsidized_fee: crate::fee::FeeParameters,
 }
 
 #[derive(Clone)]
@@ -184,6 +192,7 @@ pub struct OrderQuoter {
     pub order_validator: Arc<dyn OrderValidating>,
     pub fast_fee_calculator: Arc<dyn MinFeeCalculating>,
     pub fast_price_estimator: Arc<dyn PriceEstimating>,
+    pub quotes: Arc<dyn QuoteStoring>,
 }
 
 impl OrderQuoter {
@@ -191,6 +200,7 @@ impl OrderQuoter {
         fee_calculator: Arc<dyn MinFeeCalculating>,
         price_estimator: Arc<dyn PriceEstimating>,
         order_validator: Arc<dyn OrderValidating>,
+        quotes: Arc<dyn QuoteStoring>,
     ) -> Self {
         Self {
             fast_fee_calculator: fee_calculator.clone(),
@@ -198,6 +208,7 @@ impl OrderQuoter {
             fee_calculator,
             price_estimator,
             order_validator,
+            quotes,
         }
     }
 
@@ -224,6 +235,29 @@ impl OrderQuoter {
             .calculate_fee_parameters(quote_request)
             .await
             .map_err(OrderQuoteError::Fee)?;
+        let quote = QuoteData {
+            sell_token: quote_request.sell_token,
+            buy_token: quote_request.buy_token,
+            sell_amount: fee_parameters.sell_amount,
+            buy_amount: fee_parameters.buy_amount,
+            fee_amount: fee_parameters.fee_amount,
+            kind: fee_parameters.kind,
+            from: quote_request.from,
+            app_data: quote_request.app_data,
+            sell_token_balance: quote_request.sell_token_balance,
+            buy_token_balance: quote_request.buy_token_balance,
+            fee_parameters: fee_parameters.unsubsidized_fee,
+            expiration: fee_parameters.expiration,
+        };
+        // Storing the quote only allows orders to reference it later so we
+        // still return the quote if it fails.
+        let id = match self.quotes.insert_quote(&quote).await {
+            Ok(id) => Some(id),
+            Err(err) => {
+                tracing::warn!(?err, "failed to store quote");
+                None
+            }
+        };
         Ok(OrderQuoteResponse {
             quote: OrderQuote {
                 sell_token: quote_request.sell_token,
@@ -241,6 +275,7 @@ impl OrderQuoter {
             },
             from: quote_request.from,
             expiration: fee_parameters.expiration,
+            id,
         })
     }
 
@@ -271,8 +306,8 @@ impl OrderQuoter {
                     in_amount: sell_amount_before_fee,
                     kind: OrderKind::Sell,
                 };
-                let ((fee, expiration), estimate) = try_join!(
-                    fee_calculator.compute_subsidized_min_fee(
+                let (((fee, expiration), unsubsidized_fee), estimate) = try_join!(
+                    fee_calculator.compute_subsidized_min_fee_with_parameters(
                         FeeData {
                             sell_token: quote_request.sell_token,
                             buy_token: quote_request.buy_token,
@@ -306,6 +341,7 @@ impl OrderQuoter {
                     fee_amount: fee,
                     expiration,
                     kind: OrderKind::Sell,
+                    unsubsidized_fee,
                 }
             }
             OrderQuoteSide::Sell {
@@ -326,8 +362,8 @@ impl OrderQuoter {
                 };
 
                 // Since both futures are long running and independent, run concurrently
-                let ((fee, expiration), estimate) = try_join!(
-                    fee_calculator.compute_subsidized_min_fee(
+                let (((fee, expiration), unsubsidized_fee), estimate) = try_join!(
+                    fee_calculator.compute_subsidized_min_fee_with_parameters(
                         FeeData {
                             sell_token: quote_request.sell_token,
                             buy_token: quote_request.buy_token,
@@ -346,6 +382,7 @@ impl OrderQuoter {
                     fee_amount: fee,
                     expiration,
                     kind: OrderKind::Sell,
+                    unsubsidized_fee,
                 }
             }
             OrderQuoteSide::Buy {
@@ -363,8 +400,8 @@ impl OrderQuoter {
                 };
 
                 // Since both futures are long running and independent, run concurrently
-                let ((fee, expiration), estimate) = try_join!(
-                    fee_calculator.compute_subsidized_min_fee(
+                let (((fee, expiration), unsubsidized_fee), estimate) = try_join!(
+                    fee_calculator.compute_subsidized_min_fee_with_parameters(
                         FeeData {
                             sell_token: quote_request.sell_token,
                             buy_token: quote_request.buy_token,
@@ -384,6 +421,7 @@ impl OrderQuoter {
                     fee_amount: fee,
                     expiration,
                     kind: OrderKind::Buy,
+                    unsubsidized_fee,
                 }
             }
         })
@@ -429,6 +467,7 @@ mod tests {
     use super::*;
     use crate::{
         api::{order_validation::MockOrderValidating, response_body},
+        database::quotes::MockQuoteStoring,
         fee::MockMinFeeCalculating,
     };
     use anyhow::anyhow;
@@ -585,6 +624,7 @@ mod tests {
             quote,
             from: H160::zero(),
             expiration: DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(0, 0), Utc),
+            id: Some(0),
         };
         let response = convert_json_response::<OrderQuoteResponse, OrderQuoteError>(Ok(
             order_quote_response.clone(),
@@ -617,8 +657,8 @@ mod tests {
 
         let expiration = Utc::now();
         fee_calculator
-            .expect_compute_subsidized_min_fee()
-            .returning(move |_, _, _| Ok((3.into(), expiration)));
+            .expect_compute_subsidized_min_fee_with_parameters()
+            .returning(move |_, _, _| Ok(((3.into(), expiration), Default::default())));
 
         let fee_calculator = Arc::new(fee_calculator);
         let price_estimator = FakePriceEstimator(price_estimation::Estimate {
@@ -636,6 +676,7 @@ mod tests {
             fee_calculator,
             Arc::new(price_estimator),
             Arc::new(MockOrderValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         ));
         let result = quoter
             .calculate_fee_parameters(&sell_query)
@@ -651,7 +692,8 @@ mod tests {
                 sell_amount: 7.into(),
                 fee_amount: 3.into(),
                 expiration,
-                kind: OrderKind::Sell
+                kind: OrderKind::Sell,
+                unsubsidized_fee: Default::default(),
             }
         );
     }
@@ -661,8 +703,8 @@ mod tests {
         let mut fee_calculator = MockMinFeeCalculating::new();
         let expiration = Utc::now();
         fee_calculator
-            .expect_compute_subsidized_min_fee()
-            .returning(move |_, _, _| Ok((3.into(), expiration)));
+            .expect_compute_subsidized_min_fee_with_parameters()
+            .returning(move |_, _, _| Ok(((3.into(), expiration), Default::default())));
 
         let fee_calculator = Arc::new(fee_calculator);
         let price_estimator = FakePriceEstimator(price_estimation::Estimate {
@@ -681,6 +723,7 @@ mod tests {
             fee_calculator,
             Arc::new(price_estimator),
             Arc::new(MockOrderValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         ));
         let result = quoter
             .calculate_fee_parameters(&sell_query)
@@ -694,7 +737,8 @@ mod tests {
                 sell_amount: 7.into(),
                 fee_amount: 3.into(),
                 expiration,
-                kind: OrderKind::Sell
+                kind: OrderKind::Sell,
+                unsubsidized_fee: Default::default(),
             }
         );
     }
@@ -704,8 +748,8 @@ mod tests {
         let mut fee_calculator = MockMinFeeCalculating::new();
         let expiration = Utc::now();
         fee_calculator
-            .expect_compute_subsidized_min_fee()
-            .returning(move |_, _, _| Ok((3.into(), expiration)));
+            .expect_compute_subsidized_min_fee_with_parameters()
+            .returning(move |_, _, _| Ok(((3.into(), expiration), Default::default())));
 
         let fee_calculator = Arc::new(fee_calculator);
         let price_estimator = FakePriceEstimator(price_estimation::Estimate {
@@ -723,6 +767,7 @@ mod tests {
             fee_calculator,
             Arc::new(price_estimator),
             Arc::new(MockOrderValidating::new()),
+            Arc::new(MockQuoteStoring::new()),
         ));
         let result = quoter
             .calculate_fee_parameters(&buy_query)
@@ -738,7 +783,8 @@ mod tests {
                 sell_amount: 20.into(),
                 fee_amount: 3.into(),
                 expiration,
-                kind: OrderKind::Buy
+                kind: OrderKind::Buy,
+                unsubsidized_fee: Default::default(),
             }
         );
     }
@@ -764,8 +810,8 @@ mod tests {
 
         let mut fee_calculator = MockMinFeeCalculating::new();
         fee_calculator
-            .expect_compute_subsidized_min_fee()
-            .returning(move |_, _, _| Ok((3.into(), Utc::now())));
+            .expect_compute_subsidized_min_fee_with_parameters()
+            .returning(move |_, _, _| Ok(((3.into(), Utc::now()), Default::default())));
         let price_estimator = FakePriceEstimator(price_estimation::Estimate {
             out_amount: 14.into(),
             gas: 1000,
@@ -774,10 +820,16 @@ mod tests {
         order_validator
             .expect_partial_validate()
             .returning(|_| Ok(()));
+        let mut quotes = MockQuoteStoring::new();
+        quotes
+            .expect_insert_quote()
+            .withf(|quote| quote.fee_amount == 3.into() && quote.sell_amount == 14.into())
+            .returning(|_| Ok(42));
         let quoter = Arc::new(OrderQuoter::new(
             Arc::new(fee_calculator),
             Arc::new(price_estimator),
             Arc::new(order_validator),
+            Arc::new(quotes),
         ));
         let result = quoter.calculate_quote(&buy_request).await.unwrap();
 
@@ -796,5 +848,6 @@ mod tests {
             buy_token_balance: Default::default(),
         };
         assert_eq!(result.quote, expected);
+        assert_eq!(result.id, Some(42));
     }
 }
@@ -1,7 +1,7 @@
 //! This is a private, undocumented api which will get replaced when we move the solution
 //! competition into the api.
 
-use crate::solver_competition::SolverCompetition;
+use crate::{api::IntoWarpReply, database::solver_competition::SolverCompetitionStoring};
 use anyhow::Result;
 use model::solver_competition::SolverCompetitionResponse;
 use reqwest::StatusCode;
@@ -15,29 +15,38 @@ fn request() -> impl Filter<Extract = (u64, SolverCompetitionResponse), Error =
 }
 
 pub fn post(
-    handler: Arc<SolverCompetition>,
+    handler: Arc<dyn SolverCompetitionStoring>,
 ) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
-    request().and_then(move |auction_id: u64, model: SolverCompetitionResponse| {
-        let handler = handler.clone();
-        async move {
-            handler.set(auction_id, model);
-            let json = warp::reply::json(&());
-            let reply = warp::reply::with_status(json, StatusCode::CREATED);
-            Result::<_, Infallible>::Ok(reply)
-        }
-    })
+    request().and_then(
+        move |auction_id: u64, mut model: SolverCompetitionResponse| {
+            let handler = handler.clone();
+            async move {
+                model.auction_id = auction_id;
+                let reply = match handler.save(auction_id, &model).await {
+                    Ok(()) => warp::reply::with_status(warp::reply::json(&()), StatusCode::CREATED),
+                    Err(err) => err.into_warp_reply(),
+                };
+                Result::<_, Infallible>::Ok(reply)
+            }
+        },
+    )
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::solver_competition::MockSolverCompetitionStoring;
     use warp::{test::request, Reply};
 
     #[tokio::test]
     async fn test() {
-        let handler = SolverCompetition::default();
-        let handler = Arc::new(handler);
-        let filter = post(handler.clone());
+        let mut storage = MockSolverCompetitionStoring::new();
+        storage
+            .expect_save()
+            .withf(|id, model| *id == 1 && model.auction_id == 1)
+            .times(1)
+            .returning(|_, _| Ok(()));
+        let filter = post(Arc::new(storage));
         let body = serde_json::to_vec(&SolverCompetitionResponse::default()).unwrap();
 
         let request_ = request()
@@ -48,6 +57,5 @@ mod tests {
         let response = request_.filter(&filter).await.unwrap().into_response();
         dbg!(&response);
         assert_eq!(response.status(), StatusCode::CREATED);
-        assert!(handler.get(1).is_some());
     }
 }
@@ -0,0 +1,156 @@
+use crate::{api::IntoWarpReply, database::app_data::AppDataStoring};
+use anyhow::Result;
+use model::{
+    app_data::{app_data_hash, AppDataDocument, ParsedAppData, MAX_APP_DATA_SIZE},
+    app_id::AppId,
+};
+use std::{convert::Infallible, sync::Arc};
+use warp::{
+    body::bytes,
+    hyper::{body::Bytes, StatusCode},
+    reply::{json, with_status},
+    Filter, Rejection,
+};
+
+fn request() -> impl Filter<Extract = (AppId, Bytes), Error = Rejection> + Clone {
+    warp::path!("app_data" / AppId)
+        .and(warp::put())
+        .and(warp::body::content_length_limit(MAX_APP_DATA_SIZE as u64))
+        .and(bytes())
+}
+
+#[derive(Debug)]
+enum PutAppDataError {
+    HashMismatch(AppId),
+    InvalidAppData(anyhow::Error),
+    Other(anyhow::Error),
+}
+
+impl IntoWarpReply for PutAppDataError {
+    fn into_warp_reply(self) -> super::ApiReply {
+        match self {
+            Self::HashMismatch(hash) => with_status(
+                super::error(
+                    "AppDataHashMismatch",
+                    format!("app data document has hash {:?}", hash),
+                ),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::InvalidAppData(err) => with_status(
+                super::error("InvalidAppData", format!("{:?}", err)),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::Other(err) => err.into_warp_reply(),
+        }
+    }
+}
+
+/// Validates the document and returns it together with its parsed fields.
+fn parse(app_id: AppId, document: &[u8]) -> Result<(&str, ParsedAppData), PutAppDataError> {
+    let hash = app_data_hash(document);
+    if hash != app_id {
+        return Err(PutAppDataError::HashMismatch(hash));
+    }
+    let document =
+        std::str::from_utf8(document).map_err(|err| PutAppDataError::InvalidAppData(err.into()))?;
+    let parsed: AppDataDocument = serde_json::from_str(document)
+        .map_err(|err| PutAppDataError::InvalidAppData(err.into()))?;
+    Ok((document, parsed.into()))
+}
+
+fn response(app_id: AppId, result: Result<bool, PutAppDataError>) -> super::ApiReply {
+    match result {
+        Ok(true) => with_status(json(&app_id), StatusCode::CREATED),
+        Ok(false) => with_status(json(&app_id), StatusCode::OK),
+        Err(err) => err.into_warp_reply(),
+    }
+}
+
+pub fn put_app_data(
+    database: Arc<dyn AppDataStoring>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    request().and_then(move |app_id: AppId, document: Bytes| {
+        let database = database.clone();
+        async move {
+            let result = match parse(app_id, &document) {
+                Ok((document, parsed)) => database
+                    .insert_app_data(&app_id, document, &parsed)
+                    .await
+                    .map_err(PutAppDataError::Other),
+                Err(err) => Err(err),
+            };
+            Result::<_, Infallible>::Ok(response(app_id, result))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::app_data::MockAppDataStoring;
+    use warp::{test::request, Reply};
+
+    const DOCUMENT: &str = r#"{"version":"0.4.0","appCode":"CowSwap"}"#;
+
+    fn put(app_id: AppId, document: &str) -> warp::test::RequestBuilder {
+        request()
+            .path(&format!("/app_data/{:?}", app_id))
+            .method("PUT")
+            .body(document)
+    }
+
+    #[tokio::test]
+    async fn stores_document_matching_hash() {
+        let app_id = app_data_hash(DOCUMENT.as_bytes());
+        let mut database = MockAppDataStoring::new();
+        database
+            .expect_insert_app_data()
+            .withf(move |id, document, parsed| {
+                *id == app_id
+                    && document == DOCUMENT
+                    && parsed.app_code.as_deref() == Some("CowSwap")
+            })
+            .times(1)
+            .returning(|_, _, _| Ok(true));
+        let filter = put_app_data(Arc::new(database));
+        let response = put(app_id, DOCUMENT)
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let mut database = MockAppDataStoring::new();
+        database
+            .expect_insert_app_data()
+            .times(1)
+            .returning(|_, _, _| Ok(false));
+        let filter = put_app_data(Arc::new(database));
+        let response = put(app_id, DOCUMENT)
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_documents() {
+        let filter = put_app_data(Arc::new(MockAppDataStoring::new()));
+
+        let response = put(AppId([1; 32]), DOCUMENT)
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let document = r#"{"appCode":"CowSwap"}"#;
+        let response = put(app_data_hash(document.as_bytes()), document)
+            .filter(&filter)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{
+    api::{extract_payload, IntoWarpReply},
+    orderbook::{Orderbook, ReplaceOrderError},
+};
+use anyhow::Result;
+use model::order::{OrderCreationPayload, OrderUid};
+use std::{convert::Infallible, sync::Arc};
+use warp::reply::with_status;
+use warp::{hyper::StatusCode, Filter, Rejection};
+
+pub fn replace_order_request(
+) -> impl Filter<Extract = (OrderUid, OrderCreationPayload), Error = Rejection> + Clone {
+    warp::path!("orders" / OrderUid / "replace")
+        .and(warp::post())
+        .and(extract_payload())
+}
+
+impl IntoWarpReply for ReplaceOrderError {
+    fn into_warp_reply(self) -> super::ApiReply {
+        match self {
+            Self::AddOrder(err) => err.into_warp_reply(),
+            Self::OldOrder(err) => err.into_warp_reply(),
+            Self::OldOrderTraded => with_status(
+                super::error("OldOrderTraded", "Order to replace has already been traded"),
+                StatusCode::BAD_REQUEST,
+            ),
+            Self::OldOrderInFlight => with_status(
+                super::error(
+                    "OldOrderInFlight",
+                    "Order to replace is part of a settlement that is being submitted",
+                ),
+                StatusCode::CONFLICT,
+            ),
+        }
+    }
+}
+
+pub fn replace_order_response(result: Result<OrderUid, ReplaceOrderError>) -> super::ApiReply {
+    match result {
+        Ok(uid) => with_status(warp::reply::json(&uid), StatusCode::CREATED),
+        Err(err) => err.into_warp_reply(),
+    }
+}
+
+pub fn replace_order(
+    orderbook: Arc<Orderbook>,
+) -> impl Filter<Extract = (super::ApiReply,), Error = Rejection> + Clone {
+    replace_order_request().and_then(
+        move |old_order: OrderUid, order_payload: OrderCreationPayload| {
+            let orderbook = orderbook.clone();
+            async move {
+                let result = orderbook.replace_order(old_order, order_payload).await;
+                Result::<_, Infallible>::Ok(replace_order_response(result))
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{api::response_body, orderbook::OrderCancellationError};
+    use serde_json::json;
+    use warp::{test::request, Reply};
+
+    #[tokio::test]
+    async fn replace_order_request_ok() {
+        let filter = replace_order_request();
+        let uid = OrderUid([1u8; 56]);
+        let order_payload = OrderCreationPayload::default();
+        let request = request()
+            .path(&format!("/orders/{}/replace", uid))
+            .method("POST")
+            .header("content-type", "application/json")
+            .json(&order_payload);
+        let result = request.filter(&filter).await.unwrap();
+        assert_eq!(result, (uid, order_payload));
+    }
+
+    #[tokio::test]
+    async fn replace_order_response_errors() {
+        let response =
+            replace_order_response(Err(ReplaceOrderError::OldOrderInFlight)).into_response();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = replace_order_response(Err(ReplaceOrderError::OldOrder(
+            OrderCancellationError::WrongOwner,
+        )))
+        .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        let body: serde_json::Value =
+            serde_json::from_slice(&response_body(response).await).unwrap();
+        assert_eq!(body["errorType"], json!("WrongOwner"));
+    }
+}
@@ -0,0 +1,42 @@
+use crate::orderbook::Orderbook;
+use futures::StreamExt;
+use std::sync::Arc;
+use warp::{sse, Filter, Rejection, Reply};
+
+fn request() -> impl Filter<Extract = (), Error = Rejection> + Clone {
+    warp::path!("auction" / "updates").and(warp::get())
+}
+
+pub fn stream_auction(
+    orderbook: Arc<Orderbook>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    request().map(move || {
+        let events = orderbook
+            .auction_updates()
+            .map(|update| sse::Event::default().event("auction").json_data(&*update));
+        sse::reply(sse::keep_alive().stream(events))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn request_() {
+        assert!(
+            warp::test::request()
+                .path("/auction/updates")
+                .method("GET")
+                .matches(&request())
+                .await
+        );
+        assert!(
+            !warp::test::request()
+                .path("/auction")
+                .method("GET")
+                .matches(&request())
+                .await
+        );
+    }
+}
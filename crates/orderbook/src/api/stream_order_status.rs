@@ -0,0 +1,64 @@
+use crate::{order_status_updates::Subscription, orderbook::Orderbook};
+use futures::StreamExt;
+use model::order::OrderUid;
+use primitive_types::H160;
+use std::sync::Arc;
+use warp::{sse, Filter, Rejection, Reply};
+
+fn order_request() -> impl Filter<Extract = (Subscription,), Error = Rejection> + Clone {
+    warp::path!("orders" / OrderUid / "status_updates")
+        .and(warp::get())
+        .map(Subscription::Order)
+}
+
+fn user_orders_request() -> impl Filter<Extract = (Subscription,), Error = Rejection> + Clone {
+    warp::path!("account" / H160 / "orders" / "status_updates")
+        .and(warp::get())
+        .map(Subscription::Owner)
+}
+
+fn stream_response(orderbook: &Orderbook, subscription: Subscription) -> impl Reply {
+    let events = orderbook
+        .order_status_updates(subscription)
+        .map(|update| sse::Event::default().event("status").json_data(update));
+    sse::reply(sse::keep_alive().stream(events))
+}
+
+pub fn stream_order_status(
+    orderbook: Arc<Orderbook>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    order_request()
+        .or(user_orders_request())
+        .unify()
+        .map(move |subscription| stream_response(&orderbook, subscription))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn order_request_() {
+        let uid = OrderUid([1; 56]);
+        let path = format!("/orders/{}/status_updates", uid);
+        let result = warp::test::request()
+            .path(&path)
+            .method("GET")
+            .filter(&order_request())
+            .await
+            .unwrap();
+        assert_eq!(result, Subscription::Order(uid));
+    }
+
+    #[tokio::test]
+    async fn user_orders_request_() {
+        let path = "/account/0x0000000000000000000000000000000000000001/orders/status_updates";
+        let result = warp::test::request()
+            .path(path)
+            .method("GET")
+            .filter(&user_orders_request())
+            .await
+            .unwrap();
+        assert_eq!(result, Subscription::Owner(H160::from_low_u64_be(1)));
+    }
+}
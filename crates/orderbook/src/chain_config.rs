@@ -0,0 +1,158 @@
+//! Configuration of the chains served by a single order book process.
+
+use anyhow::{ensure, Context, Result};
+use model::u256_decimal::DecimalU256;
+use primitive_types::{H160, U256};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+use url::Url;
+
+/// The settings that differ between the chains an order book serves. All other
+/// settings are shared and taken from the command line arguments.
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ChainConfig {
+    /// The chain id is read from the node and only used to check that the
+    /// node is connected to the expected chain.
+    pub chain_id: Option<u64>,
+    #[serde_as(as = "DisplayFromStr")]
+    pub node_url: Url,
+    /// Every chain needs its own database or schema. A schema can be selected
+    /// through the `options` parameter of the url.
+    #[serde_as(as = "DisplayFromStr")]
+    pub db_url: Url,
+    #[serde(default)]
+    pub base_tokens: Vec<H160>,
+    #[serde_as(as = "Option<DecimalU256>")]
+    #[serde(default)]
+    pub amount_to_estimate_prices_with: Option<U256>,
+    #[serde(default)]
+    pub ethflow_contract: Option<H160>,
+    #[serde(default)]
+    pub ethflow_indexing_start: u64,
+    /// Path to the chain spec for chains that are not built into the services.
+    #[serde(default)]
+    pub chain_spec: Option<PathBuf>,
+    #[serde(default)]
+    pub allowed_tokens: Vec<H160>,
+    #[serde(default)]
+    pub unsupported_tokens: Vec<H160>,
+    #[serde(default)]
+    pub banned_users: Vec<H160>,
+    #[serde(default)]
+    pub liquidity_order_owners: Vec<H160>,
+    #[serde(default)]
+    pub hook_targets: Vec<H160>,
+}
+
+pub fn read_chain_configs(path: &Path) -> Result<Vec<ChainConfig>> {
+    let content =
+        std::fs::read(path).with_context(|| format!("failed to read chains file {:?}", path))?;
+    let configs = parse_chain_configs(&content)
+        .with_context(|| format!("failed to parse chains file {:?}", path))?;
+    Ok(configs)
+}
+
+fn parse_chain_configs(content: &[u8]) -> Result<Vec<ChainConfig>> {
+    let configs: Vec<ChainConfig> = serde_json::from_slice(content)?;
+    ensure!(!configs.is_empty(), "no chains configured");
+    let mut chain_ids = HashSet::new();
+    for chain_id in configs.iter().filter_map(|config| config.chain_id) {
+        ensure!(
+            chain_ids.insert(chain_id),
+            "chain {} configured twice",
+            chain_id
+        );
+    }
+    Ok(configs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_chain_configs() {
+        let configs = parse_chain_configs(
+            br#"[
+                {
+                    "chainId": 1,
+                    "nodeUrl": "http://mainnet.node",
+                    "dbUrl": "postgresql://mainnet",
+                    "baseTokens": ["0x0101010101010101010101010101010101010101"],
+                    "amountToEstimatePricesWith": "1000000000000000000",
+                    "ethflowContract": "0x0202020202020202020202020202020202020202",
+                    "ethflowIndexingStart": 42,
+                    "chainSpec": "devnet.json",
+                    "allowedTokens": ["0x0303030303030303030303030303030303030303"],
+                    "unsupportedTokens": ["0x0404040404040404040404040404040404040404"],
+                    "bannedUsers": ["0x0505050505050505050505050505050505050505"],
+                    "liquidityOrderOwners": ["0x0606060606060606060606060606060606060606"],
+                    "hookTargets": ["0x0707070707070707070707070707070707070707"]
+                },
+                {
+                    "nodeUrl": "http://gnosis.node",
+                    "dbUrl": "postgresql://gnosis"
+                }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            configs,
+            vec![
+                ChainConfig {
+                    chain_id: Some(1),
+                    node_url: "http://mainnet.node".parse().unwrap(),
+                    db_url: "postgresql://mainnet".parse().unwrap(),
+                    base_tokens: vec![H160([1; 20])],
+                    amount_to_estimate_prices_with: Some(U256::exp10(18)),
+                    ethflow_contract: Some(H160([2; 20])),
+                    ethflow_indexing_start: 42,
+                    chain_spec: Some("devnet.json".into()),
+                    allowed_tokens: vec![H160([3; 20])],
+                    unsupported_tokens: vec![H160([4; 20])],
+                    banned_users: vec![H160([5; 20])],
+                    liquidity_order_owners: vec![H160([6; 20])],
+                    hook_targets: vec![H160([7; 20])],
+                },
+                ChainConfig {
+                    chain_id: None,
+                    node_url: "http://gnosis.node".parse().unwrap(),
+                    db_url: "postgresql://gnosis".parse().unwrap(),
+                    base_tokens: vec![],
+                    amount_to_estimate_prices_with: None,
+                    ethflow_contract: None,
+                    ethflow_indexing_start: 0,
+                    chain_spec: None,
+                    allowed_tokens: vec![],
+                    unsupported_tokens: vec![],
+                    banned_users: vec![],
+                    liquidity_order_owners: vec![],
+                    hook_targets: vec![],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_chain_configs() {
+        assert!(parse_chain_configs(b"[]").is_err());
+        assert!(parse_chain_configs(
+            br#"[
+                {"chainId": 1, "nodeUrl": "http://a", "dbUrl": "postgresql://a"},
+                {"chainId": 1, "nodeUrl": "http://b", "dbUrl": "postgresql://b"}
+            ]"#
+        )
+        .is_err());
+        assert!(parse_chain_configs(
+            br#"[{"nodeUrl": "http://a", "dbUrl": "postgresql://a", "unknown": 1}]"#
+        )
+        .is_err());
+    }
+}
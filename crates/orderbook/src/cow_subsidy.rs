@@ -1,13 +1,20 @@
-use std::{sync::Mutex, time::Duration};
+use std::{
+    fmt::{self, Display, Formatter},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::{Context, Result};
 use cached::{Cached, TimedSizedCache};
 use contracts::{CowProtocolToken, CowProtocolVirtualToken};
 use ethcontract::Web3;
 use primitive_types::{H160, U256};
+use serde_with::{DeserializeFromStr, SerializeDisplay};
 use shared::transport::buffered::{Buffered, Configuration};
 use std::collections::BTreeMap;
 
+use crate::fee_subsidy::CurrentFeeSubsidy;
+
 const CACHE_SIZE: usize = 10_000;
 const CACHE_LIFESPAN: Duration = Duration::from_secs(60 * 60);
 
@@ -36,13 +43,38 @@ impl CowSubsidy for FixedCowSubsidy {
 
 /// Maps how many base units of COW someone must own at least in order to qualify for a given
 /// fee subsidy factor.
-#[derive(Clone, Debug, Default)]
+#[derive(Clone, Debug, Default, PartialEq, DeserializeFromStr, SerializeDisplay)]
 pub struct SubsidyTiers(BTreeMap<U256, f64>);
 
+impl SubsidyTiers {
+    /// The fee factor of the highest tier the balance qualifies for.
+    fn factor(&self, balance: U256) -> f64 {
+        let tier = self.0.range(..=balance).rev().next();
+        tier.map(|tier| *tier.1).unwrap_or(1.0)
+    }
+}
+
+impl Display for SubsidyTiers {
+    /// Formats the tiers the same way they are parsed.
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let tiers = self
+            .0
+            .iter()
+            .map(|(threshold, fee_factor)| {
+                format!("{}:{}", threshold / U256::exp10(18), fee_factor)
+            })
+            .collect::<Vec<_>>();
+        f.write_str(&tiers.join(","))
+    }
+}
+
 impl std::str::FromStr for SubsidyTiers {
     type Err = anyhow::Error;
     fn from_str(serialized: &str) -> Result<Self, Self::Err> {
         let mut tiers = BTreeMap::default();
+        if serialized.is_empty() {
+            return Ok(SubsidyTiers(tiers));
+        }
 
         for tier in serialized.split(',') {
             let (threshold, fee_factor) = tier
@@ -77,19 +109,28 @@ impl std::str::FromStr for SubsidyTiers {
 pub struct CowSubsidyImpl {
     token: CowProtocolToken,
     vtoken: CowProtocolVirtualToken,
-    subsidy_tiers: SubsidyTiers,
-    cache: Mutex<TimedSizedCache<H160, f64>>,
+    /// The subsidy tiers are read from the current fee subsidy configuration
+    /// so that they can change at runtime.
+    fee_subsidy: Arc<CurrentFeeSubsidy>,
+    /// Combined COW balances of users.
+    cache: Mutex<TimedSizedCache<H160, U256>>,
 }
 
 #[async_trait::async_trait]
 impl CowSubsidy for CowSubsidyImpl {
     async fn cow_subsidy_factor(&self, user: H160) -> Result<f64> {
-        if let Some(subsidy_factor) = self.cache.lock().unwrap().cache_get(&user).copied() {
-            return Ok(subsidy_factor);
-        }
-        let subsidy_factor = self.subsidy_factor_uncached(user).await?;
-        self.cache.lock().unwrap().cache_set(user, subsidy_factor);
-        Ok(subsidy_factor)
+        let cached = self.cache.lock().unwrap().cache_get(&user).copied();
+        let balance = match cached {
+            Some(balance) => balance,
+            None => {
+                let balance = self.balance_uncached(user).await?;
+                self.cache.lock().unwrap().cache_set(user, balance);
+                balance
+            }
+        };
+        let factor = self.fee_subsidy.get().cow_fee_factors.factor(balance);
+        tracing::debug!(?user, ?balance, ?factor);
+        Ok(factor)
     }
 }
 
@@ -97,7 +138,7 @@ impl CowSubsidyImpl {
     pub fn new(
         token: CowProtocolToken,
         vtoken: CowProtocolVirtualToken,
-        subsidy_tiers: SubsidyTiers,
+        fee_subsidy: Arc<CurrentFeeSubsidy>,
     ) -> Self {
         // NOTE: A long caching time might bite us should we ever start advertising that people can
         // buy COW to reduce their fees. `CACHE_LIFESPAN` would have to pass after buying COW to
@@ -125,31 +166,48 @@ impl CowSubsidyImpl {
         Self {
             token,
             vtoken,
-            subsidy_tiers,
+            fee_subsidy,
             cache: Mutex::new(cache),
         }
     }
 
-    async fn subsidy_factor_uncached(&self, user: H160) -> Result<f64> {
+    async fn balance_uncached(&self, user: H160) -> Result<U256> {
         let (balance, vbalance) = futures::future::try_join(
             self.token.balance_of(user).call(),
             self.vtoken.balance_of(user).call(),
         )
         .await?;
-        let combined = balance.saturating_add(vbalance);
-        let tier = self.subsidy_tiers.0.range(..=combined).rev().next();
-        let factor = tier.map(|tier| *tier.1).unwrap_or(1.0);
-        tracing::debug!(?user, ?balance, ?vbalance, ?combined, ?factor);
-        Ok(factor)
+        tracing::debug!(?user, ?balance, ?vbalance);
+        Ok(balance.saturating_add(vbalance))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::float_cmp)]
 mod tests {
     use super::*;
+    use crate::fee_subsidy::VersionedFeeSubsidy;
     use hex_literal::hex;
     use shared::Web3;
 
+    #[test]
+    fn subsidy_tiers_roundtrip() {
+        let tiers: SubsidyTiers = "10:0.75,150:0.5".parse().unwrap();
+        assert_eq!(tiers.to_string(), "10:0.75,150:0.5");
+        assert_eq!(tiers.to_string().parse::<SubsidyTiers>().unwrap(), tiers);
+        assert_eq!("".parse::<SubsidyTiers>().unwrap(), SubsidyTiers::default());
+    }
+
+    #[test]
+    fn subsidy_tier_factor() {
+        let tiers: SubsidyTiers = "10:0.75,150:0.5".parse().unwrap();
+        let cow = |amount: u64| U256::from(amount) * U256::exp10(18);
+        assert_eq!(tiers.factor(cow(9)), 1.0);
+        assert_eq!(tiers.factor(cow(10)), 0.75);
+        assert_eq!(tiers.factor(cow(149)), 0.75);
+        assert_eq!(tiers.factor(cow(1000)), 0.5);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn mainnet() {
@@ -161,7 +219,12 @@ mod tests {
         let subsidy = CowSubsidyImpl::new(
             token,
             vtoken,
-            SubsidyTiers([(U256::from_f64_lossy(1e18), 0.5)].into_iter().collect()),
+            Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+                cow_fee_factors: SubsidyTiers(
+                    [(U256::from_f64_lossy(1e18), 0.5)].into_iter().collect(),
+                ),
+                ..Default::default()
+            })),
         );
         //
         for user in [
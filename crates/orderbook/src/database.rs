@@ -1,7 +1,14 @@
+pub mod app_data;
+pub mod auctions;
+pub mod ethflow_orders;
 pub mod events;
 pub mod fees;
 pub mod instrumented;
+pub mod order_events;
 pub mod orders;
+pub mod quotes;
+pub mod settlements;
+pub mod solver_competition;
 pub mod trades;
 
 use anyhow::Result;
@@ -13,7 +20,7 @@ use std::collections::HashMap;
 // enough anyway.
 
 // The names of all tables we use in the db.
-const ALL_TABLES: [&str; 7] = [
+const ALL_TABLES: [&str; 15] = [
     "orders",
     "trades",
     "invalidations",
@@ -21,6 +28,14 @@ const ALL_TABLES: [&str; 7] = [
     "settlements",
     "presignature_events",
     "order_fee_parameters",
+    "solver_competitions",
+    "order_events",
+    "quotes",
+    "app_data",
+    "ethflow_orders",
+    "ethflow_invalidations",
+    "ethflow_refunds",
+    "order_hooks",
 ];
 
 // The pool uses an Arc internally.
@@ -75,7 +90,7 @@ mod tests {
         db.clear().await.unwrap();
 
         let counts = db.count_rows_in_tables().await.unwrap();
-        assert_eq!(counts.len(), 7);
+        assert_eq!(counts.len(), 15);
         assert!(counts.iter().all(|(_, count)| *count == 0));
 
         db.insert_order(&Default::default(), Default::default())
@@ -0,0 +1,116 @@
+use super::Postgres;
+use crate::conversions::h160_from_vec;
+use anyhow::{Context, Result};
+use model::{
+    app_data::{AppData, ParsedAppData},
+    app_id::AppId,
+};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait AppDataStoring: Send + Sync {
+    /// Stores the full app data document for the hash. Returns false if the
+    /// document had already been stored.
+    async fn insert_app_data(
+        &self,
+        app_id: &AppId,
+        full_app_data: &str,
+        parsed: &ParsedAppData,
+    ) -> Result<bool>;
+    async fn app_data(&self, app_id: &AppId) -> Result<Option<AppData>>;
+}
+
+#[derive(sqlx::FromRow)]
+struct AppDataRow {
+    full_app_data: Vec<u8>,
+    app_code: Option<String>,
+    referrer: Option<Vec<u8>>,
+    slippage_bips: Option<i32>,
+}
+
+impl AppDataRow {
+    fn into_app_data(self) -> Result<AppData> {
+        Ok(AppData {
+            full_app_data: String::from_utf8(self.full_app_data)
+                .context("app data is not utf-8")?,
+            parsed: ParsedAppData {
+                app_code: self.app_code,
+                referrer: self.referrer.map(h160_from_vec).transpose()?,
+                slippage_bips: self.slippage_bips.map(u32::try_from).transpose()?,
+            },
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl AppDataStoring for Postgres {
+    async fn insert_app_data(
+        &self,
+        app_id: &AppId,
+        full_app_data: &str,
+        parsed: &ParsedAppData,
+    ) -> Result<bool> {
+        const QUERY: &str = "\
+            INSERT INTO app_data (\
+                contract_app_data, full_app_data, app_code, referrer, slippage_bips) \
+            VALUES ($1, $2, $3, $4, $5) \
+            ON CONFLICT (contract_app_data) DO NOTHING;";
+        let result = sqlx::query(QUERY)
+            .bind(app_id.0.as_ref())
+            .bind(full_app_data.as_bytes())
+            .bind(parsed.app_code.as_deref())
+            .bind(parsed.referrer.as_ref().map(|referrer| referrer.as_bytes()))
+            .bind(parsed.slippage_bips.map(|bips| bips as i32))
+            .execute(&self.pool)
+            .await
+            .context("insert app data failed")?;
+        Ok(result.rows_affected() == 1)
+    }
+
+    async fn app_data(&self, app_id: &AppId) -> Result<Option<AppData>> {
+        const QUERY: &str = "\
+            SELECT full_app_data, app_code, referrer, slippage_bips \
+            FROM app_data \
+            WHERE contract_app_data = $1;";
+        let row: Option<AppDataRow> = sqlx::query_as(QUERY)
+            .bind(app_id.0.as_ref())
+            .fetch_optional(&self.pool)
+            .await
+            .context("load app data failed")?;
+        row.map(AppDataRow::into_app_data).transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use primitive_types::H160;
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_app_data_roundtrip() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let app_id = AppId([1; 32]);
+        assert_eq!(db.app_data(&app_id).await.unwrap(), None);
+
+        let app_data = AppData {
+            full_app_data: r#"{"version":"0.4.0","appCode":"CowSwap"}"#.to_string(),
+            parsed: ParsedAppData {
+                app_code: Some("CowSwap".to_string()),
+                referrer: Some(H160([2; 20])),
+                slippage_bips: Some(50),
+            },
+        };
+        assert!(db
+            .insert_app_data(&app_id, &app_data.full_app_data, &app_data.parsed)
+            .await
+            .unwrap());
+        assert!(!db
+            .insert_app_data(&app_id, &app_data.full_app_data, &app_data.parsed)
+            .await
+            .unwrap());
+        assert_eq!(db.app_data(&app_id).await.unwrap(), Some(app_data));
+    }
+}
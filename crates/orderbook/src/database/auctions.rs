@@ -0,0 +1,39 @@
+use super::Postgres;
+use anyhow::{Context, Result};
+use model::auction::AuctionId;
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait AuctionStoring: Send + Sync {
+    /// Reserves a new auction id. Ids are strictly increasing and never handed
+    /// out twice, even across restarts.
+    async fn next_auction_id(&self) -> Result<AuctionId>;
+}
+
+#[async_trait::async_trait]
+impl AuctionStoring for Postgres {
+    async fn next_auction_id(&self) -> Result<AuctionId> {
+        const QUERY: &str = "SELECT nextval('auction_ids');";
+        let id: i64 = sqlx::query_scalar(QUERY)
+            .fetch_one(&self.pool)
+            .await
+            .context("next auction id failed")?;
+        Ok(id as AuctionId)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_auction_ids_increase() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let first = db.next_auction_id().await.unwrap();
+        let second = db.next_auction_id().await.unwrap();
+        assert!(second > first);
+    }
+}
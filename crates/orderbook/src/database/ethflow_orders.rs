@@ -0,0 +1,396 @@
+use super::{
+    order_events::insert_order_event,
+    orders::{insert_order, InsertionError},
+    Postgres,
+};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use ethcontract::H256;
+use futures::FutureExt;
+use model::{
+    order::{Order, OrderUid},
+    order_event::{OrderEvent, OrderEventLabel, OrderEventReason},
+};
+use primitive_types::H160;
+use shared::event_handling::EventIndex;
+use sqlx::{Connection, Executor, Transaction};
+use std::convert::TryInto;
+
+/// An event of the ETH-flow contract.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EthflowEvent {
+    Placement(Box<EthflowPlacement>),
+    Invalidation(OrderUid),
+    Refund(EthflowRefund),
+}
+
+/// An order that the ETH-flow contract placed on behalf of `sender`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthflowPlacement {
+    pub order: Order,
+    pub sender: H160,
+    pub user_valid_to: u32,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EthflowRefund {
+    pub order_uid: OrderUid,
+    pub tx_hash: H256,
+}
+
+impl Postgres {
+    pub async fn last_ethflow_event_block(&self) -> Result<u64> {
+        const QUERY: &str = "\
+            SELECT GREATEST( \
+                (SELECT COALESCE(MAX(block_number), 0) FROM ethflow_orders), \
+                (SELECT COALESCE(MAX(block_number), 0) FROM ethflow_invalidations), \
+                (SELECT COALESCE(MAX(block_number), 0) FROM ethflow_refunds));";
+        let block_number: i64 = sqlx::query_scalar(QUERY)
+            .fetch_one(&self.pool)
+            .await
+            .context("last_ethflow_event_block failed")?;
+        block_number.try_into().context("block number is negative")
+    }
+
+    // All insertions happen in one transaction.
+    pub async fn append_ethflow_events(
+        &self,
+        events: Vec<(EventIndex, EthflowEvent)>,
+    ) -> Result<()> {
+        let mut connection = self.pool.acquire().await?;
+        connection
+            .transaction(move |transaction| {
+                async move {
+                    append_events(transaction, events.as_slice())
+                        .await
+                        .context("append_ethflow_events failed")
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    // The deletion and all insertions happen in one transaction.
+    pub async fn replace_ethflow_events(
+        &self,
+        delete_from_block_number: u64,
+        events: Vec<(EventIndex, EthflowEvent)>,
+    ) -> Result<()> {
+        let mut connection = self.pool.acquire().await?;
+        connection
+            .transaction(move |transaction| {
+                async move {
+                    delete_events(transaction, delete_from_block_number)
+                        .await
+                        .context("delete_ethflow_events failed")?;
+                    append_events(transaction, events.as_slice())
+                        .await
+                        .context("insert_ethflow_events failed")
+                }
+                .boxed()
+            })
+            .await
+    }
+}
+
+async fn delete_events(
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+    delete_from_block_number: u64,
+) -> Result<(), sqlx::Error> {
+    // Orders placed in reorged blocks might not exist anymore. Orders that
+    // were submitted to the API are kept because the API accepted them on
+    // their own. The rows that reference the deleted orders go with them.
+    const QUERY_ORDERS: &str = "\
+        WITH deleted AS ( \
+            DELETE FROM ethflow_orders WHERE block_number >= $1 RETURNING uid, order_inserted \
+        ), \
+        deleted_orders AS ( \
+            DELETE FROM orders WHERE uid IN (SELECT uid FROM deleted WHERE order_inserted) \
+            RETURNING uid \
+        ), \
+        deleted_events AS ( \
+            DELETE FROM order_events WHERE order_uid IN (SELECT uid FROM deleted_orders) \
+        ), \
+        deleted_hooks AS ( \
+            DELETE FROM order_hooks WHERE order_uid IN (SELECT uid FROM deleted_orders) \
+        ) \
+        DELETE FROM order_fee_parameters WHERE order_uid IN (SELECT uid FROM deleted_orders);";
+    transaction
+        .execute(sqlx::query(QUERY_ORDERS).bind(delete_from_block_number as i64))
+        .await?;
+
+    const QUERY_INVALIDATIONS: &str = "DELETE FROM ethflow_invalidations WHERE block_number >= $1;";
+    transaction
+        .execute(sqlx::query(QUERY_INVALIDATIONS).bind(delete_from_block_number as i64))
+        .await?;
+
+    const QUERY_REFUNDS: &str = "DELETE FROM ethflow_refunds WHERE block_number >= $1;";
+    transaction
+        .execute(sqlx::query(QUERY_REFUNDS).bind(delete_from_block_number as i64))
+        .await?;
+
+    Ok(())
+}
+
+async fn append_events(
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+    events: &[(EventIndex, EthflowEvent)],
+) -> Result<()> {
+    for (index, event) in events {
+        match event {
+            EthflowEvent::Placement(placement) => {
+                insert_placement(transaction, index, placement).await?
+            }
+            EthflowEvent::Invalidation(uid) => insert_invalidation(transaction, index, uid).await?,
+            EthflowEvent::Refund(refund) => insert_refund(transaction, index, refund).await?,
+        }
+    }
+    Ok(())
+}
+
+async fn insert_placement(
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+    index: &EventIndex,
+    placement: &EthflowPlacement,
+) -> Result<()> {
+    // The contract's orders can also be submitted to the API. We can't rely on
+    // the insertion failing because that would abort the transaction.
+    const EXISTS_QUERY: &str = "SELECT EXISTS (SELECT 1 FROM orders WHERE uid = $1);";
+    let uid = &placement.order.metadata.uid;
+    let exists: bool = sqlx::query_scalar(EXISTS_QUERY)
+        .bind(uid.0.as_ref())
+        .fetch_one(&mut *transaction)
+        .await?;
+
+    // Placements are indexed by every orderbook instance so they can already
+    // exist. See `events::insert_invalidation`.
+    const QUERY: &str = "\
+        INSERT INTO ethflow_orders \
+            (uid, sender, valid_to, block_number, log_index, order_inserted) \
+        VALUES ($1, $2, $3, $4, $5, $6) \
+        ON CONFLICT DO NOTHING;";
+    let result = transaction
+        .execute(
+            sqlx::query(QUERY)
+                .bind(uid.0.as_ref())
+                .bind(placement.sender.as_bytes())
+                .bind(placement.user_valid_to as i64)
+                .bind(index.block_number as i64)
+                .bind(index.log_index as i64)
+                .bind(!exists),
+        )
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(());
+    }
+
+    if !exists {
+        match insert_order(&placement.order, transaction).await {
+            Ok(()) => (),
+            Err(InsertionError::DuplicatedRecord) => bail!("order {} inserted concurrently", uid),
+            Err(InsertionError::DbError(err)) => return Err(err.into()),
+        }
+    }
+    let event = OrderEvent {
+        timestamp: Utc::now(),
+        label: OrderEventLabel::Created,
+        reason: None,
+    };
+    insert_order_event(transaction, uid, &event).await?;
+    Ok(())
+}
+
+async fn insert_invalidation(
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+    index: &EventIndex,
+    uid: &OrderUid,
+) -> Result<(), sqlx::Error> {
+    const QUERY: &str = "\
+        INSERT INTO ethflow_invalidations (block_number, log_index, order_uid) \
+        VALUES ($1, $2, $3) \
+        ON CONFLICT DO NOTHING;";
+    let result = transaction
+        .execute(
+            sqlx::query(QUERY)
+                .bind(index.block_number as i64)
+                .bind(index.log_index as i64)
+                .bind(uid.0.as_ref()),
+        )
+        .await?;
+    if result.rows_affected() == 0 {
+        return Ok(());
+    }
+
+    let event = OrderEvent {
+        timestamp: Utc::now(),
+        label: OrderEventLabel::Cancelled,
+        reason: Some(OrderEventReason::OnChain),
+    };
+    insert_order_event(transaction, uid, &event).await
+}
+
+async fn insert_refund(
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+    index: &EventIndex,
+    refund: &EthflowRefund,
+) -> Result<(), sqlx::Error> {
+    const QUERY: &str = "\
+        INSERT INTO ethflow_refunds (block_number, log_index, order_uid, tx_hash) \
+        VALUES ($1, $2, $3, $4) \
+        ON CONFLICT DO NOTHING;";
+    transaction
+        .execute(
+            sqlx::query(QUERY)
+                .bind(index.block_number as i64)
+                .bind(index.log_index as i64)
+                .bind(refund.order_uid.0.as_ref())
+                .bind(refund.tx_hash.as_bytes()),
+        )
+        .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::orders::OrderStoring;
+    use model::order::{EthflowData, OrderCreation, OrderMetadata, OrderStatus};
+    use model::signature::Signature;
+
+    fn placement(uid: u32, user_valid_to: u32) -> EthflowEvent {
+        EthflowEvent::Placement(Box::new(EthflowPlacement {
+            order: Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_integer(uid),
+                    owner: H160([1; 20]),
+                    ..Default::default()
+                },
+                creation: OrderCreation {
+                    sell_amount: 1.into(),
+                    buy_amount: 1.into(),
+                    valid_to: u32::MAX,
+                    signature: Signature::Eip1271(Vec::new()),
+                    ..Default::default()
+                },
+            },
+            sender: H160([2; 20]),
+            user_valid_to,
+        }))
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_ethflow_events() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+        assert_eq!(db.last_ethflow_event_block().await.unwrap(), 0);
+
+        let now = shared::time::now_in_epoch_seconds();
+        db.append_ethflow_events(vec![
+            (EventIndex::new(1, 0), placement(0, now + 3600)),
+            (EventIndex::new(1, 1), placement(1, now + 3600)),
+            (EventIndex::new(2, 0), placement(2, now - 3600)),
+        ])
+        .await
+        .unwrap();
+        assert_eq!(db.last_ethflow_event_block().await.unwrap(), 2);
+
+        // Orders are listed for the user that placed them.
+        let orders = db.user_orders(&H160([2; 20]), 0, None).await.unwrap();
+        assert_eq!(orders.len(), 3);
+        let order = db
+            .single_order(&OrderUid::from_integer(0))
+            .await
+            .unwrap()
+            .unwrap();
+        // The user owns the order that the contract signed.
+        assert_eq!(order.metadata.owner, H160([2; 20]));
+        assert_eq!(order.metadata.onchain_user, Some(H160([2; 20])));
+        assert_eq!(
+            order.metadata.ethflow_data,
+            Some(EthflowData {
+                user_valid_to: now + 3600,
+                refund_tx_hash: None,
+            })
+        );
+        assert_eq!(order.metadata.status, OrderStatus::Open);
+
+        // Only orders within the user's validity are solvable.
+        let solvable = db.solvable_orders(now).await.unwrap().orders;
+        assert_eq!(solvable.len(), 2);
+
+        db.append_ethflow_events(vec![
+            (
+                EventIndex::new(3, 0),
+                EthflowEvent::Invalidation(OrderUid::from_integer(1)),
+            ),
+            (
+                EventIndex::new(3, 1),
+                EthflowEvent::Refund(EthflowRefund {
+                    order_uid: OrderUid::from_integer(1),
+                    tx_hash: H256([3; 32]),
+                }),
+            ),
+            (
+                EventIndex::new(3, 2),
+                EthflowEvent::Refund(EthflowRefund {
+                    order_uid: OrderUid::from_integer(2),
+                    tx_hash: H256([4; 32]),
+                }),
+            ),
+        ])
+        .await
+        .unwrap();
+        let solvable = db.solvable_orders(now).await.unwrap().orders;
+        assert_eq!(solvable.len(), 1);
+        let order = db
+            .single_order(&OrderUid::from_integer(1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(order.metadata.status, OrderStatus::Cancelled);
+        let order = db
+            .single_order(&OrderUid::from_integer(2))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(order.metadata.status, OrderStatus::Expired);
+        assert_eq!(
+            order.metadata.ethflow_data.unwrap().refund_tx_hash,
+            Some(H256([4; 32]))
+        );
+
+        // Reorgs remove the orders placed in the replaced blocks.
+        db.replace_ethflow_events(2, vec![]).await.unwrap();
+        assert_eq!(db.last_ethflow_event_block().await.unwrap(), 1);
+        assert!(db
+            .single_order(&OrderUid::from_integer(2))
+            .await
+            .unwrap()
+            .is_none());
+        let order = db
+            .single_order(&OrderUid::from_integer(1))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(order.metadata.status, OrderStatus::Open);
+
+        // Orders that were submitted to the API before their placement got
+        // indexed are kept.
+        let api_placement = placement(3, now + 3600);
+        if let EthflowEvent::Placement(placement) = &api_placement {
+            db.insert_order(&placement.order, Default::default())
+                .await
+                .unwrap();
+        }
+        db.append_ethflow_events(vec![(EventIndex::new(4, 0), api_placement)])
+            .await
+            .unwrap();
+        db.replace_ethflow_events(4, vec![]).await.unwrap();
+        assert!(db
+            .single_order(&OrderUid::from_integer(3))
+            .await
+            .unwrap()
+            .is_some());
+    }
+}
@@ -1,6 +1,7 @@
-use super::Postgres;
+use super::{order_events::insert_order_event, Postgres};
 use crate::conversions::*;
 use anyhow::{anyhow, Context, Result};
+use chrono::Utc;
 use contracts::gpv2_settlement::{
     event_data::{
         OrderInvalidated as ContractInvalidation, PreSignature as ContractPreSignature,
@@ -10,7 +11,10 @@ use contracts::gpv2_settlement::{
 };
 use ethcontract::{Event as EthContractEvent, EventMetadata, H160, H256, U256};
 use futures::FutureExt;
-use model::order::OrderUid;
+use model::{
+    order::OrderUid,
+    order_event::{OrderEvent, OrderEventLabel, OrderEventReason},
+};
 use shared::event_handling::{EventIndex, EventStoring};
 use sqlx::{Connection, Executor, Transaction};
 use std::convert::TryInto;
@@ -177,28 +181,60 @@ async fn append_events(
     // tests show that even if we sleep during the transaction it does not block other
     // connections from using the database, so it's not high priority.
     for (index, event) in events {
-        match event {
+        let inserted = match event {
             Event::Trade(event) => insert_trade(transaction, index, event).await?,
             Event::Invalidation(event) => insert_invalidation(transaction, index, event).await?,
             Event::Settlement(event) => insert_settlement(transaction, index, event).await?,
             Event::PreSignature(event) => insert_presignature(transaction, index, event).await?,
         };
+        // Events that were already indexed, for example by another orderbook
+        // instance, already recorded their order event.
+        if !inserted {
+            continue;
+        }
+        if let Some((uid, event)) = order_event(event) {
+            insert_order_event(transaction, &uid, &event).await?;
+        }
     }
     Ok(())
 }
 
+/// The order lifecycle transition caused by an event.
+fn order_event(event: &Event) -> Option<(OrderUid, OrderEvent)> {
+    let (uid, label, reason) = match event {
+        Event::Trade(event) => (event.order_uid, OrderEventLabel::Traded, None),
+        Event::Invalidation(event) => (
+            event.order_uid,
+            OrderEventLabel::Cancelled,
+            Some(OrderEventReason::OnChain),
+        ),
+        Event::PreSignature(event) if event.signed => {
+            (event.order_uid, OrderEventLabel::Presigned, None)
+        }
+        Event::PreSignature(_) | Event::Settlement(_) => return None,
+    };
+    Some((
+        uid,
+        OrderEvent {
+            timestamp: Utc::now(),
+            label,
+            reason,
+        },
+    ))
+}
+
 async fn insert_invalidation(
     transaction: &mut Transaction<'_, sqlx::Postgres>,
     index: &EventIndex,
     event: &Invalidation,
-) -> Result<(), sqlx::Error> {
+) -> Result<bool, sqlx::Error> {
     // We use ON CONFLICT so that multiple updates running at the same do not error because of
     // events already existing. This can happen when multiple orderbook apis run in HPA.
     // See #444 .
     const QUERY: &str =
         "INSERT INTO invalidations (block_number, log_index, order_uid) VALUES ($1, $2, $3) \
          ON CONFLICT DO NOTHING;";
-    transaction
+    let result = transaction
         .execute(
             sqlx::query(QUERY)
                 .bind(index.block_number as i64)
@@ -206,18 +242,18 @@ async fn insert_invalidation(
                 .bind(event.order_uid.0.as_ref()),
         )
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 async fn insert_trade(
     transaction: &mut Transaction<'_, sqlx::Postgres>,
     index: &EventIndex,
     event: &Trade,
-) -> Result<(), sqlx::Error> {
+) -> Result<bool, sqlx::Error> {
     const QUERY: &str = "\
         INSERT INTO trades (block_number, log_index, order_uid, sell_amount, buy_amount, fee_amount) VALUES ($1, $2, $3, $4, $5, $6) \
         ON CONFLICT DO NOTHING;";
-    transaction
+    let result = transaction
         .execute(
             sqlx::query(QUERY)
                 .bind(index.block_number as i64)
@@ -228,18 +264,18 @@ async fn insert_trade(
                 .bind(u256_to_big_decimal(&event.fee_amount)),
         )
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 async fn insert_settlement(
     transaction: &mut Transaction<'_, sqlx::Postgres>,
     index: &EventIndex,
     event: &Settlement,
-) -> Result<(), sqlx::Error> {
+) -> Result<bool, sqlx::Error> {
     const QUERY: &str = "\
         INSERT INTO settlements (tx_hash, block_number, log_index, solver) VALUES ($1, $2, $3, $4) \
         ON CONFLICT DO NOTHING;";
-    transaction
+    let result = transaction
         .execute(
             sqlx::query(QUERY)
                 .bind(event.transaction_hash.as_bytes())
@@ -248,18 +284,18 @@ async fn insert_settlement(
                 .bind(event.solver.as_bytes()),
         )
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 async fn insert_presignature(
     transaction: &mut Transaction<'_, sqlx::Postgres>,
     index: &EventIndex,
     event: &PreSignature,
-) -> Result<(), sqlx::Error> {
+) -> Result<bool, sqlx::Error> {
     const QUERY: &str = "\
         INSERT INTO presignature_events (block_number, log_index, owner, order_uid, signed) VALUES ($1, $2, $3, $4, $5) \
         ON CONFLICT DO NOTHING;";
-    transaction
+    let result = transaction
         .execute(
             sqlx::query(QUERY)
                 .bind(index.block_number as i64)
@@ -269,7 +305,7 @@ async fn insert_presignature(
                 .bind(event.signed),
         )
         .await?;
-    Ok(())
+    Ok(result.rows_affected() > 0)
 }
 
 fn convert_trade(trade: &ContractTrade, meta: &EventMetadata) -> Result<(EventIndex, Event)> {
@@ -340,6 +376,7 @@ fn convert_presignature(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::database::order_events::OrderEventStoring;
 
     #[tokio::test]
     #[ignore]
@@ -462,5 +499,18 @@ mod tests {
             .unwrap();
         }
         assert_eq!(db.last_event_block().await.unwrap(), 2);
+
+        // Only the first insertion records order events.
+        let labels = db
+            .order_events(&OrderUid::default())
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|event| event.label)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            labels,
+            vec![OrderEventLabel::Traded, OrderEventLabel::Cancelled]
+        );
     }
 }
@@ -21,6 +21,8 @@ impl FeeRow {
             gas_amount: self.gas_amount,
             gas_price: self.gas_price,
             sell_token_price: self.sell_token_price,
+            // Measurements are independent of the subsidy configuration.
+            subsidy_version: 0,
         }
     }
 }
@@ -126,9 +128,13 @@ impl Postgres {
 #[async_trait::async_trait]
 impl Maintaining for Postgres {
     async fn run_maintenance(&self) -> Result<()> {
-        self.remove_expired_fee_measurements(Utc::now())
+        let now = Utc::now();
+        self.remove_expired_fee_measurements(now)
+            .await
+            .context("fee measurement maintenance error")?;
+        self.remove_expired_quotes(now)
             .await
-            .context("fee measurement maintenance error")
+            .context("quote maintenance error")
     }
 }
 
@@ -1,7 +1,24 @@
-use super::{orders::OrderStoring, trades::TradeRetrieving, Postgres};
+use super::{
+    app_data::AppDataStoring,
+    auctions::AuctionStoring,
+    order_events::OrderEventStoring,
+    orders::OrderStoring,
+    quotes::{QuoteData, QuoteStoring},
+    settlements::{SettlementCosts, SettlementStoring},
+    solver_competition::SolverCompetitionStoring,
+    trades::TradeRetrieving,
+    Postgres,
+};
 use crate::fee::{FeeParameters, MinFeeStoring};
 use ethcontract::H256;
-use model::order::Order;
+use model::{
+    app_data::{AppData, ParsedAppData},
+    app_id::AppId,
+    auction::AuctionId,
+    order::{Order, OrderUid, QuoteId},
+    order_event::OrderEvent,
+    solver_competition::SolverCompetitionResponse,
+};
 use prometheus::Histogram;
 use shared::{event_handling::EventStoring, maintenance::Maintaining};
 use std::sync::Arc;
@@ -117,16 +134,48 @@ impl OrderStoring for Instrumented {
         self.inner.insert_order(order, fee).await
     }
 
-    async fn cancel_order(
+    async fn insert_order_with_limit(
         &self,
-        order_uid: &model::order::OrderUid,
+        order: &model::order::Order,
+        fee: FeeParameters,
+        max_open_orders: u64,
+        min_valid_to: u32,
+    ) -> anyhow::Result<(), super::orders::LimitedInsertionError> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("insert_order_with_limit")
+            .start_timer();
+        self.inner
+            .insert_order_with_limit(order, fee, max_open_orders, min_valid_to)
+            .await
+    }
+
+    async fn replace_order(
+        &self,
+        old_order: &OrderUid,
+        new_order: &model::order::Order,
+        fee: FeeParameters,
+        max_submission_time: chrono::Duration,
+    ) -> anyhow::Result<(), super::orders::ReplacementError> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("replace_order")
+            .start_timer();
+        self.inner
+            .replace_order(old_order, new_order, fee, max_submission_time)
+            .await
+    }
+
+    async fn cancel_orders(
+        &self,
+        order_uids: Vec<OrderUid>,
         now: chrono::DateTime<chrono::Utc>,
     ) -> anyhow::Result<()> {
         let _timer = self
             .metrics
-            .database_query_histogram("cancel_order")
+            .database_query_histogram("cancel_orders")
             .start_timer();
-        self.inner.cancel_order(order_uid, now).await
+        self.inner.cancel_orders(order_uids, now).await
     }
 
     async fn orders(
@@ -159,6 +208,17 @@ impl OrderStoring for Instrumented {
         self.inner.single_order(uid).await
     }
 
+    async fn many_orders(
+        &self,
+        uids: &[model::order::OrderUid],
+    ) -> anyhow::Result<Vec<model::order::Order>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("many_orders")
+            .start_timer();
+        self.inner.many_orders(uids).await
+    }
+
     async fn solvable_orders(
         &self,
         min_valid_to: u32,
@@ -182,6 +242,18 @@ impl OrderStoring for Instrumented {
             .start_timer();
         self.inner.user_orders(owner, offset, limit).await
     }
+
+    async fn count_open_orders(
+        &self,
+        owner: &ethcontract::H160,
+        min_valid_to: u32,
+    ) -> anyhow::Result<u64> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("count_open_orders")
+            .start_timer();
+        self.inner.count_open_orders(owner, min_valid_to).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -189,12 +261,182 @@ impl TradeRetrieving for Instrumented {
     async fn trades(
         &self,
         filter: &super::trades::TradeFilter,
-    ) -> anyhow::Result<Vec<model::trade::Trade>> {
+        offset: u64,
+        limit: u64,
+    ) -> anyhow::Result<super::trades::TradesPage> {
         let _timer = self
             .metrics
             .database_query_histogram("trades")
             .start_timer();
-        self.inner.trades(filter).await
+        self.inner.trades(filter, offset, limit).await
+    }
+
+    async fn trade_accounting(
+        &self,
+        tx_hash: &H256,
+    ) -> anyhow::Result<Vec<model::trade::TradeAccounting>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("trade_accounting")
+            .start_timer();
+        self.inner.trade_accounting(tx_hash).await
+    }
+}
+
+#[async_trait::async_trait]
+impl SettlementStoring for Instrumented {
+    async fn settlements_without_costs(
+        &self,
+        limit: u64,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<Vec<H256>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("settlements_without_costs")
+            .start_timer();
+        self.inner.settlements_without_costs(limit, now).await
+    }
+
+    async fn store_settlement_costs(
+        &self,
+        tx_hash: H256,
+        costs: &SettlementCosts,
+    ) -> anyhow::Result<()> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("store_settlement_costs")
+            .start_timer();
+        self.inner.store_settlement_costs(tx_hash, costs).await
+    }
+
+    async fn record_failed_settlement_costs(
+        &self,
+        tx_hash: H256,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<()> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("record_failed_settlement_costs")
+            .start_timer();
+        self.inner
+            .record_failed_settlement_costs(tx_hash, now)
+            .await
+    }
+}
+
+#[async_trait::async_trait]
+impl AuctionStoring for Instrumented {
+    async fn next_auction_id(&self) -> anyhow::Result<AuctionId> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("next_auction_id")
+            .start_timer();
+        self.inner.next_auction_id().await
+    }
+}
+
+#[async_trait::async_trait]
+impl SolverCompetitionStoring for Instrumented {
+    async fn save(
+        &self,
+        auction_id: AuctionId,
+        data: &SolverCompetitionResponse,
+    ) -> anyhow::Result<()> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("save_solver_competition")
+            .start_timer();
+        self.inner.save(auction_id, data).await
+    }
+
+    async fn load(
+        &self,
+        auction_id: AuctionId,
+    ) -> anyhow::Result<Option<SolverCompetitionResponse>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("load_solver_competition")
+            .start_timer();
+        self.inner.load(auction_id).await
+    }
+
+    async fn load_by_tx_hash(
+        &self,
+        tx_hash: &H256,
+    ) -> anyhow::Result<Option<SolverCompetitionResponse>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("load_solver_competition_by_tx_hash")
+            .start_timer();
+        self.inner.load_by_tx_hash(tx_hash).await
+    }
+}
+
+#[async_trait::async_trait]
+impl QuoteStoring for Instrumented {
+    async fn insert_quote(&self, quote: &QuoteData) -> anyhow::Result<QuoteId> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("insert_quote")
+            .start_timer();
+        self.inner.insert_quote(quote).await
+    }
+
+    async fn find_quote(
+        &self,
+        id: QuoteId,
+        min_expiration: chrono::DateTime<chrono::Utc>,
+    ) -> anyhow::Result<Option<QuoteData>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("find_quote")
+            .start_timer();
+        self.inner.find_quote(id, min_expiration).await
+    }
+}
+
+#[async_trait::async_trait]
+impl OrderEventStoring for Instrumented {
+    async fn insert_order_events(&self, events: Vec<(OrderUid, OrderEvent)>) -> anyhow::Result<()> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("insert_order_events")
+            .start_timer();
+        self.inner.insert_order_events(events).await
+    }
+
+    async fn order_events(&self, uid: &OrderUid) -> anyhow::Result<Vec<OrderEvent>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("order_events")
+            .start_timer();
+        self.inner.order_events(uid).await
+    }
+}
+
+#[async_trait::async_trait]
+impl AppDataStoring for Instrumented {
+    async fn insert_app_data(
+        &self,
+        app_id: &AppId,
+        full_app_data: &str,
+        parsed: &ParsedAppData,
+    ) -> anyhow::Result<bool> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("insert_app_data")
+            .start_timer();
+        self.inner
+            .insert_app_data(app_id, full_app_data, parsed)
+            .await
+    }
+
+    async fn app_data(&self, app_id: &AppId) -> anyhow::Result<Option<AppData>> {
+        let _timer = self
+            .metrics
+            .database_query_histogram("app_data")
+            .start_timer();
+        self.inner.app_data(app_id).await
     }
 }
 
@@ -0,0 +1,305 @@
+use super::Postgres;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use futures::TryStreamExt;
+use model::{
+    order::OrderUid,
+    order_event::{OrderEvent, OrderEventLabel, OrderEventReason},
+};
+use sqlx::{Executor, Transaction};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait OrderEventStoring: Send + Sync {
+    /// Stores the events of any number of orders in one query. Events
+    /// with the same label and reason as the latest stored event of their
+    /// order are skipped so that restarts and multiple instances don't record
+    /// the same state change again.
+    async fn insert_order_events(&self, events: Vec<(OrderUid, OrderEvent)>) -> Result<()>;
+    /// All events of an order ordered by time (oldest events first).
+    async fn order_events(&self, uid: &OrderUid) -> Result<Vec<OrderEvent>>;
+}
+
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "OrderEventLabel")]
+#[sqlx(rename_all = "lowercase")]
+enum DbOrderEventLabel {
+    Created,
+    Ready,
+    Filtered,
+    Presigned,
+    Traded,
+    Cancelled,
+}
+
+impl DbOrderEventLabel {
+    fn from(label: OrderEventLabel) -> Self {
+        match label {
+            OrderEventLabel::Created => Self::Created,
+            OrderEventLabel::Ready => Self::Ready,
+            OrderEventLabel::Filtered => Self::Filtered,
+            OrderEventLabel::Presigned => Self::Presigned,
+            OrderEventLabel::Traded => Self::Traded,
+            OrderEventLabel::Cancelled => Self::Cancelled,
+        }
+    }
+
+    fn into(self) -> OrderEventLabel {
+        match self {
+            Self::Created => OrderEventLabel::Created,
+            Self::Ready => OrderEventLabel::Ready,
+            Self::Filtered => OrderEventLabel::Filtered,
+            Self::Presigned => OrderEventLabel::Presigned,
+            Self::Traded => OrderEventLabel::Traded,
+            Self::Cancelled => OrderEventLabel::Cancelled,
+        }
+    }
+
+    /// The name of the variant in the database for binding arrays of labels,
+    /// which sqlx only supports for built in types.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::Ready => "ready",
+            Self::Filtered => "filtered",
+            Self::Presigned => "presigned",
+            Self::Traded => "traded",
+            Self::Cancelled => "cancelled",
+        }
+    }
+}
+
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "OrderEventReason")]
+#[sqlx(rename_all = "snake_case")]
+enum DbOrderEventReason {
+    BannedUser,
+    UnsupportedToken,
+    InvalidSignature,
+    InsufficientBalance,
+    MissingNativePrice,
+    InsufficientSurplusFee,
+    OffChain,
+    OnChain,
+}
+
+impl DbOrderEventReason {
+    fn from(reason: OrderEventReason) -> Self {
+        match reason {
+            OrderEventReason::BannedUser => Self::BannedUser,
+            OrderEventReason::UnsupportedToken => Self::UnsupportedToken,
+            OrderEventReason::InvalidSignature => Self::InvalidSignature,
+            OrderEventReason::InsufficientBalance => Self::InsufficientBalance,
+            OrderEventReason::MissingNativePrice => Self::MissingNativePrice,
+            OrderEventReason::InsufficientSurplusFee => Self::InsufficientSurplusFee,
+            OrderEventReason::OffChain => Self::OffChain,
+            OrderEventReason::OnChain => Self::OnChain,
+        }
+    }
+
+    fn into(self) -> OrderEventReason {
+        match self {
+            Self::BannedUser => OrderEventReason::BannedUser,
+            Self::UnsupportedToken => OrderEventReason::UnsupportedToken,
+            Self::InvalidSignature => OrderEventReason::InvalidSignature,
+            Self::InsufficientBalance => OrderEventReason::InsufficientBalance,
+            Self::MissingNativePrice => OrderEventReason::MissingNativePrice,
+            Self::InsufficientSurplusFee => OrderEventReason::InsufficientSurplusFee,
+            Self::OffChain => OrderEventReason::OffChain,
+            Self::OnChain => OrderEventReason::OnChain,
+        }
+    }
+
+    /// The name of the variant in the database, see `DbOrderEventLabel::as_str`.
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::BannedUser => "banned_user",
+            Self::UnsupportedToken => "unsupported_token",
+            Self::InvalidSignature => "invalid_signature",
+            Self::InsufficientBalance => "insufficient_balance",
+            Self::MissingNativePrice => "missing_native_price",
+            Self::InsufficientSurplusFee => "insufficient_surplus_fee",
+            Self::OffChain => "off_chain",
+            Self::OnChain => "on_chain",
+        }
+    }
+}
+
+/// Inserts an event as part of a bigger transaction, for example together with
+/// the order or contract event that caused it.
+pub async fn insert_order_event(
+    transaction: &mut Transaction<'_, sqlx::Postgres>,
+    uid: &OrderUid,
+    event: &OrderEvent,
+) -> Result<(), sqlx::Error> {
+    const QUERY: &str = "\
+        INSERT INTO order_events (order_uid, timestamp, label, reason) \
+        VALUES ($1, $2, $3, $4);";
+    transaction
+        .execute(
+            sqlx::query(QUERY)
+                .bind(uid.0.as_ref())
+                .bind(event.timestamp)
+                .bind(DbOrderEventLabel::from(event.label))
+                .bind(event.reason.map(DbOrderEventReason::from)),
+        )
+        .await?;
+    Ok(())
+}
+
+#[async_trait::async_trait]
+impl OrderEventStoring for Postgres {
+    async fn insert_order_events(&self, events: Vec<(OrderUid, OrderEvent)>) -> Result<()> {
+        const QUERY: &str = "\
+            INSERT INTO order_events (order_uid, timestamp, label, reason) \
+            SELECT e.order_uid, e.timestamp, e.label::OrderEventLabel, e.reason::OrderEventReason \
+            FROM UNNEST($1::bytea[], $2::timestamptz[], $3::text[], $4::text[]) \
+                AS e(order_uid, timestamp, label, reason) \
+            WHERE NOT EXISTS ( \
+                SELECT 1 FROM ( \
+                    SELECT label, reason FROM order_events \
+                    WHERE order_uid = e.order_uid \
+                    ORDER BY timestamp DESC \
+                    LIMIT 1 \
+                ) AS latest \
+                WHERE latest.label = e.label::OrderEventLabel \
+                AND latest.reason IS NOT DISTINCT FROM e.reason::OrderEventReason \
+            );";
+        let uids = events
+            .iter()
+            .map(|(uid, _)| uid.0.as_ref())
+            .collect::<Vec<_>>();
+        let timestamps = events
+            .iter()
+            .map(|(_, event)| event.timestamp)
+            .collect::<Vec<_>>();
+        let labels = events
+            .iter()
+            .map(|(_, event)| DbOrderEventLabel::from(event.label).as_str())
+            .collect::<Vec<_>>();
+        let reasons = events
+            .iter()
+            .map(|(_, event)| {
+                event
+                    .reason
+                    .map(|reason| DbOrderEventReason::from(reason).as_str())
+            })
+            .collect::<Vec<_>>();
+        sqlx::query(QUERY)
+            .bind(uids)
+            .bind(timestamps)
+            .bind(labels)
+            .bind(reasons)
+            .execute(&self.pool)
+            .await
+            .context("insert_order_events failed")?;
+        Ok(())
+    }
+
+    async fn order_events(&self, uid: &OrderUid) -> Result<Vec<OrderEvent>> {
+        const QUERY: &str = "\
+            SELECT timestamp, label, reason FROM order_events \
+            WHERE order_uid = $1 \
+            ORDER BY timestamp ASC;";
+        sqlx::query_as(QUERY)
+            .bind(uid.0.as_ref())
+            .fetch(&self.pool)
+            .map_ok(|row: OrderEventsQueryRow| OrderEvent {
+                timestamp: row.timestamp,
+                label: row.label.into(),
+                reason: row.reason.map(DbOrderEventReason::into),
+            })
+            .try_collect()
+            .await
+            .context("order_events failed")
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct OrderEventsQueryRow {
+    timestamp: DateTime<Utc>,
+    label: DbOrderEventLabel,
+    reason: Option<DbOrderEventReason>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::orders::OrderStoring;
+    use chrono::NaiveDateTime;
+    use model::order::{Order, OrderMetadata};
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_order_events_roundtrip() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let uid = OrderUid([1; 56]);
+        let timestamp =
+            |seconds| DateTime::from_utc(NaiveDateTime::from_timestamp(seconds, 0), Utc);
+        let order = Order {
+            metadata: OrderMetadata {
+                uid,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        db.insert_order(&order, Default::default()).await.unwrap();
+        db.cancel_orders(vec![uid], timestamp(3)).await.unwrap();
+        // Cancelling again does not record another event.
+        db.cancel_orders(vec![uid], timestamp(4)).await.unwrap();
+
+        let filtered = OrderEvent {
+            timestamp: timestamp(2),
+            label: OrderEventLabel::Filtered,
+            reason: Some(OrderEventReason::InsufficientBalance),
+        };
+        db.insert_order_events(vec![(uid, filtered), (OrderUid([2; 56]), filtered)])
+            .await
+            .unwrap();
+
+        let events = db.order_events(&uid).await.unwrap();
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].label, OrderEventLabel::Created);
+        assert_eq!(events[1], filtered);
+        assert_eq!(
+            events[2],
+            OrderEvent {
+                timestamp: timestamp(3),
+                label: OrderEventLabel::Cancelled,
+                reason: Some(OrderEventReason::OffChain),
+            }
+        );
+        assert!(db
+            .order_events(&OrderUid([3; 56]))
+            .await
+            .unwrap()
+            .is_empty());
+
+        // Storing the same state again, for example after a restart, does not
+        // record another event but a different state does.
+        let filtered_again = OrderEvent {
+            timestamp: timestamp(5),
+            ..filtered
+        };
+        let ready = OrderEvent {
+            timestamp: timestamp(6),
+            label: OrderEventLabel::Ready,
+            reason: None,
+        };
+        db.insert_order_events(vec![(OrderUid([2; 56]), filtered_again)])
+            .await
+            .unwrap();
+        db.insert_order_events(vec![(OrderUid([2; 56]), ready)])
+            .await
+            .unwrap();
+        db.insert_order_events(vec![(OrderUid([2; 56]), ready)])
+            .await
+            .unwrap();
+        assert_eq!(
+            db.order_events(&OrderUid([2; 56])).await.unwrap(),
+            vec![filtered, ready]
+        );
+    }
+}
@@ -1,32 +1,59 @@
 use super::*;
-use crate::{conversions::*, fee::FeeParameters};
+use crate::{conversions::*, database::order_events::insert_order_event, fee::FeeParameters};
 use anyhow::{anyhow, Context as _, Result};
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration, Utc};
 use const_format::concatcp;
 use ethcontract::H256;
 use futures::{stream::TryStreamExt, FutureExt};
 use model::{
     app_id::AppId,
     order::{
-        BuyTokenDestination, Order, OrderCreation, OrderKind, OrderMetadata, OrderStatus, OrderUid,
-        SellTokenSource,
+        BuyTokenDestination, EthflowData, Hook, Hooks, Order, OrderClass, OrderCreation, OrderKind,
+        OrderMetadata, OrderStatus, OrderUid, SellTokenSource,
     },
+    order_event::{OrderEvent, OrderEventLabel, OrderEventReason},
     signature::{Signature, SigningScheme},
 };
 use num::Zero;
 use primitive_types::H160;
-use sqlx::{types::BigDecimal, Connection};
+use sqlx::{types::BigDecimal, Connection, Executor, PgConnection};
 use std::{borrow::Cow, convert::TryInto};
 
 #[cfg_attr(test, mockall::automock)]
 #[async_trait::async_trait]
 pub trait OrderStoring: Send + Sync {
     async fn insert_order(&self, order: &Order, fee: FeeParameters) -> Result<(), InsertionError>;
-    async fn cancel_order(&self, order_uid: &OrderUid, now: DateTime<Utc>) -> Result<()>;
+    /// Inserts the order unless its owner already has `max_open_orders` orders
+    /// that are open at `min_valid_to`. Counting and inserting happen in one
+    /// transaction that locks the owner so concurrent insertions can't exceed
+    /// the limit.
+    async fn insert_order_with_limit(
+        &self,
+        order: &Order,
+        fee: FeeParameters,
+        max_open_orders: u64,
+        min_valid_to: u32,
+    ) -> Result<(), LimitedInsertionError>;
+    /// Cancels the old order and inserts the new one in one transaction. Fails
+    /// without changes if the old order has been cancelled, has been traded or
+    /// is part of a winning settlement that could still be submitted for up to
+    /// `max_submission_time` after its competition.
+    async fn replace_order(
+        &self,
+        old_order: &OrderUid,
+        new_order: &Order,
+        fee: FeeParameters,
+        max_submission_time: Duration,
+    ) -> Result<(), ReplacementError>;
+    /// Cancels all of the orders in one transaction. Orders that are already
+    /// cancelled keep their original cancellation timestamp.
+    async fn cancel_orders(&self, order_uids: Vec<OrderUid>, now: DateTime<Utc>) -> Result<()>;
     // Legacy generic orders route that we are phasing out.
     async fn orders(&self, filter: &OrderFilter) -> Result<Vec<Order>>;
     async fn orders_for_tx(&self, tx_hash: &H256) -> Result<Vec<Order>>;
     async fn single_order(&self, uid: &OrderUid) -> Result<Option<Order>>;
+    /// The orders with any of the uids. Unknown uids are ignored.
+    async fn many_orders(&self, uids: &[OrderUid]) -> Result<Vec<Order>>;
     /// Orders that are solvable: minimum valid to, not fully executed, not invalidated.
     async fn solvable_orders(&self, min_valid_to: u32) -> Result<SolvableOrders>;
     /// All orders of a single user ordered by creation date descending (newest orders first).
@@ -36,6 +63,9 @@ pub trait OrderStoring: Send + Sync {
         offset: u64,
         limit: Option<u64>,
     ) -> Result<Vec<Order>>;
+    /// Number of orders of a user that are valid until at least `min_valid_to`
+    /// and are neither fully executed nor invalidated.
+    async fn count_open_orders(&self, owner: &H160, min_valid_to: u32) -> Result<u64>;
 }
 
 pub struct SolvableOrders {
@@ -74,7 +104,7 @@ impl DbOrderKind {
         }
     }
 
-    fn into(self) -> OrderKind {
+    pub fn into(self) -> OrderKind {
         match self {
             Self::Buy => OrderKind::Buy,
             Self::Sell => OrderKind::Sell,
@@ -82,6 +112,38 @@ impl DbOrderKind {
     }
 }
 
+#[derive(sqlx::Type)]
+#[sqlx(type_name = "OrderClass")]
+#[sqlx(rename_all = "lowercase")]
+pub enum DbOrderClass {
+    Market,
+    Limit,
+}
+
+#[derive(Clone, Copy, sqlx::Type)]
+#[sqlx(type_name = "HookStage")]
+#[sqlx(rename_all = "lowercase")]
+enum DbHookStage {
+    Pre,
+    Post,
+}
+
+impl DbOrderClass {
+    pub fn from(order_class: OrderClass) -> Self {
+        match order_class {
+            OrderClass::Market => Self::Market,
+            OrderClass::Limit => Self::Limit,
+        }
+    }
+
+    fn into(self) -> OrderClass {
+        match self {
+            Self::Market => OrderClass::Market,
+            Self::Limit => OrderClass::Limit,
+        }
+    }
+}
+
 /// Source from which the sellAmount should be drawn upon order fulfilment
 #[derive(sqlx::Type)]
 #[sqlx(type_name = "SellTokenSource")]
@@ -103,7 +165,7 @@ impl DbSellTokenSource {
             SellTokenSource::External => Self::External,
         }
     }
-    fn into(self) -> SellTokenSource {
+    pub fn into(self) -> SellTokenSource {
         match self {
             Self::Erc20 => SellTokenSource::Erc20,
             Self::Internal => SellTokenSource::Internal,
@@ -130,7 +192,7 @@ impl DbBuyTokenDestination {
             BuyTokenDestination::Internal => Self::Internal,
         }
     }
-    fn into(self) -> BuyTokenDestination {
+    pub fn into(self) -> BuyTokenDestination {
         match self {
             Self::Erc20 => BuyTokenDestination::Erc20,
             Self::Internal => BuyTokenDestination::Internal,
@@ -144,6 +206,7 @@ impl DbBuyTokenDestination {
 pub enum DbSigningScheme {
     Eip712,
     EthSign,
+    Eip1271,
     PreSign,
 }
 
@@ -152,6 +215,7 @@ impl DbSigningScheme {
         match signing_scheme {
             SigningScheme::Eip712 => Self::Eip712,
             SigningScheme::EthSign => Self::EthSign,
+            SigningScheme::Eip1271 => Self::Eip1271,
             SigningScheme::PreSign => Self::PreSign,
         }
     }
@@ -160,6 +224,7 @@ impl DbSigningScheme {
         match self {
             Self::Eip712 => SigningScheme::Eip712,
             Self::EthSign => SigningScheme::EthSign,
+            Self::Eip1271 => SigningScheme::Eip1271,
             Self::PreSign => SigningScheme::PreSign,
         }
     }
@@ -177,6 +242,53 @@ impl From<sqlx::Error> for InsertionError {
     }
 }
 
+#[derive(Debug)]
+pub enum LimitedInsertionError {
+    /// The owner has reached the maximum number of open orders.
+    TooManyOpenOrders {
+        max: u64,
+    },
+    Insertion(InsertionError),
+}
+
+impl From<InsertionError> for LimitedInsertionError {
+    fn from(err: InsertionError) -> Self {
+        Self::Insertion(err)
+    }
+}
+
+impl From<sqlx::Error> for LimitedInsertionError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Insertion(InsertionError::DbError(err))
+    }
+}
+
+#[derive(Debug)]
+pub enum ReplacementError {
+    OldOrderNotFound,
+    OldOrderCancelled,
+    /// The old order has already been (partially) traded. This includes
+    /// partially fillable orders with remaining amounts because the new order
+    /// could trade the already filled amount a second time.
+    OldOrderTraded,
+    /// The old order is part of the winning settlement of a recent auction
+    /// and the settlement has not been indexed yet.
+    OldOrderInFlight,
+    Insertion(InsertionError),
+}
+
+impl From<InsertionError> for ReplacementError {
+    fn from(err: InsertionError) -> Self {
+        Self::Insertion(err)
+    }
+}
+
+impl From<sqlx::Error> for ReplacementError {
+    fn from(err: sqlx::Error) -> Self {
+        Self::Insertion(InsertionError::DbError(err))
+    }
+}
+
 // When querying orders we have several specialized use cases working with their own filtering,
 // ordering, indexes. The parts that are shared between all queries are defined here so they can be
 // reused.
@@ -201,12 +313,13 @@ const ORDERS_SELECT: &str = "\
     o.uid, o.owner, o.creation_timestamp, o.sell_token, o.buy_token, o.sell_amount, o.buy_amount, \
     o.valid_to, o.app_data, o.fee_amount, o.full_fee_amount, o.kind, o.partially_fillable, o.signature, \
     o.receiver, o.signing_scheme, o.settlement_contract, o.sell_token_balance, o.buy_token_balance, \
-    o.is_liquidity_order, \
+    o.is_liquidity_order, o.class, o.quote_id, \
     (SELECT COALESCE(SUM(t.buy_amount), 0) FROM trades t WHERE t.order_uid = o.uid) AS sum_buy, \
     (SELECT COALESCE(SUM(t.sell_amount), 0) FROM trades t WHERE t.order_uid = o.uid) AS sum_sell, \
     (SELECT COALESCE(SUM(t.fee_amount), 0) FROM trades t WHERE t.order_uid = o.uid) AS sum_fee, \
     (o.cancellation_timestamp IS NOT NULL OR \
-        (SELECT COUNT(*) FROM invalidations WHERE invalidations.order_uid = o.uid) > 0 \
+        (SELECT COUNT(*) FROM invalidations WHERE invalidations.order_uid = o.uid) > 0 OR \
+        EXISTS (SELECT 1 FROM ethflow_invalidations ei WHERE ei.order_uid = o.uid) \
     ) AS invalidated, \
     (o.signing_scheme = 'presign' AND COALESCE(( \
         SELECT (NOT p.signed) as unsigned \
@@ -214,14 +327,36 @@ const ORDERS_SELECT: &str = "\
         WHERE o.uid = p.order_uid \
         ORDER BY p.block_number DESC, p.log_index DESC \
         LIMIT 1 \
-    ), true)) AS presignature_pending \
+    ), true)) AS presignature_pending, \
+    e.sender AS onchain_user, e.valid_to AS ethflow_valid_to, \
+    (SELECT r.tx_hash FROM ethflow_refunds r \
+        WHERE r.order_uid = o.uid \
+        ORDER BY r.block_number DESC, r.log_index DESC \
+        LIMIT 1 \
+    ) AS ethflow_refund_tx, \
+    h.pre_hooks, h.hook_targets, h.hook_call_data, h.hook_gas_limits \
 ";
 
+// Orders placed through the ETH-flow contract have an additional validity
+// that is set by the user and not part of the signed order.
+//
+// The hooks of an order are aggregated in a single pass over its `order_hooks`
+// rows instead of one sub query per selected column.
 const ORDERS_FROM: &str = "\
     orders o \
+    LEFT OUTER JOIN ethflow_orders e ON e.uid = o.uid \
+    CROSS JOIN LATERAL ( \
+        SELECT \
+            COUNT(*) FILTER (WHERE oh.stage = 'pre') AS pre_hooks, \
+            COALESCE(ARRAY_AGG(oh.target ORDER BY oh.stage, oh.index), '{}') AS hook_targets, \
+            COALESCE(ARRAY_AGG(oh.call_data ORDER BY oh.stage, oh.index), '{}') AS hook_call_data, \
+            COALESCE(ARRAY_AGG(oh.gas_limit ORDER BY oh.stage, oh.index), '{}') AS hook_gas_limits \
+        FROM order_hooks oh \
+        WHERE oh.order_uid = o.uid \
+    ) h \
 ";
 
-async fn insert_order(
+pub(super) async fn insert_order(
     order: &Order,
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
 ) -> Result<(), InsertionError> {
@@ -229,8 +364,9 @@ async fn insert_order(
             INSERT INTO orders (
                 uid, owner, creation_timestamp, sell_token, buy_token, receiver, sell_amount, buy_amount, \
                 valid_to, app_data, fee_amount, kind, partially_fillable, signature, signing_scheme, \
-                settlement_contract, sell_token_balance, buy_token_balance, full_fee_amount, is_liquidity_order) \
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20);";
+                settlement_contract, sell_token_balance, buy_token_balance, full_fee_amount, is_liquidity_order, class, \
+                quote_id) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18, $19, $20, $21, $22);";
     let receiver = order
         .creation
         .receiver
@@ -258,9 +394,10 @@ async fn insert_order(
         ))
         .bind(u256_to_big_decimal(&order.metadata.full_fee_amount))
         .bind(order.metadata.is_liquidity_order)
-        .execute(transaction)
+        .bind(DbOrderClass::from(order.metadata.class))
+        .bind(order.creation.quote_id)
+        .execute(&mut *transaction)
         .await
-        .map(|_| ())
         .map_err(|err| {
             if let sqlx::Error::Database(db_err) = &err {
                 if let Some(Cow::Borrowed("23505")) = db_err.code() {
@@ -268,7 +405,36 @@ async fn insert_order(
                 }
             }
             InsertionError::DbError(err)
-        })
+        })?;
+    insert_hooks(&order.metadata.uid, &order.metadata.hooks, transaction).await
+}
+
+async fn insert_hooks(
+    uid: &OrderUid,
+    hooks: &Hooks,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), InsertionError> {
+    const QUERY: &str = "\
+        INSERT INTO order_hooks (order_uid, stage, index, target, call_data, gas_limit) \
+        VALUES ($1, $2, $3, $4, $5, $6);";
+    let stages = [
+        (DbHookStage::Pre, &hooks.pre),
+        (DbHookStage::Post, &hooks.post),
+    ];
+    for (stage, hooks) in stages {
+        for (index, hook) in hooks.iter().enumerate() {
+            sqlx::query(QUERY)
+                .bind(uid.0.as_ref())
+                .bind(stage)
+                .bind(index as i32)
+                .bind(hook.target.as_bytes())
+                .bind(hook.call_data.as_slice())
+                .bind(hook.gas_limit as i64)
+                .execute(&mut *transaction)
+                .await?;
+        }
+    }
+    Ok(())
 }
 
 async fn insert_fee(
@@ -278,30 +444,158 @@ async fn insert_fee(
 ) -> Result<(), InsertionError> {
     const QUERY: &str = "\
                 INSERT INTO order_fee_parameters (\
-                    order_uid, gas_amount, gas_price, sell_token_price) \
-                VALUES($1, $2, $3, $4)\
+                    order_uid, gas_amount, gas_price, sell_token_price, subsidy_version) \
+                VALUES($1, $2, $3, $4, $5)\
             ;";
     sqlx::query(QUERY)
         .bind(uid.0.as_ref())
         .bind(fee.gas_amount)
         .bind(fee.gas_price)
         .bind(fee.sell_token_price)
+        .bind(fee.subsidy_version as i64)
         .execute(transaction)
         .await
         .map(|_| ())
         .map_err(InsertionError::DbError)
 }
 
+async fn insert_order_with_fee(
+    order: &Order,
+    fee: &FeeParameters,
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+) -> Result<(), InsertionError> {
+    insert_order(order, transaction).await?;
+    insert_fee(&order.metadata.uid, fee, transaction).await?;
+    let event = OrderEvent {
+        timestamp: order.metadata.creation_date,
+        label: OrderEventLabel::Created,
+        reason: None,
+    };
+    insert_order_event(transaction, &order.metadata.uid, &event).await?;
+    Ok(())
+}
+
 #[async_trait::async_trait]
 impl OrderStoring for Postgres {
     async fn insert_order(&self, order: &Order, fee: FeeParameters) -> Result<(), InsertionError> {
+        let order = order.clone();
+        let mut connection = self.pool.acquire().await?;
+        connection
+            .transaction(move |transaction| {
+                async move { insert_order_with_fee(&order, &fee, transaction).await }.boxed()
+            })
+            .await
+    }
+
+    async fn insert_order_with_limit(
+        &self,
+        order: &Order,
+        fee: FeeParameters,
+        max_open_orders: u64,
+        min_valid_to: u32,
+    ) -> Result<(), LimitedInsertionError> {
+        // The lock is held until the end of the transaction and serializes
+        // all limited insertions of the owner. Owners whose hashes collide
+        // only share the lock.
+        const LOCK_QUERY: &str = "SELECT pg_advisory_xact_lock(hashtext(encode($1, 'hex')));";
         let order = order.clone();
         let mut connection = self.pool.acquire().await?;
         connection
             .transaction(move |transaction| {
                 async move {
-                    insert_order(&order, transaction).await?;
-                    insert_fee(&order.metadata.uid, &fee, transaction).await?;
+                    let owner = order.metadata.owner;
+                    sqlx::query(LOCK_QUERY)
+                        .bind(owner.as_bytes())
+                        .execute(&mut *transaction)
+                        .await?;
+                    let open_orders =
+                        count_open_orders(&mut *transaction, &owner, min_valid_to).await?;
+                    if open_orders >= max_open_orders {
+                        return Err(LimitedInsertionError::TooManyOpenOrders {
+                            max: max_open_orders,
+                        });
+                    }
+                    Ok(insert_order_with_fee(&order, &fee, transaction).await?)
+                }
+                .boxed()
+            })
+            .await
+    }
+
+    async fn replace_order(
+        &self,
+        old_order: &OrderUid,
+        new_order: &Order,
+        fee: FeeParameters,
+        max_submission_time: Duration,
+    ) -> Result<(), ReplacementError> {
+        // Locking the old order serializes concurrent replacements and
+        // cancellations of it. The winning solution is the last one reported
+        // for an auction. Its transaction hash stays unset if the solution
+        // never got mined, so it only counts as in flight for as long as the
+        // driver can still be submitting it. The driver only submits one
+        // solution at a time but competitions can be reported faster than
+        // settlements get mined, so all competitions within that time are
+        // checked.
+        const OLD_ORDER_QUERY: &str = "\
+            SELECT \
+                o.cancellation_timestamp IS NOT NULL, \
+                EXISTS (SELECT 1 FROM trades t WHERE t.order_uid = o.uid), \
+                EXISTS ( \
+                    SELECT 1 FROM solver_competitions c \
+                    WHERE \
+                        c.creation_timestamp > $3 AND \
+                        (c.tx_hash IS NULL OR NOT EXISTS ( \
+                            SELECT 1 FROM settlements s WHERE s.tx_hash = c.tx_hash \
+                        )) AND \
+                        c.json -> 'solutions' -> -1 -> 'orders' @> \
+                            jsonb_build_array(jsonb_build_object('id', $2::text)) \
+                ) \
+            FROM orders o \
+            WHERE o.uid = $1 \
+            FOR UPDATE OF o;";
+        const CANCEL_QUERY: &str = "\
+            UPDATE orders \
+            SET cancellation_timestamp = $1 \
+            WHERE uid = $2;";
+        let old_order = *old_order;
+        let new_order = new_order.clone();
+        let mut connection = self.pool.acquire().await?;
+        connection
+            .transaction(move |transaction| {
+                async move {
+                    let now = new_order.metadata.creation_date;
+                    let submitting_since = now - max_submission_time;
+                    let (cancelled, traded, in_flight): (bool, bool, bool) =
+                        sqlx::query_as(OLD_ORDER_QUERY)
+                            .bind(old_order.0.as_ref())
+                            .bind(old_order.to_string())
+                            .bind(submitting_since)
+                            .fetch_optional(&mut *transaction)
+                            .await?
+                            .ok_or(ReplacementError::OldOrderNotFound)?;
+                    if cancelled {
+                        return Err(ReplacementError::OldOrderCancelled);
+                    }
+                    if traded {
+                        return Err(ReplacementError::OldOrderTraded);
+                    }
+                    if in_flight {
+                        return Err(ReplacementError::OldOrderInFlight);
+                    }
+
+                    sqlx::query(CANCEL_QUERY)
+                        .bind(now)
+                        .bind(old_order.0.as_ref())
+                        .execute(&mut *transaction)
+                        .await?;
+                    let event = OrderEvent {
+                        timestamp: now,
+                        label: OrderEventLabel::Cancelled,
+                        reason: Some(OrderEventReason::OffChain),
+                    };
+                    insert_order_event(transaction, &old_order, &event).await?;
+                    insert_order_with_fee(&new_order, &fee, transaction).await?;
                     Ok(())
                 }
                 .boxed()
@@ -309,22 +603,45 @@ impl OrderStoring for Postgres {
             .await
     }
 
-    async fn cancel_order(&self, order_uid: &OrderUid, now: DateTime<Utc>) -> Result<()> {
+    async fn cancel_orders(&self, order_uids: Vec<OrderUid>, now: DateTime<Utc>) -> Result<()> {
         // We do not overwrite previously cancelled orders,
         // but this query does allow the user to soft cancel
         // an order that has already been invalidated on-chain.
         const QUERY: &str = "\
             UPDATE orders
             SET cancellation_timestamp = $1 \
-            WHERE uid = $2\
-            AND cancellation_timestamp IS NULL;";
-        sqlx::query(QUERY)
-            .bind(now)
-            .bind(order_uid.0.as_ref())
-            .execute(&self.pool)
+            WHERE uid = ANY($2) \
+            AND cancellation_timestamp IS NULL \
+            RETURNING uid;";
+        let mut connection = self.pool.acquire().await?;
+        connection
+            .transaction(move |transaction| {
+                async move {
+                    let uids = order_uids
+                        .iter()
+                        .map(|uid| uid.0.as_ref())
+                        .collect::<Vec<_>>();
+                    let cancelled: Vec<Vec<u8>> = sqlx::query_scalar(QUERY)
+                        .bind(now)
+                        .bind(uids)
+                        .fetch_all(&mut *transaction)
+                        .await?;
+                    let event = OrderEvent {
+                        timestamp: now,
+                        label: OrderEventLabel::Cancelled,
+                        reason: Some(OrderEventReason::OffChain),
+                    };
+                    for uid in &order_uids {
+                        if cancelled.iter().any(|cancelled| cancelled == &uid.0) {
+                            insert_order_event(transaction, uid, &event).await?;
+                        }
+                    }
+                    Ok::<_, sqlx::Error>(())
+                }
+                .boxed()
+            })
             .await
-            .context("cancel_order failed")
-            .map(|_| ())
+            .context("cancel_orders failed")
     }
 
     async fn orders(&self, filter: &OrderFilter) -> Result<Vec<Order>> {
@@ -344,6 +661,7 @@ impl OrderStoring for Postgres {
                     ($5 IS NULL OR o.uid = $5) ",
             ") AS unfiltered \
             WHERE \
+                (ethflow_valid_to IS NULL OR ethflow_valid_to >= $1) AND \
                 ($6 OR CASE kind \
                     WHEN 'sell' THEN sum_sell < sell_amount \
                     WHEN 'buy' THEN sum_buy < buy_amount \
@@ -425,6 +743,23 @@ impl OrderStoring for Postgres {
         order.map(OrdersQueryRow::into_order).transpose()
     }
 
+    async fn many_orders(&self, uids: &[OrderUid]) -> Result<Vec<Order>> {
+        #[rustfmt::skip]
+        const QUERY: &str = concatcp!(
+            "SELECT ", ORDERS_SELECT,
+            "FROM ", ORDERS_FROM,
+            "WHERE o.uid = ANY($1) ",
+        );
+        let uids = uids.iter().map(|uid| uid.0.as_ref()).collect::<Vec<_>>();
+        sqlx::query_as(QUERY)
+            .bind(uids)
+            .fetch(&self.pool)
+            .err_into()
+            .and_then(|row: OrdersQueryRow| async move { row.into_order() })
+            .try_collect()
+            .await
+    }
+
     async fn solvable_orders(&self, min_valid_to: u32) -> Result<SolvableOrders> {
         #[rustfmt::skip]
         const QUERY: &str = concatcp!(
@@ -434,6 +769,7 @@ impl OrderStoring for Postgres {
                 "WHERE o.valid_to >= $1 ",
             ") AS unfiltered \
             WHERE \
+                (ethflow_valid_to IS NULL OR ethflow_valid_to >= $1) AND \
                 CASE kind \
                     WHEN 'sell' THEN sum_sell < sell_amount \
                     WHEN 'buy' THEN sum_buy < buy_amount \
@@ -481,12 +817,21 @@ impl OrderStoring for Postgres {
         // before as is the case with OFFSET.
         // On the other hand that approach is less flexible so we will consider if we see that these
         // queries are taking too long in practice.
+        // Orders that a user placed on-chain are stored with the contract as
+        // their owner. Each part of the union can use its own index while an
+        // `OR` over both tables can't.
         #[rustfmt::skip]
         const QUERY: &str = concatcp!(
-            "SELECT ", ORDERS_SELECT,
-            "FROM ", ORDERS_FROM,
-            "WHERE o.owner = $1 ",
-            "ORDER BY o.creation_timestamp DESC ",
+            "SELECT * FROM ( ",
+                "SELECT ", ORDERS_SELECT,
+                "FROM ", ORDERS_FROM,
+                "WHERE o.owner = $1 ",
+                "UNION ALL ",
+                "SELECT ", ORDERS_SELECT,
+                "FROM ", ORDERS_FROM,
+                "WHERE e.sender = $1 AND o.owner != $1 ",
+            ") AS user_orders ",
+            "ORDER BY creation_timestamp DESC ",
             "LIMIT $2 ",
             "OFFSET $3 ",
         );
@@ -500,6 +845,39 @@ impl OrderStoring for Postgres {
             .try_collect()
             .await
     }
+
+    async fn count_open_orders(&self, owner: &H160, min_valid_to: u32) -> Result<u64> {
+        let mut connection = self.pool.acquire().await?;
+        Ok(count_open_orders(&mut connection, owner, min_valid_to).await?)
+    }
+}
+
+async fn count_open_orders(
+    connection: &mut PgConnection,
+    owner: &H160,
+    min_valid_to: u32,
+) -> Result<u64, sqlx::Error> {
+    #[rustfmt::skip]
+    const QUERY: &str = concatcp!(
+        "SELECT COUNT(*) FROM ( ",
+            "SELECT ", ORDERS_SELECT,
+            "FROM ", ORDERS_FROM,
+            "WHERE o.owner = $1 AND o.valid_to >= $2 ",
+        ") AS unfiltered \
+        WHERE \
+            (ethflow_valid_to IS NULL OR ethflow_valid_to >= $2) AND \
+            CASE kind \
+                WHEN 'sell' THEN sum_sell < sell_amount \
+                WHEN 'buy' THEN sum_buy < buy_amount \
+            END AND \
+            (NOT invalidated);"
+    );
+    let count: i64 = sqlx::query_scalar(QUERY)
+        .bind(owner.as_bytes())
+        .bind(min_valid_to as i64)
+        .fetch_one(connection)
+        .await?;
+    Ok(count as u64)
 }
 
 #[derive(sqlx::FromRow)]
@@ -529,6 +907,15 @@ struct OrdersQueryRow {
     buy_token_balance: DbBuyTokenDestination,
     presignature_pending: bool,
     is_liquidity_order: bool,
+    class: DbOrderClass,
+    quote_id: Option<i64>,
+    onchain_user: Option<Vec<u8>>,
+    ethflow_valid_to: Option<i64>,
+    ethflow_refund_tx: Option<Vec<u8>>,
+    pre_hooks: i64,
+    hook_targets: Vec<Vec<u8>>,
+    hook_call_data: Vec<Vec<u8>>,
+    hook_gas_limits: Vec<i64>,
 }
 
 impl OrdersQueryRow {
@@ -548,7 +935,7 @@ impl OrdersQueryRow {
         if self.invalidated {
             return OrderStatus::Cancelled;
         }
-        if self.valid_to < Utc::now().timestamp() {
+        if self.ethflow_valid_to.unwrap_or(self.valid_to) < Utc::now().timestamp() {
             return OrderStatus::Expired;
         }
         if self.presignature_pending {
@@ -559,9 +946,16 @@ impl OrdersQueryRow {
 
     fn into_order(self) -> Result<Order> {
         let status = self.calculate_status();
+        // The user that placed an order on-chain owns it even though the
+        // stored owner is the contract that signed it.
+        let onchain_user = self.onchain_user.map(h160_from_vec).transpose()?;
+        let owner = match onchain_user {
+            Some(user) => user,
+            None => h160_from_vec(self.owner)?,
+        };
         let order_metadata = OrderMetadata {
             creation_date: self.creation_timestamp,
-            owner: h160_from_vec(self.owner)?,
+            owner,
             uid: OrderUid(
                 self.uid
                     .try_into()
@@ -585,6 +979,26 @@ impl OrdersQueryRow {
             full_fee_amount: big_decimal_to_u256(&self.full_fee_amount)
                 .ok_or_else(|| anyhow!("full_fee_amount is not U256"))?,
             is_liquidity_order: self.is_liquidity_order,
+            class: self.class.into(),
+            surplus_fee: Default::default(),
+            onchain_user,
+            ethflow_data: self
+                .ethflow_valid_to
+                .map(|user_valid_to| -> Result<_> {
+                    Ok(EthflowData {
+                        user_valid_to: user_valid_to
+                            .try_into()
+                            .context("ethflow valid_to is not u32")?,
+                        refund_tx_hash: self.ethflow_refund_tx.map(h256_from_vec).transpose()?,
+                    })
+                })
+                .transpose()?,
+            hooks: hooks_from_rows(
+                self.pre_hooks,
+                self.hook_targets,
+                self.hook_call_data,
+                self.hook_gas_limits,
+            )?,
         };
         let signing_scheme = self.signing_scheme.into();
         let order_creation = OrderCreation {
@@ -608,6 +1022,7 @@ impl OrdersQueryRow {
             signature: Signature::from_bytes(signing_scheme, &self.signature)?,
             sell_token_balance: self.sell_token_balance.into(),
             buy_token_balance: self.buy_token_balance.into(),
+            quote_id: self.quote_id,
         };
         Ok(Order {
             metadata: order_metadata,
@@ -616,6 +1031,34 @@ impl OrdersQueryRow {
     }
 }
 
+/// Hooks are selected as one array per column, sorted by stage and index.
+fn hooks_from_rows(
+    pre_hooks: i64,
+    targets: Vec<Vec<u8>>,
+    call_data: Vec<Vec<u8>>,
+    gas_limits: Vec<i64>,
+) -> Result<Hooks> {
+    let mut hooks = targets
+        .into_iter()
+        .zip(call_data)
+        .zip(gas_limits)
+        .map(|((target, call_data), gas_limit)| {
+            Ok(Hook {
+                target: h160_from_vec(target)?,
+                call_data,
+                gas_limit: gas_limit.try_into().context("gas_limit is not u64")?,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let pre_hooks: usize = pre_hooks
+        .try_into()
+        .ok()
+        .filter(|pre_hooks| *pre_hooks <= hooks.len())
+        .context("invalid number of pre hooks")?;
+    let post = hooks.split_off(pre_hooks);
+    Ok(Hooks { pre: hooks, post })
+}
+
 fn is_sell_order_filled(
     amount: &BigDecimal,
     executed_amount: &BigDecimal,
@@ -676,6 +1119,15 @@ mod tests {
             buy_token_balance: DbBuyTokenDestination::Internal,
             presignature_pending: false,
             is_liquidity_order: true,
+            class: DbOrderClass::Market,
+            quote_id: None,
+            onchain_user: None,
+            ethflow_valid_to: None,
+            ethflow_refund_tx: None,
+            pre_hooks: 0,
+            hook_targets: vec![],
+            hook_call_data: vec![],
+            hook_gas_limits: vec![],
         };
 
         // Open - sell (filled - 0%)
@@ -854,6 +1306,17 @@ mod tests {
             .calculate_status(),
             OrderStatus::Expired
         );
+
+        // Expired - ETH-flow order past the user's validity
+        assert_eq!(
+            OrdersQueryRow {
+                valid_to: u32::MAX as i64,
+                ethflow_valid_to: Some(valid_to_yesterday.timestamp()),
+                ..order_row()
+            }
+            .calculate_status(),
+            OrderStatus::Expired
+        );
     }
 
     #[tokio::test]
@@ -885,19 +1348,26 @@ mod tests {
             gas_amount: 1.,
             gas_price: 2.,
             sell_token_price: 3.,
+            subsidy_version: 4,
         };
         db.insert_order(&order, fee).await.unwrap();
         let query = "SELECT * FROM order_fee_parameters;";
-        let (uid, gas_amount, gas_price, sell_token_price): (Vec<u8>, f64, f64, f64) =
-            sqlx::query_as(query)
-                .bind(order.metadata.uid.0.as_ref())
-                .fetch_one(&db.pool)
-                .await
-                .unwrap();
+        let (uid, gas_amount, gas_price, sell_token_price, subsidy_version): (
+            Vec<u8>,
+            f64,
+            f64,
+            f64,
+            i64,
+        ) = sqlx::query_as(query)
+            .bind(order.metadata.uid.0.as_ref())
+            .fetch_one(&db.pool)
+            .await
+            .unwrap();
         assert_eq!(uid, order.metadata.uid.0.as_ref());
         assert_eq!(gas_amount, 1.);
         assert_eq!(gas_price, 2.);
         assert_eq!(sell_token_price, 3.);
+        assert_eq!(subsidy_version, 4);
     }
 
     #[tokio::test]
@@ -907,6 +1377,7 @@ mod tests {
         for signing_scheme in &[
             SigningScheme::Eip712,
             SigningScheme::EthSign,
+            SigningScheme::Eip1271,
             SigningScheme::PreSign,
         ] {
             db.clear().await.unwrap();
@@ -922,6 +1393,25 @@ mod tests {
                         SigningScheme::PreSign => OrderStatus::PresignaturePending,
                         _ => OrderStatus::Open,
                     },
+                    hooks: Hooks {
+                        pre: vec![
+                            Hook {
+                                target: H160::from_low_u64_be(8),
+                                call_data: vec![1, 2],
+                                gas_limit: 9,
+                            },
+                            Hook {
+                                target: H160::from_low_u64_be(10),
+                                call_data: vec![],
+                                gas_limit: 11,
+                            },
+                        ],
+                        post: vec![Hook {
+                            target: H160::from_low_u64_be(12),
+                            call_data: vec![3],
+                            gas_limit: 13,
+                        }],
+                    },
                     ..Default::default()
                 },
                 creation: OrderCreation {
@@ -938,6 +1428,7 @@ mod tests {
                     signature: Signature::default_with(*signing_scheme),
                     sell_token_balance: SellTokenSource::Erc20,
                     buy_token_balance: BuyTokenDestination::Internal,
+                    quote_id: Some(7),
                 },
             };
             db.insert_order(&order, Default::default()).await.unwrap();
@@ -945,6 +1436,30 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_limit_order_roundtrip() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+        let order = Order {
+            metadata: OrderMetadata {
+                creation_date: DateTime::<Utc>::from_utc(
+                    NaiveDateTime::from_timestamp(1234567890, 0),
+                    Utc,
+                ),
+                class: OrderClass::Limit,
+                ..Default::default()
+            },
+            creation: OrderCreation {
+                valid_to: u32::MAX,
+                ..Default::default()
+            },
+        };
+        db.insert_order(&order, Default::default()).await.unwrap();
+        let single_order = db.single_order(&order.metadata.uid).await.unwrap().unwrap();
+        assert_eq!(single_order.metadata.class, OrderClass::Limit);
+    }
+
     #[tokio::test]
     #[ignore]
     async fn postgres_cancel_order() {
@@ -965,7 +1480,7 @@ mod tests {
 
         let cancellation_time =
             DateTime::<Utc>::from_utc(NaiveDateTime::from_timestamp(1234567890, 0), Utc);
-        db.cancel_order(&order.metadata.uid, cancellation_time)
+        db.cancel_orders(vec![order.metadata.uid], cancellation_time)
             .await
             .unwrap();
         let db_orders = db.orders(&filter).await.unwrap();
@@ -987,7 +1502,7 @@ mod tests {
             "Expected cancellation times to be different."
         );
 
-        db.cancel_order(&order.metadata.uid, irrelevant_time)
+        db.cancel_orders(vec![order.metadata.uid], irrelevant_time)
             .await
             .unwrap();
         let second_cancellation: CancellationQueryRow =
@@ -995,6 +1510,34 @@ mod tests {
         assert_eq!(first_cancellation, second_cancellation);
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_cancel_multiple_orders() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let uids = [OrderUid([1; 56]), OrderUid([2; 56]), OrderUid([3; 56])];
+        for uid in uids {
+            let order = Order {
+                metadata: OrderMetadata {
+                    uid,
+                    ..Default::default()
+                },
+                ..Default::default()
+            };
+            db.insert_order(&order, Default::default()).await.unwrap();
+        }
+
+        // Unknown orders are ignored.
+        db.cancel_orders(vec![uids[0], uids[2], OrderUid([4; 56])], Utc::now())
+            .await
+            .unwrap();
+        for (uid, invalidated) in uids.iter().zip([true, false, true]) {
+            let order = db.single_order(uid).await.unwrap().unwrap();
+            assert_eq!(order.metadata.invalidated, invalidated);
+        }
+    }
+
     #[tokio::test]
     #[ignore]
     async fn postgres_filter_orders_by_address() {
@@ -1124,6 +1667,144 @@ mod tests {
         .await;
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_replace_order() {
+        use crate::database::solver_competition::SolverCompetitionStoring;
+        use model::solver_competition::{SolverCompetitionResponse, SolverSettlement};
+
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let order = |uid| Order {
+            metadata: OrderMetadata {
+                uid,
+                creation_date: Utc::now(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let max_submission_time = Duration::minutes(2);
+        let uids = [OrderUid([1; 56]), OrderUid([2; 56]), OrderUid([3; 56])];
+        for uid in uids {
+            db.insert_order(&order(uid), Default::default())
+                .await
+                .unwrap();
+        }
+
+        db.replace_order(
+            &uids[0],
+            &order(OrderUid([4; 56])),
+            Default::default(),
+            max_submission_time,
+        )
+        .await
+        .unwrap();
+        let old = db.single_order(&uids[0]).await.unwrap().unwrap();
+        assert!(old.metadata.invalidated);
+        assert!(db.single_order(&OrderUid([4; 56])).await.unwrap().is_some());
+
+        // Replacements fail without changes.
+        let replace = |old| {
+            let db = db.clone();
+            async move {
+                let result = db
+                    .replace_order(
+                        &old,
+                        &order(OrderUid([5; 56])),
+                        Default::default(),
+                        max_submission_time,
+                    )
+                    .await;
+                assert!(db.single_order(&OrderUid([5; 56])).await.unwrap().is_none());
+                result
+            }
+        };
+        assert!(matches!(
+            replace(uids[0]).await,
+            Err(ReplacementError::OldOrderCancelled)
+        ));
+        assert!(matches!(
+            replace(OrderUid([6; 56])).await,
+            Err(ReplacementError::OldOrderNotFound)
+        ));
+
+        db.append_events_(vec![(
+            EventIndex {
+                block_number: 0,
+                log_index: 0,
+            },
+            Event::Trade(Trade {
+                order_uid: uids[1],
+                ..Default::default()
+            }),
+        )])
+        .await
+        .unwrap();
+        assert!(matches!(
+            replace(uids[1]).await,
+            Err(ReplacementError::OldOrderTraded)
+        ));
+
+        let settlement = |uid| SolverSettlement {
+            orders: vec![model::solver_competition::Order {
+                id: uid,
+                executed_amount: 1.into(),
+            }],
+            ..Default::default()
+        };
+        db.save(
+            0,
+            &SolverCompetitionResponse {
+                // Only the winning solution, which is the last one, matters.
+                solutions: vec![settlement(uids[0]), settlement(uids[2])],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        // Later competitions don't hide the solution of an earlier one that
+        // could still be in flight.
+        db.save(
+            1,
+            &SolverCompetitionResponse {
+                solutions: vec![settlement(uids[0])],
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(matches!(
+            replace(uids[2]).await,
+            Err(ReplacementError::OldOrderInFlight)
+        ));
+        assert!(
+            !db.single_order(&uids[2])
+                .await
+                .unwrap()
+                .unwrap()
+                .metadata
+                .invalidated
+        );
+
+        // Solutions that were never mined stop blocking replacements.
+        sqlx::query(
+            "UPDATE solver_competitions SET creation_timestamp = now() - interval '1 hour';",
+        )
+        .execute(&db.pool)
+        .await
+        .unwrap();
+        db.replace_order(
+            &uids[2],
+            &order(OrderUid([7; 56])),
+            Default::default(),
+            max_submission_time,
+        )
+        .await
+        .unwrap();
+        assert!(db.single_order(&OrderUid([7; 56])).await.unwrap().is_some());
+    }
+
     #[tokio::test]
     #[ignore]
     async fn postgres_filter_orders_by_fully_executed() {
@@ -1559,6 +2240,20 @@ mod tests {
         assert!(get_order(&order0.metadata.uid).await.is_some());
         assert!(get_order(&order1.metadata.uid).await.is_some());
         assert!(get_order(&OrderUid::default()).await.is_none());
+
+        let mut orders = db
+            .many_orders(&[
+                order1.metadata.uid,
+                OrderUid::default(),
+                order0.metadata.uid,
+            ])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|order| order.metadata.uid)
+            .collect::<Vec<_>>();
+        orders.sort_by_key(|uid| uid.0);
+        assert_eq!(orders, [order0.metadata.uid, order1.metadata.uid]);
     }
 
     #[tokio::test]
@@ -1804,6 +2499,102 @@ mod tests {
         assert_eq!(result, vec![]);
     }
 
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_count_open_orders() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let owner = H160([1; 20]);
+        let order = |uid: u32, valid_to: u32| Order {
+            metadata: OrderMetadata {
+                uid: OrderUid::from_integer(uid),
+                owner,
+                ..Default::default()
+            },
+            creation: OrderCreation {
+                valid_to,
+                sell_amount: 1.into(),
+                buy_amount: 1.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        for order in [order(0, 10), order(1, 10), order(2, 5)] {
+            db.insert_order(&order, Default::default()).await.unwrap();
+        }
+        db.insert_order(
+            &Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid::from_integer(3),
+                    owner: H160([2; 20]),
+                    ..Default::default()
+                },
+                ..order(3, 10)
+            },
+            Default::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(db.count_open_orders(&owner, 0).await.unwrap(), 3);
+        assert_eq!(db.count_open_orders(&owner, 6).await.unwrap(), 2);
+
+        db.cancel_orders(vec![OrderUid::from_integer(0)], Utc::now())
+            .await
+            .unwrap();
+        assert_eq!(db.count_open_orders(&owner, 6).await.unwrap(), 1);
+        assert_eq!(db.count_open_orders(&H160([3; 20]), 0).await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_insert_order_with_limit() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let order = |uid: u32, owner: H160| Order {
+            metadata: OrderMetadata {
+                uid: OrderUid::from_integer(uid),
+                owner,
+                ..Default::default()
+            },
+            creation: OrderCreation {
+                valid_to: 10,
+                sell_amount: 1.into(),
+                buy_amount: 1.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let owner = H160([1; 20]);
+
+        // Concurrent insertions can't exceed the limit.
+        let results = futures::future::join_all((0..4).map(|uid| {
+            let db = db.clone();
+            async move {
+                db.insert_order_with_limit(&order(uid, owner), Default::default(), 2, 0)
+                    .await
+            }
+        }))
+        .await;
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 2);
+        assert!(results.iter().all(|result| matches!(
+            result,
+            Ok(()) | Err(LimitedInsertionError::TooManyOpenOrders { max: 2 })
+        )));
+        assert_eq!(db.count_open_orders(&owner, 0).await.unwrap(), 2);
+
+        // Other owners and expired orders don't count towards the limit.
+        db.insert_order_with_limit(&order(4, H160([2; 20])), Default::default(), 2, 0)
+            .await
+            .unwrap();
+        db.insert_order_with_limit(&order(5, owner), Default::default(), 2, 11)
+            .await
+            .unwrap();
+    }
+
     #[tokio::test]
     #[ignore]
     async fn postgres_returns_expected_orders_for_tx_hash_request() {
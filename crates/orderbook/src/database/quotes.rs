@@ -0,0 +1,207 @@
+use super::{
+    orders::{DbBuyTokenDestination, DbOrderKind, DbSellTokenSource},
+    Postgres,
+};
+use crate::{conversions::*, fee::FeeParameters};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use model::{
+    app_id::AppId,
+    order::{BuyTokenDestination, OrderKind, QuoteId, SellTokenSource},
+};
+use primitive_types::{H160, U256};
+use sqlx::types::BigDecimal;
+
+/// A quote as it was handed out to a user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuoteData {
+    pub sell_token: H160,
+    pub buy_token: H160,
+    pub sell_amount: U256,
+    pub buy_amount: U256,
+    /// The subsidized fee that was quoted.
+    pub fee_amount: U256,
+    pub kind: OrderKind,
+    /// The fee subsidy can depend on the owner and app data, so a quote can
+    /// only be used by orders with the same values.
+    pub from: H160,
+    pub app_data: AppId,
+    pub sell_token_balance: SellTokenSource,
+    pub buy_token_balance: BuyTokenDestination,
+    /// The unsubsidized fee parameters the quoted fee was computed from.
+    pub fee_parameters: FeeParameters,
+    pub expiration: DateTime<Utc>,
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait QuoteStoring: Send + Sync {
+    /// Stores a quote and returns the id it can be referenced by.
+    async fn insert_quote(&self, quote: &QuoteData) -> Result<QuoteId>;
+    /// Returns the quote if it exists and expires at or after `min_expiration`.
+    async fn find_quote(
+        &self,
+        id: QuoteId,
+        min_expiration: DateTime<Utc>,
+    ) -> Result<Option<QuoteData>>;
+}
+
+#[derive(sqlx::FromRow)]
+struct QuoteRow {
+    sell_token: Vec<u8>,
+    buy_token: Vec<u8>,
+    sell_amount: BigDecimal,
+    buy_amount: BigDecimal,
+    fee_amount: BigDecimal,
+    order_kind: DbOrderKind,
+    from_address: Vec<u8>,
+    app_data: Vec<u8>,
+    sell_token_balance: DbSellTokenSource,
+    buy_token_balance: DbBuyTokenDestination,
+    gas_amount: f64,
+    gas_price: f64,
+    sell_token_price: f64,
+    subsidy_version: i64,
+    expiration_timestamp: DateTime<Utc>,
+}
+
+impl QuoteRow {
+    fn into_quote(self) -> Result<QuoteData> {
+        Ok(QuoteData {
+            sell_token: h160_from_vec(self.sell_token)?,
+            buy_token: h160_from_vec(self.buy_token)?,
+            sell_amount: big_decimal_to_u256(&self.sell_amount)
+                .ok_or_else(|| anyhow!("sell_amount is not U256"))?,
+            buy_amount: big_decimal_to_u256(&self.buy_amount)
+                .ok_or_else(|| anyhow!("buy_amount is not U256"))?,
+            fee_amount: big_decimal_to_u256(&self.fee_amount)
+                .ok_or_else(|| anyhow!("fee_amount is not U256"))?,
+            kind: self.order_kind.into(),
+            from: h160_from_vec(self.from_address)?,
+            app_data: AppId(
+                self.app_data
+                    .try_into()
+                    .map_err(|_| anyhow!("app_data is not [u8; 32]"))?,
+            ),
+            sell_token_balance: self.sell_token_balance.into(),
+            buy_token_balance: self.buy_token_balance.into(),
+            fee_parameters: FeeParameters {
+                gas_amount: self.gas_amount,
+                gas_price: self.gas_price,
+                sell_token_price: self.sell_token_price,
+                subsidy_version: self.subsidy_version.try_into()?,
+            },
+            expiration: self.expiration_timestamp,
+        })
+    }
+}
+
+#[async_trait::async_trait]
+impl QuoteStoring for Postgres {
+    async fn insert_quote(&self, quote: &QuoteData) -> Result<QuoteId> {
+        const QUERY: &str = "\
+            INSERT INTO quotes (\
+                sell_token, buy_token, sell_amount, buy_amount, fee_amount, order_kind, \
+                from_address, app_data, sell_token_balance, buy_token_balance, \
+                gas_amount, gas_price, sell_token_price, subsidy_version, expiration_timestamp) \
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15) \
+            RETURNING id;";
+        sqlx::query_scalar(QUERY)
+            .bind(quote.sell_token.as_bytes())
+            .bind(quote.buy_token.as_bytes())
+            .bind(u256_to_big_decimal(&quote.sell_amount))
+            .bind(u256_to_big_decimal(&quote.buy_amount))
+            .bind(u256_to_big_decimal(&quote.fee_amount))
+            .bind(DbOrderKind::from(quote.kind))
+            .bind(quote.from.as_bytes())
+            .bind(quote.app_data.0.as_ref())
+            .bind(DbSellTokenSource::from(quote.sell_token_balance))
+            .bind(DbBuyTokenDestination::from(quote.buy_token_balance))
+            .bind(quote.fee_parameters.gas_amount)
+            .bind(quote.fee_parameters.gas_price)
+            .bind(quote.fee_parameters.sell_token_price)
+            .bind(quote.fee_parameters.subsidy_version as i64)
+            .bind(quote.expiration)
+            .fetch_one(&self.pool)
+            .await
+            .context("insert_quote failed")
+    }
+
+    async fn find_quote(
+        &self,
+        id: QuoteId,
+        min_expiration: DateTime<Utc>,
+    ) -> Result<Option<QuoteData>> {
+        const QUERY: &str = "\
+            SELECT * FROM quotes \
+            WHERE id = $1 AND expiration_timestamp >= $2;";
+        let row: Option<QuoteRow> = sqlx::query_as(QUERY)
+            .bind(id)
+            .bind(min_expiration)
+            .fetch_optional(&self.pool)
+            .await
+            .context("find_quote failed")?;
+        row.map(QuoteRow::into_quote).transpose()
+    }
+}
+
+impl Postgres {
+    pub async fn remove_expired_quotes(&self, max_expiry: DateTime<Utc>) -> Result<()> {
+        const QUERY: &str = "DELETE FROM quotes WHERE expiration_timestamp < $1;";
+        sqlx::query(QUERY)
+            .bind(max_expiry)
+            .execute(&self.pool)
+            .await
+            .context("remove_expired_quotes failed")
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, NaiveDateTime};
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_save_and_find_quotes() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let now = DateTime::from_utc(NaiveDateTime::from_timestamp(1_000_000, 0), Utc);
+        let quote = QuoteData {
+            sell_token: H160([1; 20]),
+            buy_token: H160([2; 20]),
+            sell_amount: 3.into(),
+            buy_amount: 4.into(),
+            fee_amount: 5.into(),
+            kind: OrderKind::Buy,
+            from: H160([10; 20]),
+            app_data: AppId([11; 32]),
+            sell_token_balance: SellTokenSource::Internal,
+            buy_token_balance: BuyTokenDestination::Internal,
+            fee_parameters: FeeParameters {
+                gas_amount: 6.,
+                gas_price: 7.,
+                sell_token_price: 8.,
+                subsidy_version: 9,
+            },
+            expiration: now,
+        };
+        let id = db.insert_quote(&quote).await.unwrap();
+        let other_id = db.insert_quote(&quote).await.unwrap();
+        assert_ne!(id, other_id);
+
+        assert_eq!(db.find_quote(id, now).await.unwrap(), Some(quote));
+        assert_eq!(
+            db.find_quote(id, now + Duration::seconds(1)).await.unwrap(),
+            None
+        );
+        assert_eq!(db.find_quote(other_id + 1, now).await.unwrap(), None);
+
+        db.remove_expired_quotes(now + Duration::seconds(1))
+            .await
+            .unwrap();
+        assert_eq!(db.find_quote(id, now).await.unwrap(), None);
+    }
+}
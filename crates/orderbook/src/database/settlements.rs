@@ -0,0 +1,170 @@
+use super::Postgres;
+use crate::conversions::{h256_from_vec, u256_to_big_decimal};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use ethcontract::{H256, U256};
+use futures::TryStreamExt;
+
+/// What a settlement transaction cost on chain.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SettlementCosts {
+    pub gas_used: U256,
+    pub effective_gas_price: U256,
+    /// Size of the transaction's calldata in bytes.
+    pub calldata_size: usize,
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait SettlementStoring: Send + Sync {
+    /// Transaction hashes of settlements whose costs are not yet stored and
+    /// that are due to be fetched at `now`. Settlements with fewer failed
+    /// attempts come first, then oldest settlements first.
+    async fn settlements_without_costs(&self, limit: u64, now: DateTime<Utc>) -> Result<Vec<H256>>;
+    async fn store_settlement_costs(&self, tx_hash: H256, costs: &SettlementCosts) -> Result<()>;
+    /// Records that the costs of the settlement could not be fetched at `now`.
+    /// The next attempt is delayed by a back off that doubles with every
+    /// failed attempt up to a day.
+    async fn record_failed_settlement_costs(&self, tx_hash: H256, now: DateTime<Utc>)
+        -> Result<()>;
+}
+
+#[async_trait::async_trait]
+impl SettlementStoring for Postgres {
+    async fn settlements_without_costs(&self, limit: u64, now: DateTime<Utc>) -> Result<Vec<H256>> {
+        const QUERY: &str = "\
+            SELECT tx_hash FROM settlements \
+            WHERE gas_used IS NULL AND (next_cost_fetch IS NULL OR next_cost_fetch <= $2) \
+            ORDER BY cost_fetch_attempts ASC, block_number ASC, log_index ASC \
+            LIMIT $1;";
+        sqlx::query_scalar(QUERY)
+            .bind(limit as i64)
+            .bind(now)
+            .fetch(&self.pool)
+            .err_into()
+            .and_then(|hash: Vec<u8>| async move { h256_from_vec(hash) })
+            .try_collect()
+            .await
+            .context("settlements_without_costs failed")
+    }
+
+    async fn store_settlement_costs(&self, tx_hash: H256, costs: &SettlementCosts) -> Result<()> {
+        const QUERY: &str = "\
+            UPDATE settlements \
+            SET gas_used = $2, effective_gas_price = $3, calldata_size = $4 \
+            WHERE tx_hash = $1;";
+        sqlx::query(QUERY)
+            .bind(tx_hash.as_bytes())
+            .bind(u256_to_big_decimal(&costs.gas_used))
+            .bind(u256_to_big_decimal(&costs.effective_gas_price))
+            .bind(costs.calldata_size as i64)
+            .execute(&self.pool)
+            .await
+            .context("store_settlement_costs failed")
+            .map(|_| ())
+    }
+
+    async fn record_failed_settlement_costs(
+        &self,
+        tx_hash: H256,
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        const QUERY: &str = "\
+            UPDATE settlements \
+            SET \
+                cost_fetch_attempts = cost_fetch_attempts + 1, \
+                next_cost_fetch = $2 + LEAST( \
+                    interval '1 minute' * power(2, cost_fetch_attempts), \
+                    interval '1 day' \
+                ) \
+            WHERE tx_hash = $1;";
+        sqlx::query(QUERY)
+            .bind(tx_hash.as_bytes())
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .context("record_failed_settlement_costs failed")
+            .map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::events::{Event, Settlement};
+    use shared::event_handling::EventIndex;
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_settlement_costs() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let hashes = [H256::from_low_u64_be(1), H256::from_low_u64_be(2)];
+        let events = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, hash)| {
+                (
+                    EventIndex {
+                        block_number: 2 - i as u64,
+                        log_index: 0,
+                    },
+                    Event::Settlement(Settlement {
+                        solver: Default::default(),
+                        transaction_hash: *hash,
+                    }),
+                )
+            })
+            .collect();
+        db.append_events_(events).await.unwrap();
+
+        let now = Utc::now();
+        assert_eq!(
+            db.settlements_without_costs(10, now).await.unwrap(),
+            vec![hashes[1], hashes[0]]
+        );
+        assert_eq!(
+            db.settlements_without_costs(1, now).await.unwrap(),
+            vec![hashes[1]]
+        );
+
+        // Failed settlements are skipped until their back off has passed and
+        // then come after the ones that haven't failed.
+        db.record_failed_settlement_costs(hashes[1], now)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.settlements_without_costs(10, now).await.unwrap(),
+            vec![hashes[0]]
+        );
+        let later = now + chrono::Duration::minutes(1);
+        assert_eq!(
+            db.settlements_without_costs(10, later).await.unwrap(),
+            vec![hashes[0], hashes[1]]
+        );
+        // The back off doubles.
+        db.record_failed_settlement_costs(hashes[1], later)
+            .await
+            .unwrap();
+        assert_eq!(
+            db.settlements_without_costs(10, later + chrono::Duration::minutes(1))
+                .await
+                .unwrap(),
+            vec![hashes[0]]
+        );
+
+        let costs = SettlementCosts {
+            gas_used: 100_000.into(),
+            effective_gas_price: 30_000_000_000u64.into(),
+            calldata_size: 1_000,
+        };
+        db.store_settlement_costs(hashes[1], &costs).await.unwrap();
+        assert_eq!(
+            db.settlements_without_costs(10, later + chrono::Duration::days(1))
+                .await
+                .unwrap(),
+            vec![hashes[0]]
+        );
+    }
+}
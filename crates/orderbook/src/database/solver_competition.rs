@@ -0,0 +1,119 @@
+use super::Postgres;
+use anyhow::{Context, Result};
+use ethcontract::H256;
+use model::{auction::AuctionId, solver_competition::SolverCompetitionResponse};
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait SolverCompetitionStoring: Send + Sync {
+    /// Stores the competition of an auction. Saving the same auction again
+    /// replaces the previous data, for example once the transaction hash of the
+    /// winning settlement is known.
+    async fn save(&self, auction_id: AuctionId, data: &SolverCompetitionResponse) -> Result<()>;
+    async fn load(&self, auction_id: AuctionId) -> Result<Option<SolverCompetitionResponse>>;
+    /// Loads the competition of the auction whose winning settlement was mined
+    /// in the given transaction.
+    async fn load_by_tx_hash(&self, tx_hash: &H256) -> Result<Option<SolverCompetitionResponse>>;
+}
+
+#[async_trait::async_trait]
+impl SolverCompetitionStoring for Postgres {
+    async fn save(&self, auction_id: AuctionId, data: &SolverCompetitionResponse) -> Result<()> {
+        const QUERY: &str = "\
+            INSERT INTO solver_competitions (id, tx_hash, json) \
+            VALUES ($1, $2, $3::jsonb) \
+            ON CONFLICT (id) DO UPDATE \
+            SET \
+                tx_hash = COALESCE(EXCLUDED.tx_hash, solver_competitions.tx_hash), \
+                json = EXCLUDED.json;";
+        let json = serde_json::to_string(data).context("serialize solver competition")?;
+        sqlx::query(QUERY)
+            .bind(auction_id as i64)
+            .bind(data.transaction_hash.as_ref().map(|hash| hash.as_bytes()))
+            .bind(json)
+            .execute(&self.pool)
+            .await
+            .context("insert solver competition failed")
+            .map(|_| ())
+    }
+
+    async fn load(&self, auction_id: AuctionId) -> Result<Option<SolverCompetitionResponse>> {
+        const QUERY: &str = "SELECT json::text FROM solver_competitions WHERE id = $1;";
+        let json: Option<String> = sqlx::query_scalar(QUERY)
+            .bind(auction_id as i64)
+            .fetch_optional(&self.pool)
+            .await
+            .context("load solver competition failed")?;
+        json.map(|json| serde_json::from_str(&json).context("deserialize solver competition"))
+            .transpose()
+    }
+
+    async fn load_by_tx_hash(&self, tx_hash: &H256) -> Result<Option<SolverCompetitionResponse>> {
+        const QUERY: &str = "SELECT json::text FROM solver_competitions WHERE tx_hash = $1;";
+        let json: Option<String> = sqlx::query_scalar(QUERY)
+            .bind(tx_hash.as_bytes())
+            .fetch_optional(&self.pool)
+            .await
+            .context("load solver competition by tx hash failed")?;
+        json.map(|json| serde_json::from_str(&json).context("deserialize solver competition"))
+            .transpose()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::solver_competition::{Objective, SolverSettlement};
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_solver_competition_roundtrip() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        assert_eq!(db.load(0).await.unwrap(), None);
+
+        let mut competition = SolverCompetitionResponse {
+            auction_id: 0,
+            gas_price: 1.,
+            liquidity_collected_block: 2,
+            competition_simulation_block: 3,
+            transaction_hash: None,
+            solutions: vec![SolverSettlement {
+                solver: "solver".to_string(),
+                objective: Objective {
+                    total: 4.,
+                    ..Default::default()
+                },
+                call_data: vec![5],
+                ..Default::default()
+            }],
+        };
+        db.save(0, &competition).await.unwrap();
+        assert_eq!(db.load(0).await.unwrap(), Some(competition.clone()));
+        assert_eq!(db.load(1).await.unwrap(), None);
+
+        let tx_hash = H256([6; 32]);
+        assert_eq!(db.load_by_tx_hash(&tx_hash).await.unwrap(), None);
+        competition.transaction_hash = Some(tx_hash);
+        db.save(0, &competition).await.unwrap();
+        assert_eq!(db.load(0).await.unwrap(), Some(competition.clone()));
+        assert_eq!(
+            db.load_by_tx_hash(&tx_hash).await.unwrap(),
+            Some(competition.clone())
+        );
+
+        // Saving the competition again without a transaction hash keeps the
+        // known one.
+        db.save(
+            0,
+            &SolverCompetitionResponse {
+                transaction_hash: None,
+                ..competition.clone()
+            },
+        )
+        .await
+        .unwrap();
+        assert!(db.load_by_tx_hash(&tx_hash).await.unwrap().is_some());
+    }
+}
@@ -1,15 +1,25 @@
 use crate::conversions::{big_decimal_to_big_uint, h160_from_vec, h256_from_vec};
-use crate::database::Postgres;
+use crate::database::{orders::DbOrderKind, Postgres};
 use anyhow::{anyhow, Context, Result};
-use ethcontract::H160;
+use const_format::concatcp;
+use ethcontract::{H160, H256};
 use futures::stream::TryStreamExt;
-use model::{order::OrderUid, trade::Trade};
+use model::{
+    order::{OrderKind, OrderUid},
+    trade::{Trade, TradeAccounting},
+};
+use num::{BigInt, FromPrimitive, ToPrimitive, Zero};
 use sqlx::types::BigDecimal;
 use std::convert::TryInto;
 
 #[async_trait::async_trait]
 pub trait TradeRetrieving: Send + Sync {
-    async fn trades(&self, filter: &TradeFilter) -> Result<Vec<Trade>>;
+    /// A page of the trades matching the filter ordered by the block and log
+    /// index in which they happened (oldest trades first).
+    async fn trades(&self, filter: &TradeFilter, offset: u64, limit: u64) -> Result<TradesPage>;
+    /// The fees and surplus of the trades settled by the transaction ordered
+    /// by log index.
+    async fn trade_accounting(&self, tx_hash: &H256) -> Result<Vec<TradeAccounting>>;
 }
 
 /// Any default value means that this field is unfiltered.
@@ -17,13 +27,79 @@ pub trait TradeRetrieving: Send + Sync {
 pub struct TradeFilter {
     pub owner: Option<H160>,
     pub order_uid: Option<OrderUid>,
+    /// Trades that either sell or buy the token.
+    pub token: Option<H160>,
+    /// Inclusive lower bound for the trade's block number.
+    pub from_block: Option<u64>,
+    /// Inclusive upper bound for the trade's block number.
+    pub to_block: Option<u64>,
+    pub tx_hash: Option<H256>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct TradesPage {
+    pub trades: Vec<Trade>,
+    /// The number of trades matching the filter regardless of pagination.
+    pub total_count: u64,
+}
+
+const TRADES_FROM: &str = "\
+    trades t \
+    LEFT OUTER JOIN LATERAL ( \
+        SELECT tx_hash FROM settlements s \
+        WHERE s.block_number = t.block_number \
+        AND   s.log_index > t.log_index \
+        ORDER BY s.log_index ASC \
+        LIMIT 1 \
+    ) AS settlement ON true \
+    JOIN orders o \
+    ON o.uid = t.order_uid ";
+
+const TRADES_WHERE: &str = "\
+    o.uid IS NOT null \
+    AND ($1 IS NULL OR o.owner = $1) \
+    AND ($2 IS NULL OR o.uid = $2) \
+    AND ($3 IS NULL OR o.sell_token = $3 OR o.buy_token = $3) \
+    AND ($4 IS NULL OR t.block_number >= $4) \
+    AND ($5 IS NULL OR t.block_number <= $5) \
+    AND ($6 IS NULL OR ( \
+        t.block_number IN (SELECT block_number FROM settlements WHERE tx_hash = $6) \
+        AND settlement.tx_hash = $6 \
+    )) ";
+
+impl Postgres {
+    /// The number of trades matching the filter.
+    async fn trades_count(&self, filter: &TradeFilter) -> Result<u64> {
+        #[rustfmt::skip]
+        const QUERY: &str = concatcp!(
+            "SELECT COUNT(*) ",
+            "FROM ", TRADES_FROM,
+            "WHERE ", TRADES_WHERE,
+            ";",
+        );
+
+        let count: i64 = sqlx::query_scalar(QUERY)
+            .bind(filter.owner.as_ref().map(|h160| h160.as_bytes()))
+            .bind(filter.order_uid.as_ref().map(|uid| uid.0.as_ref()))
+            .bind(filter.token.as_ref().map(|h160| h160.as_bytes()))
+            .bind(filter.from_block.map(|block| block as i64))
+            .bind(filter.to_block.map(|block| block as i64))
+            .bind(filter.tx_hash.as_ref().map(|hash| hash.as_bytes()))
+            .fetch_one(&self.pool)
+            .await
+            .context("trades_count failed")?;
+        count.try_into().context("trade count is negative")
+    }
 }
 
 #[async_trait::async_trait]
 impl TradeRetrieving for Postgres {
-    async fn trades(&self, filter: &TradeFilter) -> Result<Vec<Trade>> {
-        const QUERY: &str = "\
-            SELECT \
+    async fn trades(&self, filter: &TradeFilter, offset: u64, limit: u64) -> Result<TradesPage> {
+        // The total count is computed by the same query so that a page only
+        // needs a second query when it is past the last trade.
+        #[rustfmt::skip]
+        const QUERY: &str = concatcp!(
+            "SELECT \
                 t.block_number, \
                 t.log_index, \
                 t.order_uid, \
@@ -33,30 +109,83 @@ impl TradeRetrieving for Postgres {
                 o.owner, \
                 o.buy_token, \
                 o.sell_token, \
-                settlement.tx_hash \
-            FROM trades t \
-            LEFT OUTER JOIN LATERAL ( \
-                SELECT tx_hash FROM settlements s \
-                WHERE s.block_number = t.block_number \
-                AND   s.log_index > t.log_index \
-                ORDER BY s.log_index ASC \
-                LIMIT 1 \
-            ) AS settlement ON true \
+                settlement.tx_hash, \
+                COUNT(*) OVER () AS total_count ",
+            "FROM ", TRADES_FROM,
+            "WHERE ", TRADES_WHERE,
+            "ORDER BY t.block_number ASC, t.log_index ASC ",
+            "LIMIT $7 ",
+            "OFFSET $8;",
+        );
+
+        let rows: Vec<TradesQueryRow> = sqlx::query_as(QUERY)
+            .bind(filter.owner.as_ref().map(|h160| h160.as_bytes()))
+            .bind(filter.order_uid.as_ref().map(|uid| uid.0.as_ref()))
+            .bind(filter.token.as_ref().map(|h160| h160.as_bytes()))
+            .bind(filter.from_block.map(|block| block as i64))
+            .bind(filter.to_block.map(|block| block as i64))
+            .bind(filter.tx_hash.as_ref().map(|hash| hash.as_bytes()))
+            .bind(limit as i64)
+            .bind(offset as i64)
+            .fetch_all(&self.pool)
+            .await
+            .context("trades failed")?;
+        let total_count = match rows.first() {
+            Some(row) => row
+                .total_count
+                .try_into()
+                .context("trade count is negative")?,
+            None if offset == 0 => 0,
+            None => self.trades_count(filter).await?,
+        };
+        let trades = rows
+            .into_iter()
+            .map(TradesQueryRow::into_trade)
+            .collect::<Result<_>>()?;
+        Ok(TradesPage {
+            trades,
+            total_count,
+        })
+    }
+
+    async fn trade_accounting(&self, tx_hash: &H256) -> Result<Vec<TradeAccounting>> {
+        // The trades of a settlement are the ones emitted after the previous
+        // settlement event in the same block.
+        const QUERY: &str = "\
+            SELECT \
+                t.order_uid, \
+                s.solver, \
+                o.kind, \
+                o.sell_token, \
+                o.sell_amount, \
+                o.buy_amount, \
+                o.fee_amount, \
+                o.full_fee_amount, \
+                t.sell_amount AS executed_sell_amount, \
+                t.buy_amount AS executed_buy_amount, \
+                t.fee_amount AS executed_fee_amount, \
+                p.sell_token_price \
+            FROM settlements s \
+            JOIN trades t \
+            ON  t.block_number = s.block_number \
+            AND t.log_index < s.log_index \
+            AND t.log_index > COALESCE(( \
+                SELECT MAX(previous.log_index) FROM settlements previous \
+                WHERE previous.block_number = s.block_number \
+                AND   previous.log_index < s.log_index \
+            ), -1) \
             JOIN orders o \
             ON o.uid = t.order_uid \
-            WHERE \
-                o.uid IS NOT null \
-            AND \
-                ($1 IS NULL OR o.owner = $1) \
-            AND \
-                ($2 IS NULL OR o.uid = $2);";
+            LEFT OUTER JOIN order_fee_parameters p \
+            ON p.order_uid = o.uid \
+            WHERE s.tx_hash = $1 \
+            ORDER BY t.log_index ASC;";
 
         sqlx::query_as(QUERY)
-            .bind(filter.owner.as_ref().map(|h160| h160.as_bytes()))
-            .bind(filter.order_uid.as_ref().map(|uid| uid.0.as_ref()))
+            .bind(tx_hash.as_bytes())
             .fetch(&self.pool)
             .err_into()
-            .and_then(|row: TradesQueryRow| async move { row.into_trade() })
+            .and_then(|row: TradeAccountingQueryRow| async move { row.into_trade_accounting() })
             .try_collect()
             .await
     }
@@ -74,6 +203,7 @@ struct TradesQueryRow {
     buy_token: Vec<u8>,
     sell_token: Vec<u8>,
     tx_hash: Option<Vec<u8>>,
+    total_count: i64,
 }
 
 impl TradesQueryRow {
@@ -113,14 +243,98 @@ impl TradesQueryRow {
     }
 }
 
+#[derive(sqlx::FromRow)]
+struct TradeAccountingQueryRow {
+    order_uid: Vec<u8>,
+    solver: Vec<u8>,
+    kind: DbOrderKind,
+    sell_token: Vec<u8>,
+    sell_amount: BigDecimal,
+    buy_amount: BigDecimal,
+    fee_amount: BigDecimal,
+    full_fee_amount: BigDecimal,
+    executed_sell_amount: BigDecimal,
+    executed_buy_amount: BigDecimal,
+    executed_fee_amount: BigDecimal,
+    sell_token_price: Option<f64>,
+}
+
+impl TradeAccountingQueryRow {
+    fn into_trade_accounting(self) -> Result<TradeAccounting> {
+        let amount = |amount: &BigDecimal, name: &str| {
+            big_decimal_to_big_uint(amount)
+                .ok_or_else(|| anyhow!("{} is not an unsigned integer", name))
+        };
+        let order_uid = OrderUid(
+            self.order_uid
+                .try_into()
+                .map_err(|_| anyhow!("order uid has wrong length"))?,
+        );
+        let executed_fee = amount(&self.executed_fee_amount, "executed_fee_amount")?;
+        let executed_sell_amount = amount(&self.executed_sell_amount, "executed_sell_amount")?;
+        let surplus_in_sell_token = surplus_in_sell_token(
+            self.kind.into(),
+            &amount(&self.sell_amount, "sell_amount")?.into(),
+            &amount(&self.buy_amount, "buy_amount")?.into(),
+            &(BigInt::from(executed_sell_amount) - BigInt::from(executed_fee.clone())),
+            &amount(&self.executed_buy_amount, "executed_buy_amount")?.into(),
+        );
+        let surplus_in_native_token = self
+            .sell_token_price
+            .and_then(|price| BigInt::from_f64(surplus_in_sell_token.to_f64()? * price));
+        Ok(TradeAccounting {
+            order_uid,
+            solver: h160_from_vec(self.solver)?,
+            sell_token: h160_from_vec(self.sell_token)?,
+            executed_fee,
+            subsidized_fee: amount(&self.fee_amount, "fee_amount")?,
+            unsubsidized_fee: amount(&self.full_fee_amount, "full_fee_amount")?,
+            surplus_in_sell_token,
+            surplus_in_native_token,
+        })
+    }
+}
+
+/// The amount in sell token by which a trade beat the order's limit price.
+///
+/// The executed sell amount excludes fees. Surplus of sell orders is received
+/// in buy token and converted with the trade's executed price.
+fn surplus_in_sell_token(
+    kind: OrderKind,
+    limit_sell_amount: &BigInt,
+    limit_buy_amount: &BigInt,
+    executed_sell_amount: &BigInt,
+    executed_buy_amount: &BigInt,
+) -> BigInt {
+    match kind {
+        OrderKind::Sell => {
+            let denominator = limit_sell_amount * executed_buy_amount;
+            if denominator.is_zero() {
+                return BigInt::zero();
+            }
+            (executed_buy_amount * limit_sell_amount - limit_buy_amount * executed_sell_amount)
+                * executed_sell_amount
+                / denominator
+        }
+        OrderKind::Buy => {
+            if limit_buy_amount.is_zero() {
+                return BigInt::zero();
+            }
+            limit_sell_amount * executed_buy_amount / limit_buy_amount - executed_sell_amount
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::database::{
-        events::{Event, Settlement as DbSettlement, Trade as DbTrade},
-        orders::OrderStoring,
+    use crate::{
+        database::{
+            events::{Event, Settlement as DbSettlement, Trade as DbTrade},
+            orders::OrderStoring,
+        },
+        fee::FeeParameters,
     };
-    use ethcontract::H256;
     use model::{
         order::{Order, OrderCreation, OrderMetadata},
         trade::Trade,
@@ -187,14 +401,13 @@ mod tests {
     }
 
     async fn assert_trades(db: &Postgres, filter: &TradeFilter, expected: &[Trade]) {
-        let filtered = db
-            .trades(filter)
-            .await
-            .unwrap()
-            .into_iter()
-            .collect::<Vec<_>>();
-        let expected = expected.to_vec();
-        assert_eq!(filtered, expected);
+        assert_eq!(
+            db.trades(filter, 0, 1000).await.unwrap(),
+            TradesPage {
+                trades: expected.to_vec(),
+                total_count: expected.len() as u64,
+            }
+        );
     }
 
     // Testing trades without corresponding settlement events
@@ -525,4 +738,214 @@ mod tests {
         .await;
         assert_trades(&db, &TradeFilter::default(), &[trade_a, trade_b]).await;
     }
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_trades_with_filters_and_pagination() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+        let (owners, order_ids) = generate_owners_and_order_ids(1, 3).await;
+        let tokens = [H160([1; 20]), H160([2; 20]), H160([3; 20])];
+
+        let settlement = add_settlement(
+            &db,
+            EventIndex {
+                block_number: 2,
+                log_index: 1,
+            },
+            H160::default(),
+            H256::from_low_u64_be(1),
+        )
+        .await;
+        let mut trades = Vec::new();
+        for (i, (order_uid, (sell_token, buy_token))) in
+            order_ids.iter().zip([(0, 1), (1, 2), (2, 0)]).enumerate()
+        {
+            let order = Order {
+                metadata: OrderMetadata {
+                    owner: owners[0],
+                    uid: *order_uid,
+                    ..Default::default()
+                },
+                creation: OrderCreation {
+                    sell_token: tokens[sell_token],
+                    buy_token: tokens[buy_token],
+                    ..Default::default()
+                },
+            };
+            db.insert_order(&order, Default::default()).await.unwrap();
+            let trade = add_trade(
+                &db,
+                owners[0],
+                *order_uid,
+                EventIndex {
+                    block_number: i as u64,
+                    log_index: 0,
+                },
+                (i == 2).then(|| settlement.transaction_hash),
+            )
+            .await;
+            trades.push(Trade {
+                sell_token: tokens[sell_token],
+                buy_token: tokens[buy_token],
+                ..trade
+            });
+        }
+
+        assert_trades(
+            &db,
+            &TradeFilter {
+                token: Some(tokens[1]),
+                ..Default::default()
+            },
+            &trades[0..2],
+        )
+        .await;
+        assert_trades(
+            &db,
+            &TradeFilter {
+                from_block: Some(1),
+                to_block: Some(1),
+                ..Default::default()
+            },
+            &trades[1..2],
+        )
+        .await;
+        assert_trades(
+            &db,
+            &TradeFilter {
+                tx_hash: Some(settlement.transaction_hash),
+                ..Default::default()
+            },
+            &trades[2..3],
+        )
+        .await;
+
+        let filter = TradeFilter {
+            owner: Some(owners[0]),
+            ..Default::default()
+        };
+        let page = |trades: &[Trade]| TradesPage {
+            trades: trades.to_vec(),
+            total_count: 3,
+        };
+        assert_eq!(db.trades(&filter, 0, 2).await.unwrap(), page(&trades[0..2]));
+        assert_eq!(db.trades(&filter, 2, 2).await.unwrap(), page(&trades[2..3]));
+        // Pages past the last trade still report the total count.
+        assert_eq!(db.trades(&filter, 4, 2).await.unwrap(), page(&[]));
+    }
+
+    #[test]
+    fn surplus_in_sell_token_() {
+        let surplus = |kind, limit_sell: i32, limit_buy: i32, sell: i32, buy: i32| {
+            surplus_in_sell_token(
+                kind,
+                &limit_sell.into(),
+                &limit_buy.into(),
+                &sell.into(),
+                &buy.into(),
+            )
+        };
+        // 10 buy token surplus converted at the executed price of 100/60.
+        assert_eq!(surplus(OrderKind::Sell, 100, 50, 100, 60), 16.into());
+        assert_eq!(surplus(OrderKind::Sell, 100, 50, 100, 50), 0.into());
+        assert_eq!(surplus(OrderKind::Buy, 100, 50, 80, 50), 20.into());
+        // Partially filled buy order.
+        assert_eq!(surplus(OrderKind::Buy, 100, 50, 40, 25), 10.into());
+        assert_eq!(surplus(OrderKind::Sell, 0, 0, 0, 0), 0.into());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn postgres_trade_accounting() {
+        let db = Postgres::new("postgresql://").unwrap();
+        db.clear().await.unwrap();
+
+        let solver = H160([9; 20]);
+        let tx_hash = H256::from_low_u64_be(1);
+        let orders = [
+            (OrderUid([1; 56]), OrderKind::Sell),
+            (OrderUid([2; 56]), OrderKind::Buy),
+        ];
+        for (uid, kind) in orders {
+            let order = Order {
+                metadata: OrderMetadata {
+                    uid,
+                    full_fee_amount: 7.into(),
+                    ..Default::default()
+                },
+                creation: OrderCreation {
+                    sell_amount: 100.into(),
+                    buy_amount: 50.into(),
+                    fee_amount: 5.into(),
+                    kind,
+                    ..Default::default()
+                },
+            };
+            db.insert_order(
+                &order,
+                FeeParameters {
+                    sell_token_price: 2.,
+                    ..Default::default()
+                },
+            )
+            .await
+            .unwrap();
+        }
+        let trade = |uid, sell: u64, buy: u64| {
+            Event::Trade(DbTrade {
+                order_uid: uid,
+                sell_amount_including_fee: sell.into(),
+                buy_amount: buy.into(),
+                fee_amount: 5.into(),
+            })
+        };
+        let index = |log_index| EventIndex {
+            block_number: 0,
+            log_index,
+        };
+        db.append_events_(vec![
+            // Belongs to an earlier settlement in the same block.
+            (index(0), trade(orders[0].0, 105, 50)),
+            (
+                index(1),
+                Event::Settlement(DbSettlement {
+                    solver: H160([8; 20]),
+                    transaction_hash: H256::from_low_u64_be(2),
+                }),
+            ),
+            (index(2), trade(orders[0].0, 105, 60)),
+            (index(3), trade(orders[1].0, 85, 50)),
+            (
+                index(4),
+                Event::Settlement(DbSettlement {
+                    solver,
+                    transaction_hash: tx_hash,
+                }),
+            ),
+        ])
+        .await
+        .unwrap();
+
+        let accounting = db.trade_accounting(&tx_hash).await.unwrap();
+        let expected = |uid, surplus: i32| TradeAccounting {
+            order_uid: uid,
+            solver,
+            sell_token: Default::default(),
+            executed_fee: 5u32.into(),
+            subsidized_fee: 5u32.into(),
+            unsubsidized_fee: 7u32.into(),
+            surplus_in_sell_token: surplus.into(),
+            surplus_in_native_token: Some((2 * surplus).into()),
+        };
+        assert_eq!(
+            accounting,
+            vec![expected(orders[0].0, 16), expected(orders[1].0, 20)]
+        );
+        assert!(db
+            .trade_accounting(&H256::from_low_u64_be(3))
+            .await
+            .unwrap()
+            .is_empty());
+    }
 }
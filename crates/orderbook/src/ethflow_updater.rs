@@ -0,0 +1,379 @@
+//! Indexes the orders that users place by sending native ETH to the ETH-flow
+//! contract. The contract wraps the ETH and signs orders selling it with
+//! EIP-1271 on behalf of the user, so these orders never go through the API.
+
+use crate::{
+    database::{
+        ethflow_orders::{EthflowEvent, EthflowPlacement, EthflowRefund},
+        orders::OrderStoring,
+        Postgres,
+    },
+    order_status_updates::{OrderStatusUpdates, PublishedEvents},
+};
+use anyhow::{anyhow, ensure, Context, Result};
+use chrono::Utc;
+use contracts::{
+    cowswap_eth_flow::{self, event_data::OrderPlacement, Event as ContractEvent},
+    CoWSwapEthFlow,
+};
+use ethcontract::{dyns::DynWeb3, Event as EthContractEvent, H160};
+use model::{
+    app_id::AppId,
+    order::{
+        BuyTokenDestination, Order, OrderClass, OrderCreation, OrderKind, OrderMetadata,
+        OrderStatusUpdateKind, OrderUid, SellTokenSource,
+    },
+    signature::Signature,
+    DomainSeparator,
+};
+use shared::{
+    event_handling::{BlockNumber, EventHandler, EventIndex, EventStoring},
+    impl_event_retrieving,
+    maintenance::Maintaining,
+};
+use std::{collections::HashMap, convert::TryInto, ops::RangeInclusive, sync::Arc};
+use tokio::sync::Mutex;
+
+pub struct EthflowUpdater(Mutex<EventHandler<DynWeb3, EthflowContract, EthflowStorage>>);
+
+impl_event_retrieving! {
+    pub EthflowContract for cowswap_eth_flow
+}
+
+impl EthflowUpdater {
+    /// Events before `start_block` are never indexed. This is usually the
+    /// deployment block of the contract.
+    pub fn new(
+        contract: CoWSwapEthFlow,
+        db: Postgres,
+        domain_separator: DomainSeparator,
+        settlement_contract: H160,
+        status_updates: Arc<OrderStatusUpdates>,
+        start_block: u64,
+    ) -> Self {
+        Self(Mutex::new(EventHandler::new(
+            contract.raw_instance().web3(),
+            EthflowContract(contract),
+            EthflowStorage {
+                db,
+                domain_separator,
+                settlement_contract,
+                status_updates,
+                start_block,
+                published: Default::default(),
+            },
+            None,
+        )))
+    }
+}
+
+#[async_trait::async_trait]
+impl Maintaining for EthflowUpdater {
+    async fn run_maintenance(&self) -> Result<()> {
+        self.0.run_maintenance().await
+    }
+}
+
+pub struct EthflowStorage {
+    db: Postgres,
+    domain_separator: DomainSeparator,
+    settlement_contract: H160,
+    status_updates: Arc<OrderStatusUpdates>,
+    start_block: u64,
+    published: PublishedEvents<(OrderUid, OrderStatusUpdateKind)>,
+}
+
+impl EthflowStorage {
+    fn convert_events(
+        &self,
+        events: Vec<EthContractEvent<ContractEvent>>,
+    ) -> Result<Vec<(EventIndex, EthflowEvent)>> {
+        let mut result = Vec::new();
+        for EthContractEvent { data, meta } in events {
+            let meta = meta.ok_or_else(|| anyhow!("event without metadata"))?;
+            let event = match data {
+                ContractEvent::OrderPlacement(placement) => {
+                    match convert_placement(
+                        &placement,
+                        &self.domain_separator,
+                        self.settlement_contract,
+                    ) {
+                        Ok(placement) => EthflowEvent::Placement(Box::new(placement)),
+                        // The contract doesn't validate all of the order data
+                        // so we skip orders that can never be settled instead
+                        // of getting stuck on them.
+                        Err(err) => {
+                            tracing::warn!(?err, ?meta, "skipping invalid ethflow order");
+                            continue;
+                        }
+                    }
+                }
+                ContractEvent::OrderInvalidation(invalidation) => {
+                    EthflowEvent::Invalidation(order_uid(&invalidation.order_uid.0)?)
+                }
+                ContractEvent::OrderRefund(refund) => EthflowEvent::Refund(EthflowRefund {
+                    order_uid: order_uid(&refund.order_uid.0)?,
+                    tx_hash: meta.transaction_hash,
+                }),
+            };
+            result.push((EventIndex::from(&meta), event));
+        }
+        Ok(result)
+    }
+
+    /// Publishes the updates of the stored events that weren't published yet.
+    /// The orders are signed by the contract so the updates are published to
+    /// the user that placed them.
+    async fn publish(&mut self, events: &[(EventIndex, EthflowEvent)]) {
+        let updates = self
+            .published
+            .unpublished(events.iter().map(|(index, event)| {
+                let update = match event {
+                    EthflowEvent::Placement(placement) => {
+                        (placement.order.metadata.uid, OrderStatusUpdateKind::Created)
+                    }
+                    EthflowEvent::Invalidation(uid) => (*uid, OrderStatusUpdateKind::Cancelled),
+                    EthflowEvent::Refund(refund) => {
+                        (refund.order_uid, OrderStatusUpdateKind::Refunded)
+                    }
+                };
+                (*index, update)
+            }));
+        if updates.is_empty() {
+            return;
+        }
+        let uids = updates.iter().map(|(uid, _)| *uid).collect::<Vec<_>>();
+        let onchain_users = match self.db.many_orders(&uids).await {
+            Ok(orders) => orders
+                .into_iter()
+                .map(|order| (order.metadata.uid, order.metadata.onchain_user))
+                .collect(),
+            Err(err) => {
+                tracing::warn!(?err, "failed to load ethflow orders");
+                HashMap::new()
+            }
+        };
+        for (uid, kind) in updates {
+            let onchain_user = onchain_users.get(&uid).copied().flatten();
+            self.status_updates.publish(uid, onchain_user, kind);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl EventStoring<ContractEvent> for EthflowStorage {
+    async fn replace_events(
+        &mut self,
+        events: Vec<EthContractEvent<ContractEvent>>,
+        range: RangeInclusive<BlockNumber>,
+    ) -> Result<()> {
+        let events = self.convert_events(events)?;
+        self.db
+            .replace_ethflow_events(range.start().to_u64(), events.clone())
+            .await?;
+        self.published.replace(range.start().to_u64());
+        self.publish(&events).await;
+        Ok(())
+    }
+
+    async fn append_events(&mut self, events: Vec<EthContractEvent<ContractEvent>>) -> Result<()> {
+        let events = self.convert_events(events)?;
+        self.db.append_ethflow_events(events.clone()).await?;
+        self.publish(&events).await;
+        Ok(())
+    }
+
+    async fn last_event_block(&self) -> Result<u64> {
+        Ok(self
+            .db
+            .last_ethflow_event_block()
+            .await?
+            .max(self.start_block))
+    }
+}
+
+fn order_uid(bytes: &[u8]) -> Result<OrderUid> {
+    Ok(OrderUid(
+        bytes
+            .try_into()
+            .context("order uid has wrong number of bytes")?,
+    ))
+}
+
+/// The on-chain signing schemes of `ICoWSwapOnchainOrders`.
+const ONCHAIN_SCHEME_EIP1271: u8 = 0;
+const ONCHAIN_SCHEME_PRESIGN: u8 = 1;
+
+fn convert_placement(
+    placement: &OrderPlacement,
+    domain_separator: &DomainSeparator,
+    settlement_contract: H160,
+) -> Result<EthflowPlacement> {
+    let (
+        sell_token,
+        buy_token,
+        receiver,
+        sell_amount,
+        buy_amount,
+        valid_to,
+        app_data,
+        fee_amount,
+        kind,
+        partially_fillable,
+        sell_token_balance,
+        buy_token_balance,
+    ) = placement.order.clone();
+
+    // The signature data is the address of the order owner, which is the
+    // ETH-flow contract itself.
+    let (scheme, owner) = &placement.signature;
+    ensure!(owner.0.len() == 20, "signature data is not an address");
+    let owner = H160::from_slice(&owner.0);
+    let signature = match *scheme {
+        ONCHAIN_SCHEME_EIP1271 => Signature::Eip1271(Vec::new()),
+        ONCHAIN_SCHEME_PRESIGN => Signature::PreSign(owner),
+        scheme => return Err(anyhow!("unknown onchain signing scheme {}", scheme)),
+    };
+
+    // The contract appends the quote id and the validity chosen by the user.
+    let data: [u8; 12] = placement
+        .data
+        .0
+        .as_slice()
+        .try_into()
+        .context("order data is not a quote id and valid to")?;
+    let quote_id = i64::from_be_bytes(data[..8].try_into().unwrap());
+    let user_valid_to = u32::from_be_bytes(data[8..].try_into().unwrap());
+
+    let creation = OrderCreation {
+        sell_token,
+        buy_token,
+        receiver: Some(receiver).filter(|receiver| !receiver.is_zero()),
+        sell_amount,
+        buy_amount,
+        valid_to,
+        app_data: AppId(app_data.0),
+        fee_amount,
+        kind: OrderKind::from_contract_bytes(kind.0)?,
+        partially_fillable,
+        signature,
+        sell_token_balance: SellTokenSource::from_contract_bytes(sell_token_balance.0)?,
+        buy_token_balance: BuyTokenDestination::from_contract_bytes(buy_token_balance.0)?,
+        quote_id: Some(quote_id),
+    };
+    let order = Order {
+        metadata: OrderMetadata {
+            creation_date: Utc::now(),
+            owner,
+            uid: creation.uid(domain_separator, &owner),
+            settlement_contract,
+            full_fee_amount: fee_amount,
+            class: OrderClass::Market,
+            ..Default::default()
+        },
+        creation,
+    };
+    Ok(EthflowPlacement {
+        order,
+        sender: placement.sender,
+        user_valid_to,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ethcontract::{Bytes, U256};
+    use hex_literal::hex;
+
+    fn placement() -> OrderPlacement {
+        OrderPlacement {
+            sender: H160([1; 20]),
+            order: (
+                H160([2; 20]),
+                H160([3; 20]),
+                H160::zero(),
+                U256::from(4),
+                U256::from(5),
+                u32::MAX,
+                Bytes([6; 32]),
+                U256::from(7),
+                // keccak256("sell")
+                Bytes(hex!(
+                    "f3b277728b3fee749481eb3e0b3b48980dbbab78658fc419025cb16eee346775"
+                )),
+                false,
+                // keccak256("erc20")
+                Bytes(hex!(
+                    "5a28e9363bb942b639270062aa6bb295f434bcdfc42c97267bf003f272060dc9"
+                )),
+                Bytes(hex!(
+                    "5a28e9363bb942b639270062aa6bb295f434bcdfc42c97267bf003f272060dc9"
+                )),
+            ),
+            signature: (ONCHAIN_SCHEME_EIP1271, Bytes(vec![8; 20])),
+            data: Bytes([&42i64.to_be_bytes()[..], &1000u32.to_be_bytes()[..]].concat()),
+        }
+    }
+
+    #[test]
+    fn converts_placement() {
+        let domain_separator = DomainSeparator([9; 32]);
+        let settlement_contract = H160([10; 20]);
+        let placement =
+            convert_placement(&placement(), &domain_separator, settlement_contract).unwrap();
+
+        assert_eq!(placement.sender, H160([1; 20]));
+        assert_eq!(placement.user_valid_to, 1000);
+        let order = placement.order;
+        assert_eq!(
+            order.creation,
+            OrderCreation {
+                sell_token: H160([2; 20]),
+                buy_token: H160([3; 20]),
+                receiver: None,
+                sell_amount: 4.into(),
+                buy_amount: 5.into(),
+                valid_to: u32::MAX,
+                app_data: AppId([6; 32]),
+                fee_amount: 7.into(),
+                kind: OrderKind::Sell,
+                partially_fillable: false,
+                signature: Signature::Eip1271(Vec::new()),
+                sell_token_balance: SellTokenSource::Erc20,
+                buy_token_balance: BuyTokenDestination::Erc20,
+                quote_id: Some(42),
+            }
+        );
+        assert_eq!(order.metadata.owner, H160([8; 20]));
+        assert_eq!(
+            order.metadata.uid,
+            order.creation.uid(&domain_separator, &H160([8; 20]))
+        );
+        assert_eq!(order.metadata.settlement_contract, settlement_contract);
+    }
+
+    #[test]
+    fn rejects_invalid_placements() {
+        let convert = |placement: &OrderPlacement| {
+            convert_placement(placement, &Default::default(), H160::zero())
+        };
+        assert!(convert(&placement()).is_ok());
+
+        let mut invalid = placement();
+        invalid.signature.0 = 2;
+        assert!(convert(&invalid).is_err());
+
+        let mut invalid = placement();
+        invalid.signature.1 = Bytes(vec![8; 19]);
+        assert!(convert(&invalid).is_err());
+
+        let mut invalid = placement();
+        invalid.data = Bytes(vec![0; 8]);
+        assert!(convert(&invalid).is_err());
+
+        let mut invalid = placement();
+        invalid.order.8 = Bytes([0; 32]);
+        assert!(convert(&invalid).is_err());
+    }
+}
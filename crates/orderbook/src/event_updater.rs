@@ -1,18 +1,24 @@
+use crate::{
+    database::orders::OrderStoring,
+    order_status_updates::{OrderStatusUpdates, PublishedEvents},
+};
 use anyhow::Result;
 use contracts::{
     gpv2_settlement::{self, Event as ContractEvent},
     GPv2Settlement,
 };
-use ethcontract::dyns::DynWeb3;
+use ethcontract::{dyns::DynWeb3, Event as EthContractEvent};
+use model::order::{OrderStatus, OrderStatusUpdateKind, OrderUid};
 use shared::{
-    event_handling::{EventHandler, EventStoring},
+    event_handling::{BlockNumber, EventHandler, EventIndex, EventStoring},
     impl_event_retrieving,
     maintenance::Maintaining,
 };
+use std::{collections::HashMap, convert::TryInto, ops::RangeInclusive, sync::Arc};
 use tokio::sync::Mutex;
 
-pub struct EventUpdater<Database: EventStoring<ContractEvent>>(
-    Mutex<EventHandler<DynWeb3, GPv2SettlementContract, Database>>,
+pub struct EventUpdater<Database: EventStoring<ContractEvent> + OrderStoring>(
+    Mutex<EventHandler<DynWeb3, GPv2SettlementContract, StatusPublishingStorage<Database>>>,
 );
 
 impl_event_retrieving! {
@@ -21,13 +27,22 @@ impl_event_retrieving! {
 
 impl<Database> EventUpdater<Database>
 where
-    Database: EventStoring<ContractEvent>,
+    Database: EventStoring<ContractEvent> + OrderStoring,
 {
-    pub fn new(contract: GPv2Settlement, db: Database, start_sync_at_block: Option<u64>) -> Self {
+    pub fn new(
+        contract: GPv2Settlement,
+        db: Database,
+        status_updates: Arc<OrderStatusUpdates>,
+        start_sync_at_block: Option<u64>,
+    ) -> Self {
         Self(Mutex::new(EventHandler::new(
             contract.raw_instance().web3(),
             GPv2SettlementContract(contract),
-            db,
+            StatusPublishingStorage {
+                db,
+                status_updates,
+                published: Default::default(),
+            },
             start_sync_at_block,
         )))
     }
@@ -36,9 +51,225 @@ where
 #[async_trait::async_trait]
 impl<Database> Maintaining for EventUpdater<Database>
 where
-    Database: EventStoring<ContractEvent>,
+    Database: EventStoring<ContractEvent> + OrderStoring,
 {
     async fn run_maintenance(&self) -> Result<()> {
         self.0.run_maintenance().await
     }
 }
+
+/// Event storage that publishes the order status transitions caused by the
+/// events once they have been stored.
+pub struct StatusPublishingStorage<Database> {
+    db: Database,
+    status_updates: Arc<OrderStatusUpdates>,
+    published: PublishedEvents<OrderEvent>,
+}
+
+/// An order related event that changes the status of the order.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum OrderEvent {
+    Trade(OrderUid),
+    Invalidation(OrderUid),
+    PreSignature(OrderUid),
+}
+
+fn order_event(event: &ContractEvent) -> Option<OrderEvent> {
+    let uid = |bytes: &[u8]| -> Option<OrderUid> { Some(OrderUid(bytes.try_into().ok()?)) };
+    match event {
+        ContractEvent::Trade(trade) => uid(&trade.order_uid.0).map(OrderEvent::Trade),
+        ContractEvent::OrderInvalidated(invalidation) => {
+            uid(&invalidation.order_uid.0).map(OrderEvent::Invalidation)
+        }
+        // Revoked presignatures turn the order back into pending which is
+        // not a transition that we publish.
+        ContractEvent::PreSignature(presignature) if presignature.signed => {
+            uid(&presignature.order_uid.0).map(OrderEvent::PreSignature)
+        }
+        _ => None,
+    }
+}
+
+fn order_events(events: &[EthContractEvent<ContractEvent>]) -> Vec<(EventIndex, OrderEvent)> {
+    events
+        .iter()
+        .filter_map(|event| {
+            // Events without metadata can't be stored.
+            let index = EventIndex::from(event.meta.as_ref()?);
+            Some((index, order_event(&event.data)?))
+        })
+        .collect()
+}
+
+fn order_uid(event: OrderEvent) -> OrderUid {
+    match event {
+        OrderEvent::Trade(uid) | OrderEvent::Invalidation(uid) | OrderEvent::PreSignature(uid) => {
+            uid
+        }
+    }
+}
+
+impl<Database> StatusPublishingStorage<Database>
+where
+    Database: OrderStoring,
+{
+    async fn publish(&self, events: Vec<OrderEvent>) {
+        if events.is_empty() {
+            return;
+        }
+        // The orders are needed for the on-chain user of orders placed through
+        // an on-chain order contract.
+        let uids = events.iter().copied().map(order_uid).collect::<Vec<_>>();
+        let orders = match self.db.many_orders(&uids).await {
+            Ok(orders) => orders
+                .into_iter()
+                .map(|order| (order.metadata.uid, order))
+                .collect(),
+            Err(err) => {
+                tracing::warn!(?err, "failed to load orders");
+                HashMap::new()
+            }
+        };
+        for event in events {
+            let uid = order_uid(event);
+            let order = orders.get(&uid);
+            let kind = match (event, order) {
+                (OrderEvent::Trade(_), Some(order))
+                    if order.metadata.status == OrderStatus::Fulfilled =>
+                {
+                    OrderStatusUpdateKind::Fulfilled
+                }
+                (OrderEvent::Trade(_), Some(_)) => OrderStatusUpdateKind::PartiallyFilled,
+                // Trades of orders that were not created through the order
+                // book have nobody to notify.
+                (OrderEvent::Trade(_), None) => continue,
+                (OrderEvent::Invalidation(_), _) => OrderStatusUpdateKind::Cancelled,
+                (OrderEvent::PreSignature(_), _) => OrderStatusUpdateKind::PresignatureConfirmed,
+            };
+            let onchain_user = order.and_then(|order| order.metadata.onchain_user);
+            self.status_updates.publish(uid, onchain_user, kind);
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<Database> EventStoring<ContractEvent> for StatusPublishingStorage<Database>
+where
+    Database: EventStoring<ContractEvent> + OrderStoring,
+{
+    async fn replace_events(
+        &mut self,
+        events: Vec<EthContractEvent<ContractEvent>>,
+        range: RangeInclusive<BlockNumber>,
+    ) -> Result<()> {
+        let order_events = order_events(&events);
+        self.db.replace_events(events, range.clone()).await?;
+        self.published.replace(range.start().to_u64());
+        let order_events = self.published.unpublished(order_events);
+        self.publish(order_events).await;
+        Ok(())
+    }
+
+    async fn append_events(&mut self, events: Vec<EthContractEvent<ContractEvent>>) -> Result<()> {
+        let order_events = order_events(&events);
+        self.db.append_events(events).await?;
+        let order_events = self.published.unpublished(order_events);
+        self.publish(order_events).await;
+        Ok(())
+    }
+
+    async fn last_event_block(&self) -> Result<u64> {
+        self.db.last_event_block().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{database::orders::MockOrderStoring, order_status_updates::Subscription};
+    use contracts::gpv2_settlement::event_data::{OrderInvalidated, PreSignature, Trade};
+    use ethcontract::{Bytes, H160, U256};
+    use futures::{FutureExt, StreamExt};
+    use model::order::{Order, OrderMetadata, OrderStatusUpdate};
+
+    #[test]
+    fn extracts_order_events() {
+        let uid = OrderUid([1; 56]);
+        let trade = |order_uid| {
+            ContractEvent::Trade(Trade {
+                owner: H160::zero(),
+                sell_token: H160::zero(),
+                buy_token: H160::zero(),
+                sell_amount: U256::zero(),
+                buy_amount: U256::zero(),
+                fee_amount: U256::zero(),
+                order_uid: Bytes(order_uid),
+            })
+        };
+        let presignature = |signed| {
+            ContractEvent::PreSignature(PreSignature {
+                owner: H160::zero(),
+                order_uid: Bytes(uid.0.to_vec()),
+                signed,
+            })
+        };
+        let events = vec![
+            trade(uid.0.to_vec()),
+            ContractEvent::OrderInvalidated(OrderInvalidated {
+                owner: H160::zero(),
+                order_uid: Bytes(uid.0.to_vec()),
+            }),
+            presignature(true),
+            presignature(false),
+            // Malformed uids are ignored.
+            trade(vec![1]),
+        ];
+        assert_eq!(
+            events.iter().filter_map(order_event).collect::<Vec<_>>(),
+            vec![
+                OrderEvent::Trade(uid),
+                OrderEvent::Invalidation(uid),
+                OrderEvent::PreSignature(uid),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn publishes_onchain_orders_to_the_user() {
+        let uid = OrderUid([1; 56]);
+        let user = H160([2; 20]);
+        let mut db = MockOrderStoring::new();
+        db.expect_many_orders().times(1).returning(move |uids| {
+            Ok(uids
+                .iter()
+                .map(|uid| Order {
+                    metadata: OrderMetadata {
+                        uid: *uid,
+                        status: OrderStatus::Fulfilled,
+                        onchain_user: Some(user),
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                })
+                .collect())
+        });
+        let status_updates = Arc::new(OrderStatusUpdates::default());
+        let mut updates = Box::pin(status_updates.subscribe(Subscription::Owner(user)));
+        let storage = StatusPublishingStorage {
+            db,
+            status_updates,
+            published: Default::default(),
+        };
+
+        storage.publish(vec![OrderEvent::Trade(uid)]).await;
+        assert_eq!(
+            updates.next().now_or_never(),
+            Some(Some(OrderStatusUpdate {
+                uid,
+                owner: uid.owner(),
+                onchain_user: Some(user),
+                kind: OrderStatusUpdateKind::Fulfilled,
+            }))
+        );
+    }
+}
@@ -2,7 +2,7 @@ use anyhow::Result;
 use chrono::{DateTime, Duration, Utc, MAX_DATETIME};
 use futures::future::TryFutureExt;
 use gas_estimation::GasPriceEstimating;
-use model::{app_id::AppId, order::OrderKind};
+use model::{app_data::ParsedAppData, app_id::AppId, order::OrderKind};
 use primitive_types::{H160, U256};
 use serde::{Deserialize, Serialize};
 use shared::{
@@ -18,7 +18,11 @@ use std::{
     sync::{Arc, Mutex},
 };
 
-use crate::cow_subsidy::CowSubsidy;
+use crate::{
+    cow_subsidy::CowSubsidy,
+    database::app_data::AppDataStoring,
+    fee_subsidy::{CurrentFeeSubsidy, SubsidizedOrder, SubsidyRule, VersionedFeeSubsidy},
+};
 
 pub type Measurement = (U256, DateTime<Utc>);
 
@@ -40,8 +44,15 @@ impl Default for PriceQuality {
 /// Given an estimated fee for a trade, the mimimum fee required for an order is
 /// computed using the following formula:
 /// ```text
-/// (estimated_fee_in_eth - fee_discount) * fee_factor * (partner_additional_fee_factor || 1.0)
+/// (estimated_fee_in_eth - fee_discount) * fee_factor * additional_fee_factor
 /// ```
+///
+/// The additional fee factor is taken from the first rule in `rules` that
+/// matches the order. If no rule matches it falls back to the partner fee
+/// factor of the order's app ID and finally to 1.0. Rules are not combined
+/// with each other so their order in the configuration determines precedence.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, rename_all = "camelCase")]
 pub struct FeeSubsidyConfiguration {
     /// A flat discount nominated in the native token to discount from fees.
     ///
@@ -65,6 +76,26 @@ pub struct FeeSubsidyConfiguration {
     ///
     /// Fee factors are applied **after** flat fee discounts.
     pub partner_additional_fee_factors: HashMap<AppId, f64>,
+
+    /// Ordered rules for computing additional factors based on the order.
+    ///
+    /// Fee factors are applied **after** flat fee discounts.
+    pub rules: Vec<SubsidyRule>,
+}
+
+impl FeeSubsidyConfiguration {
+    fn additional_fee_factor(&self, order: &SubsidizedOrder) -> f64 {
+        self.rules
+            .iter()
+            .find(|rule| rule.matches(order))
+            .map(|rule| rule.fee_factor)
+            .or_else(|| {
+                self.partner_additional_fee_factors
+                    .get(&order.app_id)
+                    .copied()
+            })
+            .unwrap_or(1.0)
+    }
 }
 
 impl Default for FeeSubsidyConfiguration {
@@ -74,6 +105,7 @@ impl Default for FeeSubsidyConfiguration {
             fee_factor: 1.,
             min_discounted_fee: 0.,
             partner_additional_fee_factors: HashMap::new(),
+            rules: Vec::new(),
         }
     }
 }
@@ -84,10 +116,11 @@ pub struct MinFeeCalculator {
     measurements: Arc<dyn MinFeeStoring>,
     now: Box<dyn Fn() -> DateTime<Utc> + Send + Sync>,
     bad_token_detector: Arc<dyn BadTokenDetecting>,
-    fee_subsidy: FeeSubsidyConfiguration,
+    fee_subsidy: Arc<CurrentFeeSubsidy>,
     native_price_estimator: Arc<dyn NativePriceEstimating>,
     cow_subsidy: Arc<dyn CowSubsidy>,
     liquidity_order_owners: HashSet<H160>,
+    app_data: Arc<dyn AppDataStoring>,
 }
 
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
@@ -105,6 +138,9 @@ pub struct FeeParameters {
     pub gas_amount: f64,
     pub gas_price: f64,
     pub sell_token_price: f64,
+    /// The version of the fee subsidy configuration that was applied to the
+    /// fee.
+    pub subsidy_version: u64,
 }
 
 impl Default for FeeParameters {
@@ -119,6 +155,7 @@ impl Default for FeeParameters {
             // regardless), but the multiplicative identity seemed like a
             // natural default value to use.
             sell_token_price: 1.,
+            subsidy_version: 0,
         }
     }
 }
@@ -135,7 +172,7 @@ impl FeeParameters {
     fn apply_fee_factor(
         &self,
         config: &FeeSubsidyConfiguration,
-        app_data: AppId,
+        order: &SubsidizedOrder,
         cow_factor: f64,
     ) -> U256 {
         let fee_in_eth = self.gas_amount * self.gas_price;
@@ -149,13 +186,7 @@ impl FeeParameters {
             discounted_fee_in_eth = config.min_discounted_fee;
         }
 
-        let factor = config
-            .partner_additional_fee_factors
-            .get(&app_data)
-            .copied()
-            .unwrap_or(1.0)
-            * config.fee_factor
-            * cow_factor;
+        let factor = config.additional_fee_factor(order) * config.fee_factor * cow_factor;
         U256::from_f64_lossy((discounted_fee_in_eth * factor / self.sell_token_price).ceil())
     }
 }
@@ -168,6 +199,7 @@ impl From<u32> for FeeParameters {
             gas_amount: v as f64,
             gas_price: 1.0,
             sell_token_price: 1.0,
+            ..Default::default()
         }
     }
 }
@@ -203,15 +235,28 @@ pub trait MinFeeCalculating: Send + Sync {
         user: H160,
     ) -> Result<Measurement, PriceEstimationError>;
 
+    /// Same as `compute_subsidized_min_fee` but also returns the unsubsidized
+    /// fee parameters that the subsidized fee was derived from.
+    async fn compute_subsidized_min_fee_with_parameters(
+        &self,
+        fee_data: FeeData,
+        app_data: AppId,
+        user: H160,
+    ) -> Result<(Measurement, FeeParameters), PriceEstimationError>;
+
     /// Validates that the given subsidized fee is enough to process an order for the given token.
     /// Returns current fee estimate (i.e., unsubsidized fee) if the given subsidized fee passes
     /// a check. Returns `Err` if the check failed.
+    ///
+    /// The fee also has to cover the `hook_gas_limit` of the order's hooks
+    /// because they are executed in the same settlement.
     async fn get_unsubsidized_min_fee(
         &self,
         fee_data: FeeData,
         app_data: AppId,
         subsidized_fee: U256,
         user: H160,
+        hook_gas_limit: u64,
     ) -> Result<FeeParameters, GetUnsubsidizedMinFeeError>;
 }
 
@@ -255,10 +300,11 @@ impl MinFeeCalculator {
         gas_estimator: Arc<dyn GasPriceEstimating>,
         measurements: Arc<dyn MinFeeStoring>,
         bad_token_detector: Arc<dyn BadTokenDetecting>,
-        fee_subsidy: FeeSubsidyConfiguration,
+        fee_subsidy: Arc<CurrentFeeSubsidy>,
         native_price_estimator: Arc<dyn NativePriceEstimating>,
         cow_subsidy: Arc<dyn CowSubsidy>,
         liquidity_order_owners: HashSet<H160>,
+        app_data: Arc<dyn AppDataStoring>,
     ) -> Self {
         Self {
             price_estimator,
@@ -270,7 +316,27 @@ impl MinFeeCalculator {
             native_price_estimator,
             cow_subsidy,
             liquidity_order_owners,
+            app_data,
+        }
+    }
+
+    /// Loads the app data document of the order if any subsidy rule matches
+    /// on its fields.
+    async fn parsed_app_data(
+        &self,
+        subsidy: &VersionedFeeSubsidy,
+        app_id: AppId,
+    ) -> Result<Option<ParsedAppData>> {
+        if !subsidy
+            .fee_subsidy
+            .rules
+            .iter()
+            .any(SubsidyRule::uses_app_data)
+        {
+            return Ok(None);
         }
+        let app_data = self.app_data.app_data(&app_id).await?;
+        Ok(app_data.map(|app_data| app_data.parsed))
     }
 
     /// Computes unsubsidized min fee.
@@ -297,6 +363,7 @@ impl MinFeeCalculator {
             gas_amount,
             gas_price,
             sell_token_price,
+            ..Default::default()
         };
 
         let fee_in_eth = gas_price * gas_amount;
@@ -319,11 +386,22 @@ impl MinFeeCalculating for MinFeeCalculator {
         app_data: AppId,
         user: H160,
     ) -> Result<Measurement, PriceEstimationError> {
+        self.compute_subsidized_min_fee_with_parameters(fee_data, app_data, user)
+            .await
+            .map(|(measurement, _)| measurement)
+    }
+
+    async fn compute_subsidized_min_fee_with_parameters(
+        &self,
+        fee_data: FeeData,
+        app_data: AppId,
+        user: H160,
+    ) -> Result<(Measurement, FeeParameters), PriceEstimationError> {
         if fee_data.buy_token == fee_data.sell_token {
-            return Ok((U256::zero(), MAX_DATETIME));
+            return Ok(((U256::zero(), MAX_DATETIME), FeeParameters::default()));
         }
         if self.liquidity_order_owners.contains(&user) {
-            return Ok((U256::zero(), MAX_DATETIME));
+            return Ok(((U256::zero(), MAX_DATETIME), FeeParameters::default()));
         }
 
         ensure_token_supported(fee_data.sell_token, self.bad_token_detector.as_ref()).await?;
@@ -335,12 +413,16 @@ impl MinFeeCalculating for MinFeeCalculator {
 
         tracing::debug!(?fee_data, ?app_data, ?user, "computing subsidized fee",);
 
+        let subsidy = self.fee_subsidy.get();
         let cow_factor = async {
             self.cow_subsidy
                 .cow_subsidy_factor(user)
                 .await
                 .map_err(PriceEstimationError::Other)
         };
+        let parsed_app_data = self
+            .parsed_app_data(&subsidy, app_data)
+            .map_err(PriceEstimationError::Other);
         let unsubsidized_min_fee = async {
             if let Some(past_fee) = self
                 .measurements
@@ -365,17 +447,31 @@ impl MinFeeCalculating for MinFeeCalculator {
             }
         };
 
-        let (cow_factor, unsubsidized_min_fee) =
-            futures::future::try_join(cow_factor, unsubsidized_min_fee).await?;
+        let (cow_factor, parsed_app_data, unsubsidized_min_fee) =
+            futures::try_join!(cow_factor, parsed_app_data, unsubsidized_min_fee)?;
 
+        let unsubsidized_min_fee = FeeParameters {
+            subsidy_version: subsidy.version,
+            ..unsubsidized_min_fee
+        };
+        let order = SubsidizedOrder {
+            fee_data,
+            app_id: app_data,
+            app_data: parsed_app_data,
+            owner: user,
+            time: now,
+        };
         let subsidized_min_fee =
-            unsubsidized_min_fee.apply_fee_factor(&self.fee_subsidy, app_data, cow_factor);
+            unsubsidized_min_fee.apply_fee_factor(&subsidy.fee_subsidy, &order, cow_factor);
         tracing::debug!(
             "computed subsidized fee of {:?}",
             (subsidized_min_fee, fee_data.sell_token),
         );
 
-        Ok((subsidized_min_fee, official_valid_until))
+        Ok((
+            (subsidized_min_fee, official_valid_until),
+            unsubsidized_min_fee,
+        ))
     }
 
     async fn get_unsubsidized_min_fee(
@@ -384,16 +480,34 @@ impl MinFeeCalculating for MinFeeCalculator {
         app_data: AppId,
         subsidized_fee: U256,
         user: H160,
+        hook_gas_limit: u64,
     ) -> Result<FeeParameters, GetUnsubsidizedMinFeeError> {
         if self.liquidity_order_owners.contains(&user) {
             return Ok(FeeParameters::default());
         }
 
+        let subsidy = self.fee_subsidy.get();
         let cow_factor = self.cow_subsidy.cow_subsidy_factor(user);
+        let parsed_app_data = self.parsed_app_data(&subsidy, app_data);
         let past_fee = self
             .measurements
             .find_measurement_including_larger_amount(fee_data, (self.now)());
-        let (cow_factor, past_fee) = futures::future::try_join(cow_factor, past_fee).await?;
+        let (cow_factor, parsed_app_data, past_fee) =
+            futures::try_join!(cow_factor, parsed_app_data, past_fee)?;
+        let order = SubsidizedOrder {
+            fee_data,
+            app_id: app_data,
+            app_data: parsed_app_data,
+            owner: user,
+            time: (self.now)(),
+        };
+        // Fee measurements are shared by all orders so they don't include the
+        // gas of the order's hooks.
+        let with_hooks_and_version = |fee: FeeParameters| FeeParameters {
+            gas_amount: fee.gas_amount + hook_gas_limit as f64,
+            subsidy_version: subsidy.version,
+            ..fee
+        };
         // When validating we allow fees taken for larger amounts because as the amount increases
         // the fee increases too because it is worth to trade off more gas use for a slightly better
         // price. Thus it is acceptable if the new order has an amount <= an existing fee
@@ -404,9 +518,9 @@ impl MinFeeCalculating for MinFeeCalculator {
         // Once we have removed the `fee` route and moved all fee requests to the `quote` route we
         // might no longer need this workaround as we will know exactly how the amounts in the order
         // have been picked.
-        if let Some(past_fee) = past_fee {
+        if let Some(past_fee) = past_fee.map(with_hooks_and_version) {
             tracing::debug!("found past fee {:?}", past_fee);
-            if subsidized_fee >= past_fee.apply_fee_factor(&self.fee_subsidy, app_data, cow_factor)
+            if subsidized_fee >= past_fee.apply_fee_factor(&subsidy.fee_subsidy, &order, cow_factor)
             {
                 tracing::debug!("given fee matches past fee");
                 return Ok(past_fee);
@@ -418,9 +532,11 @@ impl MinFeeCalculating for MinFeeCalculator {
         let current_fee = self
             .compute_unsubsidized_min_fee(fee_data)
             .await
+            .map(with_hooks_and_version)
             .map_err(GetUnsubsidizedMinFeeError::PriceEstimationError)?;
         tracing::debug!("estimated new fee {:?}", current_fee);
-        if subsidized_fee >= current_fee.apply_fee_factor(&self.fee_subsidy, app_data, cow_factor) {
+        if subsidized_fee >= current_fee.apply_fee_factor(&subsidy.fee_subsidy, &order, cow_factor)
+        {
             tracing::debug!("given fee matches new fee");
             Ok(current_fee)
         } else {
@@ -508,7 +624,10 @@ mod tests {
     };
     use std::sync::Arc;
 
-    use crate::cow_subsidy::FixedCowSubsidy;
+    use crate::{
+        cow_subsidy::FixedCowSubsidy, database::app_data::MockAppDataStoring,
+        fee_subsidy::VersionedFeeSubsidy,
+    };
 
     use super::*;
 
@@ -538,10 +657,17 @@ mod tests {
                 native_price_estimator: create_default_native_token_estimator(price_estimator),
                 cow_subsidy: Arc::new(FixedCowSubsidy::default()),
                 liquidity_order_owners: Default::default(),
+                app_data: Arc::new(no_app_data()),
             }
         }
     }
 
+    fn no_app_data() -> MockAppDataStoring {
+        let mut app_data = MockAppDataStoring::new();
+        app_data.expect_app_data().returning(|_| Ok(None));
+        app_data
+    }
+
     #[tokio::test]
     async fn accepts_min_fee_if_validated_before_expiry() {
         let gas_price = Arc::new(Mutex::new(EstimatedGasPrice {
@@ -585,7 +711,7 @@ mod tests {
         // fee is valid before expiry
         *time.lock().unwrap() = expiry - Duration::seconds(10);
         assert!(fee_estimator
-            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, Default::default())
+            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, Default::default(), 0)
             .await
             .is_ok());
 
@@ -599,7 +725,8 @@ mod tests {
                 },
                 Default::default(),
                 fee,
-                Default::default()
+                Default::default(),
+                0,
             )
             .await
             .is_err());
@@ -642,7 +769,13 @@ mod tests {
         let lower_fee = fee - 1;
         // slightly lower fee is not valid
         assert!(fee_estimator
-            .get_unsubsidized_min_fee(fee_data, Default::default(), lower_fee, Default::default())
+            .get_unsubsidized_min_fee(
+                fee_data,
+                Default::default(),
+                lower_fee,
+                Default::default(),
+                0
+            )
             .await
             .is_err());
 
@@ -650,7 +783,13 @@ mod tests {
         let new_gas_price = gas_price.lock().unwrap().bump(0.5);
         *gas_price.lock().unwrap() = new_gas_price;
         assert!(fee_estimator
-            .get_unsubsidized_min_fee(fee_data, Default::default(), lower_fee, Default::default())
+            .get_unsubsidized_min_fee(
+                fee_data,
+                Default::default(),
+                lower_fee,
+                Default::default(),
+                0
+            )
             .await
             .is_ok());
     }
@@ -686,6 +825,7 @@ mod tests {
             native_price_estimator,
             cow_subsidy: Arc::new(FixedCowSubsidy::default()),
             liquidity_order_owners: Default::default(),
+            app_data: Arc::new(no_app_data()),
         };
 
         // Selling unsupported token
@@ -757,13 +897,17 @@ mod tests {
             measurements: Arc::new(InMemoryFeeStore::default()),
             now: Box::new(Utc::now),
             bad_token_detector: Arc::new(ListBasedDetector::deny_list(vec![])),
-            fee_subsidy: FeeSubsidyConfiguration {
-                partner_additional_fee_factors: hashmap! { app_data => 0.5 },
+            fee_subsidy: Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+                fee_subsidy: FeeSubsidyConfiguration {
+                    partner_additional_fee_factors: hashmap! { app_data => 0.5 },
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
+            })),
             native_price_estimator,
             cow_subsidy: Arc::new(FixedCowSubsidy(0.5)),
             liquidity_order_owners: Default::default(),
+            app_data: Arc::new(no_app_data()),
         };
         let (fee, _) = fee_estimator
             .compute_subsidized_min_fee(fee_data, app_data, user)
@@ -771,19 +915,19 @@ mod tests {
             .unwrap();
         assert_eq!(
             fee_estimator
-                .get_unsubsidized_min_fee(fee_data, app_data, fee, user)
+                .get_unsubsidized_min_fee(fee_data, app_data, fee, user, 0)
                 .await
                 .unwrap()
                 .amount_in_sell_token(),
             fee * 4
         );
         assert!(fee_estimator
-            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, user)
+            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, user, 0)
             .await
             .is_err());
         let lower_fee = fee - 1;
         assert!(fee_estimator
-            .get_unsubsidized_min_fee(fee_data, app_data, lower_fee, user)
+            .get_unsubsidized_min_fee(fee_data, app_data, lower_fee, user, 0)
             .await
             .is_err());
 
@@ -811,6 +955,7 @@ mod tests {
             gas_amount: 1337.,
             sell_token_price,
             gas_price: gas_estimate,
+            ..Default::default()
         };
 
         let gas_estimator = Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
@@ -859,14 +1004,18 @@ mod tests {
             measurements: Arc::new(measurements),
             now: Box::new(Utc::now),
             bad_token_detector: Arc::new(ListBasedDetector::deny_list(vec![])),
-            fee_subsidy: FeeSubsidyConfiguration {
-                fee_factor: 0.8,
-                partner_additional_fee_factors: hashmap! { app_data => 0.5 },
+            fee_subsidy: Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+                fee_subsidy: FeeSubsidyConfiguration {
+                    fee_factor: 0.8,
+                    partner_additional_fee_factors: hashmap! { app_data => 0.5 },
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
+            })),
             native_price_estimator,
             cow_subsidy: Arc::new(FixedCowSubsidy::default()),
             liquidity_order_owners: Default::default(),
+            app_data: Arc::new(no_app_data()),
         };
 
         let (fee, _) = fee_estimator
@@ -896,6 +1045,7 @@ mod tests {
             gas_amount: 100_000.,
             gas_price: 1_000_000_000.,
             sell_token_price: 1.,
+            ..Default::default()
         };
 
         let fee_configuration = FeeSubsidyConfiguration {
@@ -906,7 +1056,7 @@ mod tests {
         };
 
         assert_eq!(
-            unsubsidized.apply_fee_factor(&fee_configuration, Default::default(), 1.0),
+            unsubsidized.apply_fee_factor(&fee_configuration, &Default::default(), 1.0),
             // Note that the fee factor is applied to the minimum discounted fee!
             500_000.into(),
         );
@@ -918,6 +1068,7 @@ mod tests {
             gas_amount: 100_000.,
             gas_price: 1_000_000_000.,
             sell_token_price: 1.,
+            ..Default::default()
         };
 
         let app_id = AppId([1u8; 32]);
@@ -928,20 +1079,94 @@ mod tests {
             partner_additional_fee_factors: maplit::hashmap! {
                 app_id => 0.1,
             },
+            ..Default::default()
         };
 
         // (100G - 50G) * 0.5
         assert_eq!(
-            unsubsidized.apply_fee_factor(&fee_configuration, Default::default(), 1.0),
+            unsubsidized.apply_fee_factor(&fee_configuration, &Default::default(), 1.0),
             25_000_000_000_000u64.into()
         );
         // Additionally multiply with 0.1 if partner app id is used
         assert_eq!(
-            unsubsidized.apply_fee_factor(&fee_configuration, app_id, 1.0),
+            unsubsidized.apply_fee_factor(
+                &fee_configuration,
+                &SubsidizedOrder {
+                    app_id,
+                    ..Default::default()
+                },
+                1.0
+            ),
             2_500_000_000_000u64.into()
         );
     }
 
+    #[test]
+    fn additional_fee_factor_precedence() {
+        let app_id = AppId([1u8; 32]);
+        let token = H160([2; 20]);
+        let fee_configuration = FeeSubsidyConfiguration {
+            partner_additional_fee_factors: maplit::hashmap! {
+                app_id => 0.1,
+            },
+            rules: vec![
+                SubsidyRule {
+                    buy_token: Some(token),
+                    max_amount: Some(100.into()),
+                    fee_factor: 0.,
+                    ..Default::default()
+                },
+                SubsidyRule {
+                    buy_token: Some(token),
+                    fee_factor: 0.5,
+                    ..Default::default()
+                },
+                SubsidyRule {
+                    app_id: Some(app_id),
+                    fee_factor: 0.2,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        };
+        let order = |buy_token, amount: u64, app_id| SubsidizedOrder {
+            fee_data: FeeData {
+                buy_token,
+                amount: amount.into(),
+                ..Default::default()
+            },
+            app_id,
+            ..Default::default()
+        };
+
+        // The first matching rule wins even if later rules match too.
+        assert_eq!(
+            fee_configuration.additional_fee_factor(&order(token, 100, app_id)),
+            0.
+        );
+        assert_eq!(
+            fee_configuration.additional_fee_factor(&order(token, 101, app_id)),
+            0.5
+        );
+        // Rules take precedence over partner fee factors.
+        assert_eq!(
+            fee_configuration.additional_fee_factor(&order(H160::zero(), 0, app_id)),
+            0.2
+        );
+        assert_eq!(
+            FeeSubsidyConfiguration {
+                rules: Vec::new(),
+                ..fee_configuration.clone()
+            }
+            .additional_fee_factor(&order(H160::zero(), 0, app_id)),
+            0.1
+        );
+        assert_eq!(
+            fee_configuration.additional_fee_factor(&order(H160::zero(), 0, AppId::default())),
+            1.
+        );
+    }
+
     #[test]
     fn fee_rounds_up() {
         let fee_data = FeeData {
@@ -966,13 +1191,17 @@ mod tests {
             measurements: Arc::new(InMemoryFeeStore::default()),
             now: Box::new(Utc::now),
             bad_token_detector: Arc::new(ListBasedDetector::deny_list(vec![])),
-            fee_subsidy: FeeSubsidyConfiguration {
-                fee_factor: 0.5,
+            fee_subsidy: Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+                fee_subsidy: FeeSubsidyConfiguration {
+                    fee_factor: 0.5,
+                    ..Default::default()
+                },
                 ..Default::default()
-            },
+            })),
             native_price_estimator,
             cow_subsidy: Arc::new(FixedCowSubsidy::default()),
             liquidity_order_owners: Default::default(),
+            app_data: Arc::new(no_app_data()),
         };
         let (fee, _) = fee_estimator
             .compute_subsidized_min_fee(fee_data, Default::default(), Default::default())
@@ -983,7 +1212,7 @@ mod tests {
         assert_eq!(fee, 5.into());
         // Fee validates.
         fee_estimator
-            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, Default::default())
+            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, Default::default(), 0)
             .now_or_never()
             .unwrap()
             .unwrap();
@@ -1029,7 +1258,8 @@ mod tests {
                     fee_data,
                     AppId::default(),
                     0.into(),
-                    liquidity_order_owner
+                    liquidity_order_owner,
+                    0,
                 )
                 .now_or_never()
                 .unwrap()
@@ -1037,8 +1267,171 @@ mod tests {
             FeeParameters {
                 gas_amount: 0.,
                 gas_price: 0.,
-                sell_token_price: 1.
+                sell_token_price: 1.,
+                ..Default::default()
             },
         );
     }
+
+    #[test]
+    fn records_subsidy_version() {
+        let fee_subsidy = Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+            version: 3,
+            ..Default::default()
+        }));
+        let fee_estimator = MinFeeCalculator {
+            fee_subsidy: fee_subsidy.clone(),
+            ..MinFeeCalculator::new_for_test(
+                Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
+                    EstimatedGasPrice {
+                        legacy: 1.0,
+                        eip1559: None,
+                    },
+                )))),
+                Arc::new(FakePriceEstimator(price_estimation::Estimate {
+                    out_amount: 1.into(),
+                    gas: 9,
+                })),
+                Box::new(Utc::now),
+            )
+        };
+        let fee_data = FeeData {
+            sell_token: H160([1; 20]),
+            buy_token: H160([2; 20]),
+            ..Default::default()
+        };
+
+        let ((fee, _), parameters) = fee_estimator
+            .compute_subsidized_min_fee_with_parameters(
+                fee_data,
+                Default::default(),
+                Default::default(),
+            )
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(parameters.subsidy_version, 3);
+
+        fee_subsidy
+            .update(VersionedFeeSubsidy {
+                version: 4,
+                fee_subsidy: FeeSubsidyConfiguration {
+                    fee_factor: 0.5,
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .unwrap();
+        let parameters = fee_estimator
+            .get_unsubsidized_min_fee(fee_data, Default::default(), fee, Default::default(), 0)
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(parameters.subsidy_version, 4);
+    }
+
+    #[test]
+    fn subsidy_rules_use_app_data() {
+        let app_id = AppId([1; 32]);
+        let mut app_data = MockAppDataStoring::new();
+        app_data.expect_app_data().with(eq(app_id)).returning(|_| {
+            Ok(Some(model::app_data::AppData {
+                full_app_data: r#"{"version":"0.5.0","appCode":"CowSwap"}"#.to_string(),
+                parsed: ParsedAppData {
+                    app_code: Some("CowSwap".to_string()),
+                    ..Default::default()
+                },
+            }))
+        });
+        app_data
+            .expect_app_data()
+            .with(eq(AppId::default()))
+            .returning(|_| Ok(None));
+        let fee_estimator = MinFeeCalculator {
+            fee_subsidy: Arc::new(CurrentFeeSubsidy::new(VersionedFeeSubsidy {
+                fee_subsidy: FeeSubsidyConfiguration {
+                    rules: vec![SubsidyRule {
+                        app_code: Some("CowSwap".to_string()),
+                        fee_factor: 0.,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                ..Default::default()
+            })),
+            app_data: Arc::new(app_data),
+            ..MinFeeCalculator::new_for_test(
+                Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
+                    EstimatedGasPrice {
+                        legacy: 1.0,
+                        eip1559: None,
+                    },
+                )))),
+                Arc::new(FakePriceEstimator(price_estimation::Estimate {
+                    out_amount: 1.into(),
+                    gas: 9,
+                })),
+                Box::new(Utc::now),
+            )
+        };
+        let fee_data = FeeData {
+            sell_token: H160([1; 20]),
+            buy_token: H160([2; 20]),
+            ..Default::default()
+        };
+
+        let (fee, _) = fee_estimator
+            .compute_subsidized_min_fee(fee_data, app_id, Default::default())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee, U256::zero());
+        let (fee, _) = fee_estimator
+            .compute_subsidized_min_fee(fee_data, Default::default(), Default::default())
+            .now_or_never()
+            .unwrap()
+            .unwrap();
+        assert_eq!(fee, 9.into());
+    }
+
+    #[test]
+    fn fee_covers_hook_gas() {
+        let fee_estimator = MinFeeCalculator::new_for_test(
+            Arc::new(FakeGasPriceEstimator(Arc::new(Mutex::new(
+                EstimatedGasPrice {
+                    legacy: 1.0,
+                    eip1559: None,
+                },
+            )))),
+            Arc::new(FakePriceEstimator(price_estimation::Estimate {
+                out_amount: 1.into(),
+                gas: 9,
+            })),
+            Box::new(Utc::now),
+        );
+        let fee_data = FeeData {
+            sell_token: H160([1; 20]),
+            buy_token: H160([2; 20]),
+            ..Default::default()
+        };
+        let get_unsubsidized_min_fee = |fee: u64| {
+            fee_estimator
+                .get_unsubsidized_min_fee(
+                    fee_data,
+                    Default::default(),
+                    fee.into(),
+                    Default::default(),
+                    11,
+                )
+                .now_or_never()
+                .unwrap()
+        };
+
+        // The trade's gas alone isn't enough.
+        assert!(matches!(
+            get_unsubsidized_min_fee(9),
+            Err(GetUnsubsidizedMinFeeError::InsufficientFee)
+        ));
+        assert_eq!(get_unsubsidized_min_fee(20).unwrap().gas_amount, 20.);
+    }
 }
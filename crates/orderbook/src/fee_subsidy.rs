@@ -0,0 +1,426 @@
+//! Fee subsidies that can be changed at runtime by editing a configuration
+//! file instead of restarting the order book with different arguments.
+
+use crate::{
+    cow_subsidy::SubsidyTiers,
+    fee::{FeeData, FeeSubsidyConfiguration},
+};
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use model::{app_data::ParsedAppData, app_id::AppId, u256_decimal::DecimalU256};
+use primitive_types::{H160, U256};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_with::serde_as;
+use shared::maintenance::Maintaining;
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock},
+};
+
+/// The fee subsidy configuration as it is stored in the configuration file.
+///
+/// ```json
+/// {
+///   "version": 2,
+///   "feeDiscount": 0.0,
+///   "minDiscountedFee": 0.0,
+///   "feeFactor": 0.8,
+///   "partnerAdditionalFeeFactors": { "0x00...00": 0.5 },
+///   "cowFeeFactors": "10:0.75,150:0.5",
+///   "rules": [{ "sellToken": "0x00...00", "maxAmount": "1000", "feeFactor": 0.0 }]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionedFeeSubsidy {
+    /// Identifies the configuration in the fee parameters stored with orders.
+    /// Has to be increased whenever the configuration changes.
+    pub version: u64,
+    #[serde(flatten)]
+    pub fee_subsidy: FeeSubsidyConfiguration,
+    #[serde(default)]
+    pub cow_fee_factors: SubsidyTiers,
+}
+
+/// A fee subsidy that only applies to orders matching all of the specified
+/// criteria. Unspecified criteria match every order.
+///
+/// ```json
+/// {
+///   "buyToken": "0x00...00",
+///   "validUntil": "2022-06-01T00:00:00Z",
+///   "feeFactor": 0.5
+/// }
+/// ```
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SubsidyRule {
+    #[serde(default)]
+    pub sell_token: Option<H160>,
+    #[serde(default)]
+    pub buy_token: Option<H160>,
+    #[serde(default)]
+    pub owner: Option<H160>,
+    #[serde(default)]
+    pub app_id: Option<AppId>,
+    /// The app code of the order's app data document.
+    #[serde(default)]
+    pub app_code: Option<String>,
+    /// The referrer of the order's app data document.
+    #[serde(default)]
+    pub referrer: Option<H160>,
+    /// Inclusive start of the time window in which the rule applies.
+    #[serde(default)]
+    pub valid_from: Option<DateTime<Utc>>,
+    /// Exclusive end of the time window in which the rule applies.
+    #[serde(default)]
+    pub valid_until: Option<DateTime<Utc>>,
+    /// Inclusive lower bound of the order amount. Like in `FeeData` this is
+    /// the sell amount for sell orders and the buy amount for buy orders so
+    /// it is usually combined with the corresponding token.
+    #[serde(default)]
+    #[serde_as(as = "Option<DecimalU256>")]
+    pub min_amount: Option<U256>,
+    /// Inclusive upper bound of the order amount.
+    #[serde(default)]
+    #[serde_as(as = "Option<DecimalU256>")]
+    pub max_amount: Option<U256>,
+    /// The factor the fee of matching orders gets multiplied with. A factor
+    /// of 0 makes the fee free. Has to be in the range [0, 1].
+    #[serde(deserialize_with = "deserialize_fee_factor")]
+    pub fee_factor: f64,
+}
+
+fn deserialize_fee_factor<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let factor = f64::deserialize(deserializer)?;
+    if !(0. ..=1.).contains(&factor) {
+        return Err(de::Error::custom(format!(
+            "fee factor {} is not in the range [0, 1]",
+            factor
+        )));
+    }
+    Ok(factor)
+}
+
+impl Default for SubsidyRule {
+    fn default() -> Self {
+        Self {
+            sell_token: None,
+            buy_token: None,
+            owner: None,
+            app_id: None,
+            app_code: None,
+            referrer: None,
+            valid_from: None,
+            valid_until: None,
+            min_amount: None,
+            max_amount: None,
+            fee_factor: 1.,
+        }
+    }
+}
+
+impl SubsidyRule {
+    /// Whether the rule matches on fields of the app data document which
+    /// first have to be loaded.
+    pub fn uses_app_data(&self) -> bool {
+        self.app_code.is_some() || self.referrer.is_some()
+    }
+
+    pub fn matches(&self, order: &SubsidizedOrder) -> bool {
+        fn criterion_matches<T: PartialEq>(criterion: &Option<T>, value: &T) -> bool {
+            criterion
+                .as_ref()
+                .map_or(true, |criterion| criterion == value)
+        }
+
+        let no_app_data = ParsedAppData::default();
+        let app_data = order.app_data.as_ref().unwrap_or(&no_app_data);
+
+        criterion_matches(&self.sell_token, &order.fee_data.sell_token)
+            && criterion_matches(&self.buy_token, &order.fee_data.buy_token)
+            && criterion_matches(&self.owner, &order.owner)
+            && criterion_matches(&self.app_id, &order.app_id)
+            && (self.app_code.is_none() || self.app_code == app_data.app_code)
+            && (self.referrer.is_none() || self.referrer == app_data.referrer)
+            && self.valid_from.map_or(true, |from| from <= order.time)
+            && self.valid_until.map_or(true, |until| order.time < until)
+            && self
+                .min_amount
+                .map_or(true, |min| min <= order.fee_data.amount)
+            && self
+                .max_amount
+                .map_or(true, |max| order.fee_data.amount <= max)
+    }
+}
+
+/// Everything about an order that subsidy rules can match on.
+#[derive(Clone, Debug, Default)]
+pub struct SubsidizedOrder {
+    pub fee_data: FeeData,
+    pub app_id: AppId,
+    /// The app data document of the order if it is known and any rule uses
+    /// it.
+    pub app_data: Option<ParsedAppData>,
+    pub owner: H160,
+    /// The time at which the fee is computed or validated.
+    pub time: DateTime<Utc>,
+}
+
+/// The fee subsidy configuration that is currently in effect.
+pub struct CurrentFeeSubsidy(RwLock<Arc<VersionedFeeSubsidy>>);
+
+impl CurrentFeeSubsidy {
+    pub fn new(config: VersionedFeeSubsidy) -> Self {
+        Self(RwLock::new(Arc::new(config)))
+    }
+
+    pub fn get(&self) -> Arc<VersionedFeeSubsidy> {
+        self.0.read().unwrap().clone()
+    }
+
+    /// Replaces the configuration if it changed. Returns whether it did.
+    ///
+    /// Changed configurations must have a higher version so that a version
+    /// always refers to the same configuration.
+    pub fn update(&self, config: VersionedFeeSubsidy) -> Result<bool> {
+        let mut current = self.0.write().unwrap();
+        if **current == config {
+            return Ok(false);
+        }
+        anyhow::ensure!(
+            config.version > current.version,
+            "changed fee subsidy configuration must increase version {}",
+            current.version
+        );
+        *current = Arc::new(config);
+        Ok(true)
+    }
+}
+
+impl Default for CurrentFeeSubsidy {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+pub fn read_fee_subsidy_file(path: &Path) -> Result<VersionedFeeSubsidy> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("failed to read fee subsidy file {:?}", path))?;
+    serde_json::from_slice(&content)
+        .with_context(|| format!("failed to parse fee subsidy file {:?}", path))
+}
+
+/// Reloads the fee subsidy configuration from its file on every maintenance
+/// run.
+pub struct FeeSubsidyFileWatcher {
+    path: PathBuf,
+    current: Arc<CurrentFeeSubsidy>,
+}
+
+impl FeeSubsidyFileWatcher {
+    pub fn new(path: PathBuf, current: Arc<CurrentFeeSubsidy>) -> Self {
+        Self { path, current }
+    }
+}
+
+#[async_trait::async_trait]
+impl Maintaining for FeeSubsidyFileWatcher {
+    async fn run_maintenance(&self) -> Result<()> {
+        let config = read_fee_subsidy_file(&self.path)?;
+        let version = config.version;
+        if self.current.update(config)? {
+            tracing::info!(%version, "updated fee subsidy configuration");
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use maplit::hashmap;
+    use serde_json::json;
+
+    #[test]
+    fn deserializes_config() {
+        let config: VersionedFeeSubsidy = serde_json::from_value(json!({
+            "version": 2,
+            "feeFactor": 0.8,
+            "partnerAdditionalFeeFactors": {
+                "0x0101010101010101010101010101010101010101010101010101010101010101": 0.5,
+            },
+            "cowFeeFactors": "10:0.75,150:0.5",
+            "rules": [
+                {
+                    "buyToken": "0x0202020202020202020202020202020202020202",
+                    "validUntil": "2022-06-01T00:00:00Z",
+                    "maxAmount": "1000",
+                    "feeFactor": 0.5,
+                },
+            ],
+        }))
+        .unwrap();
+        assert_eq!(
+            config,
+            VersionedFeeSubsidy {
+                version: 2,
+                fee_subsidy: FeeSubsidyConfiguration {
+                    fee_factor: 0.8,
+                    partner_additional_fee_factors: hashmap! { AppId([1; 32]) => 0.5 },
+                    rules: vec![SubsidyRule {
+                        buy_token: Some(H160([2; 20])),
+                        valid_until: Some(Utc.ymd(2022, 6, 1).and_hms(0, 0, 0)),
+                        max_amount: Some(1000.into()),
+                        fee_factor: 0.5,
+                        ..Default::default()
+                    }],
+                    ..Default::default()
+                },
+                cow_fee_factors: "10:0.75,150:0.5".parse().unwrap(),
+            }
+        );
+        let serialized = serde_json::to_value(&config).unwrap();
+        assert_eq!(
+            serde_json::from_value::<VersionedFeeSubsidy>(serialized).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn rejects_rule_fee_factor_out_of_range() {
+        for fee_factor in [-0.1, 1.1] {
+            assert!(serde_json::from_value::<SubsidyRule>(json!({
+                "feeFactor": fee_factor,
+            }))
+            .is_err());
+        }
+        for fee_factor in [0., 1.] {
+            assert!(serde_json::from_value::<SubsidyRule>(json!({
+                "feeFactor": fee_factor,
+            }))
+            .is_ok());
+        }
+    }
+
+    #[test]
+    fn changed_config_requires_new_version() {
+        let current = CurrentFeeSubsidy::default();
+        let config = VersionedFeeSubsidy {
+            version: 1,
+            fee_subsidy: FeeSubsidyConfiguration {
+                fee_factor: 0.5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert!(current.update(config.clone()).unwrap());
+        assert!(!current.update(config.clone()).unwrap());
+
+        let changed = VersionedFeeSubsidy {
+            fee_subsidy: Default::default(),
+            ..config.clone()
+        };
+        assert!(current.update(changed.clone()).is_err());
+        assert_eq!(*current.get(), config);
+
+        assert!(current
+            .update(VersionedFeeSubsidy {
+                version: 2,
+                ..changed
+            })
+            .unwrap());
+        assert_eq!(current.get().version, 2);
+    }
+
+    #[test]
+    fn rule_matches_all_criteria() {
+        let time = Utc.ymd(2022, 5, 1).and_hms(0, 0, 0);
+        let order = SubsidizedOrder {
+            fee_data: FeeData {
+                sell_token: H160([1; 20]),
+                buy_token: H160([2; 20]),
+                amount: 100.into(),
+                ..Default::default()
+            },
+            app_id: AppId([3; 32]),
+            app_data: Some(ParsedAppData {
+                app_code: Some("CowSwap".to_string()),
+                referrer: Some(H160([5; 20])),
+                slippage_bips: None,
+            }),
+            owner: H160([4; 20]),
+            time,
+        };
+
+        assert!(SubsidyRule::default().matches(&order));
+        let matching = SubsidyRule {
+            sell_token: Some(H160([1; 20])),
+            buy_token: Some(H160([2; 20])),
+            owner: Some(H160([4; 20])),
+            app_id: Some(AppId([3; 32])),
+            app_code: Some("CowSwap".to_string()),
+            referrer: Some(H160([5; 20])),
+            valid_from: Some(time),
+            valid_until: Some(time + chrono::Duration::seconds(1)),
+            min_amount: Some(100.into()),
+            max_amount: Some(100.into()),
+            fee_factor: 1.,
+        };
+        assert!(matching.matches(&order));
+
+        for rule in [
+            SubsidyRule {
+                sell_token: Some(H160([2; 20])),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                buy_token: Some(H160([1; 20])),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                owner: Some(H160([5; 20])),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                app_id: Some(AppId([5; 32])),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                app_code: Some("Other".to_string()),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                referrer: Some(H160([6; 20])),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                valid_from: Some(time + chrono::Duration::seconds(1)),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                valid_until: Some(time),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                min_amount: Some(101.into()),
+                ..matching.clone()
+            },
+            SubsidyRule {
+                max_amount: Some(99.into()),
+                ..matching.clone()
+            },
+        ] {
+            assert!(!rule.matches(&order), "{:?}", rule);
+        }
+        assert!(!matching.matches(&SubsidizedOrder {
+            app_data: None,
+            ..order
+        }));
+    }
+}
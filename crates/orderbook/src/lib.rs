@@ -1,36 +1,40 @@
 pub mod account_balances;
 pub mod api;
+pub mod chain_config;
 pub mod conversions;
 pub mod cow_subsidy;
 pub mod database;
+pub mod ethflow_updater;
 pub mod event_updater;
 pub mod fee;
+pub mod fee_subsidy;
 pub mod gas_price;
+pub mod market_depth;
 pub mod metrics;
+pub mod order_status_updates;
 pub mod orderbook;
+pub mod rate_limiter;
+pub mod settlement_costs;
+pub mod signature_validator;
 pub mod solvable_orders;
-pub mod solver_competition;
 
-use crate::{api::post_quote::OrderQuoter, orderbook::Orderbook};
+use crate::{api::ChainApi, rate_limiter::IpRateLimiter};
 use anyhow::{anyhow, Context as _, Result};
 use contracts::GPv2Settlement;
-use database::trades::TradeRetrieving;
 use futures::Future;
 use model::DomainSeparator;
-use solver_competition::SolverCompetition;
 use std::{net::SocketAddr, sync::Arc};
 use tokio::{task, task::JoinHandle};
 use warp::Filter;
 
 pub fn serve_api(
-    database: Arc<dyn TradeRetrieving>,
-    orderbook: Arc<Orderbook>,
-    quoter: Arc<OrderQuoter>,
+    chains: Vec<ChainApi>,
     address: SocketAddr,
     shutdown_receiver: impl Future<Output = ()> + Send + 'static,
-    solver_competition: Arc<SolverCompetition>,
+    ip_rate_limiter: Option<Arc<IpRateLimiter>>,
+    max_stream_connections: usize,
 ) -> JoinHandle<()> {
-    let filter = api::handle_all_routes(database, orderbook, quoter, solver_competition).boxed();
+    let filter = api::handle_all_routes(chains, ip_rate_limiter, max_stream_connections).boxed();
     tracing::info!(%address, "serving order book");
     let (_, server) = warp::serve(filter).bind_with_graceful_shutdown(address, shutdown_receiver);
     task::spawn(server)
@@ -1,9 +1,6 @@
 use anyhow::{anyhow, Context, Result};
 use clap::{ArgEnum, Parser};
-use contracts::{
-    BalancerV2Vault, CowProtocolToken, CowProtocolVirtualToken, GPv2Settlement, IUniswapV3Factory,
-    WETH9,
-};
+use contracts::{CoWSwapEthFlow, CowProtocolToken, CowProtocolVirtualToken, IUniswapV3Factory};
 use ethcontract::errors::DeployError;
 use model::{
     app_id::AppId,
@@ -11,18 +8,31 @@ use model::{
     DomainSeparator,
 };
 use orderbook::{
-    account_balances::Web3BalanceFetcher,
-    api::{order_validation::OrderValidator, post_quote::OrderQuoter},
+    account_balances::{event_cache::EventBalanceCache, Web3BalanceFetcher},
+    api::{
+        handle_admin_routes,
+        order_validation::{HookValidation, OrderValidator},
+        post_quote::OrderQuoter,
+        ChainApi,
+    },
+    chain_config::{read_chain_configs, ChainConfig},
     cow_subsidy::{CowSubsidy, CowSubsidyImpl, FixedCowSubsidy, SubsidyTiers},
     database::{self, orders::OrderFilter, Postgres},
+    ethflow_updater::EthflowUpdater,
     event_updater::EventUpdater,
     fee::{FeeSubsidyConfiguration, MinFeeCalculator},
+    fee_subsidy::{
+        read_fee_subsidy_file, CurrentFeeSubsidy, FeeSubsidyFileWatcher, VersionedFeeSubsidy,
+    },
     gas_price::InstrumentedGasEstimator,
     metrics::Metrics,
-    orderbook::Orderbook,
+    order_status_updates::OrderStatusUpdates,
+    orderbook::{Orderbook, OwnerLimits},
+    rate_limiter::{IpRateLimiter, RateLimit, RateLimiter},
     serve_api,
+    settlement_costs::SettlementCostUpdater,
+    signature_validator::Web3SignatureValidator,
     solvable_orders::SolvableOrdersCache,
-    solver_competition::SolverCompetition,
     verify_deployed_contract_constants,
 };
 use primitive_types::{H160, U256};
@@ -37,10 +47,13 @@ use shared::{
         },
     },
     baseline_solver::BaseTokens,
-    current_block::current_block_stream,
+    chain_spec::{self, read_chain_spec},
+    current_block::{current_block_stream, CurrentBlockStream},
     http_solver::{DefaultHttpSolverApi, SolverConfig},
     maintenance::ServiceMaintenance,
-    metrics::{serve_metrics, setup_metrics_registry, DEFAULT_METRICS_PORT},
+    metrics::{
+        serve_metrics_with_routes, setup_metrics_registry, LivenessChecking, DEFAULT_METRICS_PORT,
+    },
     network::network_name,
     oneinch_api::OneInchClientImpl,
     paraswap_api::DefaultParaswapApi,
@@ -69,7 +82,10 @@ use shared::{
     transport::{create_instrumented_transport, http::HttpTransport},
     zeroex_api::DefaultZeroExApi,
 };
-use std::{collections::HashMap, net::SocketAddr, num::NonZeroUsize, sync::Arc, time::Duration};
+use std::{
+    collections::HashMap, net::SocketAddr, num::NonZeroUsize, path::PathBuf, sync::Arc,
+    time::Duration,
+};
 use tokio::task;
 use url::Url;
 
@@ -85,6 +101,18 @@ struct Arguments {
     #[clap(long, env, default_value = "postgresql://")]
     db_url: Url,
 
+    /// Path to a json file listing the chains to serve from this process. Each chain has its own
+    /// node, database, base tokens, price estimation amount, ETH-flow contract, chain spec and
+    /// token and user lists and all other arguments are shared. The api of a chain is served under
+    /// the `/<chainId>` prefix and the first chain additionally without a prefix.
+    ///
+    /// When set, it replaces `--node-url`, `--db-url`, `--base-tokens`,
+    /// `--amount-to-estimate-prices-with`, `--ethflow-contract`, `--ethflow-indexing-start`,
+    /// `--chain-spec`, `--allowed-tokens`, `--unsupported-tokens`, `--banned-users`,
+    /// `--liquidity-order-owners` and `--hook-targets`.
+    #[clap(long, env)]
+    chains_config: Option<PathBuf>,
+
     /// Skip syncing past events (useful for local deployments)
     #[clap(long)]
     skip_event_sync: bool,
@@ -98,6 +126,24 @@ struct Arguments {
     )]
     min_order_validity_period: Duration,
 
+    /// The maximum amount of time in seconds an order can be valid for. Unlimited by default. This
+    /// restriction does not apply to liquidity owner orders or limit orders.
+    #[clap(
+        long,
+        env,
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    max_order_validity_period: Option<Duration>,
+
+    /// The maximum amount of time in seconds a limit order can be valid for. Defaults to 1 year.
+    #[clap(
+        long,
+        env,
+        default_value = "31536000",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    max_limit_order_validity_period: Duration,
+
     /// Don't use the trace_callMany api that only some nodes support to check whether a token
     /// should be denied.
     /// Note that if a node does not support the api we still use the less accurate call api.
@@ -126,6 +172,12 @@ struct Arguments {
     #[clap(long, env, use_value_delimiter = true)]
     allowed_tokens: Vec<H160>,
 
+    /// List of token addresses whose balances can change without emitting a `Transfer` event
+    /// (for example rebasing tokens). Their balances are refetched on every block instead of
+    /// being cached until an event touches them.
+    #[clap(long, env, use_value_delimiter = true)]
+    non_event_balance_tokens: Vec<H160>,
+
     /// The number of pairs that are automatically updated in the pool cache.
     #[clap(long, env, default_value = "200")]
     pool_cache_lru_size: usize,
@@ -137,6 +189,51 @@ struct Arguments {
     #[clap(long, env, parse(try_from_str), default_value = "false")]
     enable_presign_orders: bool,
 
+    /// Limits the API requests per client IP address. Takes the form `<requests>/<seconds>`, for
+    /// example `600/60` allows bursts of 600 requests and 10 requests per second on average.
+    #[clap(long, env)]
+    ip_rate_limit: Option<RateLimit>,
+
+    /// Identify clients by the last address of the `X-Forwarded-For` header for IP rate
+    /// limiting. Only enable this when the API is exclusively reachable through a reverse proxy
+    /// that sets the header.
+    #[clap(long, env, parse(try_from_str), default_value = "false")]
+    trust_forwarded_for_header: bool,
+
+    /// The maximum number of concurrently open event streams. Further requests to open a stream
+    /// are rejected until one of them is closed.
+    #[clap(long, env, default_value = "1000")]
+    max_stream_connections: usize,
+
+    /// Limits order creations and replacements per owner. Takes the form `<requests>/<seconds>`.
+    /// Liquidity orders are not limited.
+    #[clap(long, env)]
+    owner_order_rate_limit: Option<RateLimit>,
+
+    /// The maximum number of open orders an owner can have. Liquidity orders are not limited.
+    #[clap(long, env)]
+    max_open_orders_per_owner: Option<u64>,
+
+    /// Address of the ETH-flow contract. When set, orders that users place by sending native
+    /// ETH to the contract are indexed and added to the orderbook.
+    #[clap(long, env)]
+    ethflow_contract: Option<H160>,
+
+    /// The block from which to start indexing ETH-flow events, usually the deployment block of
+    /// the contract.
+    #[clap(long, env, default_value = "0")]
+    ethflow_indexing_start: u64,
+
+    /// Contracts that order hooks are allowed to call. Hooks are executed by the settlement
+    /// contract so this should only contain contracts that can't make use of its privileges, like
+    /// a trampoline that forwards the calls. Orders with hooks are rejected when this is empty.
+    #[clap(long, env, use_value_delimiter = true)]
+    hook_targets: Vec<H160>,
+
+    /// The maximum sum of the gas limits of the hooks of an order.
+    #[clap(long, env, default_value = "1000000")]
+    max_hook_gas_limit: u64,
+
     /// If solvable orders haven't been successfully update in this time in seconds attempting
     /// to get them errors and our liveness check fails.
     #[clap(
@@ -146,6 +243,17 @@ struct Arguments {
     )]
     solvable_orders_max_update_age: Duration,
 
+    /// How long the solver keeps submitting the winning settlement of an auction. Orders that are
+    /// part of a winning settlement that could still be submitted can't be replaced. Should match
+    /// the solver's `--max-submission-seconds`.
+    #[clap(
+        long,
+        env,
+        default_value = "120",
+        parse(try_from_str = shared::arguments::duration_from_seconds),
+    )]
+    max_submission_seconds: Duration,
+
     /// A flat fee discount denominated in the network's native token (i.e. Ether for Mainnet).
     ///
     /// Note that flat fee discounts are applied BEFORE any multiplicative factors from either
@@ -191,6 +299,15 @@ struct Arguments {
     #[clap(long, env)]
     cow_fee_factors: Option<SubsidyTiers>,
 
+    /// Path to a json file containing the versioned fee subsidy configuration. The file is
+    /// reloaded on every new block so subsidies can be changed without restarting the order book.
+    ///
+    /// When set, it replaces `--fee-discount`, `--min-discounted-fee`, `--fee-factor`,
+    /// `--partner-additional-fee-factors` and `--cow-fee-factors`. Otherwise those arguments are
+    /// used as version 0 of the configuration.
+    #[clap(long, env)]
+    fee_subsidy_config: Option<PathBuf>,
+
     /// The API endpoint to call the mip v2 solver for price estimation
     #[clap(long, env)]
     quasimodo_solver_url: Option<Url>,
@@ -283,22 +400,143 @@ async fn main() {
     tracing::info!("running order book with validated {:#?}", args);
 
     setup_metrics_registry(Some("gp_v2_api".into()), None);
-    let metrics = Arc::new(Metrics::new().unwrap());
+    let metrics = Metrics::new().unwrap();
 
     let client = shared::http_client(args.shared.http_timeout);
 
-    let transport = create_instrumented_transport(
-        HttpTransport::new(client.clone(), args.shared.node_url.clone(), "".to_string()),
-        metrics.clone(),
+    let chain_configs = match &args.chains_config {
+        Some(path) => read_chain_configs(path).expect("failed to load chains configuration"),
+        None => vec![ChainConfig {
+            chain_id: None,
+            node_url: args.shared.node_url.clone(),
+            db_url: args.db_url.clone(),
+            base_tokens: args.shared.base_tokens.clone(),
+            chain_spec: args.shared.chain_spec.clone(),
+            amount_to_estimate_prices_with: args.amount_to_estimate_prices_with,
+            ethflow_contract: args.ethflow_contract,
+            ethflow_indexing_start: args.ethflow_indexing_start,
+            allowed_tokens: args.allowed_tokens.clone(),
+            unsupported_tokens: args.unsupported_tokens.clone(),
+            banned_users: args.banned_users.clone(),
+            liquidity_order_owners: args.liquidity_order_owners.clone(),
+            hook_targets: args.hook_targets.clone(),
+        }],
+    };
+    let mut chains = Vec::new();
+    for config in chain_configs {
+        let chain = setup_chain(&args, config, &client, &metrics).await;
+        assert!(
+            chains
+                .iter()
+                .all(|other: &Chain| other.api.chain_id != chain.api.chain_id),
+            "chain {} configured twice",
+            chain.api.chain_id
+        );
+        chains.push(chain);
+    }
+
+    let liveness = Arc::new(AllChainsAlive(
+        chains
+            .iter()
+            .map(|chain| chain.api.orderbook.clone())
+            .collect(),
+    ));
+    let mut chain_apis = Vec::new();
+    let mut fee_subsidies = Vec::new();
+    let mut maintenance_tasks = Vec::new();
+    let mut db_metrics_tasks = Vec::new();
+    for chain in chains {
+        fee_subsidies.push((chain.api.chain_id, chain.fee_subsidy));
+        chain_apis.push(chain.api);
+        db_metrics_tasks.push(task::spawn(database_metrics(chain.metrics, chain.postgres)));
+        maintenance_tasks.push(task::spawn(
+            chain
+                .maintenance
+                .run_maintenance_on_new_block(chain.current_block_stream),
+        ));
+    }
+    let maintenance_task = futures::future::select_all(maintenance_tasks);
+    let db_metrics_task = futures::future::select_all(db_metrics_tasks);
+
+    let (shutdown_sender, shutdown_receiver) = tokio::sync::oneshot::channel();
+    let serve_api = serve_api(
+        chain_apis,
+        args.bind_address,
+        async {
+            let _ = shutdown_receiver.await;
+        },
+        args.ip_rate_limit
+            .map(|limit| Arc::new(IpRateLimiter::new(limit, args.trust_forwarded_for_header))),
+        args.max_stream_connections,
+    );
+
+    let mut metrics_address = args.bind_address;
+    metrics_address.set_port(DEFAULT_METRICS_PORT);
+    tracing::info!(%metrics_address, "serving metrics");
+    let metrics_task = serve_metrics_with_routes(
+        liveness,
+        metrics_address,
+        handle_admin_routes(fee_subsidies),
     );
+
+    futures::pin_mut!(serve_api);
+    tokio::select! {
+        result = &mut serve_api => tracing::error!(?result, "API task exited"),
+        (result, ..) = maintenance_task => tracing::error!(?result, "maintenance task exited"),
+        (result, ..) = db_metrics_task => tracing::error!(?result, "database metrics task exited"),
+        result = metrics_task => tracing::error!(?result, "metrics task exited"),
+        _ = shutdown_signal() => {
+            tracing::info!("Gracefully shutting down API");
+            shutdown_sender.send(()).expect("failed to send shutdown signal");
+            match tokio::time::timeout(Duration::from_secs(10), serve_api).await {
+                Ok(inner) => inner.expect("API failed during shutdown"),
+                Err(_) => tracing::error!("API shutdown exceeded timeout"),
+            }
+        }
+    };
+}
+
+/// The services of a single chain.
+struct Chain {
+    api: ChainApi,
+    /// Served on the metrics port instead of the public API.
+    fee_subsidy: Arc<CurrentFeeSubsidy>,
+    postgres: Postgres,
+    metrics: Arc<Metrics>,
+    maintenance: ServiceMaintenance,
+    current_block_stream: CurrentBlockStream,
+}
+
+/// Sets up everything needed to serve the order book of one chain.
+async fn setup_chain(
+    args: &Arguments,
+    chain: ChainConfig,
+    client: &reqwest::Client,
+    metrics: &Metrics,
+) -> Chain {
+    let http_transport = HttpTransport::new(client.clone(), chain.node_url.clone(), "".to_string());
+    // The chain id is needed to label the metrics of the chain so it is read
+    // before the transport is instrumented.
+    let chain_id = web3::Web3::new(http_transport.clone())
+        .eth()
+        .chain_id()
+        .await
+        .expect("Could not get chainId")
+        .as_u64();
+    let metrics = Arc::new(metrics.for_chain(chain_id));
+    let transport = create_instrumented_transport(http_transport, metrics.clone());
     let web3 = web3::Web3::new(transport);
+    let chain_spec = chain
+        .chain_spec
+        .as_deref()
+        .map(|path| read_chain_spec(path).expect("failed to load chain spec"));
     let current_block = web3
         .eth()
         .block_number()
         .await
         .expect("block_number")
         .as_u64();
-    let settlement_contract = GPv2Settlement::deployed(&web3)
+    let settlement_contract = chain_spec::settlement_contract(&web3, chain_spec.as_ref())
         .await
         .expect("Couldn't load deployed settlement");
     let vault_relayer = settlement_contract
@@ -306,41 +544,44 @@ async fn main() {
         .call()
         .await
         .expect("Couldn't get vault relayer address");
-    let native_token = WETH9::deployed(&web3)
+    let native_token = chain_spec::native_token(&web3, chain_spec.as_ref())
         .await
         .expect("couldn't load deployed native token");
-    let chain_id = web3
-        .eth()
-        .chain_id()
-        .await
-        .expect("Could not get chainId")
-        .as_u64();
+    if let Some(expected) = chain.chain_id {
+        assert_eq!(chain_id, expected, "node is connected to the wrong chain");
+    }
+    if let Some(spec) = &chain_spec {
+        spec.verify_chain_id(chain_id)
+            .expect("chain spec doesn't match the node");
+    }
     let network = web3
         .net()
         .version()
         .await
         .expect("Failed to retrieve network version ID");
-    let network_name = network_name(&network, chain_id);
+    let network_name = chain_spec
+        .as_ref()
+        .and_then(|spec| spec.network_name.clone())
+        .unwrap_or_else(|| network_name(&network, chain_id).to_string());
 
-    let native_token_price_estimation_amount = args
+    let native_token_price_estimation_amount = chain
         .amount_to_estimate_prices_with
+        .or_else(|| chain_spec.as_ref()?.amount_to_estimate_prices_with)
         .or_else(|| default_amount_to_estimate_prices_with(&network))
         .expect("No amount to estimate prices with set.");
 
-    let vault = match BalancerV2Vault::deployed(&web3).await {
-        Ok(contract) => Some(contract),
-        Err(DeployError::NotFound(_)) => {
-            tracing::warn!("balancer contracts are not deployed on this network");
-            None
-        }
-        Err(err) => panic!("failed to get balancer vault contract: {}", err),
-    };
+    let vault = chain_spec::vault(&web3, chain_spec.as_ref())
+        .await
+        .expect("failed to get balancer vault contract");
+    if vault.is_none() {
+        tracing::warn!("balancer contracts are not deployed on this network");
+    }
 
     verify_deployed_contract_constants(&settlement_contract, chain_id)
         .await
         .expect("Deployed contract constants don't match the ones in this binary");
     let domain_separator = DomainSeparator::new(chain_id, settlement_contract.address());
-    let postgres = Postgres::new(args.db_url.as_str()).expect("failed to create database");
+    let postgres = Postgres::new(chain.db_url.as_str()).expect("failed to create database");
     let database = Arc::new(database::instrumented::Instrumented::new(
         postgres.clone(),
         metrics.clone(),
@@ -356,9 +597,11 @@ async fn main() {
         None
     };
 
+    let status_updates = Arc::new(OrderStatusUpdates::default());
     let event_updater = Arc::new(EventUpdater::new(
         settlement_contract.clone(),
         database.as_ref().clone(),
+        status_updates.clone(),
         sync_start,
     ));
     let balance_fetcher = Arc::new(Web3BalanceFetcher::new(
@@ -380,26 +623,37 @@ async fn main() {
         metrics.clone(),
     ));
 
-    let baseline_sources = args.shared.baseline_sources.unwrap_or_else(|| {
-        sources::defaults_for_chain(chain_id).expect("failed to get default baseline sources")
-    });
+    let baseline_sources = args
+        .shared
+        .baseline_sources
+        .clone()
+        .or_else(|| chain_spec.as_ref()?.baseline_sources())
+        .unwrap_or_else(|| {
+            sources::defaults_for_chain(chain_id).expect("failed to get default baseline sources")
+        });
     tracing::info!(?baseline_sources, "using baseline sources");
     let (pair_providers, pool_fetchers): (Vec<_>, Vec<_>) =
         sources::uniswap_like_liquidity_sources(&web3, &baseline_sources)
             .await
             .expect("failed to load baseline source pair providers")
-            .values()
-            .cloned()
+            .into_values()
+            .chain(
+                chain_spec
+                    .iter()
+                    .flat_map(|spec| spec.uniswap_like_sources.values())
+                    .map(|source| source.liquidity_source(&web3)),
+            )
             .unzip();
 
-    let base_tokens = Arc::new(BaseTokens::new(
-        native_token.address(),
-        &args.shared.base_tokens,
-    ));
-    let mut allowed_tokens = args.allowed_tokens.clone();
+    let mut base_tokens = chain.base_tokens.clone();
+    if let Some(spec) = &chain_spec {
+        base_tokens.extend(spec.base_tokens.iter().copied());
+    }
+    let base_tokens = Arc::new(BaseTokens::new(native_token.address(), &base_tokens));
+    let mut allowed_tokens = chain.allowed_tokens.clone();
     allowed_tokens.extend(base_tokens.tokens().iter().copied());
     allowed_tokens.push(BUY_ETH_ADDRESS);
-    let unsupported_tokens = args.unsupported_tokens.clone();
+    let unsupported_tokens = chain.unsupported_tokens.clone();
 
     let mut finders: Vec<Arc<dyn TokenOwnerFinding>> = pair_providers
         .into_iter()
@@ -634,14 +888,26 @@ async fn main() {
         (Some(token), Some(vtoken)) => Some((token, vtoken)),
         _ => panic!("should either have both cow token contracts or none"),
     };
+    let fee_subsidy = Arc::new(CurrentFeeSubsidy::new(match &args.fee_subsidy_config {
+        Some(path) => {
+            read_fee_subsidy_file(path).expect("failed to load fee subsidy configuration")
+        }
+        None => VersionedFeeSubsidy {
+            version: 0,
+            fee_subsidy: FeeSubsidyConfiguration {
+                fee_discount: args.fee_discount,
+                min_discounted_fee: args.min_discounted_fee,
+                fee_factor: args.fee_factor,
+                partner_additional_fee_factors: args.partner_additional_fee_factors.clone(),
+                ..Default::default()
+            },
+            cow_fee_factors: args.cow_fee_factors.clone().unwrap_or_default(),
+        },
+    }));
     let cow_subsidy = match cow_tokens {
         Some((token, vtoken)) => {
             tracing::debug!("using cow token contracts for subsidy");
-            Arc::new(CowSubsidyImpl::new(
-                token,
-                vtoken,
-                args.cow_fee_factors.unwrap_or_default(),
-            )) as Arc<dyn CowSubsidy>
+            Arc::new(CowSubsidyImpl::new(token, vtoken, fee_subsidy.clone())) as Arc<dyn CowSubsidy>
         }
         None => {
             tracing::debug!("disabling cow subsidy because contracts not found on network");
@@ -655,45 +921,66 @@ async fn main() {
             gas_price_estimator.clone(),
             database.clone(),
             bad_token_detector.clone(),
-            FeeSubsidyConfiguration {
-                fee_discount: args.fee_discount,
-                min_discounted_fee: args.min_discounted_fee,
-                fee_factor: args.fee_factor,
-                partner_additional_fee_factors: args.partner_additional_fee_factors.clone(),
-            },
+            fee_subsidy.clone(),
             native_price_estimator.clone(),
             cow_subsidy.clone(),
-            args.liquidity_order_owners.iter().copied().collect(),
+            chain.liquidity_order_owners.iter().copied().collect(),
+            database.clone(),
         ))
     };
     let fee_calculator = create_fee_calculator(price_estimator.clone());
     let fast_fee_calculator = create_fee_calculator(fast_price_estimator.clone());
 
+    let signature_validator = Arc::new(Web3SignatureValidator::new(web3.clone()));
+    let cached_balance_fetcher = Arc::new(EventBalanceCache::new(
+        balance_fetcher.clone(),
+        web3.clone(),
+        vault.as_ref().map(|vault| vault.address()),
+        vault_relayer,
+        args.non_event_balance_tokens.iter().copied().collect(),
+        current_block_stream.clone(),
+    ));
     let solvable_orders_cache = SolvableOrdersCache::new(
         args.min_order_validity_period,
         database.clone(),
-        args.banned_users.iter().copied().collect(),
-        balance_fetcher.clone(),
+        database.clone(),
+        chain.banned_users.iter().copied().collect(),
+        cached_balance_fetcher,
         bad_token_detector.clone(),
+        signature_validator.clone(),
         current_block_stream.clone(),
         native_price_estimator,
+        gas_price_estimator.clone(),
         metrics.clone(),
+        status_updates.clone(),
+        database.clone(),
     );
     let block = current_block_stream.borrow().number.unwrap().as_u64();
     solvable_orders_cache
         .update(block)
         .await
         .expect("failed to perform initial solvable orders update");
-    let order_validator = Arc::new(OrderValidator::new(
-        Box::new(web3.clone()),
-        native_token.clone(),
-        args.banned_users.iter().copied().collect(),
-        args.liquidity_order_owners.iter().copied().collect(),
-        args.min_order_validity_period,
-        fee_calculator.clone(),
-        bad_token_detector.clone(),
-        balance_fetcher,
-    ));
+    let order_validator = Arc::new(
+        OrderValidator::new(
+            Box::new(web3.clone()),
+            native_token.clone(),
+            chain.banned_users.iter().copied().collect(),
+            chain.liquidity_order_owners.iter().copied().collect(),
+            args.min_order_validity_period,
+            args.max_order_validity_period.unwrap_or(Duration::MAX),
+            args.max_limit_order_validity_period,
+            fee_calculator.clone(),
+            bad_token_detector.clone(),
+            balance_fetcher,
+            signature_validator,
+            database.clone(),
+        )
+        .with_hooks(HookValidation {
+            app_data: database.clone(),
+            allowed_targets: chain.hook_targets.iter().copied().collect(),
+            max_gas_limit: args.max_hook_gas_limit,
+        }),
+    );
     let orderbook = Arc::new(Orderbook::new(
         domain_separator,
         settlement_contract.address(),
@@ -702,12 +989,22 @@ async fn main() {
         args.enable_presign_orders,
         solvable_orders_cache.clone(),
         args.solvable_orders_max_update_age,
+        chrono::Duration::from_std(args.max_submission_seconds)
+            .expect("max submission seconds out of range"),
         order_validator.clone(),
+        status_updates.clone(),
+        OwnerLimits {
+            rate_limiter: args.owner_order_rate_limit.map(RateLimiter::new),
+            max_open_orders: args.max_open_orders_per_owner,
+        },
     ));
+    let settlement_cost_updater =
+        Arc::new(SettlementCostUpdater::new(web3.clone(), database.clone()));
     let mut service_maintainer = ServiceMaintenance {
         maintainers: vec![
             database.clone(),
             event_updater,
+            settlement_cost_updater,
             pool_fetcher,
             solvable_orders_cache,
         ],
@@ -715,47 +1012,68 @@ async fn main() {
     if let Some(balancer) = balancer_pool_fetcher {
         service_maintainer.maintainers.push(balancer);
     }
+    if let Some(ethflow_contract) = chain.ethflow_contract {
+        service_maintainer
+            .maintainers
+            .push(Arc::new(EthflowUpdater::new(
+                CoWSwapEthFlow::at(&web3, ethflow_contract),
+                database.as_ref().clone(),
+                domain_separator,
+                settlement_contract.address(),
+                status_updates,
+                chain.ethflow_indexing_start,
+            )));
+    }
+    if let Some(path) = &args.fee_subsidy_config {
+        service_maintainer
+            .maintainers
+            .push(Arc::new(FeeSubsidyFileWatcher::new(
+                path.clone(),
+                fee_subsidy.clone(),
+            )));
+    }
     check_database_connection(orderbook.as_ref()).await;
     let quoter = Arc::new(
-        OrderQuoter::new(fee_calculator, price_estimator, order_validator)
-            .with_fast_quotes(fast_fee_calculator, fast_price_estimator),
+        OrderQuoter::new(
+            fee_calculator,
+            price_estimator,
+            order_validator,
+            database.clone(),
+        )
+        .with_fast_quotes(fast_fee_calculator, fast_price_estimator),
     );
-    let (shutdown_sender, shutdown_receiver) = tokio::sync::oneshot::channel();
-    let solver_competition = Arc::new(SolverCompetition::default());
-    let serve_api = serve_api(
-        database.clone(),
-        orderbook.clone(),
-        quoter,
-        args.bind_address,
-        async {
-            let _ = shutdown_receiver.await;
+
+    Chain {
+        api: ChainApi {
+            chain_id,
+            database: database.clone(),
+            orderbook,
+            quoter,
+            solver_competition: database.clone(),
+            order_events: database.clone(),
+            app_data: database,
         },
-        solver_competition,
-    );
-    let maintenance_task =
-        task::spawn(service_maintainer.run_maintenance_on_new_block(current_block_stream));
-    let db_metrics_task = task::spawn(database_metrics(metrics, postgres));
+        fee_subsidy,
+        postgres,
+        metrics,
+        maintenance: service_maintainer,
+        current_block_stream,
+    }
+}
 
-    let mut metrics_address = args.bind_address;
-    metrics_address.set_port(DEFAULT_METRICS_PORT);
-    tracing::info!(%metrics_address, "serving metrics");
-    let metrics_task = serve_metrics(orderbook, metrics_address);
+/// The order book is alive as long as it is alive on every chain.
+struct AllChainsAlive(Vec<Arc<Orderbook>>);
 
-    futures::pin_mut!(serve_api);
-    tokio::select! {
-        result = &mut serve_api => tracing::error!(?result, "API task exited"),
-        result = maintenance_task => tracing::error!(?result, "maintenance task exited"),
-        result = db_metrics_task => tracing::error!(?result, "database metrics task exited"),
-        result = metrics_task => tracing::error!(?result, "metrics task exited"),
-        _ = shutdown_signal() => {
-            tracing::info!("Gracefully shutting down API");
-            shutdown_sender.send(()).expect("failed to send shutdown signal");
-            match tokio::time::timeout(Duration::from_secs(10), serve_api).await {
-                Ok(inner) => inner.expect("API failed during shutdown"),
-                Err(_) => tracing::error!("API shutdown exceeded timeout"),
+#[async_trait::async_trait]
+impl LivenessChecking for AllChainsAlive {
+    async fn is_alive(&self) -> bool {
+        for orderbook in &self.0 {
+            if !orderbook.is_alive().await {
+                return false;
             }
         }
-    };
+        true
+    }
 }
 
 #[cfg(unix)]
@@ -0,0 +1,225 @@
+//! Aggregates the solvable user orders of a token pair into an order book
+//! that frontends can display.
+
+use model::order::Order;
+use primitive_types::{H160, U256};
+use serde::Serialize;
+
+/// Prices are rounded to this many significant digits so that orders with
+/// almost the same limit price end up in the same level.
+const PRICE_SIGNIFICANT_DIGITS: i32 = 5;
+
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarketDepth {
+    /// Orders buying the base token, sorted from the highest price.
+    pub bids: Vec<PriceLevel>,
+    /// Orders selling the base token, sorted from the lowest price.
+    pub asks: Vec<PriceLevel>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceLevel {
+    /// The limit price in quote token atoms per base token atom.
+    pub price: f64,
+    /// The remaining base token amount of all orders at this price.
+    #[serde(with = "model::u256_decimal")]
+    pub volume: U256,
+    pub orders: usize,
+}
+
+/// Computes the depth of the `base_token`-`quote_token` market. Liquidity
+/// orders are not included because they are only used by solvers to fill user
+/// orders. The remaining amount of an order is limited by the sell token
+/// balance `balance` returns for it. Orders of the same owner that sell the
+/// same token are each limited by the full balance.
+pub fn market_depth(
+    orders: &[Order],
+    base_token: H160,
+    quote_token: H160,
+    balance: impl Fn(&Order) -> Option<U256>,
+) -> MarketDepth {
+    let mut bids = Vec::new();
+    let mut asks = Vec::new();
+    for order in orders {
+        if order.metadata.is_liquidity_order {
+            continue;
+        }
+        let creation = &order.creation;
+        let is_ask = match (creation.sell_token, creation.buy_token) {
+            (sell, buy) if sell == base_token && buy == quote_token => true,
+            (sell, buy) if sell == quote_token && buy == base_token => false,
+            _ => continue,
+        };
+        let remaining = match order.remaining_amounts() {
+            Ok(remaining) if !remaining.sell_amount.is_zero() => remaining,
+            _ => continue,
+        };
+        let sell_amount = match balance(order) {
+            Some(balance) => remaining.sell_amount.min(balance),
+            None => remaining.sell_amount,
+        };
+        let (limit_sell, limit_buy) = (
+            creation.sell_amount.to_f64_lossy(),
+            creation.buy_amount.to_f64_lossy(),
+        );
+        if is_ask {
+            asks.push((limit_buy / limit_sell, sell_amount));
+        } else {
+            let buy_amount = match remaining
+                .buy_amount
+                .checked_mul(sell_amount)
+                .and_then(|product| product.checked_div(remaining.sell_amount))
+            {
+                Some(buy_amount) => buy_amount,
+                None => continue,
+            };
+            bids.push((limit_sell / limit_buy, buy_amount));
+        }
+    }
+
+    let mut depth = MarketDepth {
+        bids: price_levels(bids),
+        asks: price_levels(asks),
+    };
+    depth.bids.reverse();
+    depth
+}
+
+/// Aggregates the orders into levels sorted by ascending price.
+fn price_levels(orders: Vec<(f64, U256)>) -> Vec<PriceLevel> {
+    let mut orders = orders
+        .into_iter()
+        .filter(|(price, volume)| price.is_finite() && !volume.is_zero())
+        .map(|(price, volume)| (round_to_significant_digits(price), volume))
+        .collect::<Vec<_>>();
+    orders.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+    let mut levels: Vec<PriceLevel> = Vec::new();
+    for (price, volume) in orders {
+        match levels.last_mut() {
+            Some(level) if level.price == price => {
+                level.volume = level.volume.saturating_add(volume);
+                level.orders += 1;
+            }
+            _ => levels.push(PriceLevel {
+                price,
+                volume,
+                orders: 1,
+            }),
+        }
+    }
+    levels
+}
+
+fn round_to_significant_digits(value: f64) -> f64 {
+    if value == 0. {
+        return value;
+    }
+    let exponent = PRICE_SIGNIFICANT_DIGITS - 1 - value.abs().log10().floor() as i32;
+    // Only multiply and divide by whole powers of ten so that the rounded
+    // value is as close as possible to its decimal representation.
+    if exponent >= 0 {
+        let factor = 10f64.powi(exponent);
+        (value * factor).round() / factor
+    } else {
+        let factor = 10f64.powi(-exponent);
+        (value / factor).round() * factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::order::{OrderBuilder, OrderKind};
+
+    fn order(sell_token: u8, buy_token: u8, sell_amount: u64, buy_amount: u64) -> Order {
+        OrderBuilder::default()
+            .with_sell_token(H160([sell_token; 20]))
+            .with_buy_token(H160([buy_token; 20]))
+            .with_sell_amount(sell_amount.into())
+            .with_buy_amount(buy_amount.into())
+            .with_kind(OrderKind::Sell)
+            .build()
+    }
+
+    #[test]
+    fn aggregates_orders_by_price() {
+        let mut liquidity_order = order(1, 2, 100, 200);
+        liquidity_order.metadata.is_liquidity_order = true;
+        let mut partially_filled = order(1, 2, 100, 300);
+        partially_filled.creation.partially_fillable = true;
+        partially_filled.metadata.executed_sell_amount_before_fees = 40.into();
+        let orders = [
+            // Asks
+            order(1, 2, 100, 200),
+            order(1, 2, 1_000_000, 2_000_001),
+            partially_filled,
+            liquidity_order,
+            // Bids
+            order(2, 1, 100, 50),
+            order(2, 1, 100, 25),
+            // Other market
+            order(1, 3, 100, 100),
+        ];
+
+        assert_eq!(
+            market_depth(&orders, H160([1; 20]), H160([2; 20]), |_| None),
+            MarketDepth {
+                bids: vec![
+                    PriceLevel {
+                        price: 4.,
+                        volume: 25.into(),
+                        orders: 1,
+                    },
+                    PriceLevel {
+                        price: 2.,
+                        volume: 50.into(),
+                        orders: 1,
+                    },
+                ],
+                asks: vec![
+                    PriceLevel {
+                        price: 2.,
+                        volume: 1_000_100.into(),
+                        orders: 2,
+                    },
+                    PriceLevel {
+                        price: 3.,
+                        volume: 60.into(),
+                        orders: 1,
+                    },
+                ],
+            }
+        );
+    }
+
+    #[test]
+    fn limits_volume_by_balance() {
+        let orders = [order(1, 2, 100, 200), order(2, 1, 100, 50)];
+
+        assert_eq!(
+            market_depth(&orders, H160([1; 20]), H160([2; 20]), |_| Some(50.into())),
+            MarketDepth {
+                bids: vec![PriceLevel {
+                    price: 2.,
+                    volume: 25.into(),
+                    orders: 1,
+                }],
+                asks: vec![PriceLevel {
+                    price: 2.,
+                    volume: 50.into(),
+                    orders: 1,
+                }],
+            }
+        );
+    }
+
+    #[test]
+    fn rounds_prices() {
+        assert_eq!(round_to_significant_digits(0.), 0.);
+        assert_eq!(round_to_significant_digits(123_456_789.), 123_460_000.);
+        assert_eq!(round_to_significant_digits(0.000_123_456), 0.000_123_46);
+    }
+}
@@ -1,8 +1,7 @@
 use anyhow::Result;
 use gas_estimation::EstimatedGasPrice;
 use prometheus::{
-    Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
-    IntGaugeVec, Opts,
+    GaugeVec, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts,
 };
 use shared::{
     metrics::get_metrics_registry,
@@ -14,7 +13,10 @@ use shared::{
 };
 use std::time::Duration;
 
+#[derive(Clone)]
 pub struct Metrics {
+    /// The chain that chain specific metrics are labelled with.
+    chain: String,
     db_table_row_count: IntGaugeVec,
     /// Outgoing RPC request metrics
     rpc_requests: HistogramVec,
@@ -22,14 +24,14 @@ pub struct Metrics {
     pool_cache_misses: IntCounter,
     database_queries: HistogramVec,
     /// Gas estimate metrics
-    gas_price: Gauge,
+    gas_price: GaugeVec,
     price_estimates: IntCounterVec,
     native_price_cache: IntCounterVec,
     price_estimation_times: HistogramVec,
     // auction metrics
     auction_creations: IntCounter,
-    auction_solvable_orders: IntGauge,
-    auction_filtered_orders: IntGauge,
+    auction_solvable_orders: IntGaugeVec,
+    auction_filtered_orders: IntGaugeVec,
     auction_errored_price_estimates: IntCounter,
     auction_price_estimate_timeouts: IntCounter,
 }
@@ -40,15 +42,15 @@ impl Metrics {
 
         let db_table_row_count = IntGaugeVec::new(
             Opts::new("table_rows", "Number of rows in db tables."),
-            &["table"],
+            &["chain", "table"],
         )?;
         registry.register(Box::new(db_table_row_count.clone()))?;
 
         let opts = HistogramOpts::new(
             "transport_requests",
-            "RPC Request durations labelled by method",
+            "RPC Request durations labelled by chain and method",
         );
-        let rpc_requests = HistogramVec::new(opts, &["method"]).unwrap();
+        let rpc_requests = HistogramVec::new(opts, &["chain", "method"]).unwrap();
         registry.register(Box::new(rpc_requests.clone()))?;
 
         let pool_cache_hits = IntCounter::new(
@@ -66,11 +68,11 @@ impl Metrics {
             "database_queries",
             "Sql queries to our postgresql database.",
         );
-        let database_queries = HistogramVec::new(opts, &["type"]).unwrap();
+        let database_queries = HistogramVec::new(opts, &["chain", "type"]).unwrap();
         registry.register(Box::new(database_queries.clone()))?;
 
         let opts = Opts::new("gas_price", "Gas price estimate over time.");
-        let gas_price = Gauge::with_opts(opts).unwrap();
+        let gas_price = GaugeVec::new(opts, &["chain"]).unwrap();
         registry.register(Box::new(gas_price.clone()))?;
 
         let price_estimates = IntCounterVec::new(
@@ -98,15 +100,21 @@ impl Metrics {
         )?;
         registry.register(Box::new(auction_creations.clone()))?;
 
-        let auction_solvable_orders = IntGauge::new(
-            "auction_solvable_orders",
-            "Number of orders that are in the current auction.",
+        let auction_solvable_orders = IntGaugeVec::new(
+            Opts::new(
+                "auction_solvable_orders",
+                "Number of orders that are in the current auction.",
+            ),
+            &["chain"],
         )?;
         registry.register(Box::new(auction_solvable_orders.clone()))?;
 
-        let auction_filtered_orders = IntGauge::new(
-            "auction_filtered_orders",
-            "Number of orders that have been filtered out in the current auction.",
+        let auction_filtered_orders = IntGaugeVec::new(
+            Opts::new(
+                "auction_filtered_orders",
+                "Number of orders that have been filtered out in the current auction.",
+            ),
+            &["chain"],
         )?;
         registry.register(Box::new(auction_filtered_orders.clone()))?;
 
@@ -123,6 +131,7 @@ impl Metrics {
         registry.register(Box::new(auction_price_estimate_timeouts.clone()))?;
 
         Ok(Self {
+            chain: Default::default(),
             db_table_row_count,
             rpc_requests,
             pool_cache_hits,
@@ -140,9 +149,18 @@ impl Metrics {
         })
     }
 
+    /// Returns the same metrics labelling chain specific metrics with the
+    /// chain.
+    pub fn for_chain(&self, chain_id: u64) -> Self {
+        Self {
+            chain: chain_id.to_string(),
+            ..self.clone()
+        }
+    }
+
     pub fn set_table_row_count(&self, table: &str, count: i64) {
         self.db_table_row_count
-            .with_label_values(&[table])
+            .with_label_values(&[&self.chain, table])
             .set(count);
     }
 }
@@ -150,7 +168,7 @@ impl Metrics {
 impl TransportMetrics for Metrics {
     fn report_query(&self, label: &str, elapsed: Duration) {
         self.rpc_requests
-            .with_label_values(&[label])
+            .with_label_values(&[&self.chain, label])
             .observe(elapsed.as_secs_f64())
     }
 }
@@ -171,11 +189,15 @@ impl crate::solvable_orders::AuctionMetrics for Metrics {
         timeout: bool,
     ) {
         self.auction_creations.inc();
-        self.auction_solvable_orders.set(solvable_orders as i64);
+        self.auction_solvable_orders
+            .with_label_values(&[&self.chain])
+            .set(solvable_orders as i64);
         if timeout {
             self.auction_price_estimate_timeouts.inc();
         }
-        self.auction_filtered_orders.set(filtered_orders as i64);
+        self.auction_filtered_orders
+            .with_label_values(&[&self.chain])
+            .set(filtered_orders as i64);
         self.auction_errored_price_estimates
             .inc_by(errored_estimates);
     }
@@ -183,13 +205,16 @@ impl crate::solvable_orders::AuctionMetrics for Metrics {
 
 impl crate::database::instrumented::Metrics for Metrics {
     fn database_query_histogram(&self, label: &str) -> Histogram {
-        self.database_queries.with_label_values(&[label])
+        self.database_queries
+            .with_label_values(&[&self.chain, label])
     }
 }
 
 impl crate::gas_price::Metrics for Metrics {
     fn gas_price(&self, estimate: EstimatedGasPrice) {
-        self.gas_price.set(estimate.effective_gas_price() / 1e9);
+        self.gas_price
+            .with_label_values(&[&self.chain])
+            .set(estimate.effective_gas_price() / 1e9);
     }
 }
 
@@ -0,0 +1,220 @@
+use futures::{stream, Stream};
+use model::order::{OrderStatusUpdate, OrderStatusUpdateKind, OrderUid};
+use primitive_types::H160;
+use shared::event_handling::EventIndex;
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// How many updates are buffered for subscribers that are slower than the
+/// publishers. Subscribers that fall further behind skip the oldest updates.
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// The orders a subscriber wants to receive status updates for.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Subscription {
+    Order(OrderUid),
+    Owner(H160),
+}
+
+impl Subscription {
+    fn matches(&self, update: &OrderStatusUpdate) -> bool {
+        match self {
+            Subscription::Order(uid) => update.uid == *uid,
+            Subscription::Owner(owner) => update.onchain_user.unwrap_or(update.owner) == *owner,
+        }
+    }
+}
+
+/// Fans out order status transitions to all subscribed API clients.
+///
+/// Updates are not persisted. Clients only receive the transitions that happen
+/// while they are subscribed.
+pub struct OrderStatusUpdates {
+    sender: broadcast::Sender<OrderStatusUpdate>,
+}
+
+impl Default for OrderStatusUpdates {
+    fn default() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self { sender }
+    }
+}
+
+impl OrderStatusUpdates {
+    /// Orders placed through an on-chain order contract are owned by the
+    /// contract, so their updates go to the subscribers of `onchain_user`
+    /// instead.
+    pub fn publish(&self, uid: OrderUid, onchain_user: Option<H160>, kind: OrderStatusUpdateKind) {
+        tracing::debug!(%uid, ?onchain_user, ?kind, "order status update");
+        // Sending only fails if nobody is subscribed in which case there is
+        // nobody to miss the update.
+        let _ = self.sender.send(OrderStatusUpdate {
+            uid,
+            owner: uid.owner(),
+            onchain_user,
+            kind,
+        });
+    }
+
+    pub fn subscribe(
+        &self,
+        subscription: Subscription,
+    ) -> impl Stream<Item = OrderStatusUpdate> + Send + 'static {
+        stream::unfold(self.sender.subscribe(), move |mut receiver| async move {
+            loop {
+                match receiver.recv().await {
+                    Ok(update) if subscription.matches(&update) => return Some((update, receiver)),
+                    Ok(_) => (),
+                    Err(RecvError::Lagged(skipped)) => {
+                        tracing::warn!(skipped, "order status subscriber lagged behind")
+                    }
+                    Err(RecvError::Closed) => return None,
+                }
+            }
+        })
+    }
+}
+
+/// The events whose status updates were published for the blocks that can
+/// still be reorged. The event handlers replace these blocks on every update so
+/// without this they would publish the same updates again and again.
+///
+/// Nothing is remembered across restarts so the updates of the most recent
+/// blocks are published once more after a restart.
+pub struct PublishedEvents<E> {
+    published: BTreeMap<EventIndex, E>,
+    /// The published events of the blocks that are being replaced.
+    replaced: BTreeMap<EventIndex, E>,
+}
+
+impl<E> Default for PublishedEvents<E> {
+    fn default() -> Self {
+        Self {
+            published: Default::default(),
+            replaced: Default::default(),
+        }
+    }
+}
+
+impl<E: Copy + PartialEq> PublishedEvents<E> {
+    /// Starts replacing the blocks from `from_block` on. Older blocks are never
+    /// replaced again so their events are forgotten.
+    pub fn replace(&mut self, from_block: u64) {
+        self.replaced = self.published.split_off(&EventIndex::new(from_block, 0));
+        self.published.clear();
+    }
+
+    /// Remembers the stored events and returns the ones that weren't published
+    /// before the blocks were replaced.
+    pub fn unpublished(&mut self, events: impl IntoIterator<Item = (EventIndex, E)>) -> Vec<E> {
+        events
+            .into_iter()
+            .filter(|(index, event)| {
+                self.published.insert(*index, *event);
+                self.replaced.get(index) != Some(event)
+            })
+            .map(|(_, event)| event)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{FutureExt, StreamExt};
+
+    fn uid(owner: u8, id: u8) -> OrderUid {
+        let mut uid = OrderUid::default();
+        uid.0[0] = id;
+        uid.0[32..52].copy_from_slice(&[owner; 20]);
+        uid
+    }
+
+    #[test]
+    fn subscribers_only_receive_matching_updates() {
+        let updates = OrderStatusUpdates::default();
+        let mut by_owner = Box::pin(updates.subscribe(Subscription::Owner(H160([1; 20]))));
+        let mut by_order = Box::pin(updates.subscribe(Subscription::Order(uid(2, 1))));
+
+        updates.publish(uid(1, 0), None, OrderStatusUpdateKind::Created);
+        updates.publish(uid(2, 1), None, OrderStatusUpdateKind::PartiallyFilled);
+        updates.publish(uid(1, 1), None, OrderStatusUpdateKind::Cancelled);
+        updates.publish(uid(2, 1), None, OrderStatusUpdateKind::Fulfilled);
+
+        assert_eq!(
+            by_owner.next().now_or_never(),
+            Some(Some(OrderStatusUpdate {
+                uid: uid(1, 0),
+                owner: H160([1; 20]),
+                onchain_user: None,
+                kind: OrderStatusUpdateKind::Created,
+            }))
+        );
+        assert_eq!(
+            by_owner.next().now_or_never().unwrap().unwrap().kind,
+            OrderStatusUpdateKind::Cancelled
+        );
+        assert_eq!(by_owner.next().now_or_never(), None);
+
+        assert_eq!(
+            by_order.next().now_or_never().unwrap().unwrap().kind,
+            OrderStatusUpdateKind::PartiallyFilled
+        );
+        assert_eq!(
+            by_order.next().now_or_never().unwrap().unwrap().kind,
+            OrderStatusUpdateKind::Fulfilled
+        );
+        assert_eq!(by_order.next().now_or_never(), None);
+    }
+
+    #[test]
+    fn onchain_orders_are_published_to_the_user() {
+        let updates = OrderStatusUpdates::default();
+        let mut by_contract = Box::pin(updates.subscribe(Subscription::Owner(H160([1; 20]))));
+        let mut by_user = Box::pin(updates.subscribe(Subscription::Owner(H160([2; 20]))));
+        let mut by_order = Box::pin(updates.subscribe(Subscription::Order(uid(1, 0))));
+
+        updates.publish(
+            uid(1, 0),
+            Some(H160([2; 20])),
+            OrderStatusUpdateKind::Created,
+        );
+
+        let update = OrderStatusUpdate {
+            uid: uid(1, 0),
+            owner: H160([1; 20]),
+            onchain_user: Some(H160([2; 20])),
+            kind: OrderStatusUpdateKind::Created,
+        };
+        assert_eq!(by_user.next().now_or_never(), Some(Some(update)));
+        assert_eq!(by_order.next().now_or_never(), Some(Some(update)));
+        assert_eq!(by_contract.next().now_or_never(), None);
+    }
+
+    #[test]
+    fn replaced_events_are_published_once() {
+        let mut published = PublishedEvents::default();
+        let index = EventIndex::new;
+        assert_eq!(
+            published.unpublished([(index(1, 0), 'a'), (index(2, 0), 'b')]),
+            ['a', 'b']
+        );
+
+        // Replacing the blocks with the same events publishes nothing new.
+        published.replace(2);
+        assert!(published.unpublished([(index(2, 0), 'b')]).is_empty());
+        published.replace(2);
+        assert!(published.unpublished([(index(2, 0), 'b')]).is_empty());
+
+        // Events that moved or changed because of a reorg are published.
+        published.replace(2);
+        assert_eq!(
+            published.unpublished([(index(2, 0), 'c'), (index(3, 1), 'b')]),
+            ['c', 'b']
+        );
+        // Events appended after the first replaced chunk are checked as well.
+        published.replace(2);
+        assert!(published.unpublished([(index(2, 0), 'c')]).is_empty());
+        assert!(published.unpublished([(index(3, 1), 'b')]).is_empty());
+    }
+}
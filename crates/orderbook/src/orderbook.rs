@@ -1,20 +1,34 @@
 use crate::{
     api::order_validation::{OrderValidating, OrderValidator, ValidationError},
-    database::orders::{InsertionError, OrderFilter, OrderStoring},
+    database::orders::{
+        InsertionError, LimitedInsertionError, OrderFilter, OrderStoring, ReplacementError,
+    },
+    fee::FeeParameters,
+    market_depth::{market_depth, MarketDepth},
+    order_status_updates::{OrderStatusUpdates, Subscription},
+    rate_limiter::RateLimiter,
     solvable_orders::{SolvableOrders, SolvableOrdersCache},
 };
 use anyhow::{ensure, Context, Result};
 use chrono::Utc;
 use ethcontract::H256;
+use futures::Stream;
 use model::{
-    auction::Auction,
-    order::{Order, OrderCancellation, OrderCreationPayload, OrderStatus, OrderUid},
-    signature::SigningScheme,
+    auction::{Auction, AuctionUpdate},
+    order::{
+        Order, OrderCancellation, OrderCreationPayload, OrderStatus, OrderStatusUpdate,
+        OrderStatusUpdateKind, OrderUid, SignedOrderCancellations,
+    },
+    signature::{Signature, SigningScheme},
     DomainSeparator,
 };
 use primitive_types::H160;
 use shared::{bad_token::BadTokenDetecting, metrics, metrics::LivenessChecking};
-use std::{collections::HashSet, sync::Arc, time::Duration};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
 use thiserror::Error;
 
 #[derive(prometheus_metric_storage::MetricStorage, Clone, Debug)]
@@ -22,6 +36,10 @@ use thiserror::Error;
 struct Metrics {
     /// Number of user (non-liquidity) orders created.
     user_orders_created: prometheus::Counter,
+
+    /// Number of orders rejected because of per owner limits.
+    #[metric(labels("limit"))]
+    owner_limit_rejections: prometheus::CounterVec,
 }
 
 #[derive(Debug, Error)]
@@ -32,8 +50,14 @@ pub enum AddOrderError {
     OrderValidation(ValidationError),
     #[error("unsupported signature kind")]
     UnsupportedSignature,
+    #[error("owner created too many orders in a short time")]
+    RateLimited,
+    #[error("owner has too many open orders")]
+    TooManyOpenOrders { max: u64 },
     #[error("database error: {0}")]
     Database(#[from] sqlx::Error),
+    #[error(transparent)]
+    Other(anyhow::Error),
 }
 
 impl From<InsertionError> for AddOrderError {
@@ -45,6 +69,17 @@ impl From<InsertionError> for AddOrderError {
     }
 }
 
+impl From<LimitedInsertionError> for AddOrderError {
+    fn from(err: LimitedInsertionError) -> Self {
+        match err {
+            LimitedInsertionError::TooManyOpenOrders { max } => {
+                AddOrderError::TooManyOpenOrders { max }
+            }
+            LimitedInsertionError::Insertion(err) => err.into(),
+        }
+    }
+}
+
 // This requires a manual implementation because the `#[from]` attribute from
 // `thiserror` implies `#[source]` which requires `ValidationError: Error`,
 // which it currently does not!
@@ -54,6 +89,9 @@ impl From<ValidationError> for AddOrderError {
     }
 }
 
+/// The maximum number of orders that can be cancelled with one signature.
+pub const MAX_CANCELLATIONS: usize = 1024;
+
 #[derive(Debug, Error)]
 pub enum OrderCancellationError {
     #[error("invalid signature")]
@@ -70,10 +108,46 @@ pub enum OrderCancellationError {
     OrderExpired,
     #[error("on-chain orders cannot be cancelled with off-chain signature")]
     OnChainOrder,
+    #[error("too many orders in one cancellation")]
+    TooManyOrders { max: usize },
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+#[derive(Debug, Error)]
+pub enum ReplaceOrderError {
+    #[error(transparent)]
+    AddOrder(#[from] AddOrderError),
+    #[error(transparent)]
+    OldOrder(#[from] OrderCancellationError),
+    #[error("old order has already been traded")]
+    OldOrderTraded,
+    #[error("old order is part of a settlement that is being submitted")]
+    OldOrderInFlight,
+}
+
+impl From<ReplacementError> for ReplaceOrderError {
+    fn from(err: ReplacementError) -> Self {
+        match err {
+            ReplacementError::OldOrderNotFound => OrderCancellationError::OrderNotFound.into(),
+            ReplacementError::OldOrderCancelled => OrderCancellationError::AlreadyCancelled.into(),
+            ReplacementError::OldOrderTraded => Self::OldOrderTraded,
+            ReplacementError::OldOrderInFlight => Self::OldOrderInFlight,
+            ReplacementError::Insertion(err) => AddOrderError::from(err).into(),
+        }
+    }
+}
+
+/// Limits on the orders that a single (non-liquidity) owner can place.
+#[derive(Default)]
+pub struct OwnerLimits {
+    /// Limits the rate of order creations and replacements.
+    pub rate_limiter: Option<RateLimiter<H160>>,
+    /// Maximum number of open orders. Replacing an order doesn't count
+    /// towards the limit.
+    pub max_open_orders: Option<u64>,
+}
+
 pub struct Orderbook {
     domain_separator: DomainSeparator,
     settlement_contract: H160,
@@ -82,7 +156,10 @@ pub struct Orderbook {
     enable_presign_orders: bool,
     solvable_orders: Arc<SolvableOrdersCache>,
     solvable_orders_max_update_age: Duration,
+    max_submission_time: chrono::Duration,
     order_validator: Arc<OrderValidator>,
+    status_updates: Arc<OrderStatusUpdates>,
+    owner_limits: OwnerLimits,
 }
 
 impl Orderbook {
@@ -95,7 +172,10 @@ impl Orderbook {
         enable_presign_orders: bool,
         solvable_orders: Arc<SolvableOrdersCache>,
         solvable_orders_max_update_age: Duration,
+        max_submission_time: chrono::Duration,
         order_validator: Arc<OrderValidator>,
+        status_updates: Arc<OrderStatusUpdates>,
+        owner_limits: OwnerLimits,
     ) -> Self {
         Self {
             domain_separator,
@@ -105,7 +185,10 @@ impl Orderbook {
             enable_presign_orders,
             solvable_orders,
             solvable_orders_max_update_age,
+            max_submission_time,
             order_validator,
+            status_updates,
+            owner_limits,
         }
     }
 
@@ -113,6 +196,63 @@ impl Orderbook {
         &self,
         payload: OrderCreationPayload,
     ) -> Result<OrderUid, AddOrderError> {
+        self.enforce_rate_limit(&payload)?;
+        let (order, fee) = self.validate_order(payload).await?;
+        match self.owner_limits.max_open_orders {
+            Some(max) if !order.metadata.is_liquidity_order => {
+                let result = self
+                    .database
+                    .insert_order_with_limit(&order, fee, max, shared::time::now_in_epoch_seconds())
+                    .await;
+                if let Err(LimitedInsertionError::TooManyOpenOrders { .. }) = result {
+                    owner_limit_rejected("open_orders");
+                }
+                result?;
+            }
+            _ => self.database.insert_order(&order, fee).await?,
+        }
+        self.order_added(&order);
+        Ok(order.metadata.uid)
+    }
+
+    /// Atomically cancels an open order and creates a new order of the same
+    /// owner in its place.
+    pub async fn replace_order(
+        &self,
+        old_order: OrderUid,
+        payload: OrderCreationPayload,
+    ) -> Result<OrderUid, ReplaceOrderError> {
+        let old = self
+            .database
+            .single_order(&old_order)
+            .await
+            .map_err(OrderCancellationError::Other)?
+            .ok_or(OrderCancellationError::OrderNotFound)?;
+        ensure_cancellable_status(&old)?;
+
+        self.enforce_rate_limit(&payload)?;
+        let (order, fee) = self.validate_order(payload).await?;
+        // The signature of the new order authorizes the cancellation.
+        if order.metadata.owner != old.metadata.owner {
+            return Err(OrderCancellationError::WrongOwner.into());
+        }
+
+        self.database
+            .replace_order(&old_order, &order, fee, self.max_submission_time)
+            .await?;
+        self.status_updates.publish(
+            old_order,
+            old.metadata.onchain_user,
+            OrderStatusUpdateKind::Cancelled,
+        );
+        self.order_added(&order);
+        Ok(order.metadata.uid)
+    }
+
+    async fn validate_order(
+        &self,
+        payload: OrderCreationPayload,
+    ) -> Result<(Order, FeeParameters), AddOrderError> {
         let order_creation = payload.order_creation;
         // Eventually we will support all Signature types and can remove this.
         if !matches!(
@@ -120,23 +260,52 @@ impl Orderbook {
                 order_creation.signature.scheme(),
                 self.enable_presign_orders
             ),
-            (SigningScheme::Eip712 | SigningScheme::EthSign, _) | (SigningScheme::PreSign, true)
+            (
+                SigningScheme::Eip712 | SigningScheme::EthSign | SigningScheme::Eip1271,
+                _
+            ) | (SigningScheme::PreSign, true)
         ) {
             return Err(AddOrderError::UnsupportedSignature);
         }
 
-        let (order, fee) = self
+        Ok(self
             .order_validator
             .validate_and_construct_order(
                 order_creation,
                 payload.from,
+                payload.class,
                 &self.domain_separator,
                 self.settlement_contract,
             )
-            .await?;
+            .await?)
+    }
 
-        self.database.insert_order(&order, fee).await?;
+    /// Takes a token from the rate limit bucket of the owner of the order.
+    /// This happens before validation so that limited owners can't make us
+    /// do the expensive checks. Orders whose owner can't be recovered are
+    /// rejected by the validation.
+    fn enforce_rate_limit(&self, payload: &OrderCreationPayload) -> Result<(), AddOrderError> {
+        let rate_limiter = match &self.owner_limits.rate_limiter {
+            Some(rate_limiter) => rate_limiter,
+            None => return Ok(()),
+        };
+        let order_creation = &payload.order_creation;
+        let owner = match &order_creation.signature {
+            Signature::Eip1271(_) => payload.from,
+            signature => signature.validate(&self.domain_separator, &order_creation.hash_struct()),
+        };
+        let owner = match owner {
+            Some(owner) if !self.order_validator.is_liquidity_order_owner(&owner) => owner,
+            _ => return Ok(()),
+        };
+        if !rate_limiter.try_acquire(owner) {
+            owner_limit_rejected("rate");
+            return Err(AddOrderError::RateLimited);
+        }
+        Ok(())
+    }
 
+    fn order_added(&self, order: &Order) {
         if !order.metadata.is_liquidity_order {
             Metrics::instance(metrics::get_metric_storage_registry())
                 .expect("unexpected error getting metrics instance")
@@ -144,9 +313,12 @@ impl Orderbook {
                 .inc();
         }
 
+        self.status_updates.publish(
+            order.metadata.uid,
+            order.metadata.onchain_user,
+            OrderStatusUpdateKind::Created,
+        );
         self.solvable_orders.request_update();
-
-        Ok(order.metadata.uid)
     }
 
     pub async fn cancel_order(
@@ -164,17 +336,7 @@ impl Orderbook {
         let order = orders
             .first()
             .ok_or(OrderCancellationError::OrderNotFound)?;
-
-        match order.metadata.status {
-            OrderStatus::PresignaturePending => return Err(OrderCancellationError::OnChainOrder),
-            OrderStatus::Open if !order.creation.signature.scheme().is_ecdsa_scheme() => {
-                return Err(OrderCancellationError::OnChainOrder);
-            }
-            OrderStatus::Fulfilled => return Err(OrderCancellationError::OrderFullyExecuted),
-            OrderStatus::Cancelled => return Err(OrderCancellationError::AlreadyCancelled),
-            OrderStatus::Expired => return Err(OrderCancellationError::OrderExpired),
-            _ => {}
-        }
+        ensure_cancellable_status(order)?;
 
         let signer = cancellation
             .validate(&self.domain_separator)
@@ -186,11 +348,82 @@ impl Orderbook {
         // order is already known to exist in DB at this point, and signer is
         // known to be correct!
         self.database
-            .cancel_order(&order.metadata.uid, Utc::now())
+            .cancel_orders(vec![order.metadata.uid], Utc::now())
             .await?;
+        self.status_updates.publish(
+            order.metadata.uid,
+            order.metadata.onchain_user,
+            OrderStatusUpdateKind::Cancelled,
+        );
         Ok(())
     }
 
+    /// Cancels all orders that can be cancelled and reports for every order
+    /// whether it was. Fails as a whole only if the signature is invalid, the
+    /// request contains more than `MAX_CANCELLATIONS` orders or on database
+    /// errors.
+    pub async fn cancel_orders(
+        &self,
+        cancellations: SignedOrderCancellations,
+    ) -> Result<Vec<(OrderUid, Result<(), OrderCancellationError>)>, OrderCancellationError> {
+        if cancellations.data.order_uids.len() > MAX_CANCELLATIONS {
+            return Err(OrderCancellationError::TooManyOrders {
+                max: MAX_CANCELLATIONS,
+            });
+        }
+        let signer = cancellations
+            .validate(&self.domain_separator)
+            .ok_or(OrderCancellationError::InvalidSignature)?;
+
+        let mut seen = HashSet::new();
+        let uids = cancellations
+            .data
+            .order_uids
+            .into_iter()
+            .filter(|uid| seen.insert(*uid))
+            .collect::<Vec<_>>();
+        let orders = self
+            .database
+            .many_orders(&uids)
+            .await?
+            .into_iter()
+            .map(|order| (order.metadata.uid, order))
+            .collect::<HashMap<_, _>>();
+
+        let mut results = Vec::new();
+        let mut cancellable = Vec::new();
+        for uid in uids {
+            let order = orders.get(&uid);
+            let result = cancellation_result(signer, order);
+            if let (Ok(()), Some(order)) = (&result, order) {
+                cancellable.push((uid, order.metadata.onchain_user));
+            }
+            results.push((uid, result));
+        }
+
+        if !cancellable.is_empty() {
+            self.database
+                .cancel_orders(
+                    cancellable.iter().map(|(uid, _)| *uid).collect(),
+                    Utc::now(),
+                )
+                .await?;
+        }
+        for (uid, onchain_user) in cancellable {
+            self.status_updates
+                .publish(uid, onchain_user, OrderStatusUpdateKind::Cancelled);
+        }
+        Ok(results)
+    }
+
+    /// Streams the status transitions of the subscribed orders from now on.
+    pub fn order_status_updates(
+        &self,
+        subscription: Subscription,
+    ) -> impl Stream<Item = OrderStatusUpdate> + Send + 'static {
+        self.status_updates.subscribe(subscription)
+    }
+
     pub async fn get_orders(&self, filter: &OrderFilter) -> Result<Vec<Order>> {
         let mut orders = self.database.orders(filter).await?;
         // This filter is deprecated so filtering solvable orders is a bit awkward but we'll support
@@ -237,6 +470,21 @@ impl Orderbook {
         Ok(solvable_orders)
     }
 
+    /// The order book of open user orders between the two tokens based on the
+    /// solvable orders and their owners' balances.
+    pub fn get_market_depth(&self, base_token: H160, quote_token: H160) -> Result<MarketDepth> {
+        let solvable_orders = self.get_solvable_orders()?;
+        Ok(market_depth(
+            &solvable_orders.orders,
+            base_token,
+            quote_token,
+            |order| {
+                self.solvable_orders
+                    .cached_balance(&crate::account_balances::Query::from_order(order))
+            },
+        ))
+    }
+
     pub fn get_auction(&self) -> Result<Auction> {
         let (auction, update_time) = self.solvable_orders.cached_auction();
         ensure!(
@@ -246,6 +494,11 @@ impl Orderbook {
         Ok(auction)
     }
 
+    /// Streams every new auction as soon as it has been computed.
+    pub fn auction_updates(&self) -> impl Stream<Item = Arc<AuctionUpdate>> + Send + 'static {
+        self.solvable_orders.auction_updates()
+    }
+
     pub async fn get_user_orders(
         &self,
         owner: &H160,
@@ -288,6 +541,14 @@ pub async fn filter_unsupported_tokens(
     Ok(orders)
 }
 
+fn owner_limit_rejected(limit: &str) {
+    Metrics::instance(metrics::get_metric_storage_registry())
+        .expect("unexpected error getting metrics instance")
+        .owner_limit_rejections
+        .with_label_values(&[limit])
+        .inc();
+}
+
 fn set_available_balances(orders: &mut [Order], cache: &SolvableOrdersCache) {
     for order in orders.iter_mut() {
         order.metadata.available_balance =
@@ -295,12 +556,35 @@ fn set_available_balances(orders: &mut [Order], cache: &SolvableOrdersCache) {
     }
 }
 
+/// Whether `signer` can cancel the order with a batch cancellation.
+fn cancellation_result(signer: H160, order: Option<&Order>) -> Result<(), OrderCancellationError> {
+    let order = order.ok_or(OrderCancellationError::OrderNotFound)?;
+    ensure_cancellable_status(order)?;
+    if signer != order.metadata.owner {
+        return Err(OrderCancellationError::WrongOwner);
+    }
+    Ok(())
+}
+
+fn ensure_cancellable_status(order: &Order) -> Result<(), OrderCancellationError> {
+    match order.metadata.status {
+        OrderStatus::PresignaturePending => Err(OrderCancellationError::OnChainOrder),
+        OrderStatus::Open if !order.creation.signature.scheme().is_ecdsa_scheme() => {
+            Err(OrderCancellationError::OnChainOrder)
+        }
+        OrderStatus::Fulfilled => Err(OrderCancellationError::OrderFullyExecuted),
+        OrderStatus::Cancelled => Err(OrderCancellationError::AlreadyCancelled),
+        OrderStatus::Expired => Err(OrderCancellationError::OrderExpired),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ethcontract::H160;
     use futures::FutureExt;
-    use model::order::OrderBuilder;
+    use model::order::{OrderBuilder, OrderMetadata};
     use shared::bad_token::list_based::ListBasedDetector;
 
     #[test]
@@ -329,4 +613,31 @@ mod tests {
             .unwrap();
         assert_eq!(result, &orders[1..2]);
     }
+
+    #[test]
+    fn cancellation_results() {
+        let signer = H160([1; 20]);
+        let order = |owner, status| Order {
+            metadata: OrderMetadata {
+                owner,
+                status,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        assert!(cancellation_result(signer, Some(&order(signer, OrderStatus::Open))).is_ok());
+        assert!(matches!(
+            cancellation_result(signer, None),
+            Err(OrderCancellationError::OrderNotFound)
+        ));
+        assert!(matches!(
+            cancellation_result(signer, Some(&order(H160([2; 20]), OrderStatus::Open))),
+            Err(OrderCancellationError::WrongOwner)
+        ));
+        assert!(matches!(
+            cancellation_result(signer, Some(&order(signer, OrderStatus::Fulfilled))),
+            Err(OrderCancellationError::OrderFullyExecuted)
+        ));
+    }
 }
@@ -0,0 +1,264 @@
+//! Token bucket rate limiting for the API.
+//!
+//! Every key (for example a client IP address or an order owner) gets its own
+//! bucket that holds up to `capacity` tokens and is refilled continuously so
+//! that it becomes full again after `period`. Every request takes one token
+//! and is rejected if the bucket is empty.
+
+use anyhow::{anyhow, Context, Result};
+use lru::LruCache;
+use std::{
+    hash::Hash,
+    net::{IpAddr, Ipv6Addr, SocketAddr},
+    str::FromStr,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+/// The maximum number of buckets that are kept. When it is reached the least
+/// recently used bucket is evicted, which gives its key a full bucket again.
+const MAX_BUCKETS: usize = 10_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RateLimit {
+    /// Maximum number of requests in a burst.
+    pub capacity: u32,
+    /// Time after which an empty bucket is full again.
+    pub period: Duration,
+}
+
+impl RateLimit {
+    fn tokens_per_second(&self) -> f64 {
+        self.capacity as f64 / self.period.as_secs_f64()
+    }
+}
+
+/// Parses `<requests>/<seconds>`, for example `100/60` for at most 100
+/// requests per minute.
+impl FromStr for RateLimit {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (capacity, period) = s
+            .split_once('/')
+            .ok_or_else(|| anyhow!("rate limit {:?} is not of the form <requests>/<seconds>", s))?;
+        let capacity: u32 = capacity
+            .trim()
+            .parse()
+            .context("invalid number of requests")?;
+        let period: f64 = period.trim().parse().context("invalid number of seconds")?;
+        anyhow::ensure!(capacity > 0, "number of requests must be positive");
+        anyhow::ensure!(
+            period.is_finite() && period > 0.,
+            "number of seconds must be positive"
+        );
+        Ok(Self {
+            capacity,
+            period: Duration::from_secs_f64(period),
+        })
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+pub struct RateLimiter<K> {
+    limit: RateLimit,
+    buckets: Mutex<LruCache<K, Bucket>>,
+}
+
+impl<K: Copy + Eq + Hash> RateLimiter<K> {
+    pub fn new(limit: RateLimit) -> Self {
+        Self {
+            limit,
+            buckets: Mutex::new(LruCache::new(MAX_BUCKETS)),
+        }
+    }
+
+    /// Takes a token from the bucket of `key`. Returns false if the request
+    /// should be rejected because the bucket is empty.
+    pub fn try_acquire(&self, key: K) -> bool {
+        self.try_acquire_at(key, Instant::now())
+    }
+
+    fn try_acquire_at(&self, key: K, now: Instant) -> bool {
+        let capacity = self.limit.capacity as f64;
+        let refill = |bucket: &Bucket| {
+            let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+            (bucket.tokens + elapsed * self.limit.tokens_per_second()).min(capacity)
+        };
+
+        let mut buckets = self.buckets.lock().expect("Thread holding Mutex panicked");
+        if !buckets.contains(&key) {
+            buckets.put(
+                key,
+                Bucket {
+                    tokens: capacity,
+                    updated: now,
+                },
+            );
+        }
+        // Getting the bucket marks it as the most recently used one.
+        let bucket = buckets.get_mut(&key).expect("bucket was just inserted");
+        bucket.tokens = refill(bucket);
+        bucket.updated = now;
+        if bucket.tokens < 1. {
+            return false;
+        }
+        bucket.tokens -= 1.;
+        true
+    }
+}
+
+/// Limits requests per client IP address.
+pub struct IpRateLimiter {
+    limiter: RateLimiter<IpAddr>,
+    trust_forwarded_for: bool,
+}
+
+impl IpRateLimiter {
+    /// When running behind a reverse proxy all requests come from the proxy's
+    /// address. In that case `trust_forwarded_for` makes us use the address
+    /// that the proxy appended to the `X-Forwarded-For` header instead. This
+    /// must only be enabled when the service is not reachable directly as the
+    /// header can be set by anyone.
+    pub fn new(limit: RateLimit, trust_forwarded_for: bool) -> Self {
+        Self {
+            limiter: RateLimiter::new(limit),
+            trust_forwarded_for,
+        }
+    }
+
+    /// Returns false if the client has exceeded the rate limit. Requests whose
+    /// origin can't be determined are never limited.
+    pub fn try_acquire(&self, remote: Option<SocketAddr>, forwarded_for: Option<&str>) -> bool {
+        match self.client_ip(remote, forwarded_for) {
+            Some(ip) => self.limiter.try_acquire(ip),
+            None => true,
+        }
+    }
+
+    /// Returns the address that identifies the client. IPv6 clients usually
+    /// get a whole /64 network assigned, so they are identified by it instead
+    /// of a single address that they could change for every request.
+    fn client_ip(&self, remote: Option<SocketAddr>, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let ip = self
+            .trust_forwarded_for
+            // Only the last entry was added by our proxy, the ones before it
+            // come from the client and could be spoofed.
+            .then(|| forwarded_for?.rsplit(',').next()?.trim().parse().ok())
+            .flatten()
+            .or_else(|| remote.map(|addr| addr.ip()))?;
+        Some(match ip {
+            IpAddr::V4(_) => ip,
+            IpAddr::V6(ip) => {
+                let network = u128::from(ip) & !(u128::MAX >> 64);
+                IpAddr::V6(Ipv6Addr::from(network))
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rate_limit() {
+        assert_eq!(
+            "100/60".parse::<RateLimit>().unwrap(),
+            RateLimit {
+                capacity: 100,
+                period: Duration::from_secs(60),
+            }
+        );
+        assert_eq!(
+            " 5 / 0.5 ".parse::<RateLimit>().unwrap(),
+            RateLimit {
+                capacity: 5,
+                period: Duration::from_millis(500),
+            }
+        );
+        for invalid in ["", "100", "0/60", "100/0", "-1/60", "100/-1", "a/b"] {
+            assert!(invalid.parse::<RateLimit>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn limits_bursts_and_refills() {
+        let limiter = RateLimiter::new(RateLimit {
+            capacity: 2,
+            period: Duration::from_secs(10),
+        });
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire_at(0, start));
+        assert!(limiter.try_acquire_at(0, start));
+        assert!(!limiter.try_acquire_at(0, start));
+        // Other keys have their own bucket.
+        assert!(limiter.try_acquire_at(1, start));
+
+        // One token is refilled every 5 seconds.
+        assert!(!limiter.try_acquire_at(0, start + Duration::from_secs(4)));
+        assert!(limiter.try_acquire_at(0, start + Duration::from_secs(6)));
+        assert!(!limiter.try_acquire_at(0, start + Duration::from_secs(6)));
+
+        // Buckets never hold more than their capacity.
+        let later = start + Duration::from_secs(3600);
+        assert!(limiter.try_acquire_at(0, later));
+        assert!(limiter.try_acquire_at(0, later));
+        assert!(!limiter.try_acquire_at(0, later));
+    }
+
+    #[test]
+    fn evicts_least_recently_used_buckets() {
+        let limiter = RateLimiter::new(RateLimit {
+            capacity: 1,
+            period: Duration::from_secs(60),
+        });
+        let start = Instant::now();
+        for key in 0..MAX_BUCKETS {
+            assert!(limiter.try_acquire_at(key, start));
+        }
+        // Makes key 0 the most recently used one so key 1 gets evicted.
+        assert!(!limiter.try_acquire_at(0, start));
+        assert!(limiter.try_acquire_at(MAX_BUCKETS, start));
+        assert_eq!(limiter.buckets.lock().unwrap().len(), MAX_BUCKETS);
+
+        assert!(!limiter.try_acquire_at(0, start));
+        assert!(limiter.try_acquire_at(1, start));
+    }
+
+    #[test]
+    fn client_ip_from_forwarded_for() {
+        let limit = "1/1".parse().unwrap();
+        let remote = Some("10.0.0.1:1234".parse().unwrap());
+        let forwarded_for = Some("1.1.1.1, 2.2.2.2");
+
+        let limiter = IpRateLimiter::new(limit, false);
+        assert_eq!(
+            limiter.client_ip(remote, forwarded_for),
+            Some("10.0.0.1".parse().unwrap())
+        );
+
+        let limiter = IpRateLimiter::new(limit, true);
+        assert_eq!(
+            limiter.client_ip(remote, forwarded_for),
+            Some("2.2.2.2".parse().unwrap())
+        );
+        assert_eq!(
+            limiter.client_ip(remote, Some("invalid")),
+            Some("10.0.0.1".parse().unwrap())
+        );
+        assert_eq!(limiter.client_ip(None, None), None);
+        assert_eq!(
+            limiter.client_ip(remote, Some("2001:db8:1:2:3:4:5:6")),
+            Some("2001:db8:1:2::".parse().unwrap())
+        );
+        assert!(limiter.try_acquire(None, None));
+        assert!(limiter.try_acquire(None, None));
+    }
+}
@@ -0,0 +1,120 @@
+use crate::database::settlements::{SettlementCosts, SettlementStoring};
+use anyhow::Result;
+use chrono::Utc;
+use ethcontract::H256;
+use shared::{maintenance::Maintaining, Web3};
+use std::sync::Arc;
+use web3::types::{Transaction, TransactionReceipt};
+
+/// How many settlements are indexed per maintenance run at most so that
+/// catching up on old settlements does not flood the node.
+const MAX_SETTLEMENTS_PER_UPDATE: u64 = 50;
+
+/// Indexes what stored settlements cost on chain from their transaction
+/// receipts so that fee predictions can be compared against reality.
+pub struct SettlementCostUpdater {
+    web3: Web3,
+    db: Arc<dyn SettlementStoring>,
+}
+
+impl SettlementCostUpdater {
+    pub fn new(web3: Web3, db: Arc<dyn SettlementStoring>) -> Self {
+        Self { web3, db }
+    }
+
+    async fn update(&self) -> Result<()> {
+        let hashes = self
+            .db
+            .settlements_without_costs(MAX_SETTLEMENTS_PER_UPDATE, Utc::now())
+            .await?;
+        for hash in hashes {
+            match self.fetch_costs(hash).await {
+                Ok(Some(costs)) => {
+                    self.db.store_settlement_costs(hash, &costs).await?;
+                    continue;
+                }
+                // The node might not know the transaction yet.
+                Ok(None) => tracing::debug!(?hash, "settlement costs not available"),
+                Err(err) => tracing::warn!(?hash, ?err, "failed to fetch settlement costs"),
+            }
+            // Backing off keeps settlements that repeatedly fail from using
+            // up every update.
+            self.db
+                .record_failed_settlement_costs(hash, Utc::now())
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn fetch_costs(&self, hash: H256) -> Result<Option<SettlementCosts>> {
+        let eth = self.web3.eth();
+        let (receipt, transaction) =
+            futures::try_join!(eth.transaction_receipt(hash), eth.transaction(hash.into()))?;
+        Ok(receipt
+            .zip(transaction)
+            .and_then(|(receipt, transaction)| settlement_costs(&receipt, &transaction)))
+    }
+}
+
+/// Nodes only report the effective gas price for transactions after the
+/// London hard fork. Before that the gas price was paid in full.
+fn settlement_costs(
+    receipt: &TransactionReceipt,
+    transaction: &Transaction,
+) -> Option<SettlementCosts> {
+    Some(SettlementCosts {
+        gas_used: receipt.gas_used?,
+        effective_gas_price: receipt.effective_gas_price.or(transaction.gas_price)?,
+        calldata_size: transaction.input.0.len(),
+    })
+}
+
+#[async_trait::async_trait]
+impl Maintaining for SettlementCostUpdater {
+    async fn run_maintenance(&self) -> Result<()> {
+        self.update().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use web3::types::Bytes;
+
+    #[test]
+    fn computes_settlement_costs() {
+        let receipt = TransactionReceipt {
+            gas_used: Some(100.into()),
+            effective_gas_price: Some(2.into()),
+            ..Default::default()
+        };
+        let transaction = Transaction {
+            gas_price: Some(3.into()),
+            input: Bytes(vec![0; 4]),
+            ..Default::default()
+        };
+        assert_eq!(
+            settlement_costs(&receipt, &transaction),
+            Some(SettlementCosts {
+                gas_used: 100.into(),
+                effective_gas_price: 2.into(),
+                calldata_size: 4,
+            })
+        );
+
+        let legacy_receipt = TransactionReceipt {
+            effective_gas_price: None,
+            ..receipt.clone()
+        };
+        assert_eq!(
+            settlement_costs(&legacy_receipt, &transaction).map(|costs| costs.effective_gas_price),
+            Some(3.into())
+        );
+
+        let pending_receipt = TransactionReceipt {
+            gas_used: None,
+            ..receipt
+        };
+        assert_eq!(settlement_costs(&pending_receipt, &transaction), None);
+    }
+}
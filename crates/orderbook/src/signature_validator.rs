@@ -0,0 +1,217 @@
+use contracts::ERC1271SignatureValidator;
+use ethcontract::{batch::CallBatch, Bytes};
+use futures::{FutureExt as _, StreamExt as _};
+use hex_literal::hex;
+use model::{
+    order::Order,
+    signature::{hashed_eip712_message, Signature},
+    DomainSeparator,
+};
+use primitive_types::H160;
+use shared::{ethcontract_error::EthcontractErrorType, Web3};
+use thiserror::Error;
+
+/// The magic value returned by `isValidSignature` for valid signatures.
+///
+/// See <https://eips.ethereum.org/EIPS/eip-1271>.
+const ERC1271_MAGICVALUE: [u8; 4] = hex!("1626ba7e");
+
+#[derive(Clone, Debug, Default, Hash, PartialEq, Eq)]
+pub struct SignatureCheck {
+    /// The smart contract that is expected to verify the signature.
+    pub signer: H160,
+    /// The EIP-712 signing hash of the signed struct.
+    pub hash: [u8; 32],
+    pub signature: Vec<u8>,
+}
+
+impl SignatureCheck {
+    /// Returns the check for an EIP-1271 signature or `None` if the signature
+    /// uses a different signing scheme.
+    pub fn for_signature(
+        signature: &Signature,
+        signer: H160,
+        domain_separator: &DomainSeparator,
+        struct_hash: &[u8; 32],
+    ) -> Option<Self> {
+        match signature {
+            Signature::Eip1271(signature) => Some(Self {
+                signer,
+                hash: hashed_eip712_message(domain_separator, struct_hash),
+                signature: signature.clone(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the check for an order with an EIP-1271 signature. The signing
+    /// hash is not recomputed since it is the first part of the order UID.
+    pub fn for_order(order: &Order) -> Option<Self> {
+        match &order.creation.signature {
+            Signature::Eip1271(signature) => Some(Self {
+                signer: order.signer(),
+                hash: order.metadata.uid.0[..32].try_into().unwrap(),
+                signature: signature.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum SignatureValidationError {
+    /// The signer did not return the EIP-1271 magic value for the signature.
+    #[error("invalid EIP-1271 signature")]
+    Invalid,
+    #[error(transparent)]
+    Other(#[from] anyhow::Error),
+}
+
+#[cfg_attr(test, mockall::automock)]
+#[async_trait::async_trait]
+pub trait SignatureValidating: Send + Sync {
+    /// Verifies an EIP-1271 signature by calling `isValidSignature` on the
+    /// signer.
+    async fn validate_signature(
+        &self,
+        check: &SignatureCheck,
+    ) -> Result<(), SignatureValidationError>;
+
+    /// Verifies multiple EIP-1271 signatures in a single batch. The results
+    /// are in the same order as the checks.
+    async fn validate_signatures(
+        &self,
+        checks: &[SignatureCheck],
+    ) -> Vec<Result<(), SignatureValidationError>>;
+}
+
+pub struct Web3SignatureValidator {
+    web3: Web3,
+}
+
+impl Web3SignatureValidator {
+    pub fn new(web3: Web3) -> Self {
+        Self { web3 }
+    }
+}
+
+#[async_trait::async_trait]
+impl SignatureValidating for Web3SignatureValidator {
+    async fn validate_signature(
+        &self,
+        check: &SignatureCheck,
+    ) -> Result<(), SignatureValidationError> {
+        self.validate_signatures(std::slice::from_ref(check))
+            .await
+            .pop()
+            .expect("one result per check")
+    }
+
+    async fn validate_signatures(
+        &self,
+        checks: &[SignatureCheck],
+    ) -> Vec<Result<(), SignatureValidationError>> {
+        let mut batch = CallBatch::new(self.web3.transport().clone());
+        let futures = checks
+            .iter()
+            .map(|check| {
+                ERC1271SignatureValidator::at(&self.web3, check.signer)
+                    .is_valid_signature(Bytes(check.hash), Bytes(check.signature.clone()))
+                    .batch_call(&mut batch)
+                    .boxed()
+            })
+            .collect::<Vec<_>>();
+        batch.execute_all(usize::MAX).await;
+        futures::stream::iter(futures)
+            .then(|future| async {
+                match future.await {
+                    Ok(Bytes(magic_value)) if magic_value == ERC1271_MAGICVALUE => Ok(()),
+                    Ok(_) => Err(SignatureValidationError::Invalid),
+                    Err(err) => match EthcontractErrorType::classify(&err) {
+                        // Accounts that don't implement EIP-1271 revert which
+                        // is the same as an invalid signature.
+                        EthcontractErrorType::Contract => Err(SignatureValidationError::Invalid),
+                        EthcontractErrorType::Node => {
+                            Err(SignatureValidationError::Other(err.into()))
+                        }
+                    },
+                }
+            })
+            .collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use model::order::OrderCreation;
+
+    #[test]
+    fn signature_check_only_for_eip1271_signatures() {
+        let domain_separator = DomainSeparator([1; 32]);
+        let struct_hash = [2; 32];
+        let signer = H160([3; 20]);
+
+        assert_eq!(
+            SignatureCheck::for_signature(
+                &Signature::Eip1271(vec![4, 5]),
+                signer,
+                &domain_separator,
+                &struct_hash
+            ),
+            Some(SignatureCheck {
+                signer,
+                hash: hashed_eip712_message(&domain_separator, &struct_hash),
+                signature: vec![4, 5],
+            })
+        );
+        assert_eq!(
+            SignatureCheck::for_signature(
+                &Signature::default(),
+                signer,
+                &domain_separator,
+                &struct_hash
+            ),
+            None
+        );
+        assert_eq!(
+            SignatureCheck::for_signature(
+                &Signature::PreSign(signer),
+                signer,
+                &domain_separator,
+                &struct_hash
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn signature_check_for_order_uses_uid_hash() {
+        let domain_separator = DomainSeparator([1; 32]);
+        let owner = H160([3; 20]);
+        let creation = OrderCreation {
+            signature: Signature::Eip1271(vec![4, 5]),
+            ..Default::default()
+        };
+        let order = Order::from_order_creation(
+            &creation,
+            &domain_separator,
+            Default::default(),
+            Default::default(),
+            owner,
+            false,
+        );
+
+        assert_eq!(
+            SignatureCheck::for_order(&order),
+            SignatureCheck::for_signature(
+                &creation.signature,
+                owner,
+                &domain_separator,
+                &creation.hash_struct()
+            ),
+        );
+        assert_eq!(SignatureCheck::for_order(&Order::default()), None);
+    }
+}
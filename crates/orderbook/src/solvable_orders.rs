@@ -1,15 +1,26 @@
 use crate::{
     account_balances::{BalanceFetching, Query},
-    database::orders::OrderStoring,
+    database::{auctions::AuctionStoring, order_events::OrderEventStoring, orders::OrderStoring},
+    order_status_updates::OrderStatusUpdates,
     orderbook::filter_unsupported_tokens,
+    signature_validator::{SignatureCheck, SignatureValidating, SignatureValidationError},
 };
 use anyhow::{Context as _, Result};
-use futures::StreamExt;
-use model::{auction::Auction, order::Order};
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, StreamExt};
+use gas_estimation::GasPriceEstimating;
+use model::{
+    auction::{Auction, AuctionUpdate},
+    order::{Order, OrderClass, OrderStatusUpdateKind, OrderUid},
+    order_event::{OrderEvent, OrderEventLabel, OrderEventReason},
+};
 use primitive_types::{H160, U256};
 use shared::{
-    bad_token::BadTokenDetecting, current_block::CurrentBlockStream, maintenance::Maintaining,
-    price_estimation::native::NativePriceEstimating, time::now_in_epoch_seconds,
+    bad_token::BadTokenDetecting,
+    current_block::CurrentBlockStream,
+    maintenance::Maintaining,
+    price_estimation::{gas::GAS_PER_ORDER, native::NativePriceEstimating},
+    time::now_in_epoch_seconds,
 };
 use std::{
     collections::{BTreeMap, HashMap, HashSet},
@@ -17,13 +28,23 @@ use std::{
     sync::{Arc, Mutex, Weak},
     time::Duration,
 };
-use tokio::{sync::Notify, time::Instant};
+use tokio::{
+    sync::{
+        broadcast::{self, error::RecvError},
+        Notify,
+    },
+    time::Instant,
+};
 
 // When creating the auction after solvable orders change we need to fetch native prices for a
 // potentially large amount of tokens. This is the maximum amount of time we allot for this
 // operation.
 const MAX_AUCTION_CREATION_TIME: Duration = Duration::from_secs(10);
 
+// Every update contains the full auction so subscribers that fall behind only
+// need the most recent ones.
+const AUCTION_UPDATES_CAPACITY: usize = 4;
+
 pub trait AuctionMetrics: Send + Sync + 'static {
     fn auction_updated(
         &self,
@@ -43,13 +64,24 @@ pub trait AuctionMetrics: Send + Sync + 'static {
 pub struct SolvableOrdersCache {
     min_order_validity_period: Duration,
     database: Arc<dyn OrderStoring>,
+    auction_storing: Arc<dyn AuctionStoring>,
     banned_users: HashSet<H160>,
     balance_fetcher: Arc<dyn BalanceFetching>,
     bad_token_detector: Arc<dyn BadTokenDetecting>,
+    signature_validator: Arc<dyn SignatureValidating>,
     notify: Notify,
     cache: Mutex<Inner>,
     native_price_estimator: Arc<dyn NativePriceEstimating>,
+    gas_price_estimator: Arc<dyn GasPriceEstimating>,
     auction_metrics: Arc<dyn AuctionMetrics>,
+    status_updates: Arc<OrderStatusUpdates>,
+    /// Valid to and on-chain user of orders that were solvable and have not
+    /// expired yet.
+    expiring_orders: Mutex<HashMap<OrderUid, (u32, Option<H160>)>>,
+    auction_updates: broadcast::Sender<Arc<AuctionUpdate>>,
+    order_events: Arc<dyn OrderEventStoring>,
+    /// Last known state of the solvable orders, see `FilterReasons::into_states`.
+    order_states: Mutex<HashMap<OrderUid, Option<OrderEventReason>>>,
 }
 
 type Balances = HashMap<Query, U256>;
@@ -73,19 +105,26 @@ impl SolvableOrdersCache {
     pub fn new(
         min_order_validity_period: Duration,
         database: Arc<dyn OrderStoring>,
+        auction_storing: Arc<dyn AuctionStoring>,
         banned_users: HashSet<H160>,
         balance_fetcher: Arc<dyn BalanceFetching>,
         bad_token_detector: Arc<dyn BadTokenDetecting>,
+        signature_validator: Arc<dyn SignatureValidating>,
         current_block: CurrentBlockStream,
         native_price_estimator: Arc<dyn NativePriceEstimating>,
+        gas_price_estimator: Arc<dyn GasPriceEstimating>,
         auction_metrics: Arc<dyn AuctionMetrics>,
+        status_updates: Arc<OrderStatusUpdates>,
+        order_events: Arc<dyn OrderEventStoring>,
     ) -> Arc<Self> {
         let self_ = Arc::new(Self {
             min_order_validity_period,
             database,
+            auction_storing,
             banned_users,
             balance_fetcher,
             bad_token_detector,
+            signature_validator,
             notify: Default::default(),
             cache: Mutex::new(Inner {
                 orders: SolvableOrders {
@@ -96,6 +135,7 @@ impl SolvableOrdersCache {
                 },
                 balances: Default::default(),
                 auction: Auction {
+                    id: 0,
                     block: 0,
                     latest_settlement_block: 0,
                     orders: Default::default(),
@@ -103,7 +143,13 @@ impl SolvableOrdersCache {
                 },
             }),
             native_price_estimator,
+            gas_price_estimator,
             auction_metrics,
+            status_updates,
+            expiring_orders: Default::default(),
+            auction_updates: broadcast::channel(AUCTION_UPDATES_CAPACITY).0,
+            order_events,
+            order_states: Default::default(),
         });
         tokio::task::spawn(update_task(Arc::downgrade(&self_), current_block));
         self_
@@ -125,6 +171,25 @@ impl SolvableOrdersCache {
         (cache.auction.clone(), cache.orders.update_time)
     }
 
+    /// Streams every auction computed from now on together with how its orders
+    /// differ from the previous auction.
+    pub fn auction_updates(&self) -> impl Stream<Item = Arc<AuctionUpdate>> + Send + 'static {
+        stream::unfold(
+            self.auction_updates.subscribe(),
+            |mut receiver| async move {
+                loop {
+                    match receiver.recv().await {
+                        Ok(update) => return Some((update, receiver)),
+                        Err(RecvError::Lagged(skipped)) => {
+                            tracing::debug!(skipped, "auction subscriber lagged behind")
+                        }
+                        Err(RecvError::Closed) => return None,
+                    }
+                }
+            },
+        )
+    }
+
     /// The cache will update the solvable orders and missing balances as soon as possible.
     pub fn request_update(&self) {
         self.notify.notify_one();
@@ -134,8 +199,26 @@ impl SolvableOrdersCache {
     pub async fn update(&self, block: u64) -> Result<()> {
         let min_valid_to = now_in_epoch_seconds() + self.min_order_validity_period.as_secs() as u32;
         let db_solvable_orders = self.database.solvable_orders(min_valid_to).await?;
+        let expired_orders = update_expiring_orders(
+            &mut self.expiring_orders.lock().unwrap(),
+            &db_solvable_orders.orders,
+            min_valid_to,
+            now_in_epoch_seconds(),
+        );
+        for (uid, onchain_user) in expired_orders {
+            self.status_updates
+                .publish(uid, onchain_user, OrderStatusUpdateKind::Expired);
+        }
+        let mut filter_reasons = FilterReasons::new(&db_solvable_orders.orders);
         let orders = filter_banned_user_orders(db_solvable_orders.orders, &self.banned_users);
+        filter_reasons.record(&orders, OrderEventReason::BannedUser);
         let orders = filter_unsupported_tokens(orders, self.bad_token_detector.as_ref()).await?;
+        filter_reasons.record(&orders, OrderEventReason::UnsupportedToken);
+        // Smart contract signatures can become invalid at any time so we
+        // check them again for every update.
+        let orders =
+            filter_invalid_signature_orders(orders, self.signature_validator.as_ref()).await;
+        filter_reasons.record(&orders, OrderEventReason::InvalidSignature);
 
         // If we update due to an explicit notification we can reuse existing balances as they
         // cannot have changed.
@@ -167,7 +250,11 @@ impl SolvableOrdersCache {
         }
 
         let mut orders = solvable_orders(orders, &new_balances);
-        for order in &mut orders {
+        filter_reasons.record(&orders, OrderEventReason::InsufficientBalance);
+        for order in orders
+            .iter_mut()
+            .filter(|order| order.metadata.available_balance.is_none())
+        {
             let query = Query::from_order(order);
             order.metadata.available_balance = new_balances.get(&query).copied();
         }
@@ -180,26 +267,173 @@ impl SolvableOrdersCache {
             self.auction_metrics.as_ref(),
         )
         .await;
+        filter_reasons.record(&orders, OrderEventReason::MissingNativePrice);
+        let gas_price = self
+            .gas_price_estimator
+            .estimate()
+            .await
+            .context("failed to estimate gas price")?;
+        let orders = orders_with_surplus_fees(orders, &prices, gas_price.effective_gas_price());
+        filter_reasons.record(&orders, OrderEventReason::InsufficientSurplusFee);
+        self.store_order_events(filter_reasons.into_states()).await;
+        let id = self
+            .auction_storing
+            .next_auction_id()
+            .await
+            .context("failed to reserve auction id")?;
         let auction = Auction {
+            id,
             block,
             latest_settlement_block: db_solvable_orders.latest_settlement_block,
             orders: orders.clone(),
             prices,
         };
 
-        *self.cache.lock().unwrap() = Inner {
-            orders: SolvableOrders {
-                orders,
-                update_time: Instant::now(),
-                latest_settlement_block: db_solvable_orders.latest_settlement_block,
-                block,
+        let previous = std::mem::replace(
+            &mut *self.cache.lock().unwrap(),
+            Inner {
+                orders: SolvableOrders {
+                    orders,
+                    update_time: Instant::now(),
+                    latest_settlement_block: db_solvable_orders.latest_settlement_block,
+                    block,
+                },
+                balances: new_balances,
+                auction: auction.clone(),
             },
-            balances: new_balances,
-            auction,
-        };
+        );
+        // Sending only fails if nobody is subscribed.
+        let _ = self
+            .auction_updates
+            .send(Arc::new(AuctionUpdate::new(&previous.auction, auction)));
 
         Ok(())
     }
+
+    /// Stores events for the orders whose state changed since the last update.
+    /// The in memory states only avoid writing unchanged states every update,
+    /// after a restart the database skips events that match the latest stored
+    /// state of an order. Failing to store events does not affect the auction
+    /// so errors are only logged.
+    async fn store_order_events(&self, states: HashMap<OrderUid, Option<OrderEventReason>>) {
+        let events =
+            update_order_states(&mut self.order_states.lock().unwrap(), states, Utc::now());
+        if events.is_empty() {
+            return;
+        }
+        if let Err(err) = self.order_events.insert_order_events(events).await {
+            tracing::warn!(?err, "failed to store order events");
+        }
+    }
+}
+
+/// Remembers which filter removed an order from the solvable orders.
+struct FilterReasons {
+    remaining: HashSet<OrderUid>,
+    filtered: HashMap<OrderUid, OrderEventReason>,
+}
+
+impl FilterReasons {
+    fn new(orders: &[Order]) -> Self {
+        Self {
+            remaining: order_uids(orders),
+            filtered: Default::default(),
+        }
+    }
+
+    /// Records the orders that are no longer part of `orders` as filtered for
+    /// the given reason.
+    fn record(&mut self, orders: &[Order], reason: OrderEventReason) {
+        let remaining = order_uids(orders);
+        for uid in self.remaining.difference(&remaining) {
+            self.filtered.insert(*uid, reason);
+        }
+        self.remaining = remaining;
+    }
+
+    /// The state of every order that entered the filters. `None` if the order
+    /// made it through all filters or the reason why it was filtered.
+    fn into_states(self) -> HashMap<OrderUid, Option<OrderEventReason>> {
+        let mut states = self
+            .filtered
+            .into_iter()
+            .map(|(uid, reason)| (uid, Some(reason)))
+            .collect::<HashMap<_, _>>();
+        states.extend(self.remaining.into_iter().map(|uid| (uid, None)));
+        states
+    }
+}
+
+fn order_uids(orders: &[Order]) -> HashSet<OrderUid> {
+    orders.iter().map(|order| order.metadata.uid).collect()
+}
+
+/// Replaces the last known order states and returns the events for orders
+/// whose state changed. Orders that are no longer solvable are forgotten
+/// without an event because they were traded, cancelled or expired which is
+/// recorded elsewhere.
+///
+/// The states are only kept in memory so after a restart every order gets a
+/// new event.
+fn update_order_states(
+    order_states: &mut HashMap<OrderUid, Option<OrderEventReason>>,
+    states: HashMap<OrderUid, Option<OrderEventReason>>,
+    timestamp: DateTime<Utc>,
+) -> Vec<(OrderUid, OrderEvent)> {
+    let events = states
+        .iter()
+        .filter(|(uid, state)| order_states.get(uid) != Some(state))
+        .map(|(uid, reason)| {
+            let label = match reason {
+                Some(_) => OrderEventLabel::Filtered,
+                None => OrderEventLabel::Ready,
+            };
+            let event = OrderEvent {
+                timestamp,
+                label,
+                reason: *reason,
+            };
+            (*uid, event)
+        })
+        .collect();
+    *order_states = states;
+    events
+}
+
+/// Keeps track of the valid to of solvable orders so we notice when they expire.
+/// Orders drop out of the solvable set a little before they expire because of
+/// the minimum validity period, so they are kept around until then unless they
+/// disappeared for another reason like being filled or cancelled.
+///
+/// Returns the orders that expired since the last call with their on-chain
+/// user.
+fn update_expiring_orders(
+    expiring_orders: &mut HashMap<OrderUid, (u32, Option<H160>)>,
+    solvable_orders: &[Order],
+    min_valid_to: u32,
+    now: u32,
+) -> Vec<(OrderUid, Option<H160>)> {
+    let solvable_uids = solvable_orders
+        .iter()
+        .map(|order| order.metadata.uid)
+        .collect::<HashSet<_>>();
+    expiring_orders
+        .retain(|uid, (valid_to, _)| solvable_uids.contains(uid) || *valid_to < min_valid_to);
+    let expired = expiring_orders
+        .iter()
+        .filter(|(_, (valid_to, _))| *valid_to < now)
+        .map(|(uid, (_, onchain_user))| (*uid, *onchain_user))
+        .collect::<Vec<_>>();
+    for (uid, _) in &expired {
+        expiring_orders.remove(uid);
+    }
+    for order in solvable_orders {
+        expiring_orders.insert(
+            order.metadata.uid,
+            (order.user_valid_to(), order.metadata.onchain_user),
+        );
+    }
+    expired
 }
 
 /// Filters all orders whose owners are in the set of "banned" users.
@@ -208,6 +442,49 @@ fn filter_banned_user_orders(mut orders: Vec<Order>, banned_users: &HashSet<H160
     orders
 }
 
+/// Filters all EIP-1271 orders whose signatures are no longer accepted by the
+/// owner contract.
+async fn filter_invalid_signature_orders(
+    mut orders: Vec<Order>,
+    signature_validator: &dyn SignatureValidating,
+) -> Vec<Order> {
+    let (uids, checks): (Vec<_>, Vec<_>) = orders
+        .iter()
+        .filter_map(|order| Some((order.metadata.uid, SignatureCheck::for_order(order)?)))
+        .unzip();
+    if checks.is_empty() {
+        return orders;
+    }
+
+    let results = signature_validator.validate_signatures(&checks).await;
+    let invalid_uids = uids
+        .into_iter()
+        .zip(results)
+        .filter_map(|(uid, result)| match result {
+            Ok(()) => None,
+            Err(SignatureValidationError::Invalid) => {
+                tracing::debug!(
+                    order_uid = ?uid,
+                    "filtered order because of invalid EIP-1271 signature",
+                );
+                Some(uid)
+            }
+            // Errors like node failures say nothing about the signature so
+            // the order is kept instead of disappearing from the auction.
+            Err(SignatureValidationError::Other(err)) => {
+                tracing::warn!(
+                    order_uid = ?uid,
+                    ?err,
+                    "failed to validate EIP-1271 signature",
+                );
+                None
+            }
+        })
+        .collect::<HashSet<_>>();
+    orders.retain(|order| !invalid_uids.contains(&order.metadata.uid));
+    orders
+}
+
 /// Returns existing balances and Vec of queries that need to be peformed.
 fn new_balances(old_balances: &Balances, orders: &[Order]) -> (HashMap<Query, U256>, Vec<Query>) {
     let mut new_balances = HashMap::new();
@@ -229,6 +506,8 @@ fn new_balances(old_balances: &Balances, orders: &[Order]) -> (HashMap<Query, U2
 
 // The order book has to make a choice for which orders to include when a user has multiple orders
 // selling the same token but not enough balance for all of them.
+// Partially fillable orders that only get part of the balance they need have their
+// `available_balance` set to that part so that solvers don't fill more than can be transferred.
 // Assumes balance fetcher is already tracking all balances.
 fn solvable_orders(mut orders: Vec<Order>, balances: &Balances) -> Vec<Order> {
     let mut orders_map = HashMap::<Query, Vec<Order>>::new();
@@ -244,12 +523,7 @@ fn solvable_orders(mut orders: Vec<Order>, balances: &Balances) -> Vec<Order> {
             Some(balance) => *balance,
             None => continue,
         };
-        for order in orders {
-            // TODO: This is overly pessimistic for partially filled orders where the needed balance
-            // is lower. For partially fillable orders that cannot be fully filled because of the
-            // balance we could also give them as much balance as possible instead of skipping. For
-            // that we first need a way to communicate this to the solver. We could repurpose
-            // availableBalance for this.
+        for mut order in orders {
             let needed_balance = match max_transfer_out_amount(&order) {
                 // Should only ever happen if a partially fillable order has been filled completely
                 Ok(balance) if balance.is_zero() => continue,
@@ -270,6 +544,12 @@ fn solvable_orders(mut orders: Vec<Order>, balances: &Balances) -> Vec<Order> {
             if let Some(balance) = remaining_balance.checked_sub(needed_balance) {
                 remaining_balance = balance;
                 result.push(order);
+            } else if order.creation.partially_fillable && !remaining_balance.is_zero() {
+                // Partially fillable orders can still be executed with whatever balance is left
+                // so they get all of it instead of being skipped.
+                order.metadata.available_balance = Some(remaining_balance);
+                remaining_balance = U256::zero();
+                result.push(order);
             }
         }
     }
@@ -419,6 +699,41 @@ async fn get_orders_with_native_prices(
     (orders, used_prices)
 }
 
+/// Sets the fee that limit orders pay from their surplus. The fee covers the
+/// cost of executing the order and its hooks at the current gas price and is
+/// denominated in the order's sell token. Orders that cannot pay the fee with their remaining
+/// sell amount are filtered.
+fn orders_with_surplus_fees(
+    orders: Vec<Order>,
+    prices: &BTreeMap<H160, U256>,
+    gas_price: f64,
+) -> Vec<Order> {
+    orders
+        .into_iter()
+        .filter_map(|mut order| {
+            if order.metadata.class != OrderClass::Limit {
+                return Some(order);
+            }
+            // Prices are normalized so that they are the amount of native
+            // token for 1e18 units of the token.
+            let sell_token_price = prices.get(&order.creation.sell_token)?.to_f64_lossy();
+            let gas = GAS_PER_ORDER + order.metadata.hooks.gas_limit();
+            let fee_in_native_token = gas_price * gas as f64;
+            let surplus_fee =
+                U256::from_f64_lossy((fee_in_native_token * 1e18 / sell_token_price).ceil());
+            if surplus_fee >= order.remaining_amounts().ok()?.sell_amount {
+                tracing::debug!(
+                    order_uid = ?order.metadata.uid,
+                    "filtered limit order that can't pay its surplus fee",
+                );
+                return None;
+            }
+            order.metadata.surplus_fee = surplus_fee;
+            Some(order)
+        })
+        .collect()
+}
+
 fn to_normalized_price(price: f64) -> Option<U256> {
     let uint_max = 2.0_f64.powi(256);
 
@@ -434,15 +749,30 @@ fn to_normalized_price(price: f64) -> Option<U256> {
 mod tests {
     use super::*;
     use crate::{
-        account_balances::MockBalanceFetching, database::orders::MockOrderStoring,
-        database::orders::SolvableOrders as DbOrders, metrics::NoopMetrics,
+        account_balances::MockBalanceFetching,
+        database::orders::SolvableOrders as DbOrders,
+        database::{
+            auctions::MockAuctionStoring, order_events::MockOrderEventStoring,
+            orders::MockOrderStoring,
+        },
+        metrics::NoopMetrics,
+        signature_validator::MockSignatureValidating,
     };
-    use chrono::{DateTime, NaiveDateTime, Utc};
+    use chrono::NaiveDateTime;
     use futures::StreamExt;
     use maplit::{btreemap, hashmap, hashset};
-    use model::order::{OrderBuilder, OrderCreation, OrderKind, OrderMetadata, SellTokenSource};
+    use model::{
+        order::{
+            Hook, Hooks, OrderBuilder, OrderCreation, OrderKind, OrderMetadata, OrderUid,
+            SellTokenSource,
+        },
+        signature::Signature,
+    };
     use primitive_types::H160;
-    use shared::price_estimation::{native::MockNativePriceEstimating, PriceEstimationError};
+    use shared::{
+        gas_price_estimation::FakeGasPriceEstimator,
+        price_estimation::{native::MockNativePriceEstimating, PriceEstimationError},
+    };
 
     #[tokio::test]
     async fn filters_insufficient_balances() {
@@ -481,6 +811,56 @@ mod tests {
         assert_eq!(orders_, orders[1..]);
     }
 
+    #[tokio::test]
+    async fn partially_fillable_orders_use_remaining_balance() {
+        let orders = vec![
+            Order {
+                creation: OrderCreation {
+                    sell_amount: 3.into(),
+                    fee_amount: 3.into(),
+                    ..Default::default()
+                },
+                metadata: OrderMetadata {
+                    creation_date: DateTime::from_utc(NaiveDateTime::from_timestamp(2, 0), Utc),
+                    ..Default::default()
+                },
+            },
+            Order {
+                creation: OrderCreation {
+                    sell_amount: 2.into(),
+                    fee_amount: 2.into(),
+                    partially_fillable: true,
+                    ..Default::default()
+                },
+                metadata: OrderMetadata {
+                    creation_date: DateTime::from_utc(NaiveDateTime::from_timestamp(1, 0), Utc),
+                    ..Default::default()
+                },
+            },
+            Order {
+                creation: OrderCreation {
+                    sell_amount: 1.into(),
+                    fee_amount: 1.into(),
+                    partially_fillable: true,
+                    ..Default::default()
+                },
+                metadata: OrderMetadata {
+                    creation_date: DateTime::from_utc(NaiveDateTime::from_timestamp(0, 0), Utc),
+                    ..Default::default()
+                },
+            },
+        ];
+
+        let balances = hashmap! {Query::from_order(&orders[0]) => U256::from(9)};
+        let orders_ = solvable_orders(orders.clone(), &balances);
+        // The second order only gets the 3 remaining units of balance, leaving nothing for the
+        // third order.
+        assert_eq!(orders_.len(), 2);
+        assert_eq!(orders_[0], orders[0]);
+        assert_eq!(orders_[1].creation, orders[1].creation);
+        assert_eq!(orders_[1].metadata.available_balance, Some(3.into()));
+    }
+
     #[tokio::test]
     async fn caches_orders_and_balances() {
         let mut balance_fetcher = MockBalanceFetching::new();
@@ -574,16 +954,35 @@ mod tests {
             futures::stream::iter(std::iter::repeat(Ok(1.0)).take(a.len()).enumerate()).boxed()
         });
 
+        let mut auction_storing = MockAuctionStoring::new();
+        let mut next_auction_id = 0;
+        auction_storing.expect_next_auction_id().returning(move || {
+            next_auction_id += 1;
+            Ok(next_auction_id)
+        });
+
+        let mut order_events = MockOrderEventStoring::new();
+        order_events
+            .expect_insert_order_events()
+            .times(2)
+            .returning(|_| Ok(()));
+
         let cache = SolvableOrdersCache::new(
             Duration::from_secs(0),
             Arc::new(order_storing),
+            Arc::new(auction_storing),
             Default::default(),
             Arc::new(balance_fetcher),
             Arc::new(bad_token_detector),
+            Arc::new(MockSignatureValidating::new()),
             receiver,
             Arc::new(native),
+            Arc::new(FakeGasPriceEstimator::default()),
             Arc::new(NoopMetrics),
+            Default::default(),
+            Arc::new(order_events),
         );
+        let mut auction_updates = Box::pin(cache.auction_updates());
 
         cache.update(0).await.unwrap();
         assert_eq!(
@@ -595,7 +994,12 @@ mod tests {
         assert_eq!(orders_.len(), 1);
         assert_eq!(orders_[0].metadata.available_balance, Some(1.into()));
         let auction = cache.cached_auction().0;
+        assert_eq!(auction.id, 1);
         assert_eq!(auction.orders.len(), 1);
+        let update = auction_updates.next().await.unwrap();
+        assert_eq!(update.auction, auction);
+        assert_eq!(update.added_orders, vec![orders[0].metadata.uid]);
+        assert!(update.removed_orders.is_empty());
 
         cache.update(0).await.unwrap();
         assert_eq!(
@@ -609,6 +1013,7 @@ mod tests {
         let orders_ = cache.cached_solvable_orders().orders;
         assert_eq!(orders_.len(), 2);
         let auction = cache.cached_auction().0;
+        assert_eq!(auction.id, 2);
         assert_eq!(auction.orders.len(), 2);
 
         cache.update(0).await.unwrap();
@@ -617,9 +1022,152 @@ mod tests {
         let orders_ = cache.cached_solvable_orders().orders;
         assert_eq!(orders_.len(), 0);
         let auction = cache.cached_auction().0;
+        assert_eq!(auction.id, 3);
         assert_eq!(auction.orders.len(), 0);
     }
 
+    #[test]
+    fn records_order_state_transitions() {
+        let order = |uid: u8| Order {
+            metadata: OrderMetadata {
+                uid: OrderUid([uid; 56]),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let timestamp = Utc::now();
+        let event = |uid: u8, reason: Option<OrderEventReason>| {
+            let label = match reason {
+                Some(_) => OrderEventLabel::Filtered,
+                None => OrderEventLabel::Ready,
+            };
+            (
+                OrderUid([uid; 56]),
+                OrderEvent {
+                    timestamp,
+                    label,
+                    reason,
+                },
+            )
+        };
+        let sorted = |mut events: Vec<(OrderUid, OrderEvent)>| {
+            events.sort_by_key(|(uid, _)| uid.0);
+            events
+        };
+        let mut order_states = HashMap::new();
+
+        let mut reasons = FilterReasons::new(&[order(1), order(2), order(3)]);
+        reasons.record(&[order(1), order(2)], OrderEventReason::BannedUser);
+        reasons.record(&[order(1)], OrderEventReason::InsufficientBalance);
+        let events = update_order_states(&mut order_states, reasons.into_states(), timestamp);
+        assert_eq!(
+            sorted(events),
+            vec![
+                event(1, None),
+                event(2, Some(OrderEventReason::InsufficientBalance)),
+                event(3, Some(OrderEventReason::BannedUser)),
+            ]
+        );
+
+        // Only orders whose state changed get new events. Order 3 is gone.
+        let mut reasons = FilterReasons::new(&[order(1), order(2)]);
+        reasons.record(&[order(2)], OrderEventReason::MissingNativePrice);
+        let events = update_order_states(&mut order_states, reasons.into_states(), timestamp);
+        assert_eq!(
+            sorted(events),
+            vec![
+                event(1, Some(OrderEventReason::MissingNativePrice)),
+                event(2, None),
+            ]
+        );
+
+        let reasons = FilterReasons::new(&[order(1), order(2), order(3)]);
+        let events = update_order_states(&mut order_states, reasons.into_states(), timestamp);
+        assert_eq!(sorted(events), vec![event(1, None), event(3, None)]);
+    }
+
+    #[test]
+    fn tracks_expiring_orders() {
+        let order = |uid: u8, valid_to: u32| Order {
+            creation: OrderCreation {
+                valid_to,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid([uid; 56]),
+                onchain_user: Some(H160([uid; 20])),
+                ..Default::default()
+            },
+        };
+        let mut expiring_orders = HashMap::new();
+
+        let expired = update_expiring_orders(
+            &mut expiring_orders,
+            &[order(1, 10), order(2, 20), order(3, 30)],
+            5,
+            0,
+        );
+        assert!(expired.is_empty());
+
+        // Order 1 dropped out because of its validity and order 2 for another
+        // reason, for example because it got filled.
+        let expired = update_expiring_orders(&mut expiring_orders, &[order(3, 30)], 15, 5);
+        assert!(expired.is_empty());
+        assert_eq!(
+            expiring_orders.keys().copied().collect::<HashSet<_>>(),
+            hashset! {OrderUid([1; 56]), OrderUid([3; 56])}
+        );
+
+        let expired = update_expiring_orders(&mut expiring_orders, &[order(3, 30)], 25, 15);
+        assert_eq!(expired, vec![(OrderUid([1; 56]), Some(H160([1; 20])))]);
+
+        let expired = update_expiring_orders(&mut expiring_orders, &[], 35, 31);
+        assert_eq!(expired, vec![(OrderUid([3; 56]), Some(H160([3; 20])))]);
+        assert!(expiring_orders.is_empty());
+    }
+
+    #[test]
+    fn computes_surplus_fees_for_limit_orders() {
+        let sell_token = H160([1; 20]);
+        let order = |class, sell_amount: u64, hooks| Order {
+            creation: OrderCreation {
+                sell_token,
+                sell_amount: sell_amount.into(),
+                buy_amount: 1.into(),
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                class,
+                hooks,
+                ..Default::default()
+            },
+        };
+        let hooks = Hooks {
+            pre: vec![Hook {
+                gas_limit: 33_685,
+                ..Default::default()
+            }],
+            post: vec![],
+        };
+        let orders = vec![
+            order(OrderClass::Market, 1_000, Default::default()),
+            order(OrderClass::Limit, 1_000_000_000, Default::default()),
+            order(OrderClass::Limit, 1_000, Default::default()),
+            order(OrderClass::Limit, 1_000_000_000, hooks),
+        ];
+        let prices = btreemap! {
+            sell_token => U256::exp10(17) * 5,
+        };
+
+        let orders = orders_with_surplus_fees(orders, &prices, 100.);
+        assert_eq!(orders.len(), 3);
+        assert_eq!(orders[0].metadata.surplus_fee, U256::zero());
+        // 100 gas price * 66_315 gas / 0.5 native token per sell token
+        assert_eq!(orders[1].metadata.surplus_fee, 13_263_000.into());
+        // The hooks' gas is paid by the order too.
+        assert_eq!(orders[2].metadata.surplus_fee, 20_000_000.into());
+    }
+
     #[test]
     fn computes_u256_prices_normalized_to_1e18() {
         assert_eq!(
@@ -883,6 +1431,79 @@ mod tests {
         );
     }
 
+    #[tokio::test]
+    async fn filters_invalid_eip1271_signatures() {
+        let orders = vec![
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid([1; 56]),
+                    ..Default::default()
+                },
+                creation: OrderCreation {
+                    signature: Signature::Eip1271(vec![1]),
+                    ..Default::default()
+                },
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid([2; 56]),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid([3; 56]),
+                    ..Default::default()
+                },
+                creation: OrderCreation {
+                    signature: Signature::Eip1271(vec![3]),
+                    ..Default::default()
+                },
+            },
+            Order {
+                metadata: OrderMetadata {
+                    uid: OrderUid([4; 56]),
+                    ..Default::default()
+                },
+                creation: OrderCreation {
+                    signature: Signature::Eip1271(vec![4]),
+                    ..Default::default()
+                },
+            },
+        ];
+
+        let mut signature_validator = MockSignatureValidating::new();
+        signature_validator
+            .expect_validate_signatures()
+            .withf(|checks| {
+                checks.len() == 3
+                    && checks[0].signature == [1]
+                    && checks[1].signature == [3]
+                    && checks[2].signature == [4]
+            })
+            .times(1)
+            .returning(|_| {
+                vec![
+                    Err(SignatureValidationError::Invalid),
+                    Ok(()),
+                    Err(SignatureValidationError::Other(anyhow::anyhow!(
+                        "node error"
+                    ))),
+                ]
+            });
+
+        let filtered_orders = filter_invalid_signature_orders(orders, &signature_validator).await;
+        let filtered_uids = filtered_orders
+            .iter()
+            .map(|order| order.metadata.uid)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            filtered_uids,
+            [OrderUid([2; 56]), OrderUid([3; 56]), OrderUid([4; 56])]
+        );
+    }
+
     #[test]
     fn filters_zero_amount_orders() {
         let orders = vec![
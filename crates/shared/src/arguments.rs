@@ -8,6 +8,7 @@ use anyhow::{ensure, Context, Result};
 use ethcontract::{H160, U256};
 use std::{
     num::{NonZeroU64, ParseFloatError},
+    path::PathBuf,
     str::FromStr,
     time::Duration,
 };
@@ -67,6 +68,11 @@ pub struct Arguments {
     #[clap(long, env, arg_enum, ignore_case = true, use_value_delimiter = true)]
     pub baseline_sources: Option<Vec<BaselineSource>>,
 
+    /// Path to a json file describing the chain for chains whose contract addresses and defaults
+    /// are not built into the services, like new EVM chains or local devnets.
+    #[clap(long, env)]
+    pub chain_spec: Option<PathBuf>,
+
     /// The number of blocks kept in the pool cache.
     #[clap(long, env, default_value = "10")]
     pub pool_cache_blocks: NonZeroU64,
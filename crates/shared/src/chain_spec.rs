@@ -0,0 +1,185 @@
+//! Describes a chain the services run on. The contract artifacts and the hard
+//! coded defaults only know about the chains the protocol is deployed on, so a
+//! chain spec is needed to bring the services up on a new EVM chain or a local
+//! devnet.
+
+use crate::{
+    sources::{
+        swapr,
+        uniswap_v2::{self, pair_provider::PairProvider, pool_fetching::PoolFetching},
+        BaselineSource,
+    },
+    Web3,
+};
+use anyhow::{ensure, Context, Result};
+use contracts::{BalancerV2Vault, GPv2Settlement, WETH9};
+use ethcontract::{errors::DeployError, H160, H256, U256};
+use model::u256_decimal::DecimalU256;
+use serde::Deserialize;
+use serde_with::serde_as;
+use std::{collections::HashMap, path::Path, sync::Arc};
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct ChainSpec {
+    pub chain_id: u64,
+    pub network_name: Option<String>,
+    /// Contract addresses that are used instead of the deployments from the
+    /// artifacts.
+    pub settlement_contract: Option<H160>,
+    pub vault: Option<H160>,
+    pub native_token: Option<H160>,
+    /// Added to the base tokens from the command line arguments.
+    #[serde(default)]
+    pub base_tokens: Vec<H160>,
+    /// Deployments of UniswapV2-like contracts keyed by a name of choice. They
+    /// are used in addition to the baseline sources.
+    #[serde(default)]
+    pub uniswap_like_sources: HashMap<BaselineSource, UniswapLikeSource>,
+    #[serde_as(as = "Option<DecimalU256>")]
+    #[serde(default)]
+    pub amount_to_estimate_prices_with: Option<U256>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+pub struct UniswapLikeSource {
+    pub pool_reader: UniswapLikePoolReader,
+    pub factory: H160,
+    pub init_code_digest: H256,
+    /// Only needed by the solver which settles trades through the router.
+    pub router: Option<H160>,
+}
+
+/// How the reserves and fees of the pools of a deployment are read.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq)]
+pub enum UniswapLikePoolReader {
+    /// Pools with the fixed 0.3% fee of UniswapV2.
+    UniswapV2,
+    /// Pools with dynamic fees that are read from the pair contract like the
+    /// ones of Swapr.
+    Swapr,
+}
+
+impl UniswapLikeSource {
+    /// Creates the pair provider and pool fetcher for the deployment.
+    pub fn liquidity_source(&self, web3: &Web3) -> (PairProvider, Arc<dyn PoolFetching>) {
+        let (factory, digest) = (self.factory, self.init_code_digest.0);
+        match self.pool_reader {
+            UniswapLikePoolReader::UniswapV2 => {
+                uniswap_v2::get_liquidity_source_at(web3, factory, digest)
+            }
+            UniswapLikePoolReader::Swapr => swapr::get_liquidity_source_at(web3, factory, digest),
+        }
+    }
+}
+
+impl ChainSpec {
+    /// Fails if the node is connected to a different chain than the one this
+    /// spec describes.
+    pub fn verify_chain_id(&self, chain_id: u64) -> Result<()> {
+        ensure!(
+            self.chain_id == chain_id,
+            "chain spec is for chain {} but node is connected to chain {}",
+            self.chain_id,
+            chain_id
+        );
+        Ok(())
+    }
+
+    /// The baseline sources to use when none are configured explicitly. A spec
+    /// with its own sources replaces the defaults of the chain.
+    pub fn baseline_sources(&self) -> Option<Vec<BaselineSource>> {
+        if self.uniswap_like_sources.is_empty() {
+            return None;
+        }
+        Some(Vec::new())
+    }
+}
+
+pub fn read_chain_spec(path: &Path) -> Result<ChainSpec> {
+    let content =
+        std::fs::read(path).with_context(|| format!("failed to read chain spec {:?}", path))?;
+    serde_json::from_slice(&content)
+        .with_context(|| format!("failed to parse chain spec {:?}", path))
+}
+
+pub async fn settlement_contract(web3: &Web3, spec: Option<&ChainSpec>) -> Result<GPv2Settlement> {
+    Ok(match spec.and_then(|spec| spec.settlement_contract) {
+        Some(address) => GPv2Settlement::at(web3, address),
+        None => GPv2Settlement::deployed(web3).await?,
+    })
+}
+
+pub async fn native_token(web3: &Web3, spec: Option<&ChainSpec>) -> Result<WETH9> {
+    Ok(match spec.and_then(|spec| spec.native_token) {
+        Some(address) => WETH9::at(web3, address),
+        None => WETH9::deployed(web3).await?,
+    })
+}
+
+/// Returns `None` if the vault is neither configured nor deployed on the chain.
+pub async fn vault(web3: &Web3, spec: Option<&ChainSpec>) -> Result<Option<BalancerV2Vault>> {
+    if let Some(address) = spec.and_then(|spec| spec.vault) {
+        return Ok(Some(BalancerV2Vault::at(web3, address)));
+    }
+    match BalancerV2Vault::deployed(web3).await {
+        Ok(vault) => Ok(Some(vault)),
+        Err(DeployError::NotFound(_)) => Ok(None),
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+
+    #[test]
+    fn parses_chain_spec() {
+        let spec: ChainSpec = serde_json::from_str(
+            r#"{
+                "chainId": 1337,
+                "networkName": "Devnet",
+                "settlementContract": "0x0101010101010101010101010101010101010101",
+                "nativeToken": "0x0202020202020202020202020202020202020202",
+                "baseTokens": ["0x0303030303030303030303030303030303030303"],
+                "uniswapLikeSources": {
+                    "DevSwap": {
+                        "poolReader": "UniswapV2",
+                        "factory": "0x0404040404040404040404040404040404040404",
+                        "initCodeDigest": "0x0505050505050505050505050505050505050505050505050505050505050505",
+                        "router": "0x0606060606060606060606060606060606060606"
+                    }
+                },
+                "amountToEstimatePricesWith": "1000000000000000000"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            spec,
+            ChainSpec {
+                chain_id: 1337,
+                network_name: Some("Devnet".to_string()),
+                settlement_contract: Some(H160([1; 20])),
+                vault: None,
+                native_token: Some(H160([2; 20])),
+                base_tokens: vec![H160([3; 20])],
+                uniswap_like_sources: hashmap! {
+                    "DevSwap".to_string() => UniswapLikeSource {
+                        pool_reader: UniswapLikePoolReader::UniswapV2,
+                        factory: H160([4; 20]),
+                        init_code_digest: H256([5; 32]),
+                        router: Some(H160([6; 20])),
+                    },
+                },
+                amount_to_estimate_prices_with: Some(U256::exp10(18)),
+            }
+        );
+        assert_eq!(spec.baseline_sources(), Some(vec![]));
+        assert!(spec.verify_chain_id(1337).is_ok());
+        assert!(spec.verify_chain_id(1).is_err());
+    }
+}
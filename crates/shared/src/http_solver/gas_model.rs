@@ -15,8 +15,9 @@ impl GasModel {
         }
     }
 
-    pub fn gp_order_cost(&self) -> CostModel {
-        self.cost_for_gas(GAS_PER_ORDER.into())
+    /// The cost of settling an order including the gas its hooks can use.
+    pub fn gp_order_cost(&self, hook_gas_limit: u64) -> CostModel {
+        self.cost_for_gas(U256::from(GAS_PER_ORDER) + U256::from(hook_gas_limit))
     }
 
     pub fn zeroex_order_cost(&self) -> CostModel {
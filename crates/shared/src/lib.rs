@@ -5,6 +5,7 @@ pub mod arguments;
 pub mod bad_token;
 pub mod balancer_sor_api;
 pub mod baseline_solver;
+pub mod chain_spec;
 pub mod conversions;
 pub mod current_block;
 pub mod ethcontract_error;
@@ -60,6 +60,22 @@ pub fn serve_metrics(liveness: Arc<dyn LivenessChecking>, address: SocketAddr) -
     task::spawn(warp::serve(filter).bind(address))
 }
 
+/// Like `serve_metrics` but additionally serves routes that must not be
+/// reachable through the public API, like admin endpoints.
+pub fn serve_metrics_with_routes<F>(
+    liveness: Arc<dyn LivenessChecking>,
+    address: SocketAddr,
+    routes: F,
+) -> JoinHandle<()>
+where
+    F: Filter<Error = Rejection> + Clone + Send + Sync + 'static,
+    F::Extract: Reply,
+{
+    let filter = handle_metrics().or(handle_liveness(liveness)).or(routes);
+    tracing::info!(%address, "serving metrics");
+    task::spawn(warp::serve(filter).bind(address))
+}
+
 // `/metrics` route exposing encoded prometheus data to monitoring system
 pub fn handle_metrics() -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
     let registry = get_metrics_registry();
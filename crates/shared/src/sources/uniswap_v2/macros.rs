@@ -26,19 +26,37 @@ macro_rules! impl_uniswap_like_liquidity {
             $crate::sources::uniswap_v2::pair_provider::PairProvider,
             ::std::sync::Arc<dyn $crate::sources::uniswap_v2::pool_fetching::PoolFetching>,
         )> {
+            let factory = <$factory>::deployed(web3).await?;
+            Ok(get_liquidity_source_at(
+                web3,
+                factory.address(),
+                INIT_CODE_DIGEST,
+            ))
+        }
+
+        /// Creates the pair provider and pool fetcher for a deployment of the
+        /// same contracts that is not part of the artifacts, for example on a
+        /// new chain.
+        pub fn get_liquidity_source_at(
+            web3: &$crate::Web3,
+            factory: ::ethcontract::H160,
+            init_code_digest: [u8; 32],
+        ) -> (
+            $crate::sources::uniswap_v2::pair_provider::PairProvider,
+            ::std::sync::Arc<dyn $crate::sources::uniswap_v2::pool_fetching::PoolFetching>,
+        ) {
             use $crate::sources::uniswap_v2::pool_fetching::PoolReading;
 
-            let factory = <$factory>::deployed(web3).await?;
             let provider = $crate::sources::uniswap_v2::pair_provider::PairProvider {
-                factory: factory.address(),
-                init_code_digest: INIT_CODE_DIGEST,
+                factory,
+                init_code_digest,
             };
             let fetcher = $crate::sources::uniswap_v2::pool_fetching::PoolFetcher {
                 pool_reader: <$pool_reader>::for_pair_provider(provider.clone(), web3.clone()),
                 web3: web3.clone(),
             };
 
-            Ok((provider, ::std::sync::Arc::new(fetcher)))
+            (provider, ::std::sync::Arc::new(fetcher))
         }
     };
 }
@@ -1,6 +1,10 @@
+pub mod auction_archive;
 pub mod solver_settlements;
 
-use self::solver_settlements::RatedSettlement;
+use self::{
+    auction_archive::{ArchivedLiquidity, AuctionArchive, AuctionSnapshot},
+    solver_settlements::RatedSettlement,
+};
 use crate::{
     analytics, auction_preprocessing,
     in_flight_orders::InFlightOrders,
@@ -19,9 +23,12 @@ use contracts::GPv2Settlement;
 use futures::future::join_all;
 use gas_estimation::{EstimatedGasPrice, GasPriceEstimating};
 use itertools::{Either, Itertools};
-use model::solver_competition::{self, Objective, SolverCompetitionResponse, SolverSettlement};
+use model::{
+    auction::AuctionId,
+    solver_competition::{self, Objective, SolverCompetitionResponse, SolverSettlement},
+};
 use num::{rational::Ratio, BigInt, BigRational, ToPrimitive};
-use primitive_types::{H160, H256};
+use primitive_types::{H160, H256, U256};
 use rand::prelude::SliceRandom;
 use shared::{
     current_block::{self, CurrentBlockStream},
@@ -52,7 +59,6 @@ pub struct Driver {
     market_makable_token_list: Option<TokenList>,
     block_stream: CurrentBlockStream,
     solution_submitter: SolutionSubmitter,
-    solve_id: u64,
     max_settlements_per_solver: usize,
     api: OrderBookApi,
     order_converter: OrderConverter,
@@ -63,6 +69,7 @@ pub struct Driver {
     max_settlement_price_deviation: Option<Ratio<BigInt>>,
     token_list_restriction_for_price_checks: PriceCheckTokens,
     tenderly: Option<TenderlyApi>,
+    auction_archive: Option<AuctionArchive>,
 }
 impl Driver {
     #[allow(clippy::too_many_arguments)]
@@ -115,7 +122,6 @@ impl Driver {
             market_makable_token_list,
             block_stream,
             solution_submitter,
-            solve_id: 0,
             max_settlements_per_solver,
             api,
             order_converter,
@@ -127,9 +133,16 @@ impl Driver {
             max_settlement_price_deviation,
             token_list_restriction_for_price_checks,
             tenderly,
+            auction_archive: None,
         }
     }
 
+    /// Stores a snapshot of every auction so that it can be replayed later.
+    pub fn with_auction_archive(mut self, auction_archive: AuctionArchive) -> Self {
+        self.auction_archive = Some(auction_archive);
+        self
+    }
+
     pub async fn run_forever(&mut self) -> ! {
         loop {
             match self.single_run().await {
@@ -137,7 +150,7 @@ impl Driver {
                 Err(err) => tracing::error!("single run errored: {:?}", err),
             }
             self.metrics.runloop_completed();
-            tokio::time::sleep(self.settle_interval).await;
+            self.api.wait_for_new_auction(self.settle_interval).await;
         }
     }
 
@@ -146,23 +159,7 @@ impl Driver {
         &self,
         auction: Auction,
     ) -> Vec<(Arc<dyn Solver>, Result<Vec<Settlement>, SolverRunError>)> {
-        join_all(self.solvers.iter().map(|solver| {
-            let auction = auction.clone();
-            let metrics = &self.metrics;
-            async move {
-                let start_time = Instant::now();
-                let result =
-                    match tokio::time::timeout_at(auction.deadline.into(), solver.solve(auction))
-                        .await
-                    {
-                        Ok(inner) => inner.map_err(SolverRunError::Solving),
-                        Err(_timeout) => Err(SolverRunError::Timeout),
-                    };
-                metrics.settlement_computed(solver.name(), start_time);
-                (solver.clone(), result)
-            }
-        }))
-        .await
+        run_solvers(&self.solvers, auction, self.metrics.as_ref()).await
     }
 
     async fn submit_settlement(
@@ -382,7 +379,9 @@ impl Driver {
         let gas_price =
             BigRational::from_float(gas_price.effective_gas_price()).expect("Invalid gas price.");
 
-        let rate_settlement = |id, settlement: Settlement, gas_estimate| {
+        let rate_settlement = |id, settlement: Settlement, gas_estimate: U256| {
+            // The simulation executes the hooks of the traded orders, so the
+            // gas they use is already part of the estimate.
             let surplus = settlement.total_surplus(prices);
             let scaled_solver_fees = settlement.total_scaled_unsubsidized_fees(prices);
             let unscaled_subsidized_fee = settlement.total_unscaled_subsidized_fees(prices);
@@ -411,21 +410,27 @@ impl Driver {
     }
 
     pub async fn single_run(&mut self) -> Result<()> {
-        let id = self.next_auction_id();
+        let start = Instant::now();
+        let auction = self.api.get_auction().await.context("get_auction")?;
+        let id = auction.id;
+        self.metrics.auction_started(id);
         // extra function so that we can add span information
-        self.single_run_(id)
+        self.single_run_(start, auction)
             .instrument(tracing::debug_span!("auction", id))
             .await
     }
 
-    async fn single_run_(&mut self, auction_id: u64) -> Result<()> {
-        let start = Instant::now();
+    async fn single_run_(
+        &mut self,
+        start: Instant,
+        mut auction: model::auction::Auction,
+    ) -> Result<()> {
         tracing::debug!("starting single run");
+        let auction_id = auction.id;
 
         let current_block_during_liquidity_fetch =
             current_block::block_number(&self.block_stream.borrow())?;
 
-        let mut auction = self.api.get_auction().await.context("get_auction")?;
         let before_count = auction.orders.len();
         self.in_flight_orders.update_and_filter(&mut auction);
         if before_count != auction.orders.len() {
@@ -437,6 +442,9 @@ impl Driver {
             );
         }
 
+        // Only cloned when archiving because auctions can contain many orders.
+        let archived_auction = self.auction_archive.as_ref().map(|_| auction.clone());
+
         let orders = auction
             .orders
             .into_iter()
@@ -463,6 +471,20 @@ impl Driver {
             .liquidity_collector
             .get_liquidity_for_orders(&orders, Block::Number(current_block_during_liquidity_fetch))
             .await?;
+        // Archived right away so that the auction can be replayed even if
+        // the driver fails to rate the solutions.
+        let mut snapshot = archived_auction.map(|auction| AuctionSnapshot {
+            auction,
+            liquidity: liquidity.iter().map(ArchivedLiquidity::from).collect(),
+            competition: SolverCompetitionResponse {
+                auction_id,
+                liquidity_collected_block: current_block_during_liquidity_fetch,
+                ..Default::default()
+            },
+        });
+        if let (Some(archive), Some(snapshot)) = (&self.auction_archive, &snapshot) {
+            archive.store_if_missing(snapshot.clone());
+        }
 
         self.metrics.orders_fetched(&orders);
         self.metrics.liquidity_fetched(&liquidity);
@@ -613,6 +635,7 @@ impl Driver {
 
         // Report solver competition data to the api.
         let mut solver_competition_response = SolverCompetitionResponse {
+            auction_id,
             gas_price: gas_price.effective_gas_price(),
             liquidity_collected_block: current_block_during_liquidity_fetch,
             // TODO: we don't have access to this and there is no guarantee there is one such block
@@ -653,6 +676,10 @@ impl Driver {
         // This will happen again after transaction submission with the tx hash.
         self.send_solver_competition(auction_id, &solver_competition_response)
             .await;
+        if let Some(snapshot) = &mut snapshot {
+            snapshot.competition = solver_competition_response.clone();
+            self.archive_auction(snapshot);
+        }
 
         if let Some((winning_solver, mut winning_settlement, access_list)) = rated_settlements.pop()
         {
@@ -723,6 +750,10 @@ impl Driver {
                 solver_competition_response.transaction_hash = Some(receipt.transaction_hash);
                 self.send_solver_competition(auction_id, &solver_competition_response)
                     .await;
+                if let Some(snapshot) = &mut snapshot {
+                    snapshot.competition.transaction_hash = Some(receipt.transaction_hash);
+                    self.archive_auction(snapshot);
+                }
             }
             self.metrics.transaction_submission(start.elapsed());
 
@@ -739,17 +770,104 @@ impl Driver {
         Ok(())
     }
 
-    fn next_auction_id(&mut self) -> u64 {
-        let id = self.solve_id;
-        self.solve_id += 1;
-        id
-    }
-
-    async fn send_solver_competition(&self, auction_id: u64, body: &SolverCompetitionResponse) {
+    async fn send_solver_competition(
+        &self,
+        auction_id: AuctionId,
+        body: &SolverCompetitionResponse,
+    ) {
         if let Err(err) = self.api.send_solver_competition(auction_id, body).await {
             tracing::warn!(?err, "failed to send solver competition");
         }
     }
+
+    fn archive_auction(&self, snapshot: &AuctionSnapshot) {
+        if let Some(archive) = &self.auction_archive {
+            archive.store(snapshot.clone());
+        }
+    }
+}
+
+async fn run_solvers(
+    solvers: &[Arc<dyn Solver>],
+    auction: Auction,
+    metrics: &dyn SolverMetrics,
+) -> Vec<(Arc<dyn Solver>, Result<Vec<Settlement>, SolverRunError>)> {
+    join_all(solvers.iter().map(|solver| {
+        let auction = auction.clone();
+        async move {
+            let start_time = Instant::now();
+            let result =
+                match tokio::time::timeout_at(auction.deadline.into(), solver.solve(auction)).await
+                {
+                    Ok(inner) => inner.map_err(SolverRunError::Solving),
+                    Err(_timeout) => Err(SolverRunError::Timeout),
+                };
+            metrics.settlement_computed(solver.name(), start_time);
+            (solver.clone(), result)
+        }
+    }))
+    .await
+}
+
+/// Runs the solvers on an archived auction with the archived liquidity and
+/// describes how the solutions differ from the archived ones. Unlike the
+/// driver this doesn't need anything from the chain.
+pub async fn replay_auction(
+    snapshot: AuctionSnapshot,
+    solvers: &[Arc<dyn Solver>],
+    order_converter: &OrderConverter,
+    max_merged_settlements: usize,
+    solver_time_limit: Duration,
+    metrics: &dyn SolverMetrics,
+) -> Result<Vec<String>> {
+    let orders = snapshot
+        .auction
+        .orders
+        .into_iter()
+        .map(|order| order_converter.normalize_limit_order(order))
+        .collect::<Result<Vec<_>>>()?;
+    let external_prices = ExternalPrices::try_from_auction_prices(
+        order_converter.native_token.address(),
+        snapshot.auction.prices,
+    )
+    .context("malformed auction prices")?;
+    let liquidity = snapshot
+        .liquidity
+        .into_iter()
+        .map(ArchivedLiquidity::into_liquidity)
+        .collect::<Result<Vec<_>>>()?;
+
+    let auction = Auction {
+        id: snapshot.auction.id,
+        orders,
+        liquidity,
+        gas_price: snapshot.competition.gas_price,
+        deadline: Instant::now() + solver_time_limit,
+        external_prices: external_prices.clone(),
+    };
+    let mut solutions = Vec::new();
+    for (solver, settlements) in run_solvers(solvers, auction, metrics).await {
+        let mut settlements = match settlements {
+            Ok(settlements) => settlements,
+            Err(err) => {
+                tracing::warn!("solver {} error: {:?}", solver.name(), err);
+                continue;
+            }
+        };
+        settlements.retain(solver_settlements::has_user_order);
+        solver_settlements::merge_settlements(
+            max_merged_settlements,
+            &external_prices,
+            &mut settlements,
+        );
+        solutions.extend(settlements.iter().map(|settlement| {
+            auction_archive::unrated_solution(solver.name(), settlement, &external_prices)
+        }));
+    }
+    Ok(auction_archive::diff_solutions(
+        &snapshot.competition.solutions,
+        &solutions,
+    ))
 }
 
 fn is_only_selling_trusted_tokens(settlement: &Settlement, token_list: &TokenList) -> bool {
@@ -0,0 +1,661 @@
+//! Archives what the driver saw and decided in an auction so that the auction
+//! can be replayed later with any configured solver.
+//!
+//! The liquidity the solvers get contains the handlers that encode it into a
+//! settlement, so it can't be stored as is. Instead the archive contains the
+//! state of every liquidity source and replaying rebuilds the liquidity from it
+//! without accessing the chain. Replayed liquidity is encoded without
+//! interactions because its solutions are only compared by their prices and
+//! trades.
+
+use crate::{
+    liquidity::{
+        ConstantProductOrder, Exchange, LimitOrder, Liquidity, Settleable, SettlementHandling,
+        StablePoolOrder, WeightedProductOrder,
+    },
+    settlement::{external_prices::ExternalPrices, Settlement, SettlementEncoder},
+};
+use anyhow::{Context, Result};
+use model::{
+    auction::{Auction, AuctionId},
+    order::OrderKind,
+    solver_competition::{self, Objective, SolverCompetitionResponse, SolverSettlement},
+    u256_decimal::DecimalU256,
+    TokenPair,
+};
+use num::{rational::Ratio, BigRational, ToPrimitive};
+use primitive_types::{H160, U256};
+use serde::{Deserialize, Serialize};
+use serde_with::serde_as;
+use shared::sources::balancer_v2::{
+    pool_fetching::{AmplificationParameter, TokenState, WeightedTokenState},
+    swap::fixed_point::Bfp,
+};
+use std::{
+    collections::{BTreeMap, HashMap},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+/// Everything needed to replay an auction and compare the outcome.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuctionSnapshot {
+    /// The auction as received from the order book after removing orders
+    /// that were already being settled.
+    pub auction: Auction,
+    pub liquidity: Vec<ArchivedLiquidity>,
+    /// The gas price, liquidity block and rated solutions of the auction. Only
+    /// the liquidity block is set if the driver didn't get to rate solutions.
+    pub competition: SolverCompetitionResponse,
+}
+
+/// The state of a liquidity source without its settlement handler.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "kind")]
+pub enum ArchivedLiquidity {
+    ConstantProduct(ArchivedConstantProduct),
+    BalancerWeighted(ArchivedWeightedProduct),
+    BalancerStable(ArchivedStablePool),
+    LimitOrder(ArchivedLimitOrder),
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedConstantProduct {
+    pub tokens: [H160; 2],
+    /// The reserves in the order of `tokens`.
+    #[serde_as(as = "[DecimalU256; 2]")]
+    pub reserves: [U256; 2],
+    pub fee_numerator: u32,
+    pub fee_denominator: u32,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedWeightedProduct {
+    pub reserves: BTreeMap<H160, ArchivedWeightedToken>,
+    /// The fee as a fixed point number with 18 decimals.
+    #[serde_as(as = "DecimalU256")]
+    pub fee: U256,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedWeightedToken {
+    #[serde_as(as = "DecimalU256")]
+    pub balance: U256,
+    pub scaling_exponent: u8,
+    /// The weight as a fixed point number with 18 decimals.
+    #[serde_as(as = "DecimalU256")]
+    pub weight: U256,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedStablePool {
+    pub reserves: BTreeMap<H160, ArchivedStableToken>,
+    #[serde(with = "model::ratio_as_decimal")]
+    pub fee: BigRational,
+    #[serde_as(as = "DecimalU256")]
+    pub amplification_factor: U256,
+    #[serde_as(as = "DecimalU256")]
+    pub amplification_precision: U256,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedStableToken {
+    #[serde_as(as = "DecimalU256")]
+    pub balance: U256,
+    pub scaling_exponent: u8,
+}
+
+#[serde_as]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedLimitOrder {
+    pub id: String,
+    pub sell_token: H160,
+    pub buy_token: H160,
+    #[serde_as(as = "DecimalU256")]
+    pub sell_amount: U256,
+    #[serde_as(as = "DecimalU256")]
+    pub buy_amount: U256,
+    pub kind: OrderKind,
+    pub partially_fillable: bool,
+    #[serde_as(as = "DecimalU256")]
+    pub unscaled_subsidized_fee: U256,
+    #[serde_as(as = "DecimalU256")]
+    pub scaled_unsubsidized_fee: U256,
+    pub is_liquidity_order: bool,
+    pub hook_gas_limit: u64,
+    pub exchange: Exchange,
+}
+
+impl From<&Liquidity> for ArchivedLiquidity {
+    fn from(liquidity: &Liquidity) -> Self {
+        match liquidity {
+            Liquidity::ConstantProduct(amm) => {
+                let (token0, token1) = amm.tokens.get();
+                Self::ConstantProduct(ArchivedConstantProduct {
+                    tokens: [token0, token1],
+                    reserves: [amm.reserves.0.into(), amm.reserves.1.into()],
+                    fee_numerator: *amm.fee.numer(),
+                    fee_denominator: *amm.fee.denom(),
+                })
+            }
+            Liquidity::BalancerWeighted(amm) => Self::BalancerWeighted(ArchivedWeightedProduct {
+                reserves: amm
+                    .reserves
+                    .iter()
+                    .map(|(token, state)| {
+                        (
+                            *token,
+                            ArchivedWeightedToken {
+                                balance: state.common.balance,
+                                scaling_exponent: state.common.scaling_exponent,
+                                weight: state.weight.as_uint256(),
+                            },
+                        )
+                    })
+                    .collect(),
+                fee: amm.fee.as_uint256(),
+            }),
+            Liquidity::BalancerStable(amm) => Self::BalancerStable(ArchivedStablePool {
+                reserves: amm
+                    .reserves
+                    .iter()
+                    .map(|(token, state)| {
+                        (
+                            *token,
+                            ArchivedStableToken {
+                                balance: state.balance,
+                                scaling_exponent: state.scaling_exponent,
+                            },
+                        )
+                    })
+                    .collect(),
+                fee: amm.fee.clone(),
+                amplification_factor: amm.amplification_parameter.factor(),
+                amplification_precision: amm.amplification_parameter.precision(),
+            }),
+            Liquidity::LimitOrder(order) => Self::LimitOrder(ArchivedLimitOrder {
+                id: order.id.clone(),
+                sell_token: order.sell_token,
+                buy_token: order.buy_token,
+                sell_amount: order.sell_amount,
+                buy_amount: order.buy_amount,
+                kind: order.kind,
+                partially_fillable: order.partially_fillable,
+                unscaled_subsidized_fee: order.unscaled_subsidized_fee,
+                scaled_unsubsidized_fee: order.scaled_unsubsidized_fee,
+                is_liquidity_order: order.is_liquidity_order,
+                hook_gas_limit: order.hook_gas_limit,
+                exchange: order.exchange,
+            }),
+        }
+    }
+}
+
+impl ArchivedLiquidity {
+    /// Rebuilds the liquidity with a settlement handler that doesn't encode
+    /// any interactions.
+    pub fn into_liquidity(self) -> Result<Liquidity> {
+        Ok(match self {
+            Self::ConstantProduct(amm) => Liquidity::ConstantProduct(ConstantProductOrder {
+                tokens: TokenPair::new(amm.tokens[0], amm.tokens[1])
+                    .context("constant product tokens are equal")?,
+                reserves: (
+                    u128::try_from(amm.reserves[0]).map_err(anyhow::Error::msg)?,
+                    u128::try_from(amm.reserves[1]).map_err(anyhow::Error::msg)?,
+                ),
+                fee: Ratio::new(amm.fee_numerator, amm.fee_denominator),
+                settlement_handling: Arc::new(ReplayedLiquidity),
+            }),
+            Self::BalancerWeighted(amm) => Liquidity::BalancerWeighted(WeightedProductOrder {
+                reserves: amm
+                    .reserves
+                    .into_iter()
+                    .map(|(token, state)| {
+                        (
+                            token,
+                            WeightedTokenState {
+                                common: TokenState {
+                                    balance: state.balance,
+                                    scaling_exponent: state.scaling_exponent,
+                                },
+                                weight: Bfp::from_wei(state.weight),
+                            },
+                        )
+                    })
+                    .collect(),
+                fee: Bfp::from_wei(amm.fee),
+                settlement_handling: Arc::new(ReplayedLiquidity),
+            }),
+            Self::BalancerStable(amm) => Liquidity::BalancerStable(StablePoolOrder {
+                reserves: amm
+                    .reserves
+                    .into_iter()
+                    .map(|(token, state)| {
+                        (
+                            token,
+                            TokenState {
+                                balance: state.balance,
+                                scaling_exponent: state.scaling_exponent,
+                            },
+                        )
+                    })
+                    .collect(),
+                fee: amm.fee,
+                amplification_parameter: AmplificationParameter::new(
+                    amm.amplification_factor,
+                    amm.amplification_precision,
+                )?,
+                settlement_handling: Arc::new(ReplayedLiquidity),
+            }),
+            Self::LimitOrder(order) => Liquidity::LimitOrder(LimitOrder {
+                id: order.id,
+                sell_token: order.sell_token,
+                buy_token: order.buy_token,
+                sell_amount: order.sell_amount,
+                buy_amount: order.buy_amount,
+                kind: order.kind,
+                partially_fillable: order.partially_fillable,
+                unscaled_subsidized_fee: order.unscaled_subsidized_fee,
+                scaled_unsubsidized_fee: order.scaled_unsubsidized_fee,
+                is_liquidity_order: order.is_liquidity_order,
+                hook_gas_limit: order.hook_gas_limit,
+                settlement_handling: Arc::new(ReplayedLiquidity),
+                exchange: order.exchange,
+            }),
+        })
+    }
+}
+
+struct ReplayedLiquidity;
+
+impl<L> SettlementHandling<L> for ReplayedLiquidity
+where
+    L: Settleable,
+{
+    fn encode(&self, _: L::Execution, _: &mut SettlementEncoder) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Stores one snapshot per auction as a json file named after the auction id
+/// and keeps at most `max_snapshots` of them, removing the ones of the oldest
+/// auctions first.
+#[derive(Clone)]
+pub struct AuctionArchive {
+    directory: PathBuf,
+    max_snapshots: usize,
+    /// Serializes writes so that an older snapshot of an auction can't replace
+    /// a newer one.
+    lock: Arc<Mutex<()>>,
+}
+
+impl AuctionArchive {
+    pub fn new(directory: PathBuf, max_snapshots: usize) -> Self {
+        Self {
+            directory,
+            max_snapshots,
+            lock: Default::default(),
+        }
+    }
+
+    /// Stores the snapshot in the background unless one for the same auction
+    /// already exists.
+    pub fn store_if_missing(&self, snapshot: AuctionSnapshot) {
+        self.spawn_write(snapshot, false);
+    }
+
+    /// Stores the snapshot in the background. Storing a snapshot for the same
+    /// auction again replaces it.
+    pub fn store(&self, snapshot: AuctionSnapshot) {
+        self.spawn_write(snapshot, true);
+    }
+
+    fn spawn_write(&self, snapshot: AuctionSnapshot, replace: bool) {
+        let archive = self.clone();
+        tokio::task::spawn_blocking(move || {
+            if let Err(err) = archive.write(&snapshot, replace) {
+                tracing::warn!(?err, "failed to archive auction");
+            }
+        });
+    }
+
+    fn write(&self, snapshot: &AuctionSnapshot, replace: bool) -> Result<()> {
+        let path = self.directory.join(format!("{}.json", snapshot.auction.id));
+        let content = serde_json::to_vec(snapshot)?;
+        let _lock = self.lock.lock().unwrap();
+        if !replace && path.exists() {
+            return Ok(());
+        }
+        std::fs::write(&path, content)
+            .with_context(|| format!("failed to write auction snapshot {:?}", path))?;
+        self.remove_old_snapshots()
+    }
+
+    fn remove_old_snapshots(&self) -> Result<()> {
+        let mut snapshots = std::fs::read_dir(&self.directory)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                let id = path
+                    .file_name()?
+                    .to_str()?
+                    .strip_suffix(".json")?
+                    .parse::<AuctionId>()
+                    .ok()?;
+                Some((id, path))
+            })
+            .collect::<Vec<_>>();
+        if snapshots.len() <= self.max_snapshots {
+            return Ok(());
+        }
+        snapshots.sort_unstable_by_key(|(id, _)| *id);
+        let excess = snapshots.len() - self.max_snapshots;
+        for (_, path) in &snapshots[..excess] {
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed to remove auction snapshot {:?}", path))?;
+        }
+        Ok(())
+    }
+}
+
+pub fn read_auction_snapshot(path: &Path) -> Result<AuctionSnapshot> {
+    let content = std::fs::read(path)
+        .with_context(|| format!("failed to read auction snapshot {:?}", path))?;
+    serde_json::from_slice(&content)
+        .with_context(|| format!("failed to parse auction snapshot {:?}", path))
+}
+
+/// The parts of a solution that can be computed without simulating it. The
+/// replay doesn't simulate settlements because the chain state has moved on
+/// since the auction.
+pub fn unrated_solution(
+    solver: &str,
+    settlement: &Settlement,
+    prices: &ExternalPrices,
+) -> SolverSettlement {
+    SolverSettlement {
+        solver: solver.to_string(),
+        objective: Objective {
+            surplus: settlement
+                .total_surplus(prices)
+                .to_f64()
+                .unwrap_or(f64::NAN),
+            fees: settlement
+                .total_unscaled_subsidized_fees(prices)
+                .to_f64()
+                .unwrap_or(f64::NAN),
+            ..Default::default()
+        },
+        prices: settlement.clearing_prices().clone(),
+        orders: settlement
+            .executed_trades()
+            .map(|(trade, _)| solver_competition::Order {
+                id: trade.order.metadata.uid,
+                executed_amount: trade.executed_amount,
+            })
+            .collect(),
+        call_data: Default::default(),
+    }
+}
+
+/// Describes how the best replayed solution of every solver differs from its
+/// best archived one. Only the parts of the objective that don't depend on
+/// simulating the settlement are compared.
+pub fn diff_solutions(archived: &[SolverSettlement], replayed: &[SolverSettlement]) -> Vec<String> {
+    let value = |solution: &SolverSettlement| solution.objective.surplus + solution.objective.fees;
+    let best = |solutions: &[SolverSettlement]| {
+        let mut best = BTreeMap::<&str, &SolverSettlement>::new();
+        for solution in solutions {
+            let entry = best.entry(solution.solver.as_str()).or_insert(solution);
+            if value(solution) > value(*entry) {
+                *entry = solution;
+            }
+        }
+        best
+    };
+    let (archived, replayed) = (best(archived), best(replayed));
+    let mut diff = Vec::new();
+    for (solver, archived) in &archived {
+        let replayed = match replayed.get(solver) {
+            Some(replayed) => replayed,
+            None => {
+                diff.push(format!("solver {} found no solution", solver));
+                continue;
+            }
+        };
+        for (name, archived, replayed) in [
+            (
+                "surplus",
+                archived.objective.surplus,
+                replayed.objective.surplus,
+            ),
+            ("fees", archived.objective.fees, replayed.objective.fees),
+        ] {
+            if !approximately_equal(archived, replayed) {
+                diff.push(format!(
+                    "solver {} {} changed from {} to {}",
+                    solver, name, archived, replayed
+                ));
+            }
+        }
+        if archived.prices != replayed.prices {
+            diff.push(format!(
+                "solver {} prices changed from {:?} to {:?}",
+                solver, archived.prices, replayed.prices
+            ));
+        }
+        let executed = |solution: &SolverSettlement| {
+            solution
+                .orders
+                .iter()
+                .map(|order| (order.id, order.executed_amount))
+                .collect::<HashMap<_, _>>()
+        };
+        if executed(archived) != executed(replayed) {
+            diff.push(format!(
+                "solver {} executed orders changed from {:?} to {:?}",
+                solver, archived.orders, replayed.orders
+            ));
+        }
+    }
+    for solver in replayed
+        .keys()
+        .filter(|solver| !archived.contains_key(*solver))
+    {
+        diff.push(format!("solver {} found a new solution", solver));
+    }
+    diff
+}
+
+fn approximately_equal(a: f64, b: f64) -> bool {
+    (a - b).abs() <= f64::EPSILON * a.abs().max(b.abs()) * 16.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use model::order::OrderUid;
+
+    #[test]
+    fn archived_liquidity_roundtrip() {
+        let liquidity = vec![
+            Liquidity::ConstantProduct(ConstantProductOrder {
+                tokens: TokenPair::new(H160([2; 20]), H160([1; 20])).unwrap(),
+                reserves: (10, 20),
+                fee: Ratio::new(3, 1000),
+                ..Default::default()
+            }),
+            Liquidity::BalancerWeighted(WeightedProductOrder {
+                reserves: hashmap! {
+                    H160([1; 20]) => WeightedTokenState {
+                        common: TokenState {
+                            balance: 1.into(),
+                            scaling_exponent: 2,
+                        },
+                        weight: Bfp::from_wei(3.into()),
+                    },
+                },
+                fee: Bfp::from_wei(4.into()),
+                ..Default::default()
+            }),
+            Liquidity::BalancerStable(StablePoolOrder {
+                reserves: hashmap! {
+                    H160([1; 20]) => TokenState {
+                        balance: 1.into(),
+                        scaling_exponent: 2,
+                    },
+                },
+                fee: BigRational::new(1.into(), 4.into()),
+                amplification_parameter: AmplificationParameter::new(5.into(), 6.into()).unwrap(),
+                ..Default::default()
+            }),
+            Liquidity::LimitOrder(LimitOrder {
+                id: "0x01".to_string(),
+                sell_token: H160([1; 20]),
+                buy_token: H160([2; 20]),
+                sell_amount: 3.into(),
+                buy_amount: 4.into(),
+                is_liquidity_order: true,
+                exchange: Exchange::ZeroEx,
+                ..Default::default()
+            }),
+        ];
+
+        let archived = liquidity
+            .iter()
+            .map(ArchivedLiquidity::from)
+            .collect::<Vec<_>>();
+        let json = serde_json::to_vec(&archived).unwrap();
+        let replayed = serde_json::from_slice::<Vec<ArchivedLiquidity>>(&json)
+            .unwrap()
+            .into_iter()
+            .map(ArchivedLiquidity::into_liquidity)
+            .collect::<Result<Vec<_>>>()
+            .unwrap();
+        assert_eq!(replayed, liquidity);
+    }
+
+    #[test]
+    fn diffs_solutions() {
+        let solution = |solver: &str, surplus: f64, price: u64| SolverSettlement {
+            solver: solver.to_string(),
+            objective: Objective {
+                surplus,
+                fees: 1.,
+                ..Default::default()
+            },
+            prices: hashmap! { H160([1; 20]) => price.into() },
+            orders: vec![solver_competition::Order {
+                id: OrderUid([1; 56]),
+                executed_amount: 1.into(),
+            }],
+            call_data: Default::default(),
+        };
+
+        let archived = [
+            solution("a", 1., 1),
+            solution("a", 2., 2),
+            solution("b", 1., 1),
+        ];
+        assert!(diff_solutions(&archived, &archived).is_empty());
+
+        // Only the best solution of every solver is compared.
+        let replayed = [solution("a", 2., 2), solution("b", 1., 1)];
+        assert!(diff_solutions(&archived, &replayed).is_empty());
+
+        let replayed = [solution("a", 3., 3), solution("c", 1., 1)];
+        assert_eq!(
+            diff_solutions(&archived, &replayed),
+            vec![
+                "solver a surplus changed from 2 to 3".to_string(),
+                format!(
+                    "solver a prices changed from {:?} to {:?}",
+                    hashmap! { H160([1; 20]) => U256::from(2) },
+                    hashmap! { H160([1; 20]) => U256::from(3) },
+                ),
+                "solver b found no solution".to_string(),
+                "solver c found a new solution".to_string(),
+            ]
+        );
+    }
+
+    #[test]
+    fn snapshot_roundtrip() {
+        let snapshot = AuctionSnapshot {
+            auction: Auction {
+                id: 1,
+                block: 2,
+                ..Default::default()
+            },
+            liquidity: vec![ArchivedLiquidity::ConstantProduct(
+                ArchivedConstantProduct {
+                    tokens: [H160([1; 20]), H160([2; 20])],
+                    reserves: [3.into(), 4.into()],
+                    fee_numerator: 3,
+                    fee_denominator: 1000,
+                },
+            )],
+            competition: SolverCompetitionResponse {
+                auction_id: 1,
+                gas_price: 5.,
+                liquidity_collected_block: 2,
+                ..Default::default()
+            },
+        };
+        let json = serde_json::to_vec(&snapshot).unwrap();
+        assert_eq!(
+            serde_json::from_slice::<AuctionSnapshot>(&json).unwrap(),
+            snapshot
+        );
+    }
+
+    #[test]
+    fn removes_oldest_snapshots() {
+        let directory =
+            std::env::temp_dir().join(format!("auction_archive_test_{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let archive = AuctionArchive::new(directory.clone(), 2);
+        let snapshot = |id, gas_price| AuctionSnapshot {
+            auction: Auction {
+                id,
+                ..Default::default()
+            },
+            liquidity: Default::default(),
+            competition: SolverCompetitionResponse {
+                auction_id: id,
+                gas_price,
+                ..Default::default()
+            },
+        };
+
+        for id in [3, 1, 2] {
+            archive.write(&snapshot(id, 1.), true).unwrap();
+        }
+        // Not replacing keeps the existing snapshot.
+        archive.write(&snapshot(3, 2.), false).unwrap();
+
+        let mut files = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, ["2.json", "3.json"]);
+        assert_eq!(
+            read_auction_snapshot(&directory.join("3.json")).unwrap(),
+            snapshot(3, 1.)
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
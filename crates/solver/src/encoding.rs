@@ -22,6 +22,7 @@ pub type EncodedTrade = (
 /// Creates the data which the smart contract's `decodeTrade` expects.
 pub fn encode_trade(
     order: &OrderCreation,
+    owner: &H160,
     sell_token_index: usize,
     buy_token_index: usize,
     executed_amount: &U256,
@@ -37,7 +38,7 @@ pub fn encode_trade(
         order.fee_amount,
         order_flags(order),
         *executed_amount,
-        Bytes(order.signature.to_bytes().to_vec()),
+        Bytes(order.signature.encode_for_settlement(*owner)),
     )
 }
 
@@ -65,6 +66,7 @@ fn order_flags(order: &OrderCreation) -> U256 {
     result |= match order.signature.scheme() {
         SigningScheme::Eip712 => 0b00,
         SigningScheme::EthSign => 0b01,
+        SigningScheme::Eip1271 => 0b10,
         SigningScheme::PreSign => 0b11,
     } << 5;
     result.into()
@@ -172,6 +174,22 @@ mod tests {
                 // 11..... - Pre-sign signing scheme
                 0b1111111,
             ),
+            (
+                OrderCreation {
+                    kind: OrderKind::Sell,
+                    partially_fillable: false,
+                    sell_token_balance: SellTokenSource::Erc20,
+                    buy_token_balance: BuyTokenDestination::Erc20,
+                    signature: Signature::default_with(SigningScheme::Eip1271),
+                    ..Default::default()
+                },
+                // ......0 - sell order
+                // .....0. - fill-or-kill order
+                // ...00.. - ERC20 sell token balance
+                // ..0.... - ERC20 buy token balance
+                // 10..... - EIP-1271 signing scheme
+                0b1000000,
+            ),
         ] {
             assert_eq!(order_flags(order), U256::from(*flags));
         }
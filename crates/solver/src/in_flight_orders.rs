@@ -4,6 +4,7 @@ use model::{
     auction::Auction,
     order::{Order, OrderUid},
 };
+use primitive_types::U256;
 use shared::conversions::u256_to_big_uint;
 use std::collections::{BTreeMap, HashMap, HashSet};
 
@@ -14,13 +15,29 @@ struct PartiallyFilledOrder {
 }
 
 impl PartiallyFilledOrder {
-    pub fn order_with_remaining_amounts(&self) -> Order {
+    /// The order with the in flight trades executed. `available_balance` is the
+    /// balance the order book reported for the order, which the in flight
+    /// trades still have to be taken out of.
+    pub fn order_with_remaining_amounts(&self, available_balance: Option<U256>) -> Order {
         let mut updated_order = self.order.clone();
+        updated_order.metadata.available_balance = available_balance;
 
         for trade in &self.in_flight_trades {
-            updated_order.metadata.executed_buy_amount += u256_to_big_uint(&trade.buy_amount);
-            updated_order.metadata.executed_sell_amount += u256_to_big_uint(&trade.sell_amount);
-            updated_order.metadata.executed_fee_amount += trade.fee_amount;
+            let metadata = &mut updated_order.metadata;
+            metadata.executed_buy_amount += u256_to_big_uint(&trade.buy_amount);
+            metadata.executed_sell_amount +=
+                u256_to_big_uint(&trade.sell_amount) + u256_to_big_uint(&trade.fee_amount);
+            metadata.executed_sell_amount_before_fees = metadata
+                .executed_sell_amount_before_fees
+                .saturating_add(trade.sell_amount);
+            metadata.executed_fee_amount = metadata
+                .executed_fee_amount
+                .saturating_add(trade.fee_amount);
+            metadata.available_balance = metadata.available_balance.map(|balance| {
+                balance
+                    .saturating_sub(trade.sell_amount)
+                    .saturating_sub(trade.fee_amount)
+            });
         }
 
         updated_order
@@ -58,24 +75,32 @@ impl InFlightOrders {
         self.in_flight_trades
             .retain(|uid, _| in_flight.contains(uid));
 
-        auction.orders.iter_mut().for_each(|order| {
-            let uid = &order.metadata.uid;
-
+        auction
+            .orders
+            .iter_mut()
+            .filter(|order| order.creation.partially_fillable)
+            .for_each(|order| {
+                if let Some(trades) = self.in_flight_trades.get(&order.metadata.uid) {
+                    *order = trades.order_with_remaining_amounts(order.metadata.available_balance);
+                }
+            });
+        auction.orders.retain(|order| {
             if order.creation.partially_fillable {
-                if let Some(trades) = self.in_flight_trades.get(uid) {
-                    *order = trades.order_with_remaining_amounts();
+                // Executed amounts can exceed the limit amounts because of surplus so only the
+                // remaining amounts tell whether a partially fillable order is used up. The order
+                // is also used up once in flight trades took all of its available balance.
+                match order.executable_amounts() {
+                    Ok(remaining) => {
+                        !remaining.sell_amount.is_zero() && !remaining.buy_amount.is_zero()
+                    }
+                    Err(_) => false,
                 }
-            } else if in_flight.contains(uid) {
-                // fill-or-kill orders can only be used once and there is already a trade in flight
-                // for this one => Modify it such that it gets filtered out in the next step.
-                order.metadata.executed_buy_amount = u256_to_big_uint(&order.creation.buy_amount);
+            } else {
+                // fill-or-kill orders can only be used once and there might already be a trade
+                // in flight for this one.
+                !in_flight.contains(&order.metadata.uid)
             }
         });
-        auction.orders.retain(|order| {
-            u256_to_big_uint(&order.creation.buy_amount) > order.metadata.executed_buy_amount
-                && u256_to_big_uint(&order.creation.sell_amount)
-                    > order.metadata.executed_sell_amount
-        });
     }
 
     /// Tracks all in_flight orders and how much of the executable amount of partially fillable
@@ -135,6 +160,9 @@ mod tests {
         partially_fillable_1.metadata.uid = OrderUid::from_integer(2);
         partially_fillable_1.metadata.executed_buy_amount = 30u8.into();
         partially_fillable_1.metadata.executed_sell_amount = 30u8.into();
+        partially_fillable_1
+            .metadata
+            .executed_sell_amount_before_fees = 30u8.into();
 
         // a different partially fillable order 30% filled
         let mut partially_fillable_2 = partially_fillable_1.clone();
@@ -215,6 +243,10 @@ mod tests {
         assert_eq!(filtered[1].metadata.uid, OrderUid::from_integer(3));
         assert_eq!(filtered[1].metadata.executed_buy_amount, 50u8.into());
         assert_eq!(filtered[1].metadata.executed_sell_amount, 50u8.into());
+        assert_eq!(
+            filtered[1].metadata.executed_sell_amount_before_fees,
+            50u8.into()
+        );
         // drop order 3 because in flight orders filled the remaining executable amount
 
         auction.block = 1;
@@ -232,4 +264,77 @@ mod tests {
         // is nothing left to filter solvable orders by => keep all orders unaltered
         assert_eq!(filtered.len(), 4);
     }
+
+    #[test]
+    fn keeps_partially_fillable_orders_with_surplus() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+
+        let order = Order {
+            creation: OrderCreation {
+                sell_token: token0,
+                buy_token: token1,
+                sell_amount: 100u8.into(),
+                buy_amount: 100u8.into(),
+                kind: OrderKind::Sell,
+                partially_fillable: true,
+                ..Default::default()
+            },
+            metadata: OrderMetadata {
+                uid: OrderUid::from_integer(1),
+                ..Default::default()
+            },
+        };
+
+        // Half of the order gets executed at a price twice as good as the limit price so the
+        // executed buy amount reaches the order's buy amount.
+        let user_trades = vec![OrderTrade {
+            trade: Trade {
+                order: order.clone(),
+                executed_amount: 50u8.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        }];
+        let prices = hashmap! {token0 => 2u8.into(), token1 => 1u8.into()};
+        let settlement = Settlement {
+            encoder: SettlementEncoder::with_trades(prices, user_trades, Vec::new()),
+        };
+
+        let mut inflight = InFlightOrders::default();
+        inflight.mark_settled_orders(1, &settlement);
+        let mut auction = Auction {
+            orders: vec![order.clone()],
+            ..Default::default()
+        };
+        inflight.update_and_filter(&mut auction);
+
+        assert_eq!(auction.orders.len(), 1);
+        assert_eq!(auction.orders[0].metadata.executed_buy_amount, 100u8.into());
+        assert_eq!(
+            auction.orders[0].remaining_amounts().unwrap().sell_amount,
+            50u8.into()
+        );
+
+        // The in flight trade still has to be paid from the balance the order book reported.
+        let mut auction = Auction {
+            orders: vec![Order {
+                metadata: OrderMetadata {
+                    available_balance: Some(60u8.into()),
+                    ..order.metadata.clone()
+                },
+                ..order.clone()
+            }],
+            ..Default::default()
+        };
+        inflight.update_and_filter(&mut auction);
+        assert_eq!(
+            auction.orders[0].metadata.available_balance,
+            Some(10u8.into())
+        );
+        assert_eq!(
+            auction.orders[0].executable_amounts().unwrap().sell_amount,
+            10u8.into()
+        );
+    }
 }
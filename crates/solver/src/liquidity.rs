@@ -13,6 +13,7 @@ use model::order::Order;
 use model::{order::OrderKind, TokenPair};
 use num::{rational::Ratio, BigRational};
 use primitive_types::{H160, U256};
+use serde::{Deserialize, Serialize};
 use shared::sources::balancer_v2::{
     pool_fetching::{AmplificationParameter, TokenState, WeightedTokenState},
     swap::fixed_point::Bfp,
@@ -64,7 +65,7 @@ where
     fn encode(&self, execution: L::Execution, encoder: &mut SettlementEncoder) -> Result<()>;
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Exchange {
     GnosisProtocol,
     ZeroEx,
@@ -92,6 +93,9 @@ pub struct LimitOrder {
     /// perspective.
     pub scaled_unsubsidized_fee: U256,
     pub is_liquidity_order: bool,
+    /// The sum of the gas limits of the order's hooks. The hooks are executed
+    /// as part of the settlement so they add to its cost.
+    pub hook_gas_limit: u64,
     #[cfg_attr(test, derivative(PartialEq = "ignore"))]
     pub settlement_handling: Arc<dyn SettlementHandling<Self>>,
     pub exchange: Exchange,
@@ -144,6 +148,7 @@ impl Default for LimitOrder {
             scaled_unsubsidized_fee: Default::default(),
             settlement_handling: tests::CapturingSettlementHandler::arc(),
             is_liquidity_order: false,
+            hook_gas_limit: Default::default(),
             id: Default::default(),
             exchange: Exchange::GnosisProtocol,
         }
@@ -1,9 +1,9 @@
 use super::{Exchange, LimitOrder, SettlementHandling};
 use crate::{interactions::UnwrapWethInteraction, settlement::SettlementEncoder};
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use contracts::WETH9;
 use ethcontract::U256;
-use model::order::{Order, BUY_ETH_ADDRESS};
+use model::order::{Order, OrderClass, OrderKind, BUY_ETH_ADDRESS};
 use std::sync::Arc;
 
 pub struct OrderConverter {
@@ -31,29 +31,73 @@ impl OrderConverter {
             order.creation.buy_token
         };
 
-        let remaining = order.remaining_amounts()?;
+        // Partially fillable orders whose owner can't pay for all of the
+        // remaining amounts are only given to solvers as far as the available
+        // balance goes.
+        let remaining = order.executable_amounts()?;
+        // The surplus fee of limit orders is for all of the remaining amounts,
+        // so solvers only get the part of it that the balance covers.
+        let surplus_fee = {
+            let unlimited = order.remaining_amounts()?;
+            if remaining.sell_amount == unlimited.sell_amount {
+                order.metadata.surplus_fee
+            } else {
+                order
+                    .metadata
+                    .surplus_fee
+                    .checked_mul(remaining.sell_amount)
+                    .and_then(|product| product.checked_div(unlimited.sell_amount))
+                    .context("overflow prorating surplus fee")?
+            }
+        };
 
         // The reported fee amount that is used for objective computation is the
-        // order's full full amount scaled by a constant factor.
-        let scaled_fee_amount = U256::from_f64_lossy(
-            remaining.full_fee_amount.to_f64_lossy() * self.fee_objective_scaling_factor,
-        );
+        // order's full fee amount scaled by a constant factor.
+        let scale_fee = |fee: U256| {
+            U256::from_f64_lossy(fee.to_f64_lossy() * self.fee_objective_scaling_factor)
+        };
+        // Solvers get the fee for the remaining amounts of the order. The
+        // settlement on the other hand prorates the fee of the whole order
+        // with each fill (just like the settlement contract does with the
+        // signed fee) so it needs the fee of the whole order.
+        let scaled_fee_amount = scale_fee(remaining.full_fee_amount);
+        let scaled_full_fee_amount = scale_fee(order.metadata.full_fee_amount);
         let is_liquidity_order = order.metadata.is_liquidity_order;
+        let hook_gas_limit = order.metadata.hooks.gas_limit();
+        let (sell_amount, unscaled_subsidized_fee, scaled_unsubsidized_fee) =
+            match order.metadata.class {
+                OrderClass::Market => (
+                    remaining.sell_amount,
+                    remaining.fee_amount,
+                    scaled_fee_amount,
+                ),
+                // The surplus fee of limit orders is taken from the sell
+                // amount, so solvers see it like the fee of a market order.
+                OrderClass::Limit => (
+                    remaining
+                        .sell_amount
+                        .checked_sub(surplus_fee)
+                        .context("surplus fee exceeds remaining sell amount")?,
+                    surplus_fee,
+                    surplus_fee,
+                ),
+            };
         Ok(LimitOrder {
             id: order.metadata.uid.to_string(),
             sell_token: order.creation.sell_token,
             buy_token,
-            sell_amount: remaining.sell_amount,
+            sell_amount,
             buy_amount: remaining.buy_amount,
             kind: order.creation.kind,
             partially_fillable: order.creation.partially_fillable,
-            unscaled_subsidized_fee: remaining.fee_amount,
-            scaled_unsubsidized_fee: scaled_fee_amount,
+            unscaled_subsidized_fee,
+            scaled_unsubsidized_fee,
             is_liquidity_order,
+            hook_gas_limit,
             settlement_handling: Arc::new(OrderSettlementHandler {
                 order,
                 native_token,
-                scaled_unsubsidized_fee_amount: scaled_fee_amount,
+                scaled_unsubsidized_fee_amount: scaled_full_fee_amount,
                 is_liquidity_order,
             }),
             exchange: Exchange::GnosisProtocol,
@@ -68,6 +112,34 @@ struct OrderSettlementHandler {
     is_liquidity_order: bool,
 }
 
+impl OrderSettlementHandler {
+    /// Computes the executed amount as expected by the settlement contract and
+    /// the part of the surplus fee that is charged for an execution of a limit
+    /// order. The surplus fee is prorated with the executed amount just like
+    /// the signed fee in the settlement contract.
+    fn limit_order_execution(&self, executed_amount: U256) -> Result<(U256, U256)> {
+        let remaining = self.order.remaining_amounts()?;
+        let surplus_fee = self.order.metadata.surplus_fee;
+        let prorate = |total: U256| -> Result<U256> {
+            surplus_fee
+                .checked_mul(executed_amount)
+                .and_then(|product| product.checked_div(total))
+                .context("overflow computing surplus fee")
+        };
+        match self.order.creation.kind {
+            OrderKind::Sell => {
+                // Solvers only see the sell amount without the surplus fee.
+                let fee = prorate(remaining.sell_amount.saturating_sub(surplus_fee))?;
+                let executed_amount = executed_amount
+                    .checked_add(fee)
+                    .context("overflow computing executed amount")?;
+                Ok((executed_amount, fee))
+            }
+            OrderKind::Buy => Ok((executed_amount, prorate(remaining.buy_amount)?)),
+        }
+    }
+}
+
 impl SettlementHandling<LimitOrder> for OrderSettlementHandler {
     fn encode(&self, executed_amount: U256, encoder: &mut SettlementEncoder) -> Result<()> {
         let is_native_token_buy_order = self.order.creation.buy_token == BUY_ETH_ADDRESS;
@@ -79,17 +151,21 @@ impl SettlementHandling<LimitOrder> for OrderSettlementHandler {
             encoder.add_token_equivalency(self.native_token.address(), BUY_ETH_ADDRESS)?;
         }
 
-        let trade = match self.is_liquidity_order {
-            true => encoder.add_liquidity_order_trade(
+        let trade = match (self.is_liquidity_order, self.order.metadata.class) {
+            (true, _) => encoder.add_liquidity_order_trade(
                 self.order.clone(),
                 executed_amount,
                 self.scaled_unsubsidized_fee_amount,
             )?,
-            false => encoder.add_trade(
+            (false, OrderClass::Market) => encoder.add_trade(
                 self.order.clone(),
                 executed_amount,
                 self.scaled_unsubsidized_fee_amount,
             )?,
+            (false, OrderClass::Limit) => {
+                let (executed_amount, surplus_fee) = self.limit_order_execution(executed_amount)?;
+                encoder.add_limit_order_trade(self.order.clone(), executed_amount, surplus_fee)?
+            }
         };
 
         if is_native_token_buy_order {
@@ -110,7 +186,7 @@ pub mod tests {
     use ethcontract::H160;
     use maplit::hashmap;
     use model::order::{OrderCreation, OrderKind, OrderMetadata};
-    use shared::dummy_contract;
+    use shared::{conversions::U256Ext as _, dummy_contract};
 
     #[test]
     fn eth_buy_liquidity_is_assigned_to_weth() {
@@ -358,4 +434,151 @@ pub mod tests {
         assert_eq!(order.unscaled_subsidized_fee, 15.into());
         assert_eq!(order.scaled_unsubsidized_fee, 30.into());
     }
+
+    #[test]
+    fn limits_partially_fillable_orders_to_available_balance() {
+        let sell_token = H160([0x01; 20]);
+        let buy_token = H160([0x02; 20]);
+        let converter = OrderConverter::test(H160::default());
+        let order = converter
+            .normalize_limit_order(Order {
+                creation: OrderCreation {
+                    sell_token,
+                    buy_token,
+                    sell_amount: 80.into(),
+                    buy_amount: 40.into(),
+                    fee_amount: 20.into(),
+                    kind: OrderKind::Sell,
+                    partially_fillable: true,
+                    ..Default::default()
+                },
+                metadata: OrderMetadata {
+                    full_fee_amount: 20.into(),
+                    available_balance: Some(50.into()),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        assert_eq!(order.sell_amount, 40.into());
+        assert_eq!(order.buy_amount, 20.into());
+        assert_eq!(order.unscaled_subsidized_fee, 10.into());
+
+        let mut encoder = SettlementEncoder::new(hashmap! {
+            sell_token => U256::from(1),
+            buy_token => U256::from(2),
+        });
+        // Executing more than the balance covers would revert on transfer.
+        assert!(order
+            .settlement_handling
+            .encode(41.into(), &mut encoder)
+            .is_err());
+        order
+            .settlement_handling
+            .encode(40.into(), &mut encoder)
+            .unwrap();
+    }
+
+    #[test]
+    fn prorates_full_fee_for_fills_of_partially_filled_orders() {
+        let sell_token = H160([0x01; 20]);
+        let buy_token = H160([0x02; 20]);
+        let converter = OrderConverter {
+            fee_objective_scaling_factor: 1.5,
+            ..OrderConverter::test(H160::default())
+        };
+        let order = converter
+            .normalize_limit_order(Order {
+                creation: OrderCreation {
+                    sell_token,
+                    buy_token,
+                    sell_amount: 10.into(),
+                    buy_amount: 20.into(),
+                    fee_amount: 30.into(),
+                    kind: OrderKind::Sell,
+                    partially_fillable: true,
+                    ..Default::default()
+                },
+                metadata: OrderMetadata {
+                    executed_sell_amount_before_fees: 5.into(),
+                    full_fee_amount: 40.into(),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+
+        let mut encoder = SettlementEncoder::new(hashmap! {
+            sell_token => U256::from(2),
+            buy_token => U256::from(1),
+        });
+        order
+            .settlement_handling
+            .encode(4.into(), &mut encoder)
+            .unwrap();
+        // Filling more than the remaining amount is not allowed.
+        assert!(order
+            .settlement_handling
+            .encode(6.into(), &mut encoder)
+            .is_err());
+
+        let trade = &encoder.order_trades()[0].trade;
+        assert_eq!(trade.scaled_unsubsidized_fee, 60.into());
+        // 4 out of the 10 sell tokens of the order are executed.
+        assert_eq!(trade.executed_scaled_unsubsidized_fee(), Some(24.into()));
+    }
+
+    #[test]
+    fn limit_orders_pay_surplus_fee_from_sell_amount() {
+        let sell_token = H160([0x01; 20]);
+        let buy_token = H160([0x02; 20]);
+        let converter = OrderConverter::test(H160::default());
+        let order = converter
+            .normalize_limit_order(Order {
+                creation: OrderCreation {
+                    sell_token,
+                    buy_token,
+                    sell_amount: 100.into(),
+                    buy_amount: 100.into(),
+                    kind: OrderKind::Sell,
+                    ..Default::default()
+                },
+                metadata: OrderMetadata {
+                    class: OrderClass::Limit,
+                    surplus_fee: 10.into(),
+                    ..Default::default()
+                },
+            })
+            .unwrap();
+        assert_eq!(order.sell_amount, 90.into());
+        assert_eq!(order.buy_amount, 100.into());
+        assert_eq!(order.unscaled_subsidized_fee, 10.into());
+        assert_eq!(order.scaled_unsubsidized_fee, 10.into());
+
+        let sell_price = U256::exp10(18) * 2;
+        let buy_price = U256::exp10(18);
+        let mut encoder = SettlementEncoder::new(hashmap! {
+            sell_token => sell_price,
+            buy_token => buy_price,
+        });
+        order
+            .settlement_handling
+            .encode(90.into(), &mut encoder)
+            .unwrap();
+
+        let trade = &encoder.order_trades()[0].trade;
+        // The settlement contract transfers the whole sell amount including
+        // the surplus fee.
+        assert_eq!(trade.executed_amount, 100.into());
+        assert_eq!(trade.surplus_fee, 10.into());
+        assert_eq!(trade.executed_scaled_unsubsidized_fee(), Some(10.into()));
+
+        let settlement = encoder.finish();
+        assert_eq!(settlement.tokens, [sell_token, buy_token, buy_token]);
+        let custom_buy_price = settlement.clearing_prices[2];
+        assert_eq!(custom_buy_price, U256::from(1_111_111_111_111_111_112u128));
+        assert_eq!(settlement.trades[0].1, 2.into());
+        assert_eq!(settlement.trades[0].9, 100.into());
+        // The trader receives what the 90 sell tokens without fee are worth.
+        let buy_amount = (trade.executed_amount * sell_price).ceil_div(&custom_buy_price);
+        assert_eq!(buy_amount, 180.into());
+    }
 }
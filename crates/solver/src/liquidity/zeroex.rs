@@ -79,6 +79,7 @@ impl ZeroExLiquidity {
             unscaled_subsidized_fee: U256::zero(),
             scaled_unsubsidized_fee: U256::zero(),
             is_liquidity_order: true,
+            hook_gas_limit: 0,
             settlement_handling: Arc::new(OrderSettlementHandler {
                 order: record.order,
                 zeroex: self.zeroex.clone(),
@@ -1,10 +1,11 @@
 use clap::{ArgEnum, Parser};
-use contracts::{BalancerV2Vault, IUniswapLikeRouter, WETH9};
+use contracts::IUniswapLikeRouter;
 use ethcontract::H160;
 use num::rational::Ratio;
 use reqwest::Url;
 use shared::{
     baseline_solver::BaseTokens,
+    chain_spec::{self, read_chain_spec},
     current_block::current_block_stream,
     maintenance::{Maintaining, ServiceMaintenance},
     metrics::{serve_metrics, setup_metrics_registry},
@@ -22,7 +23,10 @@ use shared::{
     zeroex_api::DefaultZeroExApi,
 };
 use solver::{
-    driver::Driver,
+    driver::{
+        auction_archive::{read_auction_snapshot, AuctionArchive},
+        replay_auction, Driver,
+    },
     liquidity::{
         balancer_v2::BalancerV2Liquidity, order_converter::OrderConverter,
         uniswap_v2::UniswapLikeLiquidity, zeroex::ZeroExLiquidity,
@@ -42,7 +46,7 @@ use solver::{
     },
     solver::{ExternalSolverArg, SolverAccountArg, SolverType},
 };
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{collections::HashMap, path::PathBuf, sync::Arc, time::Duration};
 
 #[derive(Debug, Parser)]
 struct Arguments {
@@ -53,6 +57,14 @@ struct Arguments {
     #[clap(long, env, default_value = "http://localhost:8080")]
     orderbook_url: Url,
 
+    /// Subscribe to the auction updates of the orderbook instead of polling the current auction
+    /// in every run loop. New auctions start a run loop right away instead of after the settle
+    /// interval.
+    /// Only enable this when the orderbook serves the auction stream, otherwise the driver falls
+    /// back to polling while it keeps retrying to subscribe.
+    #[clap(long, env, parse(try_from_str), default_value = "false")]
+    stream_auctions: bool,
+
     /// The API endpoint to call the mip solver
     #[clap(long, env, default_value = "http://localhost:8000")]
     mip_solver_url: Url,
@@ -304,6 +316,23 @@ struct Arguments {
     /// How pending transactions should be fetched.
     #[clap(long, env, arg_enum, default_value = "ignore")]
     pending_transaction_config: PendingTransactionConfig,
+
+    /// Directory in which a snapshot of every auction is stored as
+    /// `<auction id>.json`. The snapshots can be replayed with
+    /// `--replay-auction`.
+    #[clap(long, env)]
+    auction_archive_dir: Option<PathBuf>,
+
+    /// The maximum number of auction snapshots to keep in the archive. The
+    /// snapshots of the oldest auctions are removed first.
+    #[clap(long, env, default_value = "10000")]
+    auction_archive_max_snapshots: usize,
+
+    /// Instead of running the driver, run the configured solvers on the
+    /// archived auction at this path with its archived liquidity and log how
+    /// the outcome differs from the archived one.
+    #[clap(long, env)]
+    replay_auction: Option<PathBuf>,
 }
 
 #[derive(Copy, Clone, Debug, clap::ArgEnum)]
@@ -346,22 +375,131 @@ async fn main() {
         .version()
         .await
         .expect("failed to get network id");
-    let network_name = network_name(&network_id, chain_id);
-    let settlement_contract = solver::get_settlement_contract(&web3)
+    let chain_spec = args
+        .shared
+        .chain_spec
+        .as_deref()
+        .map(|path| read_chain_spec(path).expect("failed to load chain spec"));
+    if let Some(spec) = &chain_spec {
+        spec.verify_chain_id(chain_id)
+            .expect("chain spec doesn't match the node");
+    }
+    let network_name = chain_spec
+        .as_ref()
+        .and_then(|spec| spec.network_name.clone())
+        .unwrap_or_else(|| network_name(&network_id, chain_id).to_string());
+    let settlement_contract = chain_spec::settlement_contract(&web3, chain_spec.as_ref())
         .await
         .expect("couldn't load deployed settlement");
-    let vault_contract = BalancerV2Vault::deployed(&web3).await.ok();
-    let native_token_contract = WETH9::deployed(&web3)
+    let vault_contract = chain_spec::vault(&web3, chain_spec.as_ref())
+        .await
+        .ok()
+        .flatten();
+    let native_token_contract = chain_spec::native_token(&web3, chain_spec.as_ref())
         .await
         .expect("couldn't load deployed native token");
+    let mut base_tokens = args.shared.base_tokens.clone();
+    if let Some(spec) = &chain_spec {
+        base_tokens.extend(spec.base_tokens.iter().copied());
+    }
     let base_tokens = Arc::new(BaseTokens::new(
         native_token_contract.address(),
-        &args.shared.base_tokens,
+        &base_tokens,
     ));
 
     let token_info_fetcher = Arc::new(CachedTokenInfoFetcher::new(Box::new(TokenInfoFetcher {
         web3: web3.clone(),
     })));
+    let solvers = {
+        if let Some(solver_accounts) = args.solver_accounts {
+            assert!(
+                solver_accounts.len() == args.solvers.len(),
+                "number of solvers ({}) does not match the number of accounts ({})",
+                args.solvers.len(),
+                solver_accounts.len()
+            );
+
+            solver_accounts
+                .into_iter()
+                .map(|account_arg| account_arg.into_account(chain_id))
+                .zip(args.solvers)
+                .collect()
+        } else if let Some(account_arg) = args.solver_account {
+            std::iter::repeat(account_arg.into_account(chain_id))
+                .zip(args.solvers)
+                .collect()
+        } else {
+            panic!("either SOLVER_ACCOUNTS or SOLVER_ACCOUNT must be set")
+        }
+    };
+
+    let zeroex_api = Arc::new(
+        DefaultZeroExApi::new(
+            args.shared
+                .zeroex_url
+                .as_deref()
+                .unwrap_or(DefaultZeroExApi::DEFAULT_URL),
+            args.shared.zeroex_api_key,
+            client.clone(),
+        )
+        .unwrap(),
+    );
+
+    let solver = solver::solver::create(
+        web3.clone(),
+        solvers,
+        base_tokens.clone(),
+        native_token_contract.address(),
+        args.mip_solver_url,
+        args.cow_dex_ag_solver_url,
+        args.quasimodo_solver_url,
+        args.balancer_sor_url,
+        &settlement_contract,
+        vault_contract.as_ref(),
+        token_info_fetcher.clone(),
+        network_name,
+        chain_id,
+        args.shared.disabled_one_inch_protocols,
+        args.paraswap_slippage_bps,
+        args.shared.disabled_paraswap_dexs,
+        args.shared.paraswap_partner,
+        client.clone(),
+        metrics.clone(),
+        zeroex_api.clone(),
+        args.zeroex_slippage_bps,
+        args.shared.quasimodo_uses_internal_buffers,
+        args.shared.mip_uses_internal_buffers,
+        args.shared.one_inch_url,
+        args.external_solvers.unwrap_or_default(),
+    )
+    .expect("failure creating solvers");
+
+    let order_converter = OrderConverter {
+        native_token: native_token_contract.clone(),
+        fee_objective_scaling_factor: args.fee_objective_scaling_factor,
+    };
+
+    if let Some(path) = args.replay_auction {
+        let snapshot = read_auction_snapshot(&path).expect("failed to read auction snapshot");
+        let diff = replay_auction(
+            snapshot,
+            &solver,
+            &order_converter,
+            args.max_merged_settlements,
+            args.solver_time_limit,
+            metrics.as_ref(),
+        )
+        .await
+        .expect("failed to replay auction");
+        if diff.is_empty() {
+            tracing::info!("replayed auction matches the archived one");
+        }
+        for difference in diff {
+            tracing::info!(%difference, "replayed auction differs from the archived one");
+        }
+        return;
+    }
+
     let gas_price_estimator = Arc::new(
         shared::gas_price_estimation::create_priority_estimator(
             client.clone(),
@@ -387,26 +525,43 @@ async fn main() {
         max_retries: args.shared.pool_cache_maximum_retries,
         delay_between_retries: args.shared.pool_cache_delay_between_retries_seconds,
     };
-    let baseline_sources = args.shared.baseline_sources.unwrap_or_else(|| {
-        sources::defaults_for_chain(chain_id).expect("failed to get default baseline sources")
-    });
+    let baseline_sources = args
+        .shared
+        .baseline_sources
+        .clone()
+        .or_else(|| chain_spec.as_ref()?.baseline_sources())
+        .unwrap_or_else(|| {
+            sources::defaults_for_chain(chain_id).expect("failed to get default baseline sources")
+        });
     tracing::info!(?baseline_sources, "using baseline sources");
+    let new_pool_cache = |pool_fetcher| {
+        let pool_cache = PoolCache::new(
+            cache_config,
+            pool_fetcher,
+            current_block_stream.clone(),
+            metrics.clone(),
+        )
+        .expect("failed to create pool cache");
+        Arc::new(pool_cache)
+    };
     let pool_caches: HashMap<BaselineSource, Arc<PoolCache>> =
         sources::uniswap_like_liquidity_sources(&web3, &baseline_sources)
             .await
             .expect("failed to load baseline source uniswap liquidity")
             .into_iter()
-            .map(|(source, (_, pool_fetcher))| {
-                let pool_cache = PoolCache::new(
-                    cache_config,
-                    pool_fetcher,
-                    current_block_stream.clone(),
-                    metrics.clone(),
-                )
-                .expect("failed to create pool cache");
-                (source, Arc::new(pool_cache))
-            })
+            .map(|(source, (_, pool_fetcher))| (source, new_pool_cache(pool_fetcher)))
             .collect();
+    let chain_spec_pool_caches: HashMap<String, (H160, Arc<PoolCache>)> = chain_spec
+        .iter()
+        .flat_map(|spec| &spec.uniswap_like_sources)
+        .map(|(name, source)| {
+            let router = source
+                .router
+                .unwrap_or_else(|| panic!("chain spec source {} has no router", name));
+            let (_, pool_fetcher) = source.liquidity_source(&web3);
+            (name.clone(), (router, new_pool_cache(pool_fetcher)))
+        })
+        .collect();
 
     let (balancer_pool_maintainer, balancer_v2_liquidity) =
         if baseline_sources.contains(&BaselineSource::BalancerV2) {
@@ -442,77 +597,22 @@ async fn main() {
             (None, None)
         };
 
-    let uniswap_like_liquidity = build_amm_artifacts(
+    let mut uniswap_like_liquidity = build_amm_artifacts(
         &pool_caches,
         settlement_contract.clone(),
         base_tokens.clone(),
         web3.clone(),
     )
     .await;
-
-    let solvers = {
-        if let Some(solver_accounts) = args.solver_accounts {
-            assert!(
-                solver_accounts.len() == args.solvers.len(),
-                "number of solvers ({}) does not match the number of accounts ({})",
-                args.solvers.len(),
-                solver_accounts.len()
-            );
-
-            solver_accounts
-                .into_iter()
-                .map(|account_arg| account_arg.into_account(chain_id))
-                .zip(args.solvers)
-                .collect()
-        } else if let Some(account_arg) = args.solver_account {
-            std::iter::repeat(account_arg.into_account(chain_id))
-                .zip(args.solvers)
-                .collect()
-        } else {
-            panic!("either SOLVER_ACCOUNTS or SOLVER_ACCOUNT must be set")
-        }
-    };
-
-    let zeroex_api = Arc::new(
-        DefaultZeroExApi::new(
-            args.shared
-                .zeroex_url
-                .as_deref()
-                .unwrap_or(DefaultZeroExApi::DEFAULT_URL),
-            args.shared.zeroex_api_key,
-            client.clone(),
+    uniswap_like_liquidity.extend(chain_spec_pool_caches.values().map(|(router, pool_cache)| {
+        UniswapLikeLiquidity::new(
+            IUniswapLikeRouter::at(&web3, *router),
+            settlement_contract.clone(),
+            base_tokens.clone(),
+            web3.clone(),
+            pool_cache.clone(),
         )
-        .unwrap(),
-    );
-
-    let solver = solver::solver::create(
-        web3.clone(),
-        solvers,
-        base_tokens.clone(),
-        native_token_contract.address(),
-        args.mip_solver_url,
-        args.cow_dex_ag_solver_url,
-        args.quasimodo_solver_url,
-        args.balancer_sor_url,
-        &settlement_contract,
-        vault_contract.as_ref(),
-        token_info_fetcher,
-        network_name.to_string(),
-        chain_id,
-        args.shared.disabled_one_inch_protocols,
-        args.paraswap_slippage_bps,
-        args.shared.disabled_paraswap_dexs,
-        args.shared.paraswap_partner,
-        client.clone(),
-        metrics.clone(),
-        zeroex_api.clone(),
-        args.zeroex_slippage_bps,
-        args.shared.quasimodo_uses_internal_buffers,
-        args.shared.mip_uses_internal_buffers,
-        args.shared.one_inch_url,
-        args.external_solvers.unwrap_or_default(),
-    )
-    .expect("failure creating solvers");
+    }));
 
     let zeroex_liquidity = if baseline_sources.contains(&BaselineSource::ZeroEx) {
         Some(ZeroExLiquidity {
@@ -622,11 +722,10 @@ async fn main() {
         transaction_strategies,
         access_list_estimator,
     };
-    let api = OrderBookApi::new(args.orderbook_url, client.clone());
-    let order_converter = OrderConverter {
-        native_token: native_token_contract.clone(),
-        fee_objective_scaling_factor: args.fee_objective_scaling_factor,
-    };
+    let mut api = OrderBookApi::new(args.orderbook_url, client.clone());
+    if args.stream_auctions {
+        api = api.with_auction_stream();
+    }
     let tenderly = args
         .tenderly_url
         .zip(args.tenderly_api_key)
@@ -659,11 +758,18 @@ async fn main() {
         args.token_list_restriction_for_price_checks.into(),
         tenderly,
     );
+    if let Some(directory) = args.auction_archive_dir {
+        driver = driver.with_auction_archive(AuctionArchive::new(
+            directory,
+            args.auction_archive_max_snapshots,
+        ));
+    }
 
     let maintainer = ServiceMaintenance {
         maintainers: pool_caches
-            .into_iter()
-            .map(|(_, cache)| cache as Arc<dyn Maintaining>)
+            .into_values()
+            .chain(chain_spec_pool_caches.into_values().map(|(_, cache)| cache))
+            .map(|cache| cache as Arc<dyn Maintaining>)
             .chain(balancer_pool_maintainer)
             .collect(),
     };
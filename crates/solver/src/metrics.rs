@@ -6,7 +6,8 @@ use anyhow::Result;
 use ethcontract::U256;
 use model::order::Order;
 use prometheus::{
-    Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts,
+    Gauge, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGauge,
+    IntGaugeVec, Opts,
 };
 use shared::{
     metrics::get_metrics_registry,
@@ -78,6 +79,7 @@ pub trait SolverMetrics: Send + Sync {
     fn complete_runloop_until_transaction(&self, duration: Duration);
     fn transaction_submission(&self, duration: Duration);
     fn transaction_gas_price(&self, gas_price: U256);
+    fn auction_started(&self, auction_id: u64);
 }
 
 // TODO add labeled interaction counter once we support more than one interaction
@@ -101,6 +103,7 @@ pub struct Metrics {
     complete_runloop_until_transaction: Histogram,
     transaction_submission: Histogram,
     transaction_gas_price_gwei: Gauge,
+    auction_id: IntGauge,
 }
 
 impl Metrics {
@@ -240,6 +243,9 @@ impl Metrics {
         let transaction_gas_price_gwei = Gauge::with_opts(opts).unwrap();
         registry.register(Box::new(transaction_gas_price_gwei.clone()))?;
 
+        let auction_id = IntGauge::new("auction_id", "Id of the auction currently being solved.")?;
+        registry.register(Box::new(auction_id.clone()))?;
+
         Ok(Self {
             trade_counter,
             order_settlement_time,
@@ -260,6 +266,7 @@ impl Metrics {
             transaction_submission,
             transaction_gas_price_gwei,
             settlement_access_list_saved_gas,
+            auction_id,
         })
     }
 }
@@ -405,6 +412,10 @@ impl SolverMetrics for Metrics {
             .set(gas_price.to_f64_lossy() / 1e9)
     }
 
+    fn auction_started(&self, auction_id: u64) {
+        self.auction_id.set(auction_id as i64)
+    }
+
     fn settlement_revertable_status(&self, status: Revertable, solver: &str) {
         let result = match status {
             Revertable::NoRisk => "no_risk",
@@ -475,6 +486,7 @@ impl SolverMetrics for NoopMetrics {
     fn complete_runloop_until_transaction(&self, _: Duration) {}
     fn transaction_submission(&self, _: Duration) {}
     fn transaction_gas_price(&self, _: U256) {}
+    fn auction_started(&self, _: u64) {}
 }
 
 #[cfg(test)]
@@ -1,24 +1,84 @@
-use anyhow::Result;
-use model::{auction::Auction, solver_competition::SolverCompetitionResponse};
+use anyhow::{Context, Result};
+use model::{
+    auction::{Auction, AuctionUpdate},
+    solver_competition::SolverCompetitionResponse,
+};
 use reqwest::{Client, Url};
+use std::{
+    sync::{Arc, Mutex, Weak},
+    time::{Duration, Instant},
+};
+use tokio::sync::Notify;
+
+/// Streamed auctions older than this are considered stale and the auction is
+/// polled instead.
+const MAX_STREAMED_AUCTION_AGE: Duration = Duration::from_secs(30);
+const STREAM_RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Default)]
+struct StreamedAuction {
+    /// The most recently streamed auction and when it was received.
+    auction: Mutex<Option<(Auction, Instant)>>,
+    /// Notified whenever a new auction is received.
+    updated: Notify,
+}
 
 pub struct OrderBookApi {
     base: Url,
     client: Client,
+    streamed_auction: Option<Arc<StreamedAuction>>,
 }
 
 impl OrderBookApi {
     /// base: protocol and host of the url. example: `https://example.com`
     pub fn new(base: Url, client: Client) -> Self {
-        Self { base, client }
+        Self {
+            base,
+            client,
+            streamed_auction: None,
+        }
+    }
+
+    /// Subscribes to the auction updates of the order book in the background
+    /// so that `get_auction` returns new auctions as soon as they are computed
+    /// instead of polling them.
+    pub fn with_auction_stream(mut self) -> Self {
+        let streamed_auction = Arc::new(StreamedAuction::default());
+        tokio::task::spawn(stream_auctions(
+            self.base.clone(),
+            Arc::downgrade(&streamed_auction),
+        ));
+        self.streamed_auction = Some(streamed_auction);
+        self
     }
 
     pub async fn get_auction(&self) -> Result<Auction> {
+        if let Some(auction) = self.streamed_auction() {
+            return Ok(auction);
+        }
         let url = self.base.join("api/v1/auction")?;
         let auction = self.client.get(url).send().await?.json().await?;
         Ok(auction)
     }
 
+    fn streamed_auction(&self) -> Option<Auction> {
+        let streamed_auction = self.streamed_auction.as_ref()?.auction.lock().unwrap();
+        let (auction, received) = streamed_auction.as_ref()?;
+        (received.elapsed() <= MAX_STREAMED_AUCTION_AGE).then(|| auction.clone())
+    }
+
+    /// Waits until a new auction is streamed or `timeout` passes. Returns
+    /// immediately if an auction was streamed since the last wait. Without
+    /// a stream this always waits for the whole timeout.
+    pub async fn wait_for_new_auction(&self, timeout: Duration) {
+        match &self.streamed_auction {
+            Some(streamed_auction) => {
+                let _ = tokio::time::timeout(timeout, streamed_auction.updated.notified()).await;
+            }
+            None => tokio::time::sleep(timeout).await,
+        }
+    }
+
     pub async fn send_solver_competition(
         &self,
         auction_id: u64,
@@ -37,10 +97,90 @@ impl OrderBookApi {
     }
 }
 
+/// Keeps the streamed auction up to date, reconnecting whenever the stream
+/// fails. Exits once the api has been dropped.
+async fn stream_auctions(base: Url, streamed_auction: Weak<StreamedAuction>) {
+    // The stream stays open indefinitely so it must not use a client with a
+    // request timeout.
+    let client = Client::new();
+    while streamed_auction.strong_count() > 0 {
+        match receive_auctions(&base, &client, &streamed_auction).await {
+            Ok(()) => tracing::debug!("auction stream ended"),
+            Err(err) => tracing::warn!(?err, "auction stream failed"),
+        }
+        tokio::time::sleep(STREAM_RECONNECT_INTERVAL).await;
+    }
+}
+
+async fn receive_auctions(
+    base: &Url,
+    client: &Client,
+    streamed_auction: &Weak<StreamedAuction>,
+) -> Result<()> {
+    let url = base.join("api/v1/auction/updates")?;
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let mut buffer = Vec::new();
+    while let Some(chunk) = response.chunk().await? {
+        buffer.extend_from_slice(&chunk);
+        for data in take_event_data(&mut buffer) {
+            let update: AuctionUpdate =
+                serde_json::from_str(&data).context("invalid auction update")?;
+            tracing::debug!(
+                id = update.auction.id,
+                block = update.auction.block,
+                added_orders = update.added_orders.len(),
+                removed_orders = update.removed_orders.len(),
+                "received auction update"
+            );
+            let streamed_auction = match streamed_auction.upgrade() {
+                Some(streamed_auction) => streamed_auction,
+                None => return Ok(()),
+            };
+            *streamed_auction.auction.lock().unwrap() = Some((update.auction, Instant::now()));
+            streamed_auction.updated.notify_one();
+        }
+    }
+    Ok(())
+}
+
+/// Removes all complete server-sent events from the buffer and returns their
+/// data. Events without data like keep-alive comments are skipped.
+fn take_event_data(buffer: &mut Vec<u8>) -> Vec<String> {
+    let mut events = Vec::new();
+    while let Some(end) = buffer.windows(2).position(|window| window == b"\n\n") {
+        let event = buffer.drain(..end + 2).collect::<Vec<_>>();
+        let data = String::from_utf8_lossy(&event)
+            .lines()
+            .filter_map(|line| line.strip_prefix("data:"))
+            .map(|data| data.strip_prefix(' ').unwrap_or(data))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if !data.is_empty() {
+            events.push(data);
+        }
+    }
+    events
+}
+
 #[cfg(test)]
 pub mod test_util {
     use super::*;
 
+    #[test]
+    fn takes_complete_event_data() {
+        let mut buffer =
+            b"event: auction\ndata: {\"id\":1}\n\n:\n\ndata: a\ndata: b\n\ndata: {\"id\"".to_vec();
+        assert_eq!(
+            take_event_data(&mut buffer),
+            vec!["{\"id\":1}".to_string(), "a\nb".to_string()]
+        );
+        assert_eq!(buffer, b"data: {\"id\"");
+
+        buffer.extend_from_slice(b":2}\n\n");
+        assert_eq!(take_event_data(&mut buffer), vec!["{\"id\":2}".to_string()]);
+        assert!(buffer.is_empty());
+    }
+
     // cargo test local_orderbook -- --ignored --nocapture
     #[tokio::test]
     #[ignore]
@@ -24,6 +24,11 @@ pub struct Trade {
     pub sell_token_index: usize,
     pub executed_amount: U256,
     pub scaled_unsubsidized_fee: U256,
+    /// The part of a limit order's surplus fee that is charged for this
+    /// execution. The fee is paid in the sell token on top of the uniform
+    /// clearing price execution. Since it isn't signed, the settlement contract
+    /// treats it as part of the executed sell amount.
+    pub surplus_fee: U256,
 }
 
 #[derive(Clone, Debug, Default, Eq, PartialEq)]
@@ -55,7 +60,7 @@ impl Trade {
         sell_token_price: &BigRational,
         buy_token_price: &BigRational,
     ) -> Option<BigRational> {
-        match self.order.creation.kind {
+        let surplus = match self.order.creation.kind {
             model::order::OrderKind::Buy => buy_order_surplus(
                 sell_token_price,
                 buy_token_price,
@@ -70,7 +75,14 @@ impl Trade {
                 &self.order.creation.buy_amount.to_big_rational(),
                 &self.executed_amount.to_big_rational(),
             ),
+        }?;
+        // The surplus fee is taken from what the user would have gotten at
+        // uniform clearing prices.
+        let surplus = surplus - self.surplus_fee.to_big_rational() * sell_token_price;
+        if surplus.is_negative() {
+            return None;
         }
+        Some(surplus)
     }
 
     pub fn surplus_ratio(
@@ -95,11 +107,13 @@ impl Trade {
     /// Returns the scaled unsubsidized fee amount that should be used for
     /// objective value computation.
     pub fn executed_scaled_unsubsidized_fee(&self) -> Option<U256> {
-        self.compute_fee_execution(self.scaled_unsubsidized_fee)
+        self.compute_fee_execution(self.scaled_unsubsidized_fee)?
+            .checked_add(self.surplus_fee)
     }
 
     pub fn executed_unscaled_subsidized_fee(&self) -> Option<U256> {
-        self.compute_fee_execution(self.order.creation.fee_amount)
+        self.compute_fee_execution(self.order.creation.fee_amount)?
+            .checked_add(self.surplus_fee)
     }
 
     fn compute_fee_execution(&self, fee_amount: U256) -> Option<U256> {
@@ -120,13 +134,17 @@ impl Trade {
             OrderKind::Sell => {
                 let sell_amount = self.executed_amount;
                 let buy_amount = sell_amount
+                    .checked_sub(self.surplus_fee)?
                     .checked_mul(sell_price)?
                     .checked_ceil_div(&buy_price)?;
                 (sell_amount, buy_amount)
             }
             OrderKind::Buy => {
                 let buy_amount = self.executed_amount;
-                let sell_amount = buy_amount.checked_mul(buy_price)?.checked_div(sell_price)?;
+                let sell_amount = buy_amount
+                    .checked_mul(buy_price)?
+                    .checked_div(sell_price)?
+                    .checked_add(self.surplus_fee)?;
                 (sell_amount, buy_amount)
             }
         };
@@ -146,13 +164,44 @@ impl OrderTrade {
     /// Encodes the settlement's order_trade as a tuple, as expected by the smart
     /// contract.
     pub fn encode(&self) -> EncodedTrade {
+        self.encode_with_buy_token_index(self.buy_token_index)
+    }
+
+    /// Encodes the order trade with a custom buy token index. This is used for
+    /// limit orders that pay a surplus fee and are therefore settled with a
+    /// buy token price that differs from the uniform clearing price.
+    pub fn encode_with_buy_token_index(&self, buy_token_index: usize) -> EncodedTrade {
         encoding::encode_trade(
             &self.trade.order.creation,
+            &self.trade.order.signer(),
             self.trade.sell_token_index,
-            self.buy_token_index,
+            buy_token_index,
             &self.trade.executed_amount,
         )
     }
+
+    /// Computes the buy token price at which the order has to be settled so
+    /// that the surplus fee is taken from the trader on top of the uniform
+    /// clearing prices.
+    ///
+    /// For sell orders the trader receives `(executed - fee) * sell_price /
+    /// buy_price` which the settlement contract computes as `executed *
+    /// sell_price / custom_buy_price`. For buy orders the trader pays
+    /// `executed * buy_price / sell_price + fee` which the contract computes
+    /// as `executed * custom_buy_price / sell_price`.
+    pub fn surplus_fee_buy_token_price(&self, sell_price: U256, buy_price: U256) -> Option<U256> {
+        let executed_amount = self.trade.executed_amount;
+        let fee = self.trade.surplus_fee;
+        match self.trade.order.creation.kind {
+            OrderKind::Sell => buy_price
+                .checked_mul(executed_amount)?
+                .checked_ceil_div(&executed_amount.checked_sub(fee)?),
+            OrderKind::Buy => buy_price.checked_add(
+                fee.checked_mul(sell_price)?
+                    .checked_ceil_div(&executed_amount)?,
+            ),
+        }
+    }
 }
 
 impl LiquidityOrderTrade {
@@ -162,6 +211,7 @@ impl LiquidityOrderTrade {
         let buy_token_index = clearing_price_vec_length + self.buy_token_offset_index;
         encoding::encode_trade(
             &self.trade.order.creation,
+            &self.trade.order.signer(),
             self.trade.sell_token_index,
             buy_token_index,
             &self.trade.executed_amount,
@@ -312,7 +362,12 @@ impl Settlement {
             .map(|execution| execution.expect("invalid trade was added to encoder"))
     }
 
-    // Computes the total surplus of all protocol trades (in wei ETH).
+    /// Computes the total surplus of all protocol trades (in wei ETH).
+    ///
+    /// Partially fillable orders need no special treatment: the surplus of a
+    /// trade only counts its executed amount. The rest of the order stays
+    /// open and contributes its surplus to the auction that executes it, so
+    /// counting it now would reward settlements for trades they don't make.
     pub fn total_surplus(&self, external_prices: &ExternalPrices) -> BigRational {
         match self.encoder.total_surplus(external_prices) {
             Some(value) => value,
@@ -729,6 +784,44 @@ pub mod tests {
         assert_eq!(execution.buy_amount, 5.into());
     }
 
+    #[test]
+    fn limit_order_executed_amounts_include_surplus_fee() {
+        let trade = |kind| Trade {
+            order: Order {
+                creation: OrderCreation {
+                    kind,
+                    sell_amount: 10.into(),
+                    buy_amount: 5.into(),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            executed_amount: 6.into(),
+            surplus_fee: 2.into(),
+            ..Default::default()
+        };
+        let sell_price = 1.into();
+        let buy_price = 1.into();
+
+        // Sell orders include the surplus fee in the executed amount.
+        let sell_trade = trade(OrderKind::Sell);
+        let execution = sell_trade.executed_amounts(sell_price, buy_price).unwrap();
+        assert_eq!(execution.sell_amount, 6.into());
+        assert_eq!(execution.buy_amount, 4.into());
+        assert_eq!(execution.fee_amount, 0.into());
+        // 6 sell tokens at the limit price would get 3 buy tokens.
+        assert_eq!(sell_trade.surplus(&r(1), &r(1)), Some(r(1)));
+
+        // Buy orders pay the surplus fee on top of the executed sell amount.
+        let buy_trade = trade(OrderKind::Buy);
+        let execution = buy_trade.executed_amounts(sell_price, buy_price).unwrap();
+        assert_eq!(execution.sell_amount, 8.into());
+        assert_eq!(execution.buy_amount, 6.into());
+        assert_eq!(execution.fee_amount, 0.into());
+        // 6 buy tokens at the limit price would cost 12 sell tokens.
+        assert_eq!(buy_trade.surplus(&r(1), &r(1)), Some(r(4)));
+    }
+
     #[test]
     fn trade_order_executed_amounts_overflow() {
         for kind in [OrderKind::Sell, OrderKind::Buy] {
@@ -762,6 +855,59 @@ pub mod tests {
         }
     }
 
+    #[test]
+    fn objective_of_partially_fillable_orders() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+
+        let order = Order {
+            creation: OrderCreation {
+                sell_token: token0,
+                buy_token: token1,
+                sell_amount: 10.into(),
+                buy_amount: 9.into(),
+                fee_amount: 4.into(),
+                kind: OrderKind::Sell,
+                partially_fillable: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let trade = |executed_amount: u64| OrderTrade {
+            trade: Trade {
+                order: order.clone(),
+                executed_amount: executed_amount.into(),
+                scaled_unsubsidized_fee: 6.into(),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+
+        // Surplus and fees only count for the executed part of the order.
+        let clearing_prices = hashmap! {token0 => 1.into(), token1 => 1.into()};
+        let external_prices = externalprices! { native_token: token0, token1 => r(1) };
+        let full_fill = test_settlement(clearing_prices.clone(), vec![trade(10)], vec![]);
+        let half_fill = test_settlement(clearing_prices, vec![trade(5)], vec![]);
+
+        assert_eq!(full_fill.total_surplus(&external_prices), r(1));
+        assert_eq!(
+            half_fill.total_surplus(&external_prices),
+            BigRational::new(1.into(), 2.into())
+        );
+        assert_eq!(
+            full_fill.total_unscaled_subsidized_fees(&external_prices),
+            r(4)
+        );
+        assert_eq!(
+            half_fill.total_unscaled_subsidized_fees(&external_prices),
+            r(2)
+        );
+        assert_eq!(
+            half_fill.total_scaled_unsubsidized_fees(&external_prices),
+            r(3)
+        );
+    }
+
     #[test]
     fn total_surplus() {
         let token0 = H160::from_low_u64_be(0);
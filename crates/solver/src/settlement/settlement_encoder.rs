@@ -1,10 +1,11 @@
 use super::{ExternalPrices, Interaction, LiquidityOrderTrade, OrderTrade, Trade, TradeExecution};
 use crate::{
-    encoding::{EncodedSettlement, EncodedTrade},
+    encoding::{EncodedInteraction, EncodedSettlement, EncodedTrade},
     interactions::UnwrapWethInteraction,
 };
 use anyhow::{bail, ensure, Context as _, Result};
-use model::order::{Order, OrderKind};
+use ethcontract::Bytes;
+use model::order::{Hook, Hooks, Order, OrderKind};
 use num::{BigRational, One, Zero};
 use primitive_types::{H160, U256};
 use shared::conversions::{big_rational_to_u256, U256Ext};
@@ -124,6 +125,36 @@ impl SettlementEncoder {
         order: Order,
         executed_amount: U256,
         scaled_unsubsidized_fee: U256,
+    ) -> Result<TradeExecution> {
+        self.add_order_trade(
+            order,
+            executed_amount,
+            scaled_unsubsidized_fee,
+            U256::zero(),
+        )
+    }
+
+    /// Adds a trade for a limit order that pays the specified surplus fee for
+    /// this execution. For sell orders the executed amount includes the fee.
+    ///
+    /// Limit orders are settled at uniform clearing prices but the surplus fee
+    /// is taken from the trader on top of that, which is encoded with a custom
+    /// buy token price for the trade.
+    pub fn add_limit_order_trade(
+        &mut self,
+        order: Order,
+        executed_amount: U256,
+        surplus_fee: U256,
+    ) -> Result<TradeExecution> {
+        self.add_order_trade(order, executed_amount, U256::zero(), surplus_fee)
+    }
+
+    fn add_order_trade(
+        &mut self,
+        order: Order,
+        executed_amount: U256,
+        scaled_unsubsidized_fee: U256,
+        surplus_fee: U256,
     ) -> Result<TradeExecution> {
         verify_executed_amount(&order, executed_amount)?;
         let sell_price = self
@@ -148,6 +179,7 @@ impl SettlementEncoder {
                 sell_token_index,
                 executed_amount,
                 scaled_unsubsidized_fee,
+                surplus_fee,
             },
             buy_token_index,
         };
@@ -155,6 +187,11 @@ impl SettlementEncoder {
             .trade
             .executed_amounts(*sell_price, *buy_price)
             .context("impossible trade execution")?;
+        if !surplus_fee.is_zero() {
+            order_trade
+                .surplus_fee_buy_token_price(*sell_price, *buy_price)
+                .context("impossible surplus fee execution")?;
+        }
 
         self.order_trades.push(order_trade);
         Ok(execution)
@@ -214,6 +251,7 @@ impl SettlementEncoder {
             sell_token_index,
             executed_amount,
             scaled_unsubsidized_fee,
+            surplus_fee: U256::zero(),
         };
         let liquidity_order_trade = LiquidityOrderTrade {
             trade,
@@ -406,11 +444,25 @@ impl SettlementEncoder {
             .collect();
         tokens.append(&mut liquidity_order_buy_tokens);
         clearing_prices.append(&mut liquidity_order_prices);
-        let mut trades: Vec<EncodedTrade> = self
-            .order_trades
-            .into_iter()
-            .map(|trade| trade.encode())
-            .collect();
+        let mut trades: Vec<EncodedTrade> = Vec::with_capacity(self.order_trades.len());
+        for order_trade in &self.order_trades {
+            if order_trade.trade.surplus_fee.is_zero() {
+                trades.push(order_trade.encode());
+                continue;
+            }
+            // Limit orders that pay a surplus fee get their own buy token
+            // price which is appended after the liquidity order prices.
+            let order = &order_trade.trade.order.creation;
+            let buy_token_price = order_trade
+                .surplus_fee_buy_token_price(
+                    self.clearing_prices[&order.sell_token],
+                    self.clearing_prices[&order.buy_token],
+                )
+                .expect("surplus fee execution was verified when adding the trade");
+            trades.push(order_trade.encode_with_buy_token_index(tokens.len()));
+            tokens.push(order.buy_token);
+            clearing_prices.push(buy_token_price);
+        }
         let mut liquidity_order_trades: Vec<EncodedTrade> = self
             .liquidity_order_trades
             .into_iter()
@@ -422,7 +474,7 @@ impl SettlementEncoder {
             clearing_prices,
             trades,
             interactions: [
-                Vec::new(),
+                self.encode_hooks(|hooks| &hooks.pre),
                 iter::empty()
                     .chain(
                         self.execution_plan
@@ -431,11 +483,22 @@ impl SettlementEncoder {
                     )
                     .chain(self.unwraps.iter().flat_map(|unwrap| unwrap.encode()))
                     .collect(),
-                Vec::new(),
+                self.encode_hooks(|hooks| &hooks.post),
             ],
         }
     }
 
+    /// Encodes the hooks of all traded orders in the order of their trades.
+    fn encode_hooks(&self, stage: impl Fn(&Hooks) -> &Vec<Hook>) -> Vec<EncodedInteraction> {
+        self.order_trades
+            .iter()
+            .map(|order_trade| &order_trade.trade)
+            .chain(self.liquidity_order_trades.iter().map(|trade| &trade.trade))
+            .flat_map(|trade| stage(&trade.order.metadata.hooks))
+            .map(|hook| (hook.target, U256::zero(), Bytes(hook.call_data.clone())))
+            .collect()
+    }
+
     // Merge other into self so that the result contains both settlements.
     // Fails if the settlements cannot be merged for example because the same limit order is used in
     // both or more than one token has a different clearing prices (a single token difference is scaled)
@@ -558,11 +621,12 @@ impl SettlementEncoder {
 }
 
 fn verify_executed_amount(order: &Order, executed_amount: U256) -> Result<()> {
+    let remaining = order.executable_amounts()?;
     let valid_executed_amount = match (order.creation.partially_fillable, order.creation.kind) {
-        (true, OrderKind::Sell) => executed_amount <= order.creation.sell_amount,
-        (true, OrderKind::Buy) => executed_amount <= order.creation.buy_amount,
-        (false, OrderKind::Sell) => executed_amount == order.creation.sell_amount,
-        (false, OrderKind::Buy) => executed_amount == order.creation.buy_amount,
+        (true, OrderKind::Sell) => executed_amount <= remaining.sell_amount,
+        (true, OrderKind::Buy) => executed_amount <= remaining.buy_amount,
+        (false, OrderKind::Sell) => executed_amount == remaining.sell_amount,
+        (false, OrderKind::Buy) => executed_amount == remaining.buy_amount,
     };
     ensure!(valid_executed_amount, "invalid executed amount");
     Ok(())
@@ -571,11 +635,10 @@ fn verify_executed_amount(order: &Order, executed_amount: U256) -> Result<()> {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    use crate::{encoding::EncodedInteraction, settlement::NoopInteraction};
+    use crate::settlement::NoopInteraction;
     use contracts::WETH9;
-    use ethcontract::Bytes;
     use maplit::hashmap;
-    use model::order::{OrderBuilder, OrderCreation};
+    use model::order::{OrderBuilder, OrderCreation, OrderMetadata};
     use shared::dummy_contract;
 
     #[test]
@@ -612,6 +675,76 @@ pub mod tests {
         assert!(settlement.add_trade(order1, 1.into(), 0.into()).is_ok());
     }
 
+    #[test]
+    fn encodes_order_hooks() {
+        let token0 = H160::from_low_u64_be(0);
+        let token1 = H160::from_low_u64_be(1);
+        let hook = |n: u8| Hook {
+            target: H160([n; 20]),
+            call_data: vec![n],
+            gas_limit: 0,
+        };
+        let order = |sell_token, buy_token, hooks| Order {
+            metadata: OrderMetadata {
+                hooks,
+                ..Default::default()
+            },
+            creation: OrderCreation {
+                sell_token,
+                sell_amount: 1.into(),
+                buy_token,
+                buy_amount: 1.into(),
+                ..Default::default()
+            },
+        };
+
+        let mut encoder = SettlementEncoder::new(hashmap! {
+            token0 => 1.into(),
+            token1 => 1.into(),
+        });
+        encoder
+            .add_trade(
+                order(
+                    token0,
+                    token1,
+                    Hooks {
+                        pre: vec![hook(1), hook(2)],
+                        post: vec![hook(3)],
+                    },
+                ),
+                1.into(),
+                0.into(),
+            )
+            .unwrap();
+        encoder
+            .add_trade(
+                order(token1, token0, Default::default()),
+                1.into(),
+                0.into(),
+            )
+            .unwrap();
+        encoder
+            .add_liquidity_order_trade(
+                order(
+                    token1,
+                    token0,
+                    Hooks {
+                        pre: vec![hook(4)],
+                        post: vec![],
+                    },
+                ),
+                1.into(),
+                0.into(),
+            )
+            .unwrap();
+
+        let encode = |n: u8| (H160([n; 20]), U256::zero(), Bytes(vec![n]));
+        let interactions = encoder.finish().interactions;
+        assert_eq!(interactions[0], vec![encode(1), encode(2), encode(4)]);
+        assert!(interactions[1].is_empty());
+        assert_eq!(interactions[2], vec![encode(3)]);
+    }
+
     #[test]
     fn settlement_merges_unwraps_for_same_token() {
         let weth = dummy_contract!(WETH9, [0x42; 20]);
@@ -894,7 +1027,8 @@ pub mod tests {
                         order: order12,
                         sell_token_index: 0,
                         executed_amount: 11.into(),
-                        scaled_unsubsidized_fee: 0.into()
+                        scaled_unsubsidized_fee: 0.into(),
+                        surplus_fee: 0.into(),
                     },
                     buy_token_offset_index: 0,
                     buy_token_price: 2.into(),
@@ -904,7 +1038,8 @@ pub mod tests {
                         order: order23,
                         sell_token_index: 1,
                         executed_amount: 11.into(),
-                        scaled_unsubsidized_fee: 0.into()
+                        scaled_unsubsidized_fee: 0.into(),
+                        surplus_fee: 0.into(),
                     },
                     buy_token_offset_index: 1,
                     buy_token_price: 3.into(),
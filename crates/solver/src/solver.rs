@@ -12,6 +12,7 @@ use contracts::{BalancerV2Vault, GPv2Settlement};
 use ethcontract::errors::ExecutionError;
 use ethcontract::{Account, PrivateKey, H160, U256};
 use http_solver::{buffers::BufferRetriever, HttpSolver};
+use model::auction::AuctionId;
 use naive_solver::NaiveSolver;
 use num::BigRational;
 use oneinch_solver::OneInchSolver;
@@ -69,12 +70,11 @@ pub trait Solver: Send + Sync + 'static {
 /// A batch auction for a solver to produce a settlement for.
 #[derive(Clone, Debug)]
 pub struct Auction {
-    /// An ID that identifies a batch within a `Driver` instance.
+    /// The ID of the auction as assigned by the order book.
     ///
-    /// Note that this ID is not unique across multiple instances of drivers,
-    /// in particular it cannot be used to uniquely identify batches across
-    /// service restarts.
-    pub id: u64,
+    /// It is globally unique and can be used to identify batches across
+    /// driver instances and service restarts.
+    pub id: AuctionId,
 
     /// The GPv2 orders to match.
     pub orders: Vec<LimitOrder>,
@@ -237,7 +237,7 @@ fn order_models(
             }
 
             let cost = match order.exchange {
-                Exchange::GnosisProtocol => gas_model.gp_order_cost(),
+                Exchange::GnosisProtocol => gas_model.gp_order_cost(order.hook_gas_limit),
                 Exchange::ZeroEx => gas_model.zeroex_order_cost(),
             };
 
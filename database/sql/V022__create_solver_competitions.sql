@@ -0,0 +1,9 @@
+-- Solver competition data of every auction as reported by the driver. Kept so that rewards and
+-- disputes can be analyzed long after the auction happened.
+CREATE TABLE solver_competitions (
+  id bigint PRIMARY KEY,
+  tx_hash bytea,
+  json jsonb NOT NULL
+);
+
+CREATE INDEX solver_competition_tx_hash ON solver_competitions USING HASH (tx_hash);
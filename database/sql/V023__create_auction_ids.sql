@@ -0,0 +1,3 @@
+-- Auction ids are handed out by the order book whenever it creates a new auction. Using a sequence
+-- makes them unique across restarts and across multiple order book instances sharing the database.
+CREATE SEQUENCE auction_ids;
@@ -0,0 +1,4 @@
+CREATE TYPE OrderClass AS ENUM ('market', 'limit');
+
+-- All existing orders signed their fee so they are market orders.
+ALTER TABLE orders ADD COLUMN class OrderClass NOT NULL DEFAULT 'market';
@@ -0,0 +1,22 @@
+CREATE TYPE OrderEventLabel AS ENUM ('created', 'ready', 'filtered', 'presigned', 'traded', 'cancelled');
+
+CREATE TYPE OrderEventReason AS ENUM (
+  'banned_user',
+  'unsupported_token',
+  'invalid_signature',
+  'insufficient_balance',
+  'missing_native_price',
+  'insufficient_surplus_fee',
+  'off_chain',
+  'on_chain'
+);
+
+-- Lifecycle transitions of orders so that we can explain why an order did or did not trade.
+CREATE TABLE order_events (
+  order_uid bytea NOT NULL,
+  timestamp timestamptz NOT NULL,
+  label OrderEventLabel NOT NULL,
+  reason OrderEventReason
+);
+
+CREATE INDEX order_events_by_uid ON order_events USING BTREE (order_uid, timestamp);
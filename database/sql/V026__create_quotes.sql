@@ -0,0 +1,19 @@
+-- Quotes handed out by the order book. Orders can reference the quote they were placed against so
+-- that the quoted fee is honoured while the quote has not expired.
+CREATE TABLE quotes (
+  id bigserial PRIMARY KEY,
+  sell_token bytea NOT NULL,
+  buy_token bytea NOT NULL,
+  sell_amount numeric(78,0) NOT NULL,
+  buy_amount numeric(78,0) NOT NULL,
+  fee_amount numeric(78,0) NOT NULL,
+  order_kind OrderKind NOT NULL,
+  gas_amount double precision NOT NULL,
+  gas_price double precision NOT NULL,
+  sell_token_price double precision NOT NULL,
+  expiration_timestamp timestamptz NOT NULL
+);
+
+CREATE INDEX quotes_by_expiration ON quotes USING BTREE (expiration_timestamp);
+
+ALTER TABLE orders ADD COLUMN quote_id bigint;
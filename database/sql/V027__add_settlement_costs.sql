@@ -0,0 +1,9 @@
+-- What settlements cost on chain, indexed from the transaction receipts after the settlement
+-- events are stored. NULL until the receipt has been fetched.
+ALTER TABLE settlements
+    ADD COLUMN gas_used numeric(78,0),
+    ADD COLUMN effective_gas_price numeric(78,0),
+    ADD COLUMN calldata_size bigint;
+
+-- Find the settlements whose costs still need to be indexed.
+CREATE INDEX settlements_without_costs ON settlements USING BTREE (block_number) WHERE gas_used IS NULL;
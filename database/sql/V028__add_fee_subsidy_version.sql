@@ -0,0 +1,8 @@
+-- The version of the fee subsidy configuration that was applied to the fee. Existing rows were
+-- created with the configuration from the command line arguments which has version 0.
+ALTER TABLE order_fee_parameters ADD COLUMN subsidy_version bigint NOT NULL DEFAULT 0;
+ALTER TABLE quotes ADD COLUMN subsidy_version bigint NOT NULL DEFAULT 0;
+-- We only wanted to have a default to make the migration easier. Every new insertion should supply
+-- the version.
+ALTER TABLE order_fee_parameters ALTER COLUMN subsidy_version DROP DEFAULT;
+ALTER TABLE quotes ALTER COLUMN subsidy_version DROP DEFAULT;
@@ -0,0 +1,12 @@
+-- Full app data documents for the `appData` hash that orders commit to. The parsed fields are stored
+-- next to the document so that they can be used for analytics without parsing json.
+CREATE TABLE app_data (
+  contract_app_data bytea PRIMARY KEY,
+  full_app_data bytea NOT NULL,
+  app_code text,
+  referrer bytea,
+  slippage_bips integer
+);
+
+CREATE INDEX app_data_by_app_code ON app_data USING BTREE (app_code);
+CREATE INDEX app_data_by_referrer ON app_data USING BTREE (referrer);
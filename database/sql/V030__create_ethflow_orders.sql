@@ -0,0 +1,35 @@
+-- Orders that the ETH-flow contract placed on-chain on behalf of a user selling native ETH. The
+-- order itself is stored in the orders table with the ETH-flow contract as its owner because that
+-- is the account whose funds are traded. The user's validity is stored here because the order
+-- that the contract signs never expires.
+CREATE TABLE ethflow_orders (
+    uid bytea PRIMARY KEY,
+    sender bytea NOT NULL,
+    valid_to bigint NOT NULL,
+    block_number bigint NOT NULL,
+    log_index bigint NOT NULL
+);
+
+CREATE INDEX ethflow_orders_sender ON ethflow_orders USING BTREE (sender);
+CREATE INDEX ethflow_orders_block_number ON ethflow_orders USING BTREE (block_number);
+
+-- OrderInvalidation events of the ETH-flow contract.
+CREATE TABLE ethflow_invalidations (
+    block_number bigint NOT NULL,
+    log_index bigint NOT NULL,
+    order_uid bytea NOT NULL,
+    PRIMARY KEY (block_number, log_index)
+);
+
+CREATE INDEX ethflow_invalidations_order_uid ON ethflow_invalidations USING BTREE (order_uid);
+
+-- OrderRefund events of the ETH-flow contract.
+CREATE TABLE ethflow_refunds (
+    block_number bigint NOT NULL,
+    log_index bigint NOT NULL,
+    order_uid bytea NOT NULL,
+    tx_hash bytea NOT NULL,
+    PRIMARY KEY (block_number, log_index)
+);
+
+CREATE INDEX ethflow_refunds_order_uid ON ethflow_refunds USING BTREE (order_uid);
@@ -0,0 +1,13 @@
+-- Interactions that the settlement executes before ('pre') and after ('post') the trades. They are
+-- declared in the app data document of the order and copied here when the order is created.
+CREATE TYPE HookStage AS ENUM ('pre', 'post');
+
+CREATE TABLE order_hooks (
+    order_uid bytea NOT NULL,
+    stage HookStage NOT NULL,
+    index integer NOT NULL,
+    target bytea NOT NULL,
+    call_data bytea NOT NULL,
+    gas_limit bigint NOT NULL,
+    PRIMARY KEY (order_uid, stage, index)
+);
@@ -0,0 +1,10 @@
+-- The fee subsidy of a quote can depend on the owner and the app data of the order, so quotes can
+-- only be used by orders that match them. Existing quotes don't have these values and expire
+-- within minutes, so they are removed instead of being backfilled.
+DELETE FROM quotes;
+
+ALTER TABLE quotes
+  ADD COLUMN from_address bytea NOT NULL,
+  ADD COLUMN app_data bytea NOT NULL,
+  ADD COLUMN sell_token_balance SellTokenSource NOT NULL,
+  ADD COLUMN buy_token_balance BuyTokenDestination NOT NULL;
@@ -0,0 +1,8 @@
+-- The order of an ETH-flow placement can also have been submitted to the API before the placement
+-- was indexed. Only orders that the indexer inserted itself may be deleted when the placement gets
+-- reorged. It is unknown for existing placements, so they are treated as submitted to the API.
+ALTER TABLE ethflow_orders
+  ADD COLUMN order_inserted boolean NOT NULL DEFAULT false;
+
+ALTER TABLE ethflow_orders
+  ALTER COLUMN order_inserted DROP DEFAULT;
@@ -0,0 +1,4 @@
+-- The transaction hash of a competition stays unset if its winning solution never got mined. The
+-- creation time tells how long the solution could still be in flight.
+ALTER TABLE solver_competitions
+  ADD COLUMN creation_timestamp timestamptz NOT NULL DEFAULT now();
@@ -0,0 +1,2 @@
+-- Order replacements check all competitions whose winning solution could still be in flight.
+CREATE INDEX solver_competitions_by_creation ON solver_competitions USING BTREE (creation_timestamp);
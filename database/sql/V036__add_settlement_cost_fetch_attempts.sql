@@ -0,0 +1,5 @@
+-- Settlements whose costs could not be fetched are retried with a back off so that they don't keep
+-- the settlements after them from being indexed.
+ALTER TABLE settlements
+    ADD COLUMN cost_fetch_attempts integer NOT NULL DEFAULT 0,
+    ADD COLUMN next_cost_fetch timestamptz;
@@ -0,0 +1,2 @@
+-- Trades can be filtered by the hash of the transaction that settled them.
+CREATE INDEX settlements_tx_hash ON settlements USING HASH (tx_hash);